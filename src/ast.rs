@@ -1,3 +1,26 @@
+use std::fmt;
+use crate::error::Span;
+
+// where a node was parsed from, from the start of its first token to just past its last
+// hand built nodes have an empty span, and spans never take part in equality
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceSpan {
+    pub start: Span,
+    pub end: Span
+}
+
+impl SourceSpan {
+    pub fn is_empty(&self) -> bool {
+        self.start.line == 0
+    }
+}
+
+impl PartialEq for SourceSpan {
+    fn eq(&self, _: &SourceSpan) -> bool {
+        true
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     Declaration(Declaration),
@@ -5,45 +28,164 @@ pub enum Statement {
     Transfer(Transfer),
     FunctionCall(FunctionCall),
     IfStatement(IfStatement),
+    WhileStatement(WhileStatement),
+    ForeachStatement(ForeachStatement),
+    MatchStatement(MatchStatement),
     CheckStatement(CheckStatement),
     ReturnStatement(ReturnStatement),
+    GameOption(GameOption),
+    DeckDefinition(DeckDefinition),
+    ValueTable(ValueTable),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Symbol(String),
+    // `"Crazy Eights"`, a value that is only ever itself where a symbol can name something
+    String(String),
     Number(f64),
     Comparison(Box<Comparison>),
     Bool(bool),
     FunctionCall(FunctionCall),
-    And(Box<And>)
+    And(Box<And>),
+    Or(Box<Or>),
+    Arithmetic(Box<Arithmetic>),
+    Between(Box<Between>)
+}
+
+impl Statement {
+    pub fn span(&self) -> SourceSpan {
+        match self {
+            Statement::Declaration(d) => d.span,
+            Statement::Definition(d) => d.span,
+            Statement::Transfer(t) => t.span,
+            Statement::FunctionCall(f) => f.span,
+            Statement::IfStatement(i) => i.span,
+            Statement::WhileStatement(w) => w.span,
+            Statement::ForeachStatement(f) => f.span,
+            Statement::MatchStatement(m) => m.span,
+            Statement::CheckStatement(c) => c.span,
+            Statement::ReturnStatement(r) => r.span,
+            Statement::GameOption(o) => o.span,
+            Statement::DeckDefinition(d) => d.span,
+            Statement::ValueTable(v) => v.span
+        }
+    }
+}
+
+impl Expression {
+    // plain values are a single token, located by the node they belong to
+    pub fn span(&self) -> Option<SourceSpan> {
+        match self {
+            Expression::Comparison(c) => Some(c.span),
+            Expression::FunctionCall(f) => Some(f.span),
+            Expression::And(a) => Some(a.span),
+            Expression::Or(o) => Some(o.span),
+            Expression::Arithmetic(a) => Some(a.span),
+            Expression::Between(b) => Some(b.span),
+            _ => None
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum GlobalKey {
     Name,
     Players,
+    Teams,
+    Deal,
+    HandSize,
+    Chips,
+    Turns,
+    TurnTime,
+    MatchTo,
     Stack,
     Deck,
+    Decks,
+    // a stack whose cards are shown face down, as `stack discard facedown`
+    FaceDown,
+    // `cardlang 2`, the language version the game is written for
+    Language,
     CurrentPlayer
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Declaration {
     pub key: GlobalKey,
-    pub value: Expression
+    pub value: Expression,
+    pub span: SourceSpan
+}
+
+// one entry of `options { stock_size 31 default, jokers false }`, a name the rules can read
+// whose value can be changed when the game is built
+#[derive(Debug, PartialEq, Clone)]
+pub struct GameOption {
+    pub name: String,
+    pub value: Expression,
+    pub span: SourceSpan
+}
+
+// `deck euchre { ranks nine, ten, jack, queen, king, ace }`, the game's own deck, cut from the standard one.
+// An empty list keeps every rank or suit
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeckDefinition {
+    pub name: String,
+    pub ranks: Vec<String>,
+    pub suits: Vec<String>,
+    pub span: SourceSpan
+}
+
+// `values { ace 11, face 10 }`, what each rank is worth when the rules ask for `value(card)`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ValueTable {
+    pub values: Vec<(String, f64)>,
+    pub span: SourceSpan
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Definition {
     pub name: String,
     pub arguments: Vec<String>,
-    pub body: Vec<Statement>
+    pub body: Vec<Statement>,
+    pub span: SourceSpan
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct IfStatement {
     pub expression: Expression,
+    pub body: Vec<Statement>,
+    pub span: SourceSpan
+}
+
+// runs the body for as long as the expression holds, within the runtime's budget
+#[derive(Debug, PartialEq, Clone)]
+pub struct WhileStatement {
+    pub expression: Expression,
+    pub body: Vec<Statement>,
+    pub span: SourceSpan
+}
+
+// `foreach card in player:hand { .. }` runs the body once for each card, bound like a define's argument
+#[derive(Debug, PartialEq, Clone)]
+pub struct ForeachStatement {
+    pub variable: String,
+    pub stack: String,
+    pub body: Vec<Statement>,
+    pub span: SourceSpan
+}
+
+// runs the body of the first arm whose pattern equals the expression, or the `other` arm if none do
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchStatement {
+    pub expression: Expression,
+    pub arms: Vec<MatchArm>,
+    pub otherwise: Option<Vec<Statement>>,
+    pub span: SourceSpan
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Expression,
     pub body: Vec<Statement>
 }
 
@@ -52,44 +194,444 @@ pub struct Transfer {
     pub from: String,
     pub to: String,
     pub modifier: Option<TransferModifier>,
-    pub count: Option<TransferCount>
+    // `end` for every card, otherwise how many as an expression, `deck > middle count(discard) - 1`, from cardlang 2
+    pub count: Option<Expression>,
+    pub span: SourceSpan
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl Transfer {
+    pub fn to_end(&self) -> bool {
+        matches!(&self.count, Some(Expression::Symbol(s)) if s == "end")
+    }
+
+    pub fn from_bottom(&self) -> bool {
+        self.modifier.is_some_and(|m| m.from_bottom())
+    }
+
+    pub fn to_bottom(&self) -> bool {
+        self.modifier.is_some_and(|m| m.to_bottom())
+    }
+}
+
+// transfers take from the top of a stack and put cards on top, unless they say otherwise
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum TransferModifier {
-    //Alternate
+    // `deck bottom > players`
+    FromBottom,
+    // `discard > bottom deck`
+    ToBottom,
+    // `deck bottom > bottom deck`
+    BottomToBottom
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum TransferCount {
-    End
+impl TransferModifier {
+    pub fn new(from_bottom: bool, to_bottom: bool) -> Option<TransferModifier> {
+        match (from_bottom, to_bottom) {
+            (true, true) => Some(TransferModifier::BottomToBottom),
+            (true, false) => Some(TransferModifier::FromBottom),
+            (false, true) => Some(TransferModifier::ToBottom),
+            (false, false) => None
+        }
+    }
+
+    pub fn from_bottom(&self) -> bool {
+        matches!(self, TransferModifier::FromBottom | TransferModifier::BottomToBottom)
+    }
+
+    pub fn to_bottom(&self) -> bool {
+        matches!(self, TransferModifier::ToBottom | TransferModifier::BottomToBottom)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionCall {
     pub name: String,
-    pub arguments: Vec<Expression>
+    pub arguments: Vec<Expression>,
+    pub span: SourceSpan
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Comparison {
     pub left: Expression,
     pub right: Expression,
-    pub negative: bool
+    pub negative: bool,
+    pub span: SourceSpan
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CheckStatement {
-    pub expression: Expression
+    pub expression: Expression,
+    pub span: SourceSpan
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ReturnStatement {
-    pub expression: Expression
+    pub expression: Expression,
+    pub span: SourceSpan
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct And {
     pub left: Expression,
-    pub right: Expression
-}
\ No newline at end of file
+    pub right: Expression,
+    pub span: SourceSpan
+}
+
+// true when either side is, and the right side is only resolved when the left is false
+#[derive(Debug, PartialEq, Clone)]
+pub struct Or {
+    pub left: Expression,
+    pub right: Expression,
+    pub span: SourceSpan
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    // the remainder is never negative, so `(current_player - 2) % 4` still names a seat
+    Modulo
+}
+
+// `count(player:hand) - 1` or `score * 2`, both sides resolved as numbers
+#[derive(Debug, PartialEq, Clone)]
+pub struct Arithmetic {
+    pub left: Expression,
+    pub operator: Operator,
+    pub right: Expression,
+    pub span: SourceSpan
+}
+
+// both ends are included, so `card:value between 2 and 10` is true for 2 and 10
+#[derive(Debug, PartialEq, Clone)]
+pub struct Between {
+    pub value: Expression,
+    pub low: Expression,
+    pub high: Expression,
+    pub span: SourceSpan
+}
+
+// canonical source for a whole specification - definitions are separated by a blank line
+pub fn to_source(ast: &[Statement]) -> String {
+    let mut lines = vec!();
+    for (i, statement) in ast.iter().enumerate() {
+        if i > 0 && matches!(statement, Statement::Definition(_)) {
+            lines.push(String::new());
+        }
+        lines.push(statement.to_string());
+    }
+    format!("{}\n", lines.join("\n"))
+}
+
+fn write_block(f: &mut fmt::Formatter<'_>, body: &[Statement]) -> fmt::Result {
+    writeln!(f, "{{")?;
+    for statement in body.iter() {
+        write_indented(f, statement)?;
+    }
+    write!(f, "}}")
+}
+
+fn write_indented<T: fmt::Display>(f: &mut fmt::Formatter<'_>, node: &T) -> fmt::Result {
+    for line in node.to_string().lines() {
+        writeln!(f, "    {}", line)?;
+    }
+    Ok(())
+}
+
+// the fallback arm of a match, printed like any other arm
+struct Otherwise<'a>(&'a [Statement]);
+
+impl fmt::Display for Otherwise<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "other ")?;
+        write_block(f, self.0)
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Declaration(d) => d.fmt(f),
+            Statement::Definition(d) => d.fmt(f),
+            Statement::Transfer(t) => t.fmt(f),
+            Statement::FunctionCall(c) => c.fmt(f),
+            Statement::IfStatement(i) => i.fmt(f),
+            Statement::WhileStatement(w) => w.fmt(f),
+            Statement::ForeachStatement(e) => e.fmt(f),
+            Statement::MatchStatement(m) => m.fmt(f),
+            Statement::CheckStatement(c) => c.fmt(f),
+            Statement::ReturnStatement(r) => r.fmt(f),
+            Statement::GameOption(o) => o.fmt(f),
+            Statement::DeckDefinition(d) => d.fmt(f),
+            Statement::ValueTable(v) => v.fmt(f)
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Symbol(s) => write!(f, "{}", s),
+            Expression::String(s) => write!(f, "\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")),
+            Expression::Number(n) => write!(f, "{}", n),
+            Expression::Comparison(c) => c.fmt(f),
+            Expression::Bool(b) => write!(f, "{}", b),
+            Expression::FunctionCall(c) => c.fmt(f),
+            Expression::And(a) => a.fmt(f),
+            Expression::Or(o) => o.fmt(f),
+            Expression::Arithmetic(a) => a.fmt(f),
+            Expression::Between(b) => b.fmt(f)
+        }
+    }
+}
+
+impl fmt::Display for GlobalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = match self {
+            GlobalKey::Name => "name",
+            GlobalKey::Players => "players",
+            GlobalKey::Teams => "teams",
+            GlobalKey::Deal => "deal",
+            GlobalKey::HandSize => "hand_size",
+            GlobalKey::Chips => "chips",
+            GlobalKey::Turns => "turns",
+            GlobalKey::TurnTime => "turn_time",
+            GlobalKey::MatchTo => "match to",
+            GlobalKey::Stack => "stack",
+            GlobalKey::Deck => "deck",
+            GlobalKey::Decks => "decks",
+            GlobalKey::FaceDown => "facedown",
+            GlobalKey::Language => "cardlang",
+            GlobalKey::CurrentPlayer => "current_player"
+        };
+        write!(f, "{}", key)
+    }
+}
+
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.key, self.value)?;
+        match self.key {
+            GlobalKey::Deal => write!(f, " each"),
+            _ => Ok(())
+        }
+    }
+}
+
+impl fmt::Display for GameOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "options {{ {} {} }}", self.name, self.value)
+    }
+}
+
+impl fmt::Display for DeckDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deck {} {{", self.name)?;
+        if !self.ranks.is_empty() {
+            write!(f, "\n    ranks {}", join(&self.ranks))?;
+        }
+        if !self.suits.is_empty() {
+            write!(f, "\n    suits {}", join(&self.suits))?;
+        }
+        write!(f, "\n}}")
+    }
+}
+
+impl fmt::Display for ValueTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values: Vec<String> = self.values.iter().map(|(rank, value)| format!("{} {}", rank, value)).collect();
+        write!(f, "values {{ {} }}", values.join(", "))
+    }
+}
+
+impl fmt::Display for Definition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "define {}({})", self.name, join(&self.arguments))?;
+        write_block(f, &self.body)
+    }
+}
+
+impl fmt::Display for IfStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "if({})", self.expression)?;
+        write_block(f, &self.body)
+    }
+}
+
+impl fmt::Display for WhileStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "while({})", self.expression)?;
+        write_block(f, &self.body)
+    }
+}
+
+impl fmt::Display for ForeachStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "foreach {} in {} ", self.variable, self.stack)?;
+        write_block(f, &self.body)
+    }
+}
+
+impl fmt::Display for MatchStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "match {} {{", self.expression)?;
+        for arm in self.arms.iter() {
+            write_indented(f, arm)?;
+        }
+        if let Some(body) = &self.otherwise {
+            write_indented(f, &Otherwise(body))?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.pattern)?;
+        write_block(f, &self.body)
+    }
+}
+
+impl fmt::Display for Transfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bottom = |b: bool| if b { "bottom " } else { "" };
+        write!(f, "{} {}> {}{}", self.from, bottom(self.from_bottom()), bottom(self.to_bottom()), self.to)?;
+        match &self.count {
+            Some(count) => write!(f, " {}", count),
+            None => Ok(())
+        }
+    }
+}
+
+impl fmt::Display for FunctionCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.name, join(&self.arguments))
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = if self.negative { "is not" } else { "is" };
+        write!(f, "{} {} {}", self.left, operator, self.right)
+    }
+}
+
+impl fmt::Display for CheckStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "check({})", self.expression)
+    }
+}
+
+impl fmt::Display for ReturnStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "return({})", self.expression)
+    }
+}
+
+impl fmt::Display for And {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} & {}", self.left, self.right)
+    }
+}
+
+impl fmt::Display for Or {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} | {}", self.left, self.right)
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Add => write!(f, "+"),
+            Operator::Subtract => write!(f, "-"),
+            Operator::Multiply => write!(f, "*"),
+            Operator::Divide => write!(f, "/"),
+            Operator::Modulo => write!(f, "%")
+        }
+    }
+}
+
+impl fmt::Display for Arithmetic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.left, self.operator, self.right)
+    }
+}
+
+impl fmt::Display for Between {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} between {} and {}", self.value, self.low, self.high)
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Statement> {
+        let tokens = crate::lex::lexer(source).unwrap();
+        crate::parse::parse(&tokens).unwrap()
+    }
+
+    #[test]
+    fn it_prints_canonical_source() {
+        let source = "name turns\nplayers 2\nstack middle\n\ndefine setup(){\n    shuffle(deck)\n    deck > players end\n}\n\ndefine player_move(player){\n    check(player:id is current_player & true)\n    check(count(player:hand) between 1 and 5)\n    player:hand > middle\n    if(count(player:hand) is not 0){\n        winner(player:id)\n        end()\n    }\n    next_player()\n}\n";
+
+        assert_eq!(to_source(&parse(source)), source.to_string());
+    }
+
+    #[test]
+    fn nodes_can_be_quoted_on_their_own() {
+        let transfer = Statement::Transfer(Transfer{
+            from: "deck".to_string(),
+            to: "middle".to_string(),
+            modifier: None,
+            count: None,
+            span: SourceSpan::default()
+        });
+        let definition = Definition{ name: "deal".to_string(), arguments: vec!("a".to_string(), "b".to_string()), body: vec!(), span: SourceSpan::default() };
+
+        assert_eq!(transfer.to_string(), "deck > middle".to_string());
+        assert_eq!(definition.to_string(), "define deal(a, b){\n}".to_string());
+        assert_eq!(Expression::Number(2.5).to_string(), "2.5".to_string());
+    }
+
+    #[test]
+    fn examples_round_trip() {
+        for (name, _, source) in crate::examples::EXAMPLES.iter() {
+            let ast = parse(source);
+
+            assert_eq!(parse(&to_source(&ast)), ast, "{}", name);
+        }
+    }
+
+    #[test]
+    fn generated_specifications_round_trip() {
+        let mut generator = crate::generators::Generator::new(4);
+        for _ in 0..50 {
+            let ast = generator.ast();
+
+            assert_eq!(parse(&to_source(&ast)), ast);
+        }
+    }
+
+    #[test]
+    fn match_statements_print_one_arm_per_block() {
+        let source = "define player_move(player){\n    match count(player:hand) {\n        0 {\n            end()\n        }\n        other {\n            next_player()\n        }\n    }\n}\n";
+
+        assert_eq!(to_source(&parse(source)), source.to_string());
+    }
+
+    #[test]
+    fn definitions_with_several_arguments_round_trip() {
+        let ast = parse("define deal(from, to){\nfrom > to\n}");
+
+        assert_eq!(parse(&to_source(&ast)), ast);
+    }
+}
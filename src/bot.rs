@@ -0,0 +1,139 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use crate::cards::Card;
+use crate::interpreter::{Game, GameStateView};
+
+// guards against bots that keep a game going forever
+const BOT_MOVE_LIMIT: usize = 1000;
+
+// moves are made by naming the player to move, as with `move n`
+pub type Move = usize;
+
+// what a bot sitting in a seat is allowed to see
+pub struct GameView<'a> {
+    pub seat: usize,
+    pub state: GameStateView<'a>,
+    pub hand: &'a [Card],
+    // the moves this seat can make right now, never empty when a bot is asked
+    pub legal_moves: Vec<Move>
+}
+
+impl<'a> GameView<'a> {
+    pub fn for_seat(game: &'a Game, seat: usize) -> GameView<'a> {
+        GameView {
+            seat,
+            state: game.state(),
+            hand: game.player(seat).map(|p| p.hand).unwrap_or(&[]),
+            legal_moves: game.legal_moves().into_iter().filter(|m| *m == seat).collect()
+        }
+    }
+}
+
+pub trait Bot {
+    fn name(&self) -> &str;
+
+    fn choose_move(&mut self, view: &GameView) -> Move;
+}
+
+pub struct RandomBot {
+    rng: StdRng
+}
+
+impl RandomBot {
+    pub fn new() -> RandomBot {
+        RandomBot { rng: StdRng::from_entropy() }
+    }
+
+    pub fn seeded(seed: u64) -> RandomBot {
+        RandomBot { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl Default for RandomBot {
+    fn default() -> RandomBot {
+        RandomBot::new()
+    }
+}
+
+impl Bot for RandomBot {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn choose_move(&mut self, view: &GameView) -> Move {
+        let choice = self.rng.gen_range(0..view.legal_moves.len());
+        view.legal_moves[choice]
+    }
+}
+
+// the built in bots by name, as used by `bot 2 random`
+pub fn create(kind: &str, seed: Option<u64>) -> Option<Box<dyn Bot>> {
+    match (kind, seed) {
+        ("random", Some(seed)) => Some(Box::new(RandomBot::seeded(seed))),
+        ("random", None) => Some(Box::new(RandomBot::new())),
+        _ => None
+    }
+}
+
+pub type Seats = Vec<(usize, Box<dyn Bot>)>;
+
+// lets seated bots move for as long as one of them can, returning what they did
+pub fn play_bots(game: &mut Game, bots: &mut Seats) -> Vec<String> {
+    let mut played = vec!();
+    while played.len() < BOT_MOVE_LIMIT && game.get_status() == "active" {
+        let turn = bots.iter_mut().find_map(|(seat, bot)| {
+            let view = GameView::for_seat(game, *seat);
+            if view.legal_moves.is_empty() {
+                None
+            } else {
+                Some((*seat, bot.choose_move(&view), bot.name().to_string()))
+            }
+        });
+
+        match turn {
+            Some((seat, player_move, name)) if player_move == seat && game.player_move(player_move) => {
+                played.push(format!("player {} ({}) moved", seat, name));
+            },
+            _ => break
+        }
+    }
+    played
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn game() -> Game {
+        let source = "players 2\ndefine setup(){\ndeck > players end\n}\ndefine player_move(player){\ncheck(player:id is current_player)\nplayer:hand > deck\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        game
+    }
+
+    #[test]
+    fn views_only_offer_the_seats_own_moves() {
+        let game = game();
+
+        assert_eq!(GameView::for_seat(&game, 1).legal_moves, vec!(1));
+        assert_eq!(GameView::for_seat(&game, 2).legal_moves, Vec::<usize>::new());
+        assert_eq!(GameView::for_seat(&game, 2).hand.len(), 26);
+    }
+
+    #[test]
+    fn bots_move_until_it_is_a_humans_turn() {
+        let mut game = game();
+        let mut bots: Seats = vec!((2, Box::new(RandomBot::seeded(1))));
+
+        assert_eq!(play_bots(&mut game, &mut bots), Vec::<String>::new());
+
+        game.player_move(1);
+        assert_eq!(play_bots(&mut game, &mut bots), vec!("player 2 (random) moved".to_string()));
+        assert_eq!(game.get_current_player(), 1);
+    }
+
+    #[test]
+    fn bots_are_created_by_name() {
+        assert_eq!(create("random", None).map(|b| b.name().to_string()), Some("random".to_string()));
+        assert!(create("genius", Some(1)).is_none());
+    }
+}
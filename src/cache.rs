@@ -0,0 +1,343 @@
+use std::fs;
+use crate::ast::*;
+use crate::error::{CardlangError, Span};
+use crate::json::Json;
+
+// bumped whenever the AST changes shape, so caches from another release are parsed again
+const CACHE_VERSION: f64 = 8.0;
+
+const KEYS: [GlobalKey; 15] = [
+    GlobalKey::Name, GlobalKey::Players, GlobalKey::Teams, GlobalKey::Deal, GlobalKey::HandSize, GlobalKey::Chips,
+    GlobalKey::Turns, GlobalKey::TurnTime, GlobalKey::MatchTo, GlobalKey::Stack, GlobalKey::Deck, GlobalKey::Decks,
+    GlobalKey::FaceDown, GlobalKey::Language, GlobalKey::CurrentPlayer
+];
+
+// kept next to the game file, as `game.card.cache`
+pub fn cache_path(path: &str) -> String {
+    format!("{}.cache", path)
+}
+
+// the source read from `path`, parsed, or taken from its cache when it's unchanged since.
+// Bundled examples aren't files, so they're always parsed
+pub fn parse_cached(path: &str, source: &str) -> Result<Vec<Statement>, CardlangError> {
+    if path.starts_with(crate::examples::PREFIX) {
+        return crate::parse_source(source);
+    }
+
+    let hash = source_hash(source);
+    let cached = fs::read_to_string(cache_path(path)).ok()
+        .and_then(|c| Json::parse(&c).ok())
+        .and_then(|c| decode_cache(&c, &hash));
+    if let Some(ast) = cached {
+        return Ok(ast);
+    }
+
+    let ast = crate::parse_source(source)?;
+    // a cache that can't be written only costs the next build a parse
+    let _ = fs::write(cache_path(path), encode_cache(&ast, &hash).to_string());
+    Ok(ast)
+}
+
+// fnv-1a, which unlike std's hasher is the same on every machine and release
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn source_hash(source: &str) -> String {
+    format!("{:016x}", fnv1a(source.as_bytes()))
+}
+
+pub fn encode_cache(ast: &[Statement], hash: &str) -> Json {
+    Json::object(vec!(
+        ("version", Json::Number(CACHE_VERSION)),
+        ("hash", Json::string(hash)),
+        ("ast", Json::Array(ast.iter().map(encode_statement).collect()))
+    ))
+}
+
+pub fn decode_cache(cache: &Json, hash: &str) -> Option<Vec<Statement>> {
+    if cache.get("version")?.as_f64()? != CACHE_VERSION || cache.get("hash")?.as_str()? != hash {
+        return None;
+    }
+    decode_statements(cache.get("ast")?)
+}
+
+// each node is an array, its kind first and its span last
+fn node(kind: &str, mut fields: Vec<Json>, span: &SourceSpan) -> Json {
+    fields.insert(0, Json::string(kind));
+    fields.push(encode_span(span));
+    Json::Array(fields)
+}
+
+fn encode_span(span: &SourceSpan) -> Json {
+    Json::Array([span.start.line, span.start.column, span.end.line, span.end.column].iter()
+        .map(|n| Json::Number(*n as f64)).collect())
+}
+
+fn encode_statements(statements: &[Statement]) -> Json {
+    Json::Array(statements.iter().map(encode_statement).collect())
+}
+
+fn encode_strings(strings: &[String]) -> Json {
+    Json::Array(strings.iter().map(|s| Json::string(s)).collect())
+}
+
+fn encode_statement(statement: &Statement) -> Json {
+    match statement {
+        Statement::Declaration(d) => node("declaration", vec!(Json::String(format!("{:?}", d.key)), encode_expression(&d.value)), &d.span),
+        Statement::Definition(d) => node("definition", vec!(Json::string(&d.name), encode_strings(&d.arguments), encode_statements(&d.body)), &d.span),
+        Statement::Transfer(t) => node("transfer", vec!(
+            Json::string(&t.from), Json::string(&t.to), encode_count(&t.count),
+            t.modifier.map(|m| Json::String(format!("{:?}", m))).unwrap_or(Json::Null)
+        ), &t.span),
+        Statement::FunctionCall(f) => encode_function_call(f),
+        Statement::IfStatement(i) => node("if", vec!(encode_expression(&i.expression), encode_statements(&i.body)), &i.span),
+        Statement::WhileStatement(w) => node("while", vec!(encode_expression(&w.expression), encode_statements(&w.body)), &w.span),
+        Statement::ForeachStatement(f) => node("foreach", vec!(Json::string(&f.variable), Json::string(&f.stack), encode_statements(&f.body)), &f.span),
+        Statement::MatchStatement(m) => {
+            let arms = m.arms.iter().map(|a| Json::Array(vec!(encode_expression(&a.pattern), encode_statements(&a.body)))).collect();
+            let otherwise = m.otherwise.as_ref().map(|o| encode_statements(o)).unwrap_or(Json::Null);
+            node("match", vec!(encode_expression(&m.expression), Json::Array(arms), otherwise), &m.span)
+        },
+        Statement::CheckStatement(c) => node("check", vec!(encode_expression(&c.expression)), &c.span),
+        Statement::ReturnStatement(r) => node("return", vec!(encode_expression(&r.expression)), &r.span),
+        Statement::GameOption(o) => node("option", vec!(Json::string(&o.name), encode_expression(&o.value)), &o.span),
+        Statement::DeckDefinition(d) => node("deck", vec!(Json::string(&d.name), encode_strings(&d.ranks), encode_strings(&d.suits)), &d.span),
+        Statement::ValueTable(v) => node("values", vec!(
+            Json::Array(v.values.iter().map(|(rank, value)| Json::Array(vec!(Json::string(rank), Json::Number(*value)))).collect())
+        ), &v.span)
+    }
+}
+
+fn encode_count(count: &Option<Expression>) -> Json {
+    match count {
+        Some(count) => encode_expression(count),
+        None => Json::Null
+    }
+}
+
+fn encode_function_call(f: &FunctionCall) -> Json {
+    node("call", vec!(Json::string(&f.name), Json::Array(f.arguments.iter().map(encode_expression).collect())), &f.span)
+}
+
+fn encode_expression(expression: &Expression) -> Json {
+    match expression {
+        Expression::Symbol(s) => Json::Array(vec!(Json::string("symbol"), Json::string(s))),
+        Expression::String(s) => Json::Array(vec!(Json::string("string"), Json::string(s))),
+        Expression::Number(n) => Json::Array(vec!(Json::string("number"), Json::Number(*n))),
+        Expression::Bool(b) => Json::Array(vec!(Json::string("bool"), Json::Bool(*b))),
+        Expression::FunctionCall(f) => encode_function_call(f),
+        Expression::Comparison(c) => node("comparison", vec!(
+            encode_expression(&c.left), encode_expression(&c.right), Json::Bool(c.negative)
+        ), &c.span),
+        Expression::And(a) => node("and", vec!(encode_expression(&a.left), encode_expression(&a.right)), &a.span),
+        Expression::Or(o) => node("or", vec!(encode_expression(&o.left), encode_expression(&o.right)), &o.span),
+        Expression::Arithmetic(a) => node("arithmetic", vec!(
+            encode_expression(&a.left), Json::string(&a.operator.to_string()), encode_expression(&a.right)
+        ), &a.span),
+        Expression::Between(b) => node("between", vec!(
+            encode_expression(&b.value), encode_expression(&b.low), encode_expression(&b.high)
+        ), &b.span)
+    }
+}
+
+fn decode_span(span: &Json) -> Option<SourceSpan> {
+    let n: Vec<u32> = span.as_array()?.iter().map(|n| n.as_f64().map(|n| n as u32)).collect::<Option<Vec<u32>>>()?;
+    match n.as_slice() {
+        [start_line, start_column, end_line, end_column] => Some(SourceSpan {
+            start: Span { line: *start_line, column: *start_column },
+            end: Span { line: *end_line, column: *end_column }
+        }),
+        _ => None
+    }
+}
+
+fn decode_statements(statements: &Json) -> Option<Vec<Statement>> {
+    statements.as_array()?.iter().map(decode_statement).collect()
+}
+
+fn decode_strings(strings: &Json) -> Option<Vec<String>> {
+    strings.as_array()?.iter().map(|s| s.as_str().map(|s| s.to_string())).collect()
+}
+
+// the node's kind, its fields and its span
+fn split_node(json: &Json) -> Option<(&str, &[Json], SourceSpan)> {
+    let fields = json.as_array()?;
+    let kind = fields.first()?.as_str()?;
+    let span = decode_span(fields.last()?)?;
+    Some((kind, &fields[1..fields.len() - 1], span))
+}
+
+fn decode_statement(json: &Json) -> Option<Statement> {
+    let (kind, fields, span) = split_node(json)?;
+    let statement = match (kind, fields) {
+        ("declaration", [key, value]) => {
+            let key = KEYS.iter().find(|k| format!("{:?}", k) == key.as_str().unwrap_or_default())?.clone();
+            Statement::Declaration(Declaration { key, value: decode_expression(value)?, span })
+        },
+        ("definition", [name, arguments, body]) => Statement::Definition(Definition {
+            name: name.as_str()?.to_string(),
+            arguments: decode_strings(arguments)?,
+            body: decode_statements(body)?,
+            span
+        }),
+        ("transfer", [from, to, count, modifier]) => Statement::Transfer(Transfer {
+            from: from.as_str()?.to_string(),
+            to: to.as_str()?.to_string(),
+            modifier: match modifier {
+                Json::Null => None,
+                modifier => Some(match modifier.as_str()? {
+                    "FromBottom" => TransferModifier::FromBottom,
+                    "ToBottom" => TransferModifier::ToBottom,
+                    "BottomToBottom" => TransferModifier::BottomToBottom,
+                    _ => return None
+                })
+            },
+            count: match count {
+                Json::Null => None,
+                count => Some(decode_expression(count)?)
+            },
+            span
+        }),
+        ("call", _) => Statement::FunctionCall(decode_function_call(json)?),
+        ("if", [expression, body]) => Statement::IfStatement(IfStatement {
+            expression: decode_expression(expression)?,
+            body: decode_statements(body)?,
+            span
+        }),
+        ("while", [expression, body]) => Statement::WhileStatement(WhileStatement {
+            expression: decode_expression(expression)?,
+            body: decode_statements(body)?,
+            span
+        }),
+        ("foreach", [variable, stack, body]) => Statement::ForeachStatement(ForeachStatement {
+            variable: variable.as_str()?.to_string(),
+            stack: stack.as_str()?.to_string(),
+            body: decode_statements(body)?,
+            span
+        }),
+        ("match", [expression, arms, otherwise]) => {
+            let arms = arms.as_array()?.iter().map(|arm| match arm.as_array()? {
+                [pattern, body] => Some(MatchArm { pattern: decode_expression(pattern)?, body: decode_statements(body)? }),
+                _ => None
+            }).collect::<Option<Vec<MatchArm>>>()?;
+            let otherwise = match otherwise {
+                Json::Null => None,
+                o => Some(decode_statements(o)?)
+            };
+            Statement::MatchStatement(MatchStatement { expression: decode_expression(expression)?, arms, otherwise, span })
+        },
+        ("check", [expression]) => Statement::CheckStatement(CheckStatement { expression: decode_expression(expression)?, span }),
+        ("return", [expression]) => Statement::ReturnStatement(ReturnStatement { expression: decode_expression(expression)?, span }),
+        ("option", [name, value]) => Statement::GameOption(GameOption { name: name.as_str()?.to_string(), value: decode_expression(value)?, span }),
+        ("values", [values]) => Statement::ValueTable(ValueTable {
+            values: values.as_array()?.iter().map(|entry| match entry.as_array()? {
+                [rank, value] => Some((rank.as_str()?.to_string(), value.as_f64()?)),
+                _ => None
+            }).collect::<Option<Vec<(String, f64)>>>()?,
+            span
+        }),
+        ("deck", [name, ranks, suits]) => Statement::DeckDefinition(DeckDefinition {
+            name: name.as_str()?.to_string(),
+            ranks: decode_strings(ranks)?,
+            suits: decode_strings(suits)?,
+            span
+        }),
+        _ => return None
+    };
+    Some(statement)
+}
+
+fn decode_function_call(json: &Json) -> Option<FunctionCall> {
+    match split_node(json)? {
+        ("call", [name, arguments], span) => Some(FunctionCall {
+            name: name.as_str()?.to_string(),
+            arguments: arguments.as_array()?.iter().map(decode_expression).collect::<Option<Vec<Expression>>>()?,
+            span
+        }),
+        _ => None
+    }
+}
+
+fn decode_expression(json: &Json) -> Option<Expression> {
+    let fields = json.as_array()?;
+    let expression = match (fields.first()?.as_str()?, &fields[1..]) {
+        ("symbol", [s]) => Expression::Symbol(s.as_str()?.to_string()),
+        ("string", [s]) => Expression::String(s.as_str()?.to_string()),
+        ("number", [n]) => Expression::Number(n.as_f64()?),
+        ("bool", [b]) => Expression::Bool(b.as_bool()?),
+        ("call", _) => Expression::FunctionCall(decode_function_call(json)?),
+        (kind, _) => {
+            let (_, fields, span) = split_node(json)?;
+            match (kind, fields) {
+                ("comparison", [left, right, negative]) => Expression::Comparison(Box::new(Comparison {
+                    left: decode_expression(left)?, right: decode_expression(right)?, negative: negative.as_bool()?, span
+                })),
+                ("and", [left, right]) => Expression::And(Box::new(And {
+                    left: decode_expression(left)?, right: decode_expression(right)?, span
+                })),
+                ("or", [left, right]) => Expression::Or(Box::new(Or {
+                    left: decode_expression(left)?, right: decode_expression(right)?, span
+                })),
+                ("arithmetic", [left, operator, right]) => Expression::Arithmetic(Box::new(Arithmetic {
+                    left: decode_expression(left)?,
+                    operator: match operator.as_str()? {
+                        "+" => Operator::Add,
+                        "-" => Operator::Subtract,
+                        "*" => Operator::Multiply,
+                        "/" => Operator::Divide,
+                        "%" => Operator::Modulo,
+                        _ => return None
+                    },
+                    right: decode_expression(right)?,
+                    span
+                })),
+                ("between", [value, low, high]) => Expression::Between(Box::new(Between {
+                    value: decode_expression(value)?, low: decode_expression(low)?, high: decode_expression(high)?, span
+                })),
+                _ => return None
+            }
+        }
+    };
+    Some(expression)
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn every_example_survives_the_cache() {
+        for (name, _, source) in crate::examples::EXAMPLES.iter() {
+            let ast = crate::parse_source(source).unwrap();
+            let cache = Json::parse(&encode_cache(&ast, "abc").to_string()).unwrap();
+            let decoded = decode_cache(&cache, "abc").unwrap();
+
+            assert_eq!(decoded, ast, "{}", name);
+            // spans are left out of equality, so compare them through the debug output
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", ast), "{}", name);
+        }
+    }
+
+    #[test]
+    fn stale_caches_are_ignored() {
+        let ast = crate::parse_source("players 2").unwrap();
+        let cache = encode_cache(&ast, "abc");
+
+        assert_eq!(decode_cache(&cache, "def"), None);
+    }
+
+    #[test]
+    fn builds_read_the_cache_until_the_source_changes() {
+        let path = std::env::temp_dir().join("cardlang_cached_game.card");
+        let path = path.to_str().unwrap();
+        fs::write(path, "players 2\n").unwrap();
+        let _ = fs::remove_file(cache_path(path));
+
+        assert_eq!(parse_cached(path, "players 2\n").unwrap(), crate::parse_source("players 2").unwrap());
+        assert!(fs::read_to_string(cache_path(path)).unwrap().contains("\"hash\""));
+
+        fs::write(path, "players 3\n").unwrap();
+        assert_eq!(parse_cached(path, "players 3\n").unwrap(), crate::parse_source("players 3").unwrap());
+    }
+}
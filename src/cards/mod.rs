@@ -28,10 +28,12 @@ pub enum Rank {
     King
 }
 
+// one physical card: with several decks, two cards can share a rank and suit but never an id
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Card {
     suit: Suit,
-    rank: Rank
+    rank: Rank,
+    id: u32
 }
 
 impl Card {
@@ -42,6 +44,46 @@ impl Card {
     pub fn get_rank_str(&self) -> String {
         format!("{:?}", self.rank)
     }
+
+    pub fn get_color_str(&self) -> String {
+        match self.suit {
+            Suit::Hearts | Suit::Diamonds => "Red".to_string(),
+            Suit::Spades | Suit::Clubs => "Black".to_string()
+        }
+    }
+
+    // ranks in order, from 1 for an ace up to 13 for a king
+    pub fn value(&self) -> u32 {
+        self.rank as u32 + 1
+    }
+
+    pub fn suit(&self) -> Suit {
+        self.suit
+    }
+
+    pub fn rank(&self) -> Rank {
+        self.rank
+    }
+
+    // position in a standard deck, from 0 (ace spades) to 51
+    pub fn index(&self) -> usize {
+        self.suit as usize * 13 + self.rank as usize
+    }
+
+    // the same as the index for the first deck, 52 on for the second and so on
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    // which of the decks the card came from, counting from 0
+    pub fn deck_number(&self) -> u32 {
+        self.id / DECK_SIZE as u32
+    }
+
+    // the same card from another of the decks
+    pub fn in_deck(&self, deck_number: u32) -> Card {
+        Card { id: deck_number * DECK_SIZE as u32 + self.index() as u32, ..*self }
+    }
 }
 
 impl fmt::Display for Card {
@@ -75,18 +117,159 @@ fn get_rank_array() -> [Rank; 13] {
     ]
 }
 
-pub fn standard_deck() -> Vec<Card> {
-    let suits = get_suit_array();
-    let ranks = get_rank_array();
-    let mut cards = vec!();
-    for suit in &suits {
-        for rank in &ranks {
-            let card = Card {
-                rank: *rank,
-                suit: *suit,
+pub const DECK_SIZE: usize = 52;
+
+// the ranks and suits a game's deck is made of. Every deck is cut from the standard one,
+// so a card keeps the id it has there however many cards the game leaves out
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeckSpec {
+    ranks: Vec<Rank>,
+    suits: Vec<Suit>
+}
+
+impl DeckSpec {
+    pub fn standard() -> DeckSpec {
+        DeckSpec { ranks: get_rank_array().to_vec(), suits: get_suit_array().to_vec() }
+    }
+
+    // the decks a game can name in `deck StandardDeck`. Piquet runs from seven to ace,
+    // and the spanish deck has no eights, nines or tens, its jack, queen and king standing for sota, caballo and rey
+    pub fn named(name: &str) -> Option<DeckSpec> {
+        let without = |left_out: &[Rank]| DeckSpec {
+            ranks: get_rank_array().iter().copied().filter(|r| !left_out.contains(r)).collect(),
+            suits: get_suit_array().to_vec()
+        };
+        match name {
+            "StandardDeck" => Some(DeckSpec::standard()),
+            "PiquetDeck" => Some(without(&[Rank::Two, Rank::Three, Rank::Four, Rank::Five, Rank::Six])),
+            "SpanishDeck" => Some(without(&[Rank::Eight, Rank::Nine, Rank::Ten])),
+            _ => None
+        }
+    }
+
+    // `deck euchre { ranks nine, ten, jack, queen, king, ace }`, with every rank or suit when a list is left out.
+    // Ranks are named as `card:rank` reads them or by value, so `9` is a nine and `1` an ace
+    pub fn from_names(ranks: &[String], suits: &[String]) -> Result<DeckSpec, String> {
+        let rank = |name: &String| rank_named(name).ok_or_else(|| name.to_string());
+        let suit = |name: &String| suit_named(name).ok_or_else(|| name.to_string());
+
+        let standard = DeckSpec::standard();
+        Ok(DeckSpec {
+            ranks: if ranks.is_empty() { standard.ranks } else { ranks.iter().map(rank).collect::<Result<Vec<Rank>, String>>()? },
+            suits: if suits.is_empty() { standard.suits } else { suits.iter().map(suit).collect::<Result<Vec<Suit>, String>>()? }
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranks.len() * self.suits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // each deck in order, one after the other
+    pub fn cards(&self, count: u32) -> Vec<Card> {
+        let mut cards = vec!();
+        for deck in 0..count {
+            for suit in get_suit_array().iter().filter(|s| self.suits.contains(s)) {
+                for rank in get_rank_array().iter().filter(|r| self.ranks.contains(r)) {
+                    let card = Card {
+                        rank: *rank,
+                        suit: *suit,
+                        id: 0
+                    };
+                    cards.push(card.in_deck(deck));
+                }
+            }
+        }
+        cards
+    }
+}
+
+// what each rank is worth to the game, as `values { ace 11, face 10 }`, where `face` is the jack, queen and king.
+// Ranks the game leaves out are worth their place from the ace (1) to the king (13)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RankValues {
+    values: Vec<(Rank, f64)>
+}
+
+impl RankValues {
+    pub fn from_names(values: &[(String, f64)]) -> Result<RankValues, String> {
+        let mut table = vec!();
+        for (name, value) in values.iter() {
+            let ranks = match name.as_str() {
+                "face" => vec!(Rank::Jack, Rank::Queen, Rank::King),
+                name => vec!(rank_named(name).ok_or_else(|| name.to_string())?)
             };
-            cards.push(card);
+            table.extend(ranks.into_iter().map(|r| (r, *value)));
         }
+        Ok(RankValues { values: table })
+    }
+
+    // a rank named more than once is worth what it was given last
+    pub fn value(&self, rank: Rank) -> f64 {
+        self.values.iter().rev().find(|(r, _)| *r == rank).map(|(_, v)| *v).unwrap_or(rank as u32 as f64 + 1.0)
+    }
+}
+
+// a rank as `card:rank` reads it, in any case, or by its value
+pub fn rank_named(name: &str) -> Option<Rank> {
+    get_rank_array().iter().copied().find(|r| format!("{:?}", r).eq_ignore_ascii_case(name) || name.parse() == Ok(*r as u32 + 1))
+}
+
+pub fn suit_named(name: &str) -> Option<Suit> {
+    get_suit_array().iter().copied().find(|s| format!("{:?}", s).eq_ignore_ascii_case(name))
+}
+
+pub fn standard_deck() -> Vec<Card> {
+    decks(1)
+}
+
+// each standard deck in order, one after the other
+pub fn decks(count: u32) -> Vec<Card> {
+    DeckSpec::standard().cards(count)
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn decks_can_leave_cards_out() {
+        let ranks: Vec<String> = ["nine", "10", "Jack", "queen", "king", "ace"].iter().map(|r| r.to_string()).collect();
+        let euchre = DeckSpec::from_names(&ranks, &[]).unwrap();
+        let cards = euchre.cards(2);
+
+        assert_eq!(euchre.len(), 24);
+        assert_eq!(cards.len(), 48);
+        assert_eq!(cards[0].to_string(), "ace spades".to_string());
+        assert_eq!(cards[1], standard_deck()[8]);
+        assert_eq!(cards[24].id(), 52);
+        assert_eq!(DeckSpec::from_names(&[], &["hearts".to_string(), "spade".to_string()]), Err("spade".to_string()));
+        assert_eq!(DeckSpec::named("StandardDeck").map(|d| d.cards(1)), Some(standard_deck()));
+    }
+
+    #[test]
+    fn ranks_are_worth_what_the_game_says() {
+        let values = RankValues::from_names(&[("ace".to_string(), 11.0), ("face".to_string(), 10.0), ("King".to_string(), 4.0)]).unwrap();
+
+        assert_eq!(values.value(Rank::Ace), 11.0);
+        assert_eq!(values.value(Rank::Queen), 10.0);
+        assert_eq!(values.value(Rank::King), 4.0);
+        assert_eq!(values.value(Rank::Seven), 7.0);
+        assert_eq!(RankValues::from_names(&[("knave".to_string(), 10.0)]), Err("knave".to_string()));
+    }
+
+    #[test]
+    fn short_decks_are_built_in() {
+        let piquet = DeckSpec::named("PiquetDeck").unwrap().cards(1);
+        let spanish = DeckSpec::named("SpanishDeck").unwrap().cards(1);
+
+        assert_eq!(piquet.len(), 32);
+        assert_eq!(piquet.iter().filter(|c| c.rank() == Rank::Ace).count(), 4);
+        assert!(piquet.iter().all(|c| c.rank() == Rank::Ace || c.value() >= 7));
+        assert_eq!(spanish.len(), 40);
+        assert!(spanish.iter().all(|c| !(8..=10).contains(&c.value())));
     }
-    cards
 }
\ No newline at end of file
@@ -1,21 +1,35 @@
 use super::*;
-use std::fmt;
+use std::{fmt, collections::HashMap};
 
 #[derive(Debug, Clone)]
 pub struct Player {
     id: u32,
-    hand: Vec<Card>
+    hand: Vec<Card>,
+    // players are on a team of their own unless the game declares teams
+    team: u32,
+    score: f64,
+    stacks: HashMap<String, Vec<Card>>,
+    chips: f64,
+    // what they've put in the pot this betting round
+    bet: f64,
+    folded: bool,
+    // out of the game for good, unlike folding which only lasts until the pot is awarded
+    eliminated: bool
 }
 
 impl Player{
     pub fn new(id: u32) -> Player {
-        Player { hand: vec!(), id }
+        Player { hand: vec!(), id, team: id, score: 0.0, stacks: HashMap::new(), chips: 0.0, bet: 0.0, folded: false, eliminated: false }
     }
 
     pub fn get_hand(&self) -> Vec<Card> {
         self.hand.clone()
     }
 
+    pub fn hand(&self) -> &[Card] {
+        &self.hand
+    }
+
     pub fn set_hand(&mut self, hand: Vec<Card>) {
         self.hand = hand;
     }
@@ -23,6 +37,67 @@ impl Player{
     pub fn get_id(&self) -> u32 {
         self.id
     }
+
+    pub fn team(&self) -> u32 {
+        self.team
+    }
+
+    pub fn set_team(&mut self, team: u32) {
+        self.team = team;
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    pub fn set_score(&mut self, score: f64) {
+        self.score = score;
+    }
+
+    pub fn chips(&self) -> f64 {
+        self.chips
+    }
+
+    pub fn set_chips(&mut self, chips: f64) {
+        self.chips = chips;
+    }
+
+    pub fn bet(&self) -> f64 {
+        self.bet
+    }
+
+    pub fn set_bet(&mut self, bet: f64) {
+        self.bet = bet;
+    }
+
+    pub fn folded(&self) -> bool {
+        self.folded
+    }
+
+    pub fn set_folded(&mut self, folded: bool) {
+        self.folded = folded;
+    }
+
+    pub fn eliminated(&self) -> bool {
+        self.eliminated
+    }
+
+    pub fn set_eliminated(&mut self, eliminated: bool) {
+        self.eliminated = eliminated;
+    }
+
+    // stacks every player has besides their hand, declared as `stack player:tricks`
+    pub fn stacks(&self) -> &HashMap<String, Vec<Card>> {
+        &self.stacks
+    }
+
+    pub fn stack(&self, name: &str) -> Option<&[Card]> {
+        self.stacks.get(name).map(|s| s.as_slice())
+    }
+
+    pub fn set_stack(&mut self, name: &str, cards: Vec<Card>) {
+        self.stacks.insert(name.to_string(), cards);
+    }
 }
 
 impl fmt::Display for Player {
@@ -0,0 +1,720 @@
+use std::{fmt, time::Duration};
+use crate::ast::*;
+use crate::cards::{DeckSpec, RankValues};
+use crate::json::Json;
+use crate::error::{CardlangError, Span};
+use crate::version::{self, Feature, LANGUAGE_VERSION};
+
+pub(crate) const BUILT_IN_FUNCTIONS: [&str; 25] = [
+    "end", "shuffle", "shuffle_into", "winner", "burn", "count", "count_where", "move_all_matching", "exchange",
+    "pass", "next_player", "bet", "call", "raise", "fold", "award_pot", "bets_settled", "end_betting", "pay",
+    "add_score", "get_score", "summary", "chance", "phase", "value"
+];
+// `std:count` always calls the builtin, even in a game that defines a count of its own
+pub(crate) const STD_NAMESPACE: &str = "std:";
+
+// the builtin a call names, without its namespace
+pub(crate) fn builtin_name(name: &str) -> &str {
+    name.strip_prefix(STD_NAMESPACE).unwrap_or(name)
+}
+
+// builtins taking the name of a define to test cards with, which argument it is, and what it's used for
+const PREDICATE_ARGUMENTS: [(&str, usize, &str); 3] = [
+    ("count_where", 1, "count"), ("move_all_matching", 2, "match"), ("chance", 1, "test")
+];
+// globals the runtime resolves itself, which stacks and definitions can't reuse
+const GLOBALS: [&str; 6] = ["deck", "players", "current_player", "pot", "turn", "round"];
+// definitions the runtime calls itself, also as a phase's own, as `scoring:player_move`
+const CALLBACKS: [&str; 4] = ["setup", "player_move", "resolve", "bot_move"];
+// properties of the player object, which per-player stacks can't reuse
+const PLAYER_PROPERTIES: [&str; 9] = ["id", "hand", "score", "team", "is_current", "chips", "bet", "folded", "eliminated"];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning")
+        }
+    }
+}
+
+// line and column are 0 when the source position isn't known
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub line: u32,
+    pub column: u32
+}
+
+impl Diagnostic {
+    pub fn error(message: &str, line: u32, column: u32) -> Diagnostic {
+        Diagnostic{ severity: Severity::Error, message: message.to_string(), line, column }
+    }
+
+    pub fn warning(message: &str, line: u32, column: u32) -> Diagnostic {
+        Diagnostic{ severity: Severity::Warning, message: message.to_string(), line, column }
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec!(
+            ("severity", Json::String(self.severity.to_string())),
+            ("message", Json::string(&self.message)),
+            ("line", Json::Number(self.line as f64)),
+            ("column", Json::Number(self.column as f64))
+        ))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (0, _) => write!(f, "{}: {}", self.severity, self.message),
+            (line, 0) => write!(f, "{} at line {}: {}", self.severity, line, self.message),
+            (line, column) => write!(f, "{} at line {}, column {}: {}", self.severity, line, column, self.message)
+        }
+    }
+}
+
+impl From<CardlangError> for Diagnostic {
+    fn from(e: CardlangError) -> Diagnostic {
+        let message = e.message();
+        match e.span() {
+            Some(span) => Diagnostic::error(&message, span.line, span.column),
+            None => Diagnostic::error(&message, 0, 0)
+        }
+    }
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+pub fn check(ast: &[Statement]) -> Vec<Diagnostic> {
+    let mut stacks = vec!("deck".to_string(), "players".to_string());
+    let mut definitions = vec!();
+    for statement in ast.iter() {
+        match statement {
+            Statement::Declaration(Declaration{ key: GlobalKey::Stack, value: Expression::Symbol(s), .. }) => {
+                stacks.push(s.to_string());
+            },
+            Statement::Definition(d) => definitions.push(d.name.to_string()),
+            _ => ()
+        }
+    }
+
+    let mut diagnostics = vec!();
+    let version = version::language_version(ast);
+    for statement in ast.iter() {
+        if let Statement::Declaration(Declaration{ key: GlobalKey::Language, span, .. }) = statement {
+            if version > LANGUAGE_VERSION {
+                let message = format!("cardlang {} is newer than this release, which reads up to cardlang {}", version, LANGUAGE_VERSION);
+                diagnostics.push(Diagnostic::error(&message, span.start.line, span.start.column));
+            }
+        }
+    }
+
+    for statement in ast.iter() {
+        if let Statement::Definition(d) = statement {
+            let mut known = stacks.clone();
+            known.extend(d.arguments.iter().cloned());
+            let context = Context{ name: &d.name, stacks: &known, definitions: &definitions, version };
+            check_block(&d.body, &context, &mut diagnostics);
+        }
+    }
+    if let Some(e) = card_error(ast) {
+        let span = e.span().unwrap_or_default();
+        diagnostics.push(Diagnostic::error(&e.message(), span.line, span.column));
+    }
+    diagnostics.extend(settings(ast));
+    diagnostics.extend(reserved(ast));
+    diagnostics.extend(duplicates(ast));
+    diagnostics.extend(unused(ast));
+    diagnostics
+}
+
+// the first deck the game names that isn't built in or its own, or that its own can't be made from,
+// then the first rank given a value that isn't one
+pub(crate) fn card_error(ast: &[Statement]) -> Option<CardlangError> {
+    let declared: Vec<&str> = ast.iter().filter_map(|s| match s {
+        Statement::DeckDefinition(d) => Some(d.name.as_str()),
+        _ => None
+    }).collect();
+    ast.iter().find_map(|statement| match statement {
+        Statement::Declaration(Declaration{ key: GlobalKey::Deck, value: Expression::Symbol(s), span }) => {
+            match DeckSpec::named(s).is_some() || declared.contains(&s.as_str()) {
+                true => None,
+                false => Some(CardlangError::UnknownDeck(s.to_string(), span.start))
+            }
+        },
+        Statement::DeckDefinition(d) => DeckSpec::from_names(&d.ranks, &d.suits).err()
+            .map(|word| CardlangError::InvalidDeck(d.name.to_string(), word, d.span.start)),
+        _ => None
+    }).or_else(|| ast.iter().find_map(|statement| match statement {
+        Statement::ValueTable(v) => RankValues::from_names(&v.values).err().map(|word| CardlangError::UnknownRank(word, v.span.start)),
+        _ => None
+    }))
+}
+
+// settings the runtime can't use, which would otherwise be dropped without a word
+fn settings(ast: &[Statement]) -> Vec<Diagnostic> {
+    ast.iter().filter_map(|statement| match statement {
+        Statement::Declaration(Declaration{ key: GlobalKey::TurnTime, value: Expression::Number(n), span })
+            if Duration::try_from_secs_f64(*n).is_err() => {
+            Some(Diagnostic::error("turn_time needs a number of seconds from 0 up, short enough to count", span.start.line, span.start.column))
+        },
+        Statement::Declaration(Declaration{ key: GlobalKey::Decks, value: Expression::Number(n), span }) if *n < 1.0 || n.fract() != 0.0 => {
+            let message = format!("decks {} needs to be a whole number of decks, 1 or more", n);
+            Some(Diagnostic::error(&message, span.start.line, span.start.column))
+        },
+        _ => None
+    }).collect()
+}
+
+// a stack named after a builtin would shadow it. A definition may, but only for calls without `std:`
+fn reserved(ast: &[Statement]) -> Vec<Diagnostic> {
+    let is_reserved = |name: &str| match name.strip_prefix("player:") {
+        Some(stack) => PLAYER_PROPERTIES.contains(&stack),
+        None => BUILT_IN_FUNCTIONS.contains(&name) || GLOBALS.contains(&name)
+    };
+    let mut diagnostics = vec!();
+    for statement in ast.iter() {
+        let (kind, name, span) = match statement {
+            Statement::Declaration(Declaration{ key: GlobalKey::Stack, value: Expression::Symbol(s), span }) => ("stack", s, span),
+            Statement::Definition(d) if BUILT_IN_FUNCTIONS.contains(&d.name.as_str()) => {
+                let message = format!("define {} overrides the builtin {}, which std:{} still calls", d.name, d.name, d.name);
+                diagnostics.push(Diagnostic::warning(&message, d.span.start.line, d.span.start.column));
+                continue;
+            },
+            Statement::Definition(d) => ("define", &d.name, &d.span),
+            _ => continue
+        };
+        if is_reserved(name) {
+            let message = format!("{} {} uses a reserved name", kind, name);
+            diagnostics.push(Diagnostic::error(&message, span.start.line, span.start.column));
+        }
+    }
+    diagnostics
+}
+
+// a later definition silently replaces an earlier one with the same name
+fn duplicates(ast: &[Statement]) -> Vec<Diagnostic> {
+    let mut seen: Vec<&Definition> = vec!();
+    let mut diagnostics = vec!();
+    for statement in ast.iter() {
+        if let Statement::Definition(d) = statement {
+            match seen.iter().find(|s| s.name == d.name) {
+                Some(first) => {
+                    let message = format!(
+                        "define {} appears twice, at lines {} and {}",
+                        d.name, first.span.start.line, d.span.start.line
+                    );
+                    diagnostics.push(Diagnostic::error(&message, d.span.start.line, d.span.start.column));
+                },
+                None => seen.push(d)
+            }
+        }
+    }
+    diagnostics
+}
+
+struct Context<'a> {
+    name: &'a str,
+    stacks: &'a [String],
+    definitions: &'a [String],
+    version: u32
+}
+
+fn check_block(statements: &[Statement], context: &Context, diagnostics: &mut Vec<Diagnostic>) {
+    for statement in statements.iter() {
+        match statement {
+            Statement::Transfer(t) => {
+                let Span{ line, column } = t.span.start;
+                if let Some(count) = t.count.as_ref().filter(|_| !t.to_end()) {
+                    if !Feature::TransferCounts.enabled(context.version) {
+                        let message = format!(
+                            "moving {} cards at once needs cardlang {}, this moves one card in define {}",
+                            count, Feature::TransferCounts.since(), context.name
+                        );
+                        diagnostics.push(Diagnostic::warning(&message, line, column));
+                    }
+                    check_expression(count, context, diagnostics);
+                }
+                for key in [&t.from, &t.to].iter() {
+                    check_stack(key, t.span.start, context, diagnostics);
+                }
+            },
+            Statement::ForeachStatement(f) => {
+                check_stack(&f.stack, f.span.start, context, diagnostics);
+                check_block(&f.body, context, diagnostics);
+            },
+            Statement::FunctionCall(f) => check_function_call(f, context, diagnostics),
+            Statement::IfStatement(i) => {
+                check_expression(&i.expression, context, diagnostics);
+                check_block(&i.body, context, diagnostics);
+            },
+            Statement::WhileStatement(w) => {
+                check_expression(&w.expression, context, diagnostics);
+                check_block(&w.body, context, diagnostics);
+            },
+            Statement::MatchStatement(m) => {
+                check_expression(&m.expression, context, diagnostics);
+                for arm in m.arms.iter() {
+                    check_block(&arm.body, context, diagnostics);
+                }
+                if let Some(body) = &m.otherwise {
+                    check_block(body, context, diagnostics);
+                }
+            },
+            Statement::CheckStatement(c) => check_expression(&c.expression, context, diagnostics),
+            Statement::ReturnStatement(r) => check_expression(&r.expression, context, diagnostics),
+            _ => ()
+        }
+    }
+}
+
+fn check_stack(key: &str, at: Span, context: &Context, diagnostics: &mut Vec<Diagnostic>) {
+    let stack = key.split(&[' ', ':'][..]).next().unwrap_or("");
+    if stack.is_empty() {
+        let message = format!("missing stack in transfer in define {}", context.name);
+        diagnostics.push(Diagnostic::error(&message, at.line, at.column));
+    } else if !context.stacks.iter().any(|s| s == stack) {
+        let known: Vec<&str> = context.stacks.iter().map(|s| s.as_str()).collect();
+        let hint = crate::suggest::did_you_mean(stack, &known);
+        let message = format!("unknown stack '{}' in define {}{}", stack, context.name, hint);
+        diagnostics.push(Diagnostic::error(&message, at.line, at.column));
+    }
+}
+
+fn check_expression(expression: &Expression, context: &Context, diagnostics: &mut Vec<Diagnostic>) {
+    match expression {
+        Expression::FunctionCall(f) => check_function_call(f, context, diagnostics),
+        Expression::Comparison(c) => {
+            check_expression(&c.left, context, diagnostics);
+            check_expression(&c.right, context, diagnostics);
+        },
+        Expression::And(a) => {
+            check_expression(&a.left, context, diagnostics);
+            check_expression(&a.right, context, diagnostics);
+        },
+        Expression::Or(o) => {
+            check_expression(&o.left, context, diagnostics);
+            check_expression(&o.right, context, diagnostics);
+        },
+        Expression::Arithmetic(a) => {
+            check_expression(&a.left, context, diagnostics);
+            check_expression(&a.right, context, diagnostics);
+        },
+        Expression::Between(b) => {
+            for e in [&b.value, &b.low, &b.high].iter() {
+                check_expression(e, context, diagnostics);
+            }
+        },
+        _ => ()
+    }
+}
+
+fn check_function_call(f: &FunctionCall, context: &Context, diagnostics: &mut Vec<Diagnostic>) {
+    let defined = context.definitions.iter().any(|d| d == &f.name);
+    let known = BUILT_IN_FUNCTIONS.contains(&builtin_name(&f.name)) || defined;
+    if !known {
+        let mut names = BUILT_IN_FUNCTIONS.to_vec();
+        names.extend(context.definitions.iter().map(|d| d.as_str()));
+        let hint = crate::suggest::did_you_mean(&f.name, &names);
+        let message = format!("unknown function '{}' in define {}{}", f.name, context.name, hint);
+        diagnostics.push(Diagnostic::warning(&message, f.span.start.line, f.span.start.column));
+    }
+
+    // calls a define overrides are checked like any other define
+    let builtin = if defined { "" } else { builtin_name(&f.name) };
+    if let Some((_, index, verb)) = PREDICATE_ARGUMENTS.iter().find(|(name, _, _)| *name == builtin) {
+        match f.arguments.get(*index) {
+            Some(Expression::Symbol(predicate)) if context.definitions.iter().any(|d| d == predicate) => (),
+            predicate => {
+                let known: Vec<&str> = context.definitions.iter().map(|d| d.as_str()).collect();
+                let name = predicate.map(|p| p.to_string()).unwrap_or_default();
+                let hint = crate::suggest::did_you_mean(&name, &known);
+                let message = format!(
+                    "{} needs the name of a define to {} with, found '{}' in define {}{}",
+                    f.name, verb, name, context.name, hint
+                );
+                diagnostics.push(Diagnostic::error(&message, f.span.start.line, f.span.start.column));
+            }
+        }
+    }
+
+    if builtin == "pass" {
+        match f.arguments.get(1) {
+            Some(Expression::Symbol(d)) if d == "left" || d == "right" => (),
+            direction => {
+                let name = direction.map(|d| d.to_string()).unwrap_or_default();
+                let message = format!("pass needs a direction of left or right, found '{}' in define {}", name, context.name);
+                diagnostics.push(Diagnostic::error(&message, f.span.start.line, f.span.start.column));
+            }
+        }
+    }
+
+    if builtin == "shuffle" {
+        match f.arguments.get(1) {
+            None => (),
+            Some(Expression::Symbol(s)) if crate::runtime::std::Shuffle::by_name(s, None).is_some() => (),
+            Some(method) => {
+                let message = format!(
+                    "shuffle needs a method of uniform, riffle, overhand or perfect, found '{}' in define {}",
+                    method, context.name
+                );
+                diagnostics.push(Diagnostic::error(&message, f.span.start.line, f.span.start.column));
+            }
+        }
+    }
+
+    for argument in f.arguments.iter() {
+        check_expression(argument, context, diagnostics);
+    }
+}
+
+// stacks and definitions that nothing refers to - only warnings, as the game still runs
+fn unused(ast: &[Statement]) -> Vec<Diagnostic> {
+    let mut used = vec!();
+    for statement in ast.iter() {
+        find_references(statement, &mut used);
+    }
+
+    let mut diagnostics = vec!();
+    for statement in ast.iter() {
+        match statement {
+            Statement::Declaration(Declaration{ key: GlobalKey::Stack, value: Expression::Symbol(s), span }) if !is_used(s, &used) => {
+                let message = format!("stack '{}' is never used", s);
+                diagnostics.push(Diagnostic::warning(&message, span.start.line, span.start.column));
+            },
+            Statement::GameOption(o) if !used.contains(&o.name) => {
+                let message = format!("option '{}' is never used", o.name);
+                diagnostics.push(Diagnostic::warning(&message, o.span.start.line, o.span.start.column));
+            },
+            Statement::Definition(d) if !is_callback(&d.name) && !used.contains(&d.name) => {
+                let message = format!("define {} is never called", d.name);
+                diagnostics.push(Diagnostic::warning(&message, d.span.start.line, d.span.start.column));
+            },
+            _ => ()
+        }
+    }
+    diagnostics
+}
+
+fn is_callback(name: &str) -> bool {
+    let callback = name.rsplit_once(':').map(|(_, callback)| callback).unwrap_or(name);
+    CALLBACKS.contains(&callback)
+}
+
+// every stack and function named by a statement
+fn find_references(statement: &Statement, used: &mut Vec<String>) {
+    match statement {
+        Statement::Transfer(t) => {
+            use_name(&t.from, used);
+            use_name(&t.to, used);
+            if let Some(count) = &t.count {
+                find_expression_references(count, used);
+            }
+        },
+        Statement::FunctionCall(f) => find_call_references(f, used),
+        Statement::IfStatement(i) => {
+            find_expression_references(&i.expression, used);
+            for s in i.body.iter() {
+                find_references(s, used);
+            }
+        },
+        Statement::WhileStatement(w) => {
+            find_expression_references(&w.expression, used);
+            for s in w.body.iter() {
+                find_references(s, used);
+            }
+        },
+        Statement::ForeachStatement(f) => {
+            use_name(&f.stack, used);
+            for s in f.body.iter() {
+                find_references(s, used);
+            }
+        },
+        Statement::MatchStatement(m) => {
+            find_expression_references(&m.expression, used);
+            let bodies = m.arms.iter().map(|a| &a.body).chain(m.otherwise.iter());
+            for s in bodies.flatten() {
+                find_references(s, used);
+            }
+        },
+        Statement::CheckStatement(c) => find_expression_references(&c.expression, used),
+        Statement::ReturnStatement(r) => find_expression_references(&r.expression, used),
+        Statement::Definition(d) => {
+            for s in d.body.iter() {
+                find_references(s, used);
+            }
+        },
+        Statement::Declaration(_) | Statement::GameOption(_) | Statement::DeckDefinition(_) | Statement::ValueTable(_) => ()
+    }
+}
+
+fn find_call_references(f: &FunctionCall, used: &mut Vec<String>) {
+    use_name(&f.name, used);
+    for argument in f.arguments.iter() {
+        find_expression_references(argument, used);
+    }
+}
+
+fn find_expression_references(expression: &Expression, used: &mut Vec<String>) {
+    match expression {
+        Expression::Symbol(s) => use_name(s, used),
+        Expression::FunctionCall(f) => find_call_references(f, used),
+        Expression::Comparison(c) => {
+            find_expression_references(&c.left, used);
+            find_expression_references(&c.right, used);
+        },
+        Expression::And(a) => {
+            find_expression_references(&a.left, used);
+            find_expression_references(&a.right, used);
+        },
+        Expression::Or(o) => {
+            find_expression_references(&o.left, used);
+            find_expression_references(&o.right, used);
+        },
+        Expression::Arithmetic(a) => {
+            find_expression_references(&a.left, used);
+            find_expression_references(&a.right, used);
+        },
+        Expression::Between(b) => {
+            for e in [&b.value, &b.low, &b.high].iter() {
+                find_expression_references(e, used);
+            }
+        },
+        _ => ()
+    }
+}
+
+// player:hand uses player, and the hand of every player
+fn use_name(name: &str, used: &mut Vec<String>) {
+    let owner = name.split(':').next().unwrap_or("");
+    for name in [owner, name].iter() {
+        if !used.iter().any(|u| u == name) {
+            used.push(name.to_string());
+        }
+    }
+}
+
+// a per-player stack is used through whatever its owner is called, like p:tricks for player:tricks
+fn is_used(stack: &str, used: &[String]) -> bool {
+    match stack.strip_prefix("player:") {
+        Some(stack) => used.iter().any(|u| u.split(':').nth(1) == Some(stack)),
+        None => used.iter().any(|u| u == stack)
+    }
+}
+
+// every diagnostic for a source file - lexing stops at its first error, parsing recovers and carries on
+pub fn diagnose(source: &str) -> Vec<Diagnostic> {
+    let tokens = match crate::lex::lexer(source) {
+        Ok(tokens) => tokens,
+        Err(e) => return vec!(CardlangError::from(e).into())
+    };
+
+    let (ast, errors) = crate::parse::parse_all(&tokens);
+    if errors.is_empty() {
+        check(&ast)
+    } else {
+        errors.into_iter().map(|e| CardlangError::from(e).into()).collect()
+    }
+}
+
+pub fn report(file: &str, diagnostics: &[Diagnostic]) -> Json {
+    Json::object(vec!(
+        ("file", Json::string(file)),
+        ("success", Json::Bool(!has_errors(diagnostics))),
+        ("diagnostics", Json::Array(diagnostics.iter().map(|d| d.to_json()).collect()))
+    ))
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn parse_errors_carry_their_position() {
+        let diagnostics = diagnose("name test\ndefine (){\n}");
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].to_string(), "error at line 2, column 8: expected a symbol".to_string());
+    }
+
+    #[test]
+    fn it_reports_every_parse_error() {
+        let diagnostics = diagnose("define (){\n}\nplayers 2\ndefine player_move(player){\ncheck(player:id is )\nif(>){\nend()\n}\n}\ndeck 3");
+
+        assert_eq!(diagnostics.iter().map(|d| d.to_string()).collect::<Vec<String>>(), vec!(
+            "error at line 1, column 8: expected a symbol".to_string(),
+            "error at line 5, column 20: unexpected token".to_string(),
+            "error at line 6, column 4: unexpected token".to_string(),
+            "error at line 10, column 1: unexpected token".to_string()
+        ));
+    }
+
+    #[test]
+    fn it_reports_every_unknown_stack() {
+        let diagnostics = diagnose("stack middle\ndefine setup(){\ndeck > middle\ndeck > pile\n}\ndefine player_move(player){\nplayer:hand > discard\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::error("unknown stack 'pile' in define setup", 4, 1),
+            Diagnostic::error("unknown stack 'discard' in define player_move", 7, 1)
+        ));
+        assert!(has_errors(&diagnostics));
+    }
+
+    #[test]
+    fn transfers_need_a_target() {
+        let diagnostics = diagnose("define setup(){\ndeck > \n}");
+
+        assert_eq!(diagnostics[0].message, "missing stack in transfer in define setup".to_string());
+    }
+
+    #[test]
+    fn unknown_names_suggest_a_known_one() {
+        let diagnostics = diagnose("stack middle\ndefine setup(){\nshufle(deck)\ndeck > midle\nmiddle > deck\n}");
+
+        assert_eq!(diagnostics.iter().map(|d| d.message.as_str()).collect::<Vec<&str>>(), vec!(
+            "unknown function 'shufle' in define setup, did you mean 'shuffle'?",
+            "unknown stack 'midle' in define setup, did you mean 'middle'?"
+        ));
+    }
+
+    #[test]
+    fn settings_the_runtime_cant_use_are_errors() {
+        let diagnostics = diagnose("players 2\nturn_time 1e300\nturn_time -1\nturn_time 30");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::error("turn_time needs a number of seconds from 0 up, short enough to count", 2, 1),
+            Diagnostic::error("turn_time needs a number of seconds from 0 up, short enough to count", 3, 1)
+        ));
+        assert!(crate::load_game("players 2\nturn_time 1e300").is_ok());
+        assert_eq!(diagnose("decks 0\ndecks 1.5\ndecks 2"), vec!(
+            Diagnostic::error("decks 0 needs to be a whole number of decks, 1 or more", 1, 1),
+            Diagnostic::error("decks 1.5 needs to be a whole number of decks, 1 or more", 2, 1)
+        ));
+    }
+
+    #[test]
+    fn it_warns_about_unused_stacks_and_definitions() {
+        let diagnostics = diagnose("stack middle\nstack discard\nstack pile\ndefine setup(){\ndeck > middle\nif(count(pile) is 0){\ndeal()\n}\n}\ndefine deal(){\n}\ndefine unused(){\n}\ndefine scoring:setup(){\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::warning("stack 'discard' is never used", 2, 1),
+            Diagnostic::warning("define unused is never called", 12, 1)
+        ));
+        assert!(!has_errors(&diagnostics));
+    }
+
+    #[test]
+    fn duplicate_definitions_are_errors() {
+        let diagnostics = diagnose("define setup(){\nshuffle(deck)\n}\n\ndefine setup(){\n}");
+
+        assert_eq!(diagnostics, vec!(Diagnostic::error("define setup appears twice, at lines 1 and 5", 5, 1)));
+    }
+
+    #[test]
+    fn builtin_names_are_reserved() {
+        let diagnostics = diagnose("stack count\ndefine end(){\n}\ndefine setup(){\ndeck > count\nend()\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::error("stack count uses a reserved name", 1, 1),
+            Diagnostic::warning("define end overrides the builtin end, which std:end still calls", 2, 1)
+        ));
+    }
+
+    #[test]
+    fn transfer_counts_need_the_version_that_added_them() {
+        let source = "define setup(){\ndeck > players 3\n}";
+
+        assert_eq!(diagnose(source), vec!(
+            Diagnostic::warning("moving 3 cards at once needs cardlang 2, this moves one card in define setup", 2, 1)
+        ));
+        assert_eq!(diagnose(&format!("cardlang 2\n{}", source)), vec!());
+        assert_eq!(diagnose(&format!("cardlang 9\n{}", source))[0],
+            Diagnostic::error("cardlang 9 is newer than this release, which reads up to cardlang 2", 1, 1));
+    }
+
+    #[test]
+    fn defines_can_override_a_builtin() {
+        let diagnostics = diagnose("define pass(n){\n}\ndefine setup(){\npass(3)\nstd:pass(3, left)\nstd:passs(3)\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::warning("unknown function 'std:passs' in define setup", 6, 1),
+            Diagnostic::warning("define pass overrides the builtin pass, which std:pass still calls", 1, 1)
+        ));
+    }
+
+    #[test]
+    fn count_where_needs_a_known_predicate() {
+        let diagnostics = diagnose("define is_heart(card){\nreturn(card:suit is Hearts)\n}\ndefine player_move(player){\nif(count_where(player:hand, is_hart) is 0){\nend()\n}\nif(count_where(player:hand, is_heart) is 1){\nend()\n}\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::error("count_where needs the name of a define to count with, found 'is_hart' in define player_move, did you mean 'is_heart'?", 5, 4)
+        ));
+    }
+
+    #[test]
+    fn move_all_matching_needs_a_known_predicate() {
+        let diagnostics = diagnose("stack sets\ndefine player_move(player){\nmove_all_matching(player:hand, sets, is_set)\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::error("move_all_matching needs the name of a define to match with, found 'is_set' in define player_move", 3, 1)
+        ));
+    }
+
+    #[test]
+    fn passes_go_left_or_right() {
+        let diagnostics = diagnose("define setup(){\npass(3, left)\npass(3, across)\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::error("pass needs a direction of left or right, found 'across' in define setup", 3, 1)
+        ));
+    }
+
+    #[test]
+    fn shuffles_are_known_methods() {
+        let diagnostics = diagnose("define setup(){\nshuffle(deck, riffle 7)\nshuffle(deck, overhnd)\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::error("shuffle needs a method of uniform, riffle, overhand or perfect, found 'overhnd' in define setup", 3, 1)
+        ));
+    }
+
+    #[test]
+    fn per_player_stacks_are_used_through_their_owner() {
+        let diagnostics = diagnose("stack player:tricks\nstack player:pile\nstack player:score\ndefine player_move(p){\ndeck > p:tricks\n}");
+
+        assert_eq!(diagnostics, vec!(
+            Diagnostic::error("stack player:score uses a reserved name", 3, 1),
+            Diagnostic::warning("stack 'player:pile' is never used", 2, 1),
+            Diagnostic::warning("stack 'player:score' is never used", 3, 1)
+        ));
+    }
+
+    #[test]
+    fn unknown_functions_are_warnings() {
+        let diagnostics = diagnose("define setup(){\nfly()\n}");
+
+        assert_eq!(diagnostics[0].to_string(), "warning at line 2, column 1: unknown function 'fly' in define setup".to_string());
+        assert!(!has_errors(&diagnostics));
+    }
+
+    #[test]
+    fn it_builds_a_json_report() {
+        let diagnostics = diagnose("1foo");
+
+        assert_eq!(
+            report("game.card", &diagnostics).to_string(),
+            r#"{"file":"game.card","success":false,"diagnostics":[{"severity":"error","message":"unrecognised token","line":1,"column":1}]}"#
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use crate::ast::*;
+
+// renders a game specification as markdown rules for people who don't read cardlang
+pub fn document(ast: &[Statement]) -> String {
+    let ast = &crate::version::lower(ast.to_vec());
+    let mut name = "Untitled game".to_string();
+    let mut overview = vec!();
+    let mut stacks = vec!();
+    let mut player_stacks = vec!();
+    let mut face_down = vec!();
+    for statement in ast.iter() {
+        if let Statement::ValueTable(v) = statement {
+            let values: Vec<String> = v.values.iter().map(|(rank, value)| format!("{} {}", rank, value)).collect();
+            overview.push(format!("- Card values: {}, any other rank its number", values.join(", ")));
+        }
+        if let Statement::DeckDefinition(d) = statement {
+            let ranks = if d.ranks.is_empty() { "every rank".to_string() } else { d.ranks.join(", ") };
+            let suits = if d.suits.is_empty() { "each suit".to_string() } else { d.suits.join(", ") };
+            overview.push(format!("- Deck: {} ({} of {})", d.name, ranks, suits));
+        }
+        if let Statement::Declaration(d) = statement {
+            let value = describe_expression(&d.value);
+            match d.key {
+                GlobalKey::Name => name = value,
+                GlobalKey::Players => overview.push(format!("- Players: {}", value)),
+                GlobalKey::Teams => overview.push(format!("- Teams: {}", value)),
+                GlobalKey::Deal => overview.push(format!("- Cards dealt to each player: {}", value)),
+                GlobalKey::HandSize => overview.push(format!("- Hand size: {}", value)),
+                GlobalKey::Chips => overview.push(format!("- Starting chips: {}", value)),
+                GlobalKey::MatchTo => overview.push(format!("- Hands are played until a player scores {}", value)),
+                GlobalKey::TurnTime => overview.push(format!("- Time per turn: {} seconds, then a move is made for the player", value)),
+                GlobalKey::Turns => overview.push("- Turns: everyone moves at once, then the round is resolved".to_string()),
+                GlobalKey::Deck => overview.push(format!("- Deck: {}", value)),
+                GlobalKey::Decks => overview.push(format!("- Decks shuffled together: {}", value)),
+                GlobalKey::CurrentPlayer => overview.push(format!("- First player: {}", value)),
+                GlobalKey::FaceDown => face_down.push(value),
+                GlobalKey::Language => (),
+                GlobalKey::Stack => match value.strip_prefix("the player's ") {
+                    Some(stack) => player_stacks.push(stack.to_string()),
+                    None => stacks.push(value)
+                }
+            }
+        }
+    }
+
+    if !stacks.is_empty() {
+        overview.push(format!("- Stacks: {}", stacks.join(", ")));
+    }
+    if !player_stacks.is_empty() {
+        overview.push(format!("- Each player also has: {}", player_stacks.join(", ")));
+    }
+    if !face_down.is_empty() {
+        overview.push(format!("- Kept face down: {}", face_down.join(", ")));
+    }
+    let options: Vec<String> = crate::options::options(ast).iter()
+        .map(|o| format!("{} ({})", o.name, describe_expression(&o.value)))
+        .collect();
+    if !options.is_empty() {
+        overview.push(format!("- Options, which can be changed for house rules: {}", options.join(", ")));
+    }
+
+    let mut sections = vec!(format!("# {}", name));
+    if !overview.is_empty() {
+        sections.push(format!("## Overview\n{}", overview.join("\n")));
+    }
+
+    let definitions: Vec<&Definition> = ast.iter().filter_map(|s| match s {
+        Statement::Definition(d) => Some(d),
+        _ => None
+    }).collect();
+
+    for (callback, title) in [("setup", "Setup"), ("player_move", "Each turn"), ("resolve", "Once everyone has moved")].iter() {
+        if let Some(d) = definitions.iter().find(|d| d.name == *callback) {
+            sections.push(format!("## {}\n{}", title, describe_block(&d.body, 0)));
+        }
+    }
+
+    let mut win_conditions = vec!();
+    for d in definitions.iter() {
+        find_win_conditions(&d.body, &[], &mut win_conditions);
+    }
+    if win_conditions.is_empty() {
+        sections.push("## Winning\nNo win conditions are defined.".to_string());
+    } else {
+        sections.push(format!("## Winning\n{}", win_conditions.join("\n")));
+    }
+
+    for d in definitions.iter().filter(|d| !["setup", "player_move", "resolve"].contains(&d.name.as_str())) {
+        sections.push(format!("## {}({})\n{}", d.name, d.arguments.join(", "), describe_block(&d.body, 0)));
+    }
+
+    format!("{}\n", sections.join("\n\n"))
+}
+
+// the game's own summary: a `.( comment )` among the declarations, before any definition
+pub fn description(source: &str) -> Option<String> {
+    let header_end = source.lines()
+        .take_while(|line| !["define ", "def "].iter().any(|d| line.trim_start().starts_with(d)))
+        .map(|line| line.len() + 1)
+        .sum::<usize>();
+    let start = source[..header_end.min(source.len())].find(".(")? + 2;
+    let mut depth = 1;
+    for (i, c) in source[start..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => ()
+        }
+        if depth == 0 {
+            let text = source[start..start + i].split_whitespace().collect::<Vec<&str>>().join(" ");
+            return if text.is_empty() { None } else { Some(capitalise(&text)) };
+        }
+    }
+    None
+}
+
+// the documentation as plain text for `show rules`, led by the game's description
+pub fn rules(ast: &[Statement], description: Option<&str>) -> String {
+    let mut lines = vec!();
+    for line in document(ast).lines() {
+        if let Some(title) = line.strip_prefix("## ") {
+            lines.push(format!("{}:", title));
+        } else if let Some(name) = line.strip_prefix("# ") {
+            lines.push(name.to_string());
+            if let Some(description) = description {
+                lines.push(description.to_string());
+            }
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+fn describe_block(statements: &[Statement], depth: usize) -> String {
+    let indent = "  ".repeat(depth);
+    let mut lines = vec!();
+    for statement in statements.iter() {
+        match statement {
+            Statement::IfStatement(i) => {
+                lines.push(format!("{}- If {}:", indent, describe_expression(&i.expression)));
+                lines.push(describe_block(&i.body, depth + 1));
+            },
+            Statement::WhileStatement(w) => {
+                lines.push(format!("{}- While {}:", indent, describe_expression(&w.expression)));
+                lines.push(describe_block(&w.body, depth + 1));
+            },
+            Statement::ForeachStatement(f) => {
+                lines.push(format!("{}- For each {} in {}:", indent, f.variable, describe_stack(&f.stack)));
+                lines.push(describe_block(&f.body, depth + 1));
+            },
+            Statement::MatchStatement(m) => {
+                for (condition, body) in match_conditions(m) {
+                    lines.push(format!("{}- If {}:", indent, condition));
+                    lines.push(describe_block(body, depth + 1));
+                }
+            },
+            _ => lines.push(format!("{}- {}", indent, describe_statement(statement)))
+        }
+    }
+    lines.join("\n")
+}
+
+fn describe_statement(statement: &Statement) -> String {
+    match statement {
+        Statement::Transfer(t) => describe_transfer(t),
+        Statement::FunctionCall(f) => match f.name.as_str() {
+            "shuffle" => match (f.arguments.first(), f.arguments.get(1), f.arguments.get(2)) {
+                (Some(stack), Some(method), times) if method.to_string() != "uniform" => format!(
+                    "Give {} {} {} shuffle{}.",
+                    describe_stack(&stack.to_string()),
+                    times.map(describe_expression).unwrap_or_else(|| "a".to_string()),
+                    method,
+                    if times.is_some_and(|t| t.to_string() != "1") { "s" } else { "" }
+                ),
+                (Some(stack), _, _) => format!("Shuffle {}.", describe_stack(&stack.to_string())),
+                _ => "Shuffle the deck.".to_string()
+            },
+            "shuffle_into" if f.arguments.len() == 2 => format!(
+                "Shuffle {} into {}.",
+                describe_stack(&f.arguments[0].to_string()), describe_stack(&f.arguments[1].to_string())
+            ),
+            "move_all_matching" if f.arguments.len() == 3 => format!(
+                "Move every card in {} that passes {} to {}.",
+                describe_stack(&f.arguments[0].to_string()), describe_expression(&f.arguments[2]),
+                describe_stack(&f.arguments[1].to_string())
+            ),
+            "exchange" => match f.arguments.as_slice() {
+                [a, b] => format!("{} and {} swap hands.", capitalise(&describe_expression(a)), describe_expression(b)),
+                [a, b, n] => format!(
+                    "{} and {} swap the top {} of their hands.",
+                    capitalise(&describe_expression(a)), describe_expression(b), describe_count(n)
+                ),
+                _ => format!("Do {}.", describe_function(f))
+            },
+            "pass" if f.arguments.len() == 2 => format!(
+                "Every player chooses {} from their hand to pass to the player on their {}.",
+                describe_count(&f.arguments[0]), describe_expression(&f.arguments[1])
+            ),
+            "bet" | "raise" if f.arguments.len() == 2 => format!(
+                "{} {} {}.",
+                capitalise(&describe_expression(&f.arguments[0])),
+                if f.name == "bet" { "bets" } else { "raises by" },
+                describe_expression(&f.arguments[1])
+            ),
+            "call" | "fold" if f.arguments.len() == 1 => format!("{} {}s.", capitalise(&describe_expression(&f.arguments[0])), f.name),
+            "add_score" if f.arguments.len() == 2 => format!(
+                "{} scores {}.", capitalise(&describe_expression(&f.arguments[0])), describe_expression(&f.arguments[1])
+            ),
+            "award_pot" if f.arguments.len() == 1 => format!("{} wins the pot.", capitalise(&describe_expression(&f.arguments[0]))),
+            "end_betting" => "The betting round ends.".to_string(),
+            "burn" => match f.arguments.first() {
+                Some(n) => format!("Burn {} from the top of the deck, face down.", describe_count(n)),
+                None => "Burn a card from the top of the deck, face down.".to_string()
+            },
+            "next_player" => "Play passes to the next player.".to_string(),
+            "phase" if f.arguments.len() == 1 => format!("Play moves on to the {} phase.", f.arguments[0]),
+            "end" => "The game ends.".to_string(),
+            "winner" => format!("{} wins.", capitalise(&describe_arguments(&f.arguments))),
+            _ => format!("Do {}.", describe_function(f))
+        },
+        Statement::CheckStatement(c) => format!("Only allowed if {}.", describe_expression(&c.expression)),
+        Statement::ReturnStatement(r) => format!("The result is {}.", describe_expression(&r.expression)),
+        Statement::IfStatement(i) => format!("If {}.", describe_expression(&i.expression)),
+        Statement::WhileStatement(w) => format!("While {}.", describe_expression(&w.expression)),
+        Statement::ForeachStatement(f) => format!("For each {} in {}.", f.variable, describe_stack(&f.stack)),
+        Statement::MatchStatement(m) => format!("Depending on {}.", describe_expression(&m.expression)),
+        Statement::Declaration(d) => format!("Set {:?} to {}.", d.key, describe_expression(&d.value)),
+        Statement::GameOption(o) => format!("Set the option {} to {}.", o.name, describe_expression(&o.value)),
+        Statement::DeckDefinition(d) => format!("Play with the {} deck.", d.name),
+        Statement::ValueTable(v) => format!(
+            "Give the ranks values: {}.",
+            v.values.iter().map(|(rank, value)| format!("{} {}", rank, value)).collect::<Vec<String>>().join(", ")
+        ),
+        Statement::Definition(d) => format!("Define {}.", d.name)
+    }
+}
+
+fn describe_transfer(t: &Transfer) -> String {
+    let from = describe_stack(&t.from);
+    let end = if t.from_bottom() { "bottom" } else { "top" };
+    let dealt_from = if t.from_bottom() { format!("the bottom of {}", from) } else { from.to_string() };
+    let to = match t.to.as_str() {
+        to if t.to_bottom() => format!("the bottom of {}", describe_stack(to)),
+        to => describe_stack(to)
+    };
+    match (t.to.as_str(), &t.count) {
+        ("players", Some(_)) if t.to_end() => format!("Deal all of {} out between the players.", from),
+        ("players", None) => format!("Deal one card from {} to each player.", dealt_from),
+        ("players", Some(n)) => format!("Deal {} cards from {} to each player.", describe_expression(n), dealt_from),
+        (_, Some(_)) if t.to_end() => format!("Move all of {} to {}.", from, to),
+        (_, Some(n)) => format!("Move the {} {} cards of {} to {}.", end, describe_expression(n), from, to),
+        (_, None) => format!("Move the {} card of {} to {}.", end, from, to)
+    }
+}
+
+fn describe_stack(key: &str) -> String {
+    match key {
+        "deck" => "the deck".to_string(),
+        "players" => "the players".to_string(),
+        key if key.contains(':') => describe_symbol(key),
+        key => format!("the {}", key)
+    }
+}
+
+fn describe_symbol(symbol: &str) -> String {
+    match symbol {
+        "current_player" => "the current player".to_string(),
+        "pot" => "the pot".to_string(),
+        symbol => match symbol.split_once(':') {
+            Some((owner, "id")) => format!("the {}", owner),
+            Some((owner, property)) => format!("the {}'s {}", owner, property),
+            None => symbol.to_string()
+        }
+    }
+}
+
+fn describe_expression(expression: &Expression) -> String {
+    match expression {
+        Expression::Symbol(s) => describe_symbol(s),
+        Expression::String(s) => s.to_string(),
+        Expression::Number(n) => n.to_string(),
+        Expression::Bool(b) => b.to_string(),
+        Expression::Comparison(c) => {
+            let operator = if c.negative { "is not" } else { "is" };
+            format!("{} {} {}", describe_expression(&c.left), operator, describe_expression(&c.right))
+        },
+        Expression::And(a) => format!("{} and {}", describe_expression(&a.left), describe_expression(&a.right)),
+        Expression::Or(o) => format!("{} or {}", describe_expression(&o.left), describe_expression(&o.right)),
+        Expression::Arithmetic(a) => {
+            let operator = match a.operator {
+                Operator::Add => "plus",
+                Operator::Subtract => "minus",
+                Operator::Multiply => "times",
+                Operator::Divide => "divided by",
+                Operator::Modulo => "modulo"
+            };
+            format!("{} {} {}", describe_expression(&a.left), operator, describe_expression(&a.right))
+        },
+        Expression::Between(b) => format!(
+            "{} is between {} and {}",
+            describe_expression(&b.value), describe_expression(&b.low), describe_expression(&b.high)
+        ),
+        Expression::FunctionCall(f) => match f.name.as_str() {
+            "count" => format!("the number of cards in {}", describe_arguments(&f.arguments)),
+            "bets_settled" => "every player still in has matched the highest bet".to_string(),
+            "count_where" if f.arguments.len() == 2 => format!(
+                "the number of cards in {} that pass {}",
+                describe_expression(&f.arguments[0]), describe_expression(&f.arguments[1])
+            ),
+            "chance" if f.arguments.len() == 2 => format!(
+                "the chance that a card from {} passes {}",
+                describe_stack(&f.arguments[0].to_string()), describe_expression(&f.arguments[1])
+            ),
+            _ => describe_function(f)
+        }
+    }
+}
+
+// each arm as the condition it runs under - the other arm runs when none of the patterns match
+fn match_conditions(m: &MatchStatement) -> Vec<(String, &[Statement])> {
+    let subject = describe_expression(&m.expression);
+    let patterns: Vec<String> = m.arms.iter().map(|a| describe_expression(&a.pattern)).collect();
+    let mut conditions: Vec<(String, &[Statement])> = m.arms.iter().zip(patterns.iter())
+        .map(|(arm, pattern)| (format!("{} is {}", subject, pattern), arm.body.as_slice()))
+        .collect();
+
+    if let Some(body) = &m.otherwise {
+        let others = match patterns.split_last() {
+            Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+            _ => patterns.join("")
+        };
+        let condition = if others.is_empty() { subject } else { format!("{} is not {}", subject, others) };
+        conditions.push((condition, body.as_slice()));
+    }
+    conditions
+}
+
+fn describe_count(n: &Expression) -> String {
+    match n {
+        Expression::Number(n) if *n == 1.0 => "a card".to_string(),
+        n => format!("{} cards", describe_expression(n))
+    }
+}
+
+fn describe_function(f: &FunctionCall) -> String {
+    format!("{}({})", f.name, describe_arguments(&f.arguments))
+}
+
+fn describe_arguments(arguments: &[Expression]) -> String {
+    arguments.iter().map(describe_expression).collect::<Vec<String>>().join(", ")
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => format!("{}{}", first.to_uppercase(), chars.as_str()),
+        None => String::new()
+    }
+}
+
+// every winner() call, along with the if conditions leading up to it
+fn find_win_conditions(statements: &[Statement], conditions: &[String], found: &mut Vec<String>) {
+    for statement in statements.iter() {
+        match statement {
+            Statement::IfStatement(i) => {
+                let mut nested = conditions.to_vec();
+                nested.push(describe_expression(&i.expression));
+                find_win_conditions(&i.body, &nested, found);
+            },
+            Statement::WhileStatement(w) => {
+                let mut nested = conditions.to_vec();
+                nested.push(describe_expression(&w.expression));
+                find_win_conditions(&w.body, &nested, found);
+            },
+            Statement::ForeachStatement(f) => find_win_conditions(&f.body, conditions, found),
+            Statement::MatchStatement(m) => {
+                for (condition, body) in match_conditions(m) {
+                    let mut nested = conditions.to_vec();
+                    nested.push(condition);
+                    find_win_conditions(body, &nested, found);
+                }
+            },
+            Statement::FunctionCall(f) if f.name == "winner" => {
+                let player = capitalise(&describe_arguments(&f.arguments));
+                if conditions.is_empty() {
+                    found.push(format!("- {} wins.", player));
+                } else {
+                    found.push(format!("- {} wins when {}.", player, conditions.join(" and ")));
+                }
+            },
+            _ => ()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn turns() -> Vec<Statement> {
+        let source = "name turns\nplayers 2\nteams 2\nstack middle\nstack player:tricks\ndefine setup(){\nshuffle(deck)\nburn(2)\ndeck > players end\n}\ndefine player_move(player){\ncheck(player:id is current_player)\nplayer:hand > middle\nif(count(player:hand) is 0){\nwinner(player:id)\nend()\n}\nnext_player()\n}";
+        let tokens = crate::lex::lexer(source).unwrap();
+        crate::parse::parse(&tokens).unwrap()
+    }
+
+    #[test]
+    fn rules_lead_with_the_description() {
+        let source = crate::examples::find("go_fish").unwrap();
+        let description = description(source);
+        let rules = rules(&crate::parse_source(source).unwrap(), description.as_deref());
+
+        assert_eq!(description, Some("On their turn a player asks the player on their left for a rank they hold. If that player has any of it they hand them all over and the asker goes again, otherwise the asker fishes a card from the pond and play moves on. Four of a rank make a book, which is laid down, and once all thirteen books are down whoever laid the most wins".to_string()));
+        assert!(rules.starts_with(&format!("go_fish\n{}\n\nOverview:\n- Deck: StandardDeck\n- Players: 3\n", description.unwrap())));
+        assert!(!rules.contains('#'));
+        assert_eq!(super::description("players 2\n.(  )\n"), None);
+        assert_eq!(super::description(crate::examples::find("turns").unwrap()), None);
+    }
+
+    #[test]
+    fn it_documents_every_section() {
+        let expected = "# turns
+
+## Overview
+- Players: 2
+- Teams: 2
+- Stacks: middle
+- Each player also has: tricks
+
+## Setup
+- Shuffle the deck.
+- Burn 2 cards from the top of the deck, face down.
+- Deal all of the deck out between the players.
+
+## Each turn
+- Only allowed if the player is the current player.
+- Move the top card of the player's hand to the middle.
+- If the number of cards in the player's hand is 0:
+  - The player wins.
+  - The game ends.
+- Play passes to the next player.
+
+## Winning
+- The player wins when the number of cards in the player's hand is 0.
+";
+
+        assert_eq!(document(&turns()), expected.to_string());
+    }
+
+    #[test]
+    fn match_arms_read_as_conditions() {
+        let source = "define player_move(player){\nmatch count(player:hand) {\n0 {\nwinner(player:id)\n}\n1 {\nend()\n}\n2 {\n}\nother {\nnext_player()\n}\n}\n}";
+        let tokens = crate::lex::lexer(source).unwrap();
+        let doc = document(&crate::parse::parse(&tokens).unwrap());
+
+        assert!(doc.contains("## Each turn\n- If the number of cards in the player's hand is 0:\n  - The player wins.\n- If the number of cards in the player's hand is 1:\n  - The game ends."));
+        assert!(doc.contains("- If the number of cards in the player's hand is not 0, 1 or 2:\n  - Play passes to the next player."));
+        assert!(doc.contains("- The player wins when the number of cards in the player's hand is 0."));
+    }
+
+    #[test]
+    fn games_without_a_winner_say_so() {
+        let ast = vec!(Statement::Declaration(Declaration{
+            key: GlobalKey::Name,
+            value: Expression::Symbol("solitaire".to_string()),
+            span: SourceSpan::default()
+        }));
+
+        assert_eq!(document(&ast), "# solitaire\n\n## Winning\nNo win conditions are defined.\n".to_string());
+    }
+}
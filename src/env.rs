@@ -0,0 +1,126 @@
+use crate::interpreter::Game;
+
+// rewards for the player whose move was just taken
+pub const WIN_REWARD: f64 = 1.0;
+pub const LOSS_REWARD: f64 = -1.0;
+pub const ILLEGAL_MOVE_REWARD: f64 = -0.1;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Observation {
+    pub current_player: usize,
+    pub legal_moves: Vec<usize>,
+    pub deck_size: usize,
+    pub hand_sizes: Vec<usize>,
+    // Game::encode_observation for the player to move
+    pub encoded: Vec<f64>
+}
+
+// a gym style environment over a game, where each action is the number of the player to move
+#[derive(Debug, Clone)]
+pub struct Env {
+    game: Game,
+    seed: Option<u64>,
+    episode: u64
+}
+
+impl Env {
+    pub fn new(game: Game) -> Env {
+        Env { game, seed: None, episode: 0 }
+    }
+
+    // each reset uses the next seed along, so episodes differ but can be replayed
+    pub fn with_seed(game: Game, seed: u64) -> Env {
+        Env { game, seed: Some(seed), episode: 0 }
+    }
+
+    pub fn reset(&mut self) -> Observation {
+        if let Some(seed) = self.seed {
+            self.game.set_seed(seed.wrapping_add(self.episode));
+        }
+        self.episode += 1;
+        self.game.start();
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: usize) -> (Observation, f64, bool) {
+        if self.game.try_move(action).is_err() {
+            return (self.observe(), ILLEGAL_MOVE_REWARD, self.is_done());
+        }
+
+        let done = self.is_done();
+        let reward = if !done {
+            0.0
+        } else if self.game.state().winners.contains(&action) {
+            WIN_REWARD
+        } else {
+            LOSS_REWARD
+        };
+
+        (self.observe(), reward, done)
+    }
+
+    // the number of discrete actions, which are the player numbers 1..=n
+    pub fn action_space(&self) -> usize {
+        self.game.state().player_count
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    fn is_done(&self) -> bool {
+        self.game.get_status() == "game over"
+    }
+
+    fn observe(&self) -> Observation {
+        let state = self.game.state();
+        Observation {
+            current_player: state.current_player,
+            legal_moves: self.game.legal_moves(),
+            deck_size: self.game.deck().len(),
+            hand_sizes: (1..=state.player_count)
+                .filter_map(|n| self.game.player(n).map(|p| p.hand.len()))
+                .collect(),
+            encoded: self.game.encode_observation(state.current_player)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn env() -> Env {
+        let source = "players 2\ndefine setup(){\ndeck > players\n}\ndefine player_move(player){\ncheck(player:id is current_player)\nplayer:hand > deck\nif(count(player:hand) is 0){\nwinner(player:id)\nend()\n}\nnext_player()\n}";
+        Env::with_seed(crate::load_game(source).unwrap(), 1)
+    }
+
+    #[test]
+    fn it_resets_to_a_fresh_game() {
+        let mut env = env();
+        let observation = env.reset();
+
+        assert_eq!(env.action_space(), 2);
+        assert_eq!(observation.current_player, 1);
+        assert_eq!(observation.legal_moves, vec!(1));
+        assert_eq!(observation.deck_size, 50);
+        assert_eq!(observation.hand_sizes, vec!(1, 1));
+        assert_eq!(observation.encoded.len(), env.game().observation_size());
+    }
+
+    #[test]
+    fn winning_moves_are_rewarded() {
+        let mut env = env();
+        env.reset();
+
+        let (_, reward, done) = env.step(2);
+        assert_eq!((reward, done), (ILLEGAL_MOVE_REWARD, false));
+
+        assert_eq!(env.step(1).1, 0.0);
+        assert_eq!(env.step(2).1, 0.0);
+
+        let (observation, reward, done) = env.step(1);
+        assert_eq!((reward, done), (WIN_REWARD, true));
+        assert!(observation.legal_moves.is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+use std::{error::Error, fmt};
+use crate::lex::LexError;
+use crate::parse::ParseError;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RuntimeError {
+    NotActive,
+    UnknownPlayer(usize),
+    NoPlayerMove,
+    CheckFailed(String, Span),
+    // the player, how many cards they would have held, and the game's hand size
+    HandSizeExceeded(usize, usize, u32),
+    Eliminated(usize),
+    AlreadyMoved(usize),
+    // the player, how many cards they chose to pass, and how many the pass takes
+    WrongPass(usize, usize, usize),
+    // the define that was running, and the steps it ran for
+    OutOfSteps(String, usize),
+    // the define that was called, and how deep the calls already were
+    TooDeep(String, usize)
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::NotActive => write!(f, "the game isn't active"),
+            RuntimeError::UnknownPlayer(n) => write!(f, "there is no player {}", n),
+            RuntimeError::NoPlayerMove => write!(f, "the game doesn't define player_move"),
+            RuntimeError::CheckFailed(name, _) => write!(f, "a check failed in {}", name),
+            RuntimeError::Eliminated(n) => write!(f, "player {} has been eliminated", n),
+            RuntimeError::AlreadyMoved(n) => write!(f, "player {} has already moved this round", n),
+            RuntimeError::WrongPass(player, chosen, count) => {
+                write!(f, "player {} chose {} cards to pass, where the pass takes {}", player, chosen, count)
+            },
+            RuntimeError::OutOfSteps(name, steps) => write!(f, "{} ran for {} steps without finishing, and was stopped", name, steps),
+            RuntimeError::TooDeep(name, depth) => write!(f, "{} was called {} calls deep, and was stopped", name, depth),
+            RuntimeError::HandSizeExceeded(player, cards, limit) => {
+                write!(f, "player {} would end the turn with {} cards, more than the hand size of {}", player, cards, limit)
+            }
+        }
+    }
+}
+
+impl Error for RuntimeError {}
+
+// one reason a move went wrong, a move can collect several
+#[derive(Debug, PartialEq, Clone)]
+pub enum Violation {
+    CheckFailed(String, Span),
+    // the two sides of a transfer that had nothing to move, or nowhere to move it
+    InvalidTransfer(String, String, Span),
+    TransferDenied(String, String, Span),
+    Rejected(RuntimeError)
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (message, span) = match self {
+            Violation::CheckFailed(name, span) => (format!("a check failed in {}", name), *span),
+            Violation::InvalidTransfer(from, to, span) => (format!("{} > {} couldn't move any cards", from, to), *span),
+            Violation::TransferDenied(from, to, span) => (format!("the host denied {} > {}", from, to), *span),
+            Violation::Rejected(e) => (e.to_string(), Span::default())
+        };
+        match span {
+            Span{ line: 0, .. } => write!(f, "{}", message),
+            Span{ line, column } => write!(f, "{} at line {}, column {}", message, line, column)
+        }
+    }
+}
+
+// everything that can go wrong between reading a game file and playing it
+#[derive(Debug, PartialEq, Clone)]
+pub enum CardlangError {
+    Lex(LexError),
+    Parse(ParseError),
+    Runtime(RuntimeError),
+    UnreadableFile(String),
+    UnwritableFile(String),
+    InvalidFile(String),
+    InvalidReplay(String),
+    UnknownExample(String),
+    // a `cardlang n` pragma newer than this release
+    UnsupportedVersion(u32),
+    // `--opt name=value` for an option the game doesn't declare
+    UnknownOption(String),
+    // `deck name` for a deck neither built in nor declared by the game
+    UnknownDeck(String, Span),
+    // a game's own deck, and the word in it that isn't a rank or a suit
+    InvalidDeck(String, String, Span),
+    // a rank in `values { .. }` that no card has
+    UnknownRank(String, Span)
+}
+
+impl CardlangError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            CardlangError::Lex(e) => Some(Span{ line: e.line_number, column: e.column }),
+            CardlangError::Parse(e) => Some(Span{ line: e.line_number, column: e.column }),
+            CardlangError::Runtime(RuntimeError::CheckFailed(_, span)) if span.line > 0 => Some(*span),
+            CardlangError::UnknownDeck(_, span) | CardlangError::InvalidDeck(_, _, span) | CardlangError::UnknownRank(_, span) => Some(*span),
+            _ => None
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            CardlangError::Lex(e) => e.to_string(),
+            CardlangError::Parse(e) => e.to_string(),
+            CardlangError::Runtime(e) => e.to_string(),
+            CardlangError::UnreadableFile(path) => format!("unable to read '{}'", path),
+            CardlangError::UnwritableFile(path) => format!("unable to write '{}'", path),
+            CardlangError::InvalidFile(path) => format!("'{}' isn't in the expected format", path),
+            CardlangError::InvalidReplay(reason) => format!("unable to replay the game: {}", reason),
+            CardlangError::UnknownExample(name) => format!("no example called '{}', try `cardlang examples`", name),
+            CardlangError::UnsupportedVersion(v) => format!(
+                "the game is written for cardlang {}, this release reads up to cardlang {}", v, crate::version::LANGUAGE_VERSION
+            ),
+            CardlangError::UnknownOption(name) => format!("the game has no option called '{}'", name),
+            CardlangError::UnknownDeck(name, _) => format!("there's no deck called '{}'", name),
+            CardlangError::InvalidDeck(deck, word, _) => format!("'{}' in deck {} isn't a rank or a suit", word, deck),
+            CardlangError::UnknownRank(rank, _) => format!("'{}' isn't a rank, so can't be given a value", rank)
+        }
+    }
+}
+
+impl fmt::Display for CardlangError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span() {
+            Some(Span{ line, column: 0 }) => write!(f, "{} at line {}", self.message(), line),
+            Some(Span{ line, column }) => write!(f, "{} at line {}, column {}", self.message(), line, column),
+            None => write!(f, "{}", self.message())
+        }
+    }
+}
+
+impl Error for CardlangError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CardlangError::Lex(e) => Some(e),
+            CardlangError::Parse(e) => Some(e),
+            CardlangError::Runtime(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<LexError> for CardlangError {
+    fn from(e: LexError) -> CardlangError {
+        CardlangError::Lex(e)
+    }
+}
+
+impl From<ParseError> for CardlangError {
+    fn from(e: ParseError) -> CardlangError {
+        CardlangError::Parse(e)
+    }
+}
+
+impl From<RuntimeError> for CardlangError {
+    fn from(e: RuntimeError) -> CardlangError {
+        CardlangError::Runtime(e)
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+    use crate::lex::LexErrorType;
+    use crate::parse::ParseErrorType;
+
+    #[test]
+    fn errors_include_their_position() {
+        let error = CardlangError::from(ParseError::new(ParseErrorType::UnexpectedToken, 3));
+
+        assert_eq!(error.to_string(), "unexpected token at line 3".to_string());
+        assert_eq!(error.span(), Some(Span{ line: 3, column: 0 }));
+    }
+
+    #[test]
+    fn the_wrapped_error_is_the_source() {
+        let lex_error = LexError::new(LexErrorType::ParseError, 1, 4);
+        let error = CardlangError::from(lex_error.clone());
+
+        assert_eq!(error.to_string(), "unrecognised token at line 1, column 4".to_string());
+        assert_eq!(error.source().map(|e| e.to_string()), Some(lex_error.to_string()));
+    }
+
+    #[test]
+    fn runtime_errors_have_no_span() {
+        let error = CardlangError::from(RuntimeError::UnknownPlayer(5));
+
+        assert_eq!(error.span(), None);
+        assert_eq!(error.to_string(), "there is no player 5".to_string());
+    }
+}
@@ -0,0 +1,43 @@
+// complete games bundled into the binary, available to build as `example:<name>`
+pub(crate) const EXAMPLES: [(&str, &str, &str); 4] = [
+    ("turns", "take turns emptying your hand into the middle", include_str!("../examples/turns.card")),
+    ("war", "turn over cards two at a time, the higher takes both", include_str!("../examples/war.card")),
+    ("go_fish", "ask for ranks, fish from the pond and lay down books", include_str!("../examples/go_fish.card")),
+    ("crazy_eights", "match the rank or suit, with eights wild", include_str!("../examples/crazy_eights.card"))
+];
+
+pub const PREFIX: &str = "example:";
+
+pub fn find(name: &str) -> Option<&'static str> {
+    EXAMPLES.iter().find(|(n, _, _)| *n == name).map(|(_, _, source)| *source)
+}
+
+pub fn list() -> String {
+    EXAMPLES.iter()
+        .map(|(name, description, _)| format!("{} - {}", name, description))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn every_example_plays_to_the_end() {
+        for (name, _, source) in EXAMPLES.iter() {
+            let mut game = crate::load_game(source).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            game.start();
+            game.auto(None);
+
+            assert_eq!(game.get_status(), "game over".to_string(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn examples_are_found_by_name() {
+        assert!(find("war").is_some());
+        assert!(find("poker").is_none());
+        assert!(list().starts_with("turns - "));
+    }
+}
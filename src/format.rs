@@ -0,0 +1,168 @@
+use std::{fmt, sync::Arc};
+use crate::cards::{Card, Rank, Suit};
+use crate::json::Json;
+
+// how `show` writes out cards and tables, implement it for a notation of your own
+// and pass it to `Game::set_formatter`
+pub trait Formatter: fmt::Debug + Send + Sync {
+    fn card(&self, card: &Card) -> String;
+
+    fn cards(&self, cards: &[Card]) -> String {
+        cards.iter().map(|c| self.card(c)).collect::<Vec<String>>().join(", ")
+    }
+
+    // cards the viewer isn't allowed to see
+    fn hidden(&self, count: usize) -> String {
+        format!("hidden ({} cards)", count)
+    }
+
+    // a stack declared `facedown`, which nobody sees, its owner included
+    fn face_down(&self, count: usize) -> String {
+        format!("?? ({} cards)", count)
+    }
+
+    // a line for each entry, like the piles in `show all`
+    fn table(&self, rows: &[(String, String)]) -> String {
+        rows.iter().map(|(label, value)| format!("{}: {}", label, value)).collect::<Vec<String>>().join("\n")
+    }
+}
+
+// `king diamonds, ace spades`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainText;
+
+impl Formatter for PlainText {
+    fn card(&self, card: &Card) -> String {
+        card.to_string()
+    }
+}
+
+// `KD AS TH`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Compact;
+
+impl Formatter for Compact {
+    fn card(&self, card: &Card) -> String {
+        let rank = match card.rank() {
+            Rank::Ten => "T".to_string(),
+            rank => short_rank(rank)
+        };
+        format!("{}{}", rank, format!("{:?}", card.suit()).chars().next().unwrap_or('?'))
+    }
+
+    fn cards(&self, cards: &[Card]) -> String {
+        cards.iter().map(|c| self.card(c)).collect::<Vec<String>>().join(" ")
+    }
+
+    fn hidden(&self, count: usize) -> String {
+        format!("[{}]", count)
+    }
+
+    fn face_down(&self, count: usize) -> String {
+        vec!("??"; count).join(" ")
+    }
+}
+
+// `K♦, A♠, 10♥`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unicode;
+
+impl Formatter for Unicode {
+    fn card(&self, card: &Card) -> String {
+        let suit = match card.suit() {
+            Suit::Spades => '♠',
+            Suit::Hearts => '♥',
+            Suit::Clubs => '♣',
+            Suit::Diamonds => '♦'
+        };
+        format!("{}{}", short_rank(card.rank()), suit)
+    }
+
+    fn hidden(&self, count: usize) -> String {
+        format!("🂠×{}", count)
+    }
+}
+
+// `["king diamonds","ace spades"]`, with tables as objects
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatter;
+
+impl Formatter for JsonFormatter {
+    fn card(&self, card: &Card) -> String {
+        Json::String(card.to_string()).to_string()
+    }
+
+    fn cards(&self, cards: &[Card]) -> String {
+        Json::Array(cards.iter().map(|c| Json::String(c.to_string())).collect()).to_string()
+    }
+
+    fn hidden(&self, count: usize) -> String {
+        Json::object(vec!(("hidden", Json::Number(count as f64)))).to_string()
+    }
+
+    fn face_down(&self, count: usize) -> String {
+        Json::object(vec!(("facedown", Json::Number(count as f64)))).to_string()
+    }
+
+    // values already formatted as json are kept as they are, anything else becomes a string
+    fn table(&self, rows: &[(String, String)]) -> String {
+        Json::Object(rows.iter().map(|(label, value)| {
+            (label.to_string(), Json::parse(value).unwrap_or_else(|_| Json::string(value)))
+        }).collect()).to_string()
+    }
+}
+
+pub const FORMATTERS: [&str; 4] = ["plain", "compact", "unicode", "json"];
+
+pub fn by_name(name: &str) -> Option<Arc<dyn Formatter>> {
+    match name {
+        "plain" => Some(Arc::new(PlainText)),
+        "compact" => Some(Arc::new(Compact)),
+        "unicode" => Some(Arc::new(Unicode)),
+        "json" => Some(Arc::new(JsonFormatter)),
+        _ => None
+    }
+}
+
+fn short_rank(rank: Rank) -> String {
+    match rank {
+        Rank::Ace => "A".to_string(),
+        Rank::Jack => "J".to_string(),
+        Rank::Queen => "Q".to_string(),
+        Rank::King => "K".to_string(),
+        rank => (rank as usize + 1).to_string()
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+    use crate::cards::standard_deck;
+
+    fn cards() -> Vec<Card> {
+        let deck = standard_deck();
+        vec!(deck[51], deck[0], deck[9])
+    }
+
+    #[test]
+    fn each_formatter_has_its_own_notation() {
+        assert_eq!(PlainText.cards(&cards()), "king diamonds, ace spades, ten spades".to_string());
+        assert_eq!(Compact.cards(&cards()), "KD AS TS".to_string());
+        assert_eq!(Unicode.cards(&cards()), "K♦, A♠, 10♠".to_string());
+        assert_eq!(JsonFormatter.cards(&cards()), r#"["king diamonds","ace spades","ten spades"]"#.to_string());
+    }
+
+    #[test]
+    fn json_tables_keep_formatted_values() {
+        let rows = vec!(
+            ("deck".to_string(), JsonFormatter.cards(&cards()[..1])),
+            ("player 2".to_string(), JsonFormatter.hidden(3)),
+            ("status".to_string(), "game over".to_string())
+        );
+
+        assert_eq!(
+            JsonFormatter.table(&rows),
+            r#"{"deck":["king diamonds"],"player 2":{"hidden":3},"status":"game over"}"#.to_string()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use crate::ast::Statement;
+use crate::interpreter::Game;
+use crate::token::{Token, SourceToken};
+
+// characters the lexer has some meaning for, plus a few it doesn't
+const NOISE: &[char] = &[
+    'a', 'z', 'A', '0', '9', ':', '.', '(', ')', '{', '}', ',', '>', '&', ' ', '\n', '_', '-', '#'
+];
+
+// lexed tokens borrow from their source, so noise tokens draw on names that live forever
+const SYMBOLS: &[&str] = &["s0", "s1", "s2"];
+
+// random but valid game specifications, for property testing the lexer, parser and runtime
+pub struct Generator {
+    rng: StdRng,
+    stacks: Vec<String>
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Generator {
+        Generator { rng: StdRng::seed_from_u64(seed), stacks: vec!() }
+    }
+
+    pub fn source(&mut self) -> String {
+        let stack_count = self.rng.gen_range(0..3);
+        self.stacks = (0..stack_count).map(|n| format!("pile{}", n)).collect();
+
+        let mut lines = vec!(
+            format!("name game{}", self.rng.gen_range(0..100)),
+            format!("players {}", self.rng.gen_range(1..5))
+        );
+        lines.extend(self.stacks.iter().map(|s| format!("stack {}", s)));
+        if self.rng.gen() {
+            lines.push(format!("deal {} each", self.rng.gen_range(1..8)));
+        }
+
+        let setup = self.block(false, 2);
+        lines.push(format!("define setup(){{\n{}\n}}", setup));
+        let player_move = self.block(true, 2);
+        lines.push(format!("define player_move(player){{\n{}\n}}", player_move));
+        lines.join("\n")
+    }
+
+    pub fn ast(&mut self) -> Vec<Statement> {
+        let source = self.source();
+        let tokens = crate::lex::lexer(&source).expect("generated sources always lex");
+        crate::parse::parse(&tokens).expect("generated sources always parse")
+    }
+
+    // a started game, some way into play
+    pub fn game(&mut self) -> Game {
+        let mut game = Game::new(self.ast());
+        game.set_seed(self.rng.gen());
+        game.start();
+        let moves = self.rng.gen_range(0..20);
+        game.auto(Some(moves));
+        game
+    }
+
+    // arbitrary text that is unlikely to be a valid game
+    pub fn noise(&mut self, length: usize) -> String {
+        (0..length).map(|_| NOISE[self.rng.gen_range(0..NOISE.len())]).collect()
+    }
+
+    // any tokens at all, in any order
+    pub fn noise_tokens(&mut self, length: usize) -> Vec<SourceToken<'static>> {
+        (0..length).map(|i| {
+            let token = match self.rng.gen_range(0..39) {
+                0 => Token::Name,
+                1 => Token::Symbol(SYMBOLS[self.rng.gen_range(0..SYMBOLS.len())]),
+                2 => Token::Number(self.rng.gen_range(0..5) as f64, 1),
+                3 => Token::Stack,
+                4 => Token::Deck,
+                5 => Token::Players,
+                6 => Token::CurrentPlayer,
+                7 => Token::Define,
+                8 => Token::OpenParens,
+                9 => Token::CloseParens,
+                10 => Token::Comma,
+                11 => Token::OpenBracket,
+                12 => Token::CloseBracket,
+                13 => Token::Transfer,
+                14 => Token::Check,
+                15 => Token::Is,
+                16 => Token::If,
+                17 => Token::Newline,
+                18 => Token::True,
+                19 => Token::False,
+                20 => Token::Ampersand,
+                21 => Token::Return,
+                22 => Token::Test,
+                23 => Token::Not,
+                24 => Token::Match,
+                25 => Token::Symbol("other"),
+                26 => Token::Between,
+                27 => Token::Symbol("and"),
+                28 => Token::Pipe,
+                29 => Token::Plus,
+                30 => Token::Minus,
+                31 => Token::Star,
+                32 => Token::Slash,
+                33 => Token::Percent,
+                34 => Token::While,
+                35 => Token::Foreach,
+                36 => Token::Symbol("in"),
+                37 => Token::String("Crazy Eights"),
+                _ => Token::Symbol("end")
+            };
+            SourceToken{ token, line_number: 1, column: i as u32 + 1 }
+        }).collect()
+    }
+
+    fn block(&mut self, in_move: bool, depth: usize) -> String {
+        let count = self.rng.gen_range(1..5);
+        (0..count).map(|_| self.statement(in_move, depth)).collect::<Vec<String>>().join("\n")
+    }
+
+    fn statement(&mut self, in_move: bool, depth: usize) -> String {
+        match self.rng.gen_range(0..7) {
+            0 => "shuffle(deck)".to_string(),
+            1 | 2 => {
+                let end = if self.rng.gen() { " end" } else { "" };
+                format!("{} > {}{}", self.stack(in_move, false), self.stack(in_move, true), end)
+            },
+            3 => format!("check({})", self.expression(in_move)),
+            4 if depth > 0 => format!("if({}){{\n{}\n}}", self.expression(in_move), self.block(in_move, depth - 1)),
+            5 if in_move => "winner(player:id)".to_string(),
+            6 => "end()".to_string(),
+            _ => "next_player()".to_string()
+        }
+    }
+
+    fn stack(&mut self, in_move: bool, target: bool) -> String {
+        let mut options = vec!("deck".to_string());
+        if target {
+            options.push("players".to_string());
+        }
+        if in_move {
+            options.push("player:hand".to_string());
+        }
+        options.extend(self.stacks.iter().cloned());
+        options[self.rng.gen_range(0..options.len())].to_string()
+    }
+
+    fn expression(&mut self, in_move: bool) -> String {
+        match self.rng.gen_range(0..5) {
+            0 if in_move => "player:id is current_player".to_string(),
+            1 if in_move => format!("count(player:hand) is {}", self.rng.gen_range(0..5)),
+            3 if in_move => {
+                let low = self.rng.gen_range(0..5);
+                format!("count(player:hand) between {} and {}", low, low + self.rng.gen_range(0..5))
+            },
+            2 => "true".to_string(),
+            _ => "false".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn generated_sources_have_no_errors() {
+        let mut generator = Generator::new(1);
+        for _ in 0..100 {
+            let source = generator.source();
+            let diagnostics = crate::diagnostics::diagnose(&source);
+
+            assert!(!crate::diagnostics::has_errors(&diagnostics), "{}\n{:?}", source, diagnostics);
+        }
+    }
+
+    #[test]
+    fn generated_games_play_without_panicking() {
+        let mut generator = Generator::new(2);
+        for _ in 0..50 {
+            let mut game = generator.game();
+            game.auto(Some(50));
+        }
+    }
+
+    #[test]
+    fn the_lexer_survives_noise() {
+        let mut generator = Generator::new(3);
+        for _ in 0..200 {
+            let noise = generator.noise(40);
+            let _ = crate::lex::lexer(&noise);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::json::Json;
+use crate::lex::KEYWORDS;
+use crate::parse::CONTEXTUAL_KEYS;
+
+// one production of the language, with a game the parser accepts that uses it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rule {
+    pub name: &'static str,
+    pub definition: String,
+    pub example: &'static str
+}
+
+fn rule(name: &'static str, definition: &str, example: &'static str) -> Rule {
+    Rule { name, definition: definition.to_string(), example }
+}
+
+fn quoted(words: &[&str]) -> String {
+    words.iter().map(|w| format!("\"{}\"", w)).collect::<Vec<String>>().join(" | ")
+}
+
+// the grammar the parser implements, in the order it tries each statement. Keywords and
+// settings come from the tables the lexer and parser read, and every example is parsed in the tests
+pub fn rules() -> Vec<Rule> {
+    let settings: Vec<&str> = CONTEXTUAL_KEYS.iter().map(|(key, _)| *key).collect();
+    vec!(
+        rule("spec", "{ statement newline }", "name war\nplayers 2"),
+        rule(
+            "statement",
+            "declaration | deck | stack | setting | deal | options | values | facedown | turns | match_to | definition \
+             | transfer | call | if | while | foreach | match | check | return",
+            "players 2"
+        ),
+        rule("declaration", "( \"name\" | \"players\" | \"teams\" | \"hand_size\" | \"current_player\" ) value", "teams 2"),
+        rule(
+            "deck",
+            "\"deck\" symbol [ \"{\" { ( \"ranks\" | \"suits\" ) ( symbol | number ) { \",\" ( symbol | number ) } newline } \"}\" ]",
+            "deck euchre {\nranks 9, 10, jack, queen, king, ace\n}"
+        ),
+        rule("stack", "\"stack\" symbol { \",\" symbol } [ \"facedown\" ]", "stack middle, discard facedown"),
+        rule("setting", &format!("( {} ) number", quoted(&settings)), "chips 100"),
+        rule("deal", "\"deal\" number \"each\"", "deal 7 each"),
+        rule(
+            "options",
+            "\"options\" \"{\" { symbol ( number | \"true\" | \"false\" | symbol ) [ \"default\" ] [ \",\" ] } \"}\"",
+            "options { stock_size 31 default, jokers false }"
+        ),
+        rule("values", "\"values\" \"{\" { ( symbol | number ) number [ \",\" ] } \"}\"", "values { ace 11, face 10 }"),
+        rule("facedown", "\"facedown\" symbol", "facedown discard"),
+        rule("turns", "\"turns\" \"simultaneous\"", "turns simultaneous"),
+        rule("match_to", "\"match\" \"to\" number", "match to 500"),
+        rule(
+            "definition",
+            "\"define\" symbol \"(\" [ symbol { \",\" symbol } ] \")\" block",
+            "define player_move(player){\nnext_player()\n}"
+        ),
+        rule("block", "\"{\" { statement newline } \"}\"", "define setup(){\nshuffle(deck)\ndeck > players end\n}"),
+        rule(
+            "transfer",
+            "( symbol | \"deck\" ) [ \"top\" | \"bottom\" ] \">\" [ \"top\" | \"bottom\" ] ( symbol | \"deck\" | \"players\" ) [ \"end\" | sum ]",
+            "define setup(){\ndeck > players end\ndeck bottom > bottom discard count(discard) - 1\n}"
+        ),
+        rule("call", "symbol \"(\" [ value { \",\" value [ number ] } ] \")\"", "define setup(){\nshuffle(deck, riffle 7)\n}"),
+        rule("if", "\"if\" \"(\" expression \")\" block", "define player_move(player){\nif(player:id is 1){\nend()\n}\n}"),
+        rule("while", "\"while\" \"(\" expression \")\" block", "define setup(){\nwhile(count(deck) between 10 and 52){\ndeck > middle\n}\n}"),
+        rule(
+            "foreach",
+            "\"foreach\" symbol \"in\" ( symbol | \"deck\" ) block",
+            "define player_move(player){\nforeach card in player:hand {\ncheck(card:value between 2 and 10)\n}\n}"
+        ),
+        rule(
+            "match",
+            "\"match\" expression \"{\" { ( symbol | number ) block } \"}\"",
+            "define player_move(player){\nmatch player:id {\n1 {\nend()\n}\nother {\nnext_player()\n}\n}\n}"
+        ),
+        rule("check", "\"check\" \"(\" expression \")\"", "define player_move(player){\ncheck(player:id is current_player)\n}"),
+        rule("return", "\"return\" \"(\" expression \")\"", "define is_first(p){\nreturn(p:id is 1)\n}"),
+        rule(
+            "expression",
+            "conjunction { \"|\" conjunction }",
+            "define player_move(player){\ncheck(player:id is 1 | player:id is 3)\n}"
+        ),
+        rule(
+            "conjunction",
+            "comparison { \"&\" comparison }",
+            "define player_move(player){\ncheck(player:id is not 2 & count(player:hand) is 1)\n}"
+        ),
+        rule("comparison", "sum [ \"is\" [ \"not\" ] sum ]", "define player_move(player){\ncheck(player:id is current_player)\n}"),
+        rule("sum", "product { ( \"+\" | \"-\" ) product }", "define player_move(player){\ncheck(count(player:hand) - 1 is 0)\n}"),
+        rule("product", "operand { ( \"*\" | \"/\" | \"%\" ) operand }", "define player_move(player){\nadd_score(player:id, count(player:hand) * 2)\n}"),
+        rule(
+            "operand",
+            "value { \"(\" [ expression { \",\" expression } ] \")\" | \"between\" value \"and\" value }",
+            "define valid(card){\nreturn(card:value between 2 and 10)\n}"
+        ),
+        rule("value", "\"true\" | \"false\" | number | string | symbol | \"current_player\" | \"deck\" | \"hand_size\"", "define setup(){\ncheck(true)\n}"),
+        rule("symbol", "letter { letter | digit | \":\" }", "stack player:tricks"),
+        rule("string", "'\"' { any - ( '\"' | newline ) } '\"'", "name \"Crazy Eights\""),
+        rule("number", "[ \"-\" ] digit { digit } [ \".\" digit { digit } ]", "chips 2.5"),
+        rule("comment", "\".(\" { any } \")\"", ".( a comment (which can nest) )\nplayers 2")
+    )
+}
+
+// the rules as ebnf, led by the words the lexer reserves
+pub fn ebnf() -> String {
+    let keywords: Vec<&str> = KEYWORDS.iter().map(|(keyword, _)| *keyword).collect();
+    let mut lines = vec!(format!("(* keywords: {} *)", keywords.join(", ")));
+    for r in rules().iter() {
+        lines.push(format!("{} = {} ;", r.name, r.definition));
+    }
+    format!("{}\n", lines.join("\n"))
+}
+
+pub fn to_json() -> Json {
+    Json::object(vec!(
+        ("keywords", Json::Array(KEYWORDS.iter().map(|(keyword, _)| Json::string(keyword)).collect())),
+        ("contextual_keywords", Json::Array(CONTEXTUAL_KEYS.iter().map(|(key, _)| Json::string(key)).collect())),
+        ("rules", Json::Array(rules().iter().map(|r| Json::object(vec!(
+            ("name", Json::string(r.name)),
+            ("definition", Json::string(&r.definition)),
+            ("example", Json::string(r.example))
+        ))).collect()))
+    ))
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn every_example_parses() {
+        for r in rules().iter() {
+            assert!(crate::parse_source(r.example).is_ok(), "the example for {} doesn't parse", r.name);
+        }
+    }
+
+    #[test]
+    fn every_statement_has_a_rule() {
+        let names: Vec<&str> = rules().iter().map(|r| r.name).collect();
+        let statements = rules().into_iter().find(|r| r.name == "statement").unwrap();
+
+        for alternative in statements.definition.split('|') {
+            assert!(names.contains(&alternative.trim()), "no rule for {}", alternative.trim());
+        }
+    }
+
+    #[test]
+    fn it_renders_ebnf() {
+        let grammar = ebnf();
+
+        assert!(grammar.starts_with("(* keywords: name, stack, deck,"));
+        assert!(grammar.contains("\nsetting = ( \"chips\" | \"turn_time\" | \"decks\" | \"cardlang\" ) number ;\n"));
+    }
+}
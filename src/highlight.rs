@@ -0,0 +1,232 @@
+use std::fmt;
+use crate::token::{Token, SourceToken};
+use crate::error::CardlangError;
+use crate::json::Json;
+use crate::diagnostics::BUILT_IN_FUNCTIONS;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SemanticKind {
+    Keyword,
+    Symbol,
+    Stack,
+    Builtin,
+    Number,
+    String,
+    Comment
+}
+
+impl fmt::Display for SemanticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SemanticKind::Keyword => "keyword",
+            SemanticKind::Symbol => "symbol",
+            SemanticKind::Stack => "stack",
+            SemanticKind::Builtin => "builtin",
+            SemanticKind::Number => "number",
+            SemanticKind::String => "string",
+            SemanticKind::Comment => "comment"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// lines and columns start at 1, length is in characters and never crosses a line
+#[derive(Debug, PartialEq, Clone)]
+pub struct SemanticToken {
+    pub kind: SemanticKind,
+    pub line: u32,
+    pub column: u32,
+    pub length: u32
+}
+
+impl SemanticToken {
+    pub fn to_json(&self) -> Json {
+        Json::object(vec!(
+            ("kind", Json::String(self.kind.to_string())),
+            ("line", Json::Number(self.line as f64)),
+            ("column", Json::Number(self.column as f64)),
+            ("length", Json::Number(self.length as f64))
+        ))
+    }
+}
+
+// classifies every meaningful span of a source file, in source order - punctuation is left out
+pub fn semantic_tokens(source: &str) -> Result<Vec<SemanticToken>, CardlangError> {
+    let tokens = crate::lex::lexer(source)?;
+    let stacks: Vec<&str> = tokens.windows(2).filter_map(|pair| match (&pair[0].token, &pair[1].token) {
+        (Token::Stack, Token::Symbol(s)) => Some(*s),
+        _ => None
+    }).collect();
+
+    let mut semantic = comments(source);
+    for (i, source_token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).map(|t| &t.token);
+        if let Some((kind, length)) = classify(source_token, next, &stacks) {
+            semantic.push(SemanticToken{
+                kind,
+                line: source_token.line_number,
+                column: source_token.column,
+                length
+            });
+        }
+    }
+
+    semantic.sort_by_key(|t| (t.line, t.column));
+    Ok(semantic)
+}
+
+fn classify(source_token: &SourceToken, next: Option<&Token>, stacks: &[&str]) -> Option<(SemanticKind, u32)> {
+    let width = source_token.token.width();
+    match &source_token.token {
+        Token::Name | Token::Stack | Token::Define | Token::Check | Token::Is | Token::If | Token::While | Token::Foreach
+            | Token::True | Token::False | Token::Return | Token::Test | Token::Not | Token::Match | Token::Between | Token::Teams | Token::HandSize => Some((SemanticKind::Keyword, width)),
+        Token::Deck | Token::Players => Some((SemanticKind::Stack, width)),
+        Token::CurrentPlayer => Some((SemanticKind::Builtin, width)),
+        Token::Number(..) => Some((SemanticKind::Number, width)),
+        Token::String(_) => Some((SemanticKind::String, width)),
+        Token::Symbol(s) => {
+            let is_call = next == Some(&Token::OpenParens);
+            if is_call && BUILT_IN_FUNCTIONS.contains(s) {
+                Some((SemanticKind::Builtin, width))
+            } else if stacks.contains(s) || s.ends_with(":hand") {
+                Some((SemanticKind::Stack, width))
+            } else {
+                Some((SemanticKind::Symbol, width))
+            }
+        },
+        _ => None
+    }
+}
+
+// the lexer throws comments away, so they're found by scanning the source again
+fn comments(source: &str) -> Vec<SemanticToken> {
+    let mut found = vec!();
+    let mut chars = source.chars().peekable();
+    let (mut line, mut column) = (1, 0);
+    // the start of the comment segment on the current line, and how deeply nested its parens are
+    let mut comment: Option<(u32, u32)> = None;
+    // `.(` inside a string is just text
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        column += 1;
+        match comment {
+            None if in_string => match c {
+                '"' => in_string = false,
+                '\\' if matches!(chars.peek(), Some('"') | Some('\\')) => {
+                    chars.next();
+                    column += 1;
+                },
+                _ => ()
+            },
+            None if c == '"' => in_string = true,
+            None if c == '.' && chars.peek() == Some(&'(') => {
+                chars.next();
+                comment = Some((column, 0));
+                column += 1;
+            },
+            Some((start, depth)) => {
+                match c {
+                    '(' => comment = Some((start, depth + 1)),
+                    ')' if depth == 0 => {
+                        found.push(SemanticToken{ kind: SemanticKind::Comment, line, column: start, length: column - start + 1 });
+                        comment = None;
+                    },
+                    ')' => comment = Some((start, depth - 1)),
+                    '\n' => {
+                        if column > start {
+                            found.push(SemanticToken{ kind: SemanticKind::Comment, line, column: start, length: column - start });
+                        }
+                        comment = Some((1, depth));
+                    },
+                    _ => ()
+                }
+            },
+            None => ()
+        }
+
+        if c == '\n' {
+            line += 1;
+            column = 0;
+        }
+    }
+
+    // an unclosed comment runs to the end of the file
+    if let Some((start, _)) = comment {
+        if column >= start {
+            found.push(SemanticToken{ kind: SemanticKind::Comment, line, column: start, length: column - start + 1 });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<(SemanticKind, u32, u32, u32)> {
+        semantic_tokens(source).unwrap().into_iter().map(|t| (t.kind, t.line, t.column, t.length)).collect()
+    }
+
+    #[test]
+    fn it_classifies_each_kind_of_span() {
+        let source = "stack middle\ndefine setup(){\nshuffle(deck)\nif(count(middle) is 10){\nfly()\n}\n}";
+
+        assert_eq!(kinds(source), vec!(
+            (SemanticKind::Keyword, 1, 1, 5),
+            (SemanticKind::Stack, 1, 7, 6),
+            (SemanticKind::Keyword, 2, 1, 6),
+            (SemanticKind::Symbol, 2, 8, 5),
+            (SemanticKind::Builtin, 3, 1, 7),
+            (SemanticKind::Stack, 3, 9, 4),
+            (SemanticKind::Keyword, 4, 1, 2),
+            (SemanticKind::Builtin, 4, 4, 5),
+            (SemanticKind::Stack, 4, 10, 6),
+            (SemanticKind::Keyword, 4, 18, 2),
+            (SemanticKind::Number, 4, 21, 2),
+            (SemanticKind::Symbol, 5, 1, 3)
+        ));
+    }
+
+    #[test]
+    fn comments_are_split_across_lines() {
+        let source = "name .( a (nested)\ncomment ) turns";
+
+        assert_eq!(kinds(source), vec!(
+            (SemanticKind::Keyword, 1, 1, 4),
+            (SemanticKind::Comment, 1, 6, 13),
+            (SemanticKind::Comment, 2, 1, 9),
+            (SemanticKind::Symbol, 2, 11, 5)
+        ));
+    }
+
+    #[test]
+    fn comments_arent_found_in_strings() {
+        let source = "name \"a .( \\\" b\" .( c )";
+
+        assert_eq!(kinds(source), vec!(
+            (SemanticKind::Keyword, 1, 1, 4),
+            (SemanticKind::String, 1, 6, 11),
+            (SemanticKind::Comment, 1, 18, 6)
+        ));
+    }
+
+    #[test]
+    fn player_attributes_are_stacks_or_symbols() {
+        let source = "player:hand > players\ncheck(player:id is current_player)";
+
+        assert_eq!(kinds(source), vec!(
+            (SemanticKind::Stack, 1, 1, 11),
+            (SemanticKind::Stack, 1, 15, 7),
+            (SemanticKind::Keyword, 2, 1, 5),
+            (SemanticKind::Symbol, 2, 7, 9),
+            (SemanticKind::Keyword, 2, 17, 2),
+            (SemanticKind::Builtin, 2, 20, 14)
+        ));
+    }
+
+    #[test]
+    fn lex_errors_are_reported() {
+        assert!(semantic_tokens("1foo").is_err());
+    }
+}
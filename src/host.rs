@@ -0,0 +1,49 @@
+use std::{fmt, sync::{Arc, Mutex}};
+use crate::interpreter::GameStateView;
+use crate::cards::Card;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferDecision {
+    Allow,
+    Deny
+}
+
+// lets an embedder react to a game as it's played instead of polling `show`
+pub trait GameHost {
+    fn state_changed(&mut self, _state: &GameStateView) {}
+
+    fn message(&mut self, _message: &str) {}
+
+    // asked by `Game::play` for the next player to move, None stops play
+    fn request_move(&mut self, _state: &GameStateView, _legal_moves: &[usize]) -> Option<usize> {
+        None
+    }
+
+    // asked before a transfer statement moves any cards, a denied transfer leaves both sides as they were
+    fn before_transfer(&mut self, _from: &str, _to: &str, _cards: &[Card]) -> TransferDecision {
+        TransferDecision::Allow
+    }
+}
+
+pub type SharedHost = Arc<Mutex<dyn GameHost + Send>>;
+
+// shared so the embedder keeps its own handle, and so a Game stays Clone
+#[derive(Clone)]
+pub struct HostHandle(SharedHost);
+
+impl HostHandle {
+    pub fn new(host: SharedHost) -> HostHandle {
+        HostHandle(host)
+    }
+
+    // a host that panicked while locked is skipped rather than taking the game down with it
+    pub fn with<T>(&self, f: impl FnOnce(&mut (dyn GameHost + Send)) -> T) -> Option<T> {
+        self.0.lock().ok().map(|mut host| f(&mut *host))
+    }
+}
+
+impl fmt::Debug for HostHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostHandle")
+    }
+}
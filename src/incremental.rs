@@ -0,0 +1,170 @@
+use crate::ast::Statement;
+use crate::error::CardlangError;
+
+// a run of lines holding whole top-level statements, split where the braces balance
+#[derive(Debug, Clone)]
+struct Chunk {
+    start_line: usize,
+    text: String,
+    parsed: Result<Vec<Statement>, CardlangError>
+}
+
+// keeps each top-level statement's parse from the last load, so reloading an edited file
+// only lexes and parses the statements whose lines changed
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalParser {
+    chunks: Vec<Chunk>,
+    reparsed: usize
+}
+
+impl IncrementalParser {
+    pub fn parse(&mut self, source: &str) -> Result<Vec<Statement>, CardlangError> {
+        let mut previous = std::mem::take(&mut self.chunks);
+        self.reparsed = 0;
+
+        for (start_line, text) in split_chunks(source) {
+            // keyed by where it starts too, as spans carry line numbers
+            let cached = previous.iter().position(|c| c.start_line == start_line && c.text == text);
+            let chunk = match cached {
+                Some(i) => previous.swap_remove(i),
+                None => {
+                    self.reparsed += 1;
+                    // padded so line numbers in spans and errors match the whole file
+                    let padded = format!("{}{}", "\n".repeat(start_line - 1), text);
+                    let parsed = crate::lex::lexer(&padded)
+                        .map_err(CardlangError::from)
+                        .and_then(|tokens| Ok(crate::parse::parse(&tokens)?));
+                    Chunk { start_line, text, parsed }
+                }
+            };
+            self.chunks.push(chunk);
+        }
+
+        let mut ast = vec!();
+        for chunk in self.chunks.iter() {
+            ast.extend(chunk.parsed.clone()?);
+        }
+        // a chunk on its own can't tell if a deck is declared in another, so only the whole file is checked
+        crate::check_source(ast)
+    }
+
+    // how many chunks the last call had to parse, rather than reusing
+    pub fn reparsed(&self) -> usize {
+        self.reparsed
+    }
+
+    pub fn chunks(&self) -> usize {
+        self.chunks.len()
+    }
+}
+
+// braces inside comments, `.( like { this )`, and strings, `"like { this"`, don't count
+fn split_chunks(source: &str) -> Vec<(usize, String)> {
+    let mut chunks = vec!();
+    let mut current = String::new();
+    let mut start_line = 1;
+    let mut line = 1;
+    let mut braces: i32 = 0;
+    let mut comment_depth = 0;
+    let mut in_string = false;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        current.push(c);
+        match c {
+            '\\' if in_string => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                    if escaped == '\n' {
+                        line += 1;
+                    }
+                }
+            },
+            '"' if comment_depth == 0 => in_string = !in_string,
+            '\n' if in_string => line += 1,
+            _ if in_string => (),
+            '.' if comment_depth == 0 && chars.peek() == Some(&'(') => {
+                current.push('(');
+                chars.next();
+                comment_depth = 1;
+            },
+            '(' if comment_depth > 0 => comment_depth += 1,
+            ')' if comment_depth > 0 => comment_depth -= 1,
+            '{' if comment_depth == 0 => braces += 1,
+            '}' if comment_depth == 0 => braces -= 1,
+            '\n' => {
+                line += 1;
+                if braces <= 0 && comment_depth == 0 {
+                    chunks.push((start_line, std::mem::take(&mut current)));
+                    start_line = line;
+                    braces = 0;
+                }
+            },
+            _ => ()
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push((start_line, current));
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn it_parses_the_same_as_a_whole_file() {
+        for (name, _, source) in crate::examples::EXAMPLES.iter() {
+            let mut parser = IncrementalParser::default();
+
+            assert_eq!(parser.parse(source), crate::parse_source(source), "{}", name);
+        }
+    }
+
+    #[test]
+    fn only_changed_statements_are_parsed_again() {
+        let source = "players 2\n.( a { comment )\ndefine setup(){\ndeck > players\n}\ndefine player_move(p){\nnext_player()\n}\n";
+        let mut parser = IncrementalParser::default();
+        parser.parse(source).unwrap();
+        assert_eq!(parser.chunks(), 4);
+        assert_eq!(parser.reparsed(), 4);
+
+        let edited = source.replace("next_player()", "end()");
+        let ast = parser.parse(&edited).unwrap();
+
+        assert_eq!(parser.reparsed(), 1);
+        assert_eq!(ast, crate::parse_source(&edited).unwrap());
+    }
+
+    #[test]
+    fn decks_can_be_declared_in_another_statement() {
+        let source = "deck euchre {\nranks 9, 10, jack, queen, king, ace\nsuits hearts\n}\ndeck euchre\nplayers 4\n";
+        let mut parser = IncrementalParser::default();
+
+        assert_eq!(parser.parse(source), crate::parse_source(source));
+        assert!(parser.parse(source).is_ok());
+        assert!(parser.parse("deck eucre\n").is_err());
+    }
+
+    #[test]
+    fn braces_in_strings_dont_split_statements() {
+        let source = "players 2\ndefine setup(){\nprint(\"{ not a block\")\nprint(\"a \\\" quote }\")\n}\ndefine player_move(p){\nend()\n}\n";
+        let mut parser = IncrementalParser::default();
+
+        assert_eq!(parser.parse(source), crate::parse_source(source));
+        assert_eq!(parser.chunks(), 3);
+    }
+
+    #[test]
+    fn errors_keep_their_line_numbers() {
+        let mut parser = IncrementalParser::default();
+        let source = "players 2\ndefine setup(){\ndeck > players\n}\n";
+        parser.parse(source).unwrap();
+
+        let broken = format!("{}define player_move(p){{\ncheck 5\n}}\n", source);
+        assert_eq!(parser.parse(&broken), crate::parse_source(&broken));
+        assert!(parser.parse(&broken).is_err());
+    }
+}
@@ -1,33 +1,123 @@
 use crate::ast::*;
-use std::fmt::Display;
+use crate::cards::{Card, DeckSpec, Player, RankValues, DECK_SIZE};
+use crate::json::Json;
+use crate::error::{CardlangError, Violation};
+use crate::format::{Formatter, PlainText};
+use crate::host::{HostHandle, SharedHost};
+use std::{collections::HashMap, fmt::Display, fs, sync::Arc, time::{Duration, Instant}};
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use crate::runtime::{
     Runtime,
+    GameState,
+    Snapshot,
+    Metrics,
     InitialValues,
-    Callbacks
+    Callbacks,
+    Coverage,
+    CoverageReport,
+    Debugger,
+    Builtins,
+    NativeFunction,
+    PrimitiveValue,
+    std::{BURN_PILE, summary}
 };
 
+// guards `auto` against games that never reach an end condition
+const AUTO_MOVE_LIMIT: usize = 1000;
+pub const REPLAY_PREFIX: &str = "cardlang/1";
+// hint playouts are shallow, a game still going after this many moves counts as not won
+const PLAYOUT_DEPTH: usize = 200;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Suggestion {
+    pub player_move: usize,
+    pub win_rate: f64,
+    pub playouts: usize
+}
+
+#[derive(Clone, Debug)]
+struct HistoryEntry {
+    player: usize,
+    previous_state: Runtime
+}
+
+// running totals for each player over every game played since the game was built
+#[derive(Clone, Debug, Default)]
+struct SessionTally {
+    games: u32,
+    scores: Vec<f64>,
+    wins: Vec<u32>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PlayerView<'a> {
+    pub id: usize,
+    pub hand: &'a [Card],
+    pub is_current: bool,
+    pub is_winner: bool
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GameStateView<'a> {
+    pub name: Option<&'a str>,
+    pub status: GameState,
+    pub current_player: usize,
+    pub player_count: usize,
+    pub winners: Vec<usize>
+}
+
 #[derive(Clone, Debug)]
 pub struct Game {
     name: Option<String>,
-    ast: Vec<Statement>,
+    // from the opening comment of the source, when the game was loaded from one
+    description: Option<String>,
+    ast: Arc<Vec<Statement>>,
     runtime: Runtime,
     initial_values: InitialValues,
-    callbacks: Callbacks
+    callbacks: Callbacks,
+    seed: Option<u64>,
+    history: Vec<HistoryEntry>,
+    breakpoints: Vec<String>,
+    tracing: bool,
+    host: Option<HostHandle>,
+    builtins: Builtins,
+    coverage: Option<Coverage>,
+    turn_limit: Option<Duration>,
+    turn_started: Instant,
+    formatter: Arc<dyn Formatter>,
+    session: SessionTally,
+    // what the current game's shuffles were seeded with, for exporting it as a replay
+    replay_seed: Option<u64>
 }
 
 impl Game {
     pub fn new(ast: Vec<Statement>) -> Game {
+        // an older game keeps the meaning it had in the version it declares, and options are read as their values
+        let ast = crate::options::apply(crate::version::lower(ast));
         let mut name = None;
+        let mut turn_limit = None;
 
         let mut initial_values = InitialValues{ 
             players: 1,
             card_stacks: vec!(),
-            current_player: 1 
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
+            current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
         };
 
         let mut callbacks = Callbacks {
             player_move: None,
-            setup: None
+            setup: None,
+            definitions: HashMap::new()
         };
 
         for statement in ast.iter() {
@@ -36,55 +126,183 @@ impl Game {
                     d
                 ) => {
                     match d.name.as_str() {
-                        "setup" => callbacks.setup = Some(d.clone()),
-                        "player_move" => callbacks.player_move = Some(d.clone()),
-                        _ => ()
+                        "setup" => callbacks.setup = Some(Arc::new(d.clone())),
+                        "player_move" => callbacks.player_move = Some(Arc::new(d.clone())),
+                        _ => {
+                            callbacks.definitions.insert(d.name.clone(), Arc::new(d.clone()));
+                        }
                     }
                 },
                 Statement::Declaration(Declaration{
                     key: GlobalKey::Name,
-                    value: Expression::Symbol(v)
+                    value: Expression::Symbol(v) | Expression::String(v),
+                    ..
                 }) => {
                     name = Some(v.to_string());
                 },
                 Statement::Declaration(Declaration{
                     key: GlobalKey::Players,
-                    value: Expression::Number(n)
+                    value: Expression::Number(n),
+                    ..
                 }) => {
                     initial_values.players = *n as u32;
                 },
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::Teams,
+                    value: Expression::Number(n),
+                    ..
+                }) => {
+                    initial_values.teams = Some(*n as u32);
+                },
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::Deal,
+                    value: Expression::Number(n),
+                    ..
+                }) => {
+                    initial_values.deal = Some(*n as u32);
+                },
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::MatchTo,
+                    value: Expression::Number(n),
+                    ..
+                }) => {
+                    initial_values.match_to = Some(*n);
+                },
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::TurnTime,
+                    value: Expression::Number(n),
+                    ..
+                }) => {
+                    // a time too long to keep, or negative, is left to diagnostics to report
+                    turn_limit = Duration::try_from_secs_f64(*n).ok();
+                },
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::Turns,
+                    ..
+                }) => {
+                    initial_values.simultaneous = true;
+                },
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::Chips,
+                    value: Expression::Number(n),
+                    ..
+                }) => {
+                    initial_values.chips = Some(*n);
+                },
+                // anything short of one whole deck is reported by diagnostics, and played with one
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::Decks,
+                    value: Expression::Number(n),
+                    ..
+                }) if *n >= 1.0 => {
+                    initial_values.decks = *n as u32;
+                },
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::HandSize,
+                    value: Expression::Number(n),
+                    ..
+                }) => {
+                    initial_values.hand_size = Some(*n as u32);
+                },
                 Statement::Declaration(Declaration{
                     key: GlobalKey::CurrentPlayer,
-                    value: Expression::Number(n)
+                    value: Expression::Number(n),
+                    ..
                 }) => {
                     initial_values.current_player = *n as usize;
                 },
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::FaceDown,
+                    value: Expression::Symbol(s),
+                    ..
+                }) => {
+                    initial_values.face_down.push(s.to_string());
+                },
                 Statement::Declaration(Declaration{
                     key: GlobalKey::Stack,
-                    value: Expression::Symbol(s)
+                    value: Expression::Symbol(s),
+                    ..
+                }) => {
+                    match s.strip_prefix("player:") {
+                        Some(stack) => initial_values.player_stacks.push(stack.to_string()),
+                        None => initial_values.card_stacks.push(s.to_string())
+                    }
+                },
+                // unknown decks and ranks have already been turned away by `parse_source`
+                Statement::Declaration(Declaration{
+                    key: GlobalKey::Deck,
+                    value: Expression::Symbol(s),
+                    ..
                 }) => {
-                    initial_values.card_stacks.push(s.to_string());
+                    if let Some(deck) = DeckSpec::named(s) {
+                        initial_values.deck = deck;
+                    }
+                },
+                Statement::DeckDefinition(d) => {
+                    if let Ok(deck) = DeckSpec::from_names(&d.ranks, &d.suits) {
+                        initial_values.deck = deck;
+                    }
+                },
+                Statement::ValueTable(v) => {
+                    if let Ok(values) = RankValues::from_names(&v.values) {
+                        initial_values.values = values;
+                    }
                 },
                 _ => ()
             }
 
         }
 
+        // nobody sees burnt cards, not even the table
+        if calls_function(&ast, "burn") {
+            initial_values.card_stacks.push(BURN_PILE.to_string());
+            initial_values.face_down.push(BURN_PILE.to_string());
+        }
+
         let runtime = Runtime::new(initial_values.clone(), callbacks.clone());
 
         Game {
             name,
-            ast,
+            description: None,
+            ast: Arc::new(ast),
             runtime,
             initial_values: initial_values.clone(),
-            callbacks: callbacks.clone()
+            callbacks: callbacks.clone(),
+            seed: None,
+            history: vec!(),
+            breakpoints: vec!(),
+            tracing: false,
+            host: None,
+            builtins: Builtins::default(),
+            coverage: None,
+            turn_limit,
+            turn_started: Instant::now(),
+            formatter: Arc::new(PlainText),
+            session: SessionTally::default(),
+            replay_seed: None
         }
     }
 
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.description = description;
+    }
+
+    pub fn set_formatter(&mut self, formatter: Arc<dyn Formatter>) {
+        self.formatter = formatter;
+    }
+
     pub fn show(&self, key: &str) -> String {
+        self.show_for_seat(key, None)
+    }
+
+    // a seated view only reveals the hidden cards (deck and hands) that belong to that seat
+    pub fn show_for_seat(&self, key: &str, seat: Option<usize>) -> String {
         match key {
-            "deck" => Self::display_list(&self.runtime.get_deck()),
+            "deck" if seat.is_some() => self.display_hidden(&self.runtime.get_deck()),
+            "deck" => self.formatter.cards(&self.runtime.get_deck()),
+            BURN_PILE if seat.is_some() => self.display_hidden(&self.runtime.find_custom_item(BURN_PILE).unwrap_or_default()),
             "name" => self.display_name(),
+            "rules" => crate::doc::rules(&self.ast, self.description.as_deref()),
             "players" => Self::display_list(&self.runtime.get_players()),
             "game" => {
                 let winner_list = self.runtime.get_winners();
@@ -95,359 +313,2695 @@ impl Game {
                     "".to_string()
                 };
                 let status = self.runtime.get_status();
-                format!("{}{}", status, winners)
+                let hand = match self.initial_values.match_to {
+                    Some(_) => format!("\nhand: {}", self.runtime.hand_number()),
+                    None => "".to_string()
+                };
+                format!("{}{}{}", status, hand, winners)
             },
             "current_player" => {
                 format!("{}", self.runtime.get_current_player())
             },
-            _ => self.check_exploded_show(key)
+            "pot" => format!("{}", self.runtime.pot()),
+            "turn" => format!("turn: {}\nround: {}", self.runtime.turn_number(), self.runtime.round_number()),
+            "phase" => self.runtime.phase().to_string(),
+            "chips" => self.display_chips(),
+            "scores" => self.display_scores(),
+            "session" => self.display_session(),
+            "perf" => self.formatter.table(&self.runtime.metrics().rows()),
+            "stacks" => self.display_stacks(),
+            "all" => self.display_all(seat),
+            "table" => self.display_table(),
+            _ => self.check_exploded_show(key, seat)
+        }
+    }
+
+    pub fn show_json(&self, key: &str) -> Json {
+        match key {
+            "deck" => Json::object(vec!(("deck", Self::cards_json(&self.runtime.get_deck())))),
+            "name" => Json::object(vec!((
+                "name", self.name.as_ref().map(|n| Json::string(n)).unwrap_or(Json::Null)
+            ))),
+            "rules" => Json::object(vec!(
+                ("description", self.description.as_ref().map(|d| Json::string(d)).unwrap_or(Json::Null)),
+                ("rules", Json::String(crate::doc::rules(&self.ast, self.description.as_deref())))
+            )),
+            "players" => {
+                let players = self.runtime.get_players().iter().map(|p| Json::object(vec!(
+                    ("id", Json::Number(p.get_id() as f64)),
+                    ("cards", Json::Number(p.get_hand().len() as f64))
+                ))).collect();
+                Json::object(vec!(("players", Json::Array(players))))
+            },
+            "game" => {
+                let winners = self.runtime.get_winners().into_iter().map(Json::Number).collect();
+                Json::object(vec!(
+                    ("status", Json::String(self.runtime.get_status())),
+                    ("winners", Json::Array(winners))
+                ))
+            },
+            "current_player" => Json::object(vec!(
+                ("current_player", Json::Number(self.runtime.get_current_player() as f64))
+            )),
+            "turn" => Json::object(vec!(
+                ("turn", Json::Number(self.runtime.turn_number() as f64)),
+                ("round", Json::Number(self.runtime.round_number() as f64))
+            )),
+            "phase" => Json::object(vec!(("phase", Json::string(self.runtime.phase())))),
+            "scores" => {
+                let scores = self.runtime.players().iter().map(|p| Json::object(vec!(
+                    ("id", Json::Number(p.get_id() as f64)),
+                    ("score", Json::Number(p.score()))
+                ))).collect();
+                Json::object(vec!(("scores", Json::Array(scores))))
+            },
+            "stacks" => {
+                let stacks = self.initial_values.card_stacks.iter().map(|stack| {
+                    let count = self.runtime.find_custom_item(stack).unwrap_or_default().len();
+                    Json::object(vec!(
+                        ("name", Json::string(stack)),
+                        ("cards", Json::Number(count as f64))
+                    ))
+                }).collect();
+                Json::object(vec!(("stacks", Json::Array(stacks))))
+            },
+            "all" => {
+                let stacks = self.initial_values.card_stacks.iter().map(|stack| {
+                    let cards = self.runtime.find_custom_item(stack).unwrap_or_default();
+                    (stack.to_string(), self.stack_json(stack, &cards))
+                }).collect();
+                let players = self.runtime.get_players().iter().map(|p| Json::object(vec!(
+                    ("id", Json::Number(p.get_id() as f64)),
+                    ("hand", Self::cards_json(&p.get_hand()))
+                ))).collect();
+                Json::object(vec!(
+                    ("deck", Self::cards_json(&self.runtime.get_deck())),
+                    ("stacks", Json::Object(stacks)),
+                    ("players", Json::Array(players))
+                ))
+            },
+            "table" => {
+                let stacks = self.initial_values.card_stacks.iter().map(|stack| {
+                    let cards = self.runtime.find_custom_item(stack).unwrap_or_default();
+                    let json = match stack.as_str() {
+                        BURN_PILE => Json::object(vec!(("hidden", Json::Number(cards.len() as f64)))),
+                        _ => self.stack_json(stack, &cards)
+                    };
+                    (stack.to_string(), json)
+                }).collect();
+                let players = self.runtime.players().iter().map(|p| {
+                    let stacks = self.initial_values.player_stacks.iter().map(|stack| {
+                        (stack.to_string(), Json::Number(p.stack(stack).unwrap_or_default().len() as f64))
+                    }).collect();
+                    Json::object(vec!(
+                        ("id", Json::Number(p.get_id() as f64)),
+                        ("cards", Json::Number(p.hand().len() as f64)),
+                        ("stacks", Json::Object(stacks)),
+                        ("score", Json::Number(p.score()))
+                    ))
+                }).collect();
+                Json::object(vec!(
+                    ("deck", Json::Number(self.runtime.get_deck().len() as f64)),
+                    ("stacks", Json::Object(stacks)),
+                    ("current_player", Json::Number(self.runtime.get_current_player() as f64)),
+                    ("players", Json::Array(players))
+                ))
+            },
+            _ => self.check_exploded_show_json(key)
+        }
+    }
+
+    pub fn move_json(&self, player: usize, accepted: bool) -> Json {
+        Json::object(vec!(
+            ("move", Json::Number(player as f64)),
+            ("accepted", Json::Bool(accepted)),
+            ("status", Json::String(self.runtime.get_status())),
+            ("current_player", Json::Number(self.runtime.get_current_player() as f64))
+        ))
+    }
+
+    fn check_exploded_show_json(&self, key: &str) -> Json {
+        let instructions: Vec<&str> = key.split(' ').collect();
+        let player = instructions.get(1).and_then(|n| n.parse::<usize>().ok());
+        match (instructions[0], player) {
+            ("player", Some(n)) if n > 0 && n <= self.runtime.get_players().len() => {
+                Json::object(vec!(
+                    ("player", Json::Number(n as f64)),
+                    ("hand", Self::cards_json(&self.runtime.get_player(n - 1).get_hand()))
+                ))
+            },
+            (key, _) => match self.runtime.find_custom_item(key) {
+                Some(cards) => Json::Object(vec!((key.to_string(), self.stack_json(key, &cards)))),
+                None => Json::object(vec!(("error", Json::String(format!("{} not found", key)))))
+            }
+        }
+    }
+
+    fn stack_json(&self, stack: &str, cards: &[Card]) -> Json {
+        match self.is_face_down(stack) {
+            true => Json::object(vec!(("facedown", Json::Number(cards.len() as f64)))),
+            false => Self::cards_json(cards)
         }
     }
 
+    fn cards_json(cards: &[Card]) -> Json {
+        Json::Array(cards.iter().map(|card| Json::object(vec!(
+            ("rank", Json::String(card.get_rank_str().to_lowercase())),
+            ("suit", Json::String(card.get_suit_str().to_lowercase()))
+        ))).collect())
+    }
+
     pub fn start(&mut self) {
-        self.runtime = Runtime::new(self.initial_values.clone(), self.callbacks.clone());
+        self.session = self.session_tally();
+        let seed = self.seed.unwrap_or_else(rand::random);
+        self.runtime = self.fresh_runtime(Some(seed));
+        self.replay_seed = Some(seed);
+        self.history = vec!();
         self.runtime.setup();
+        self.turn_started = Instant::now();
+        self.notify_host();
     }
 
-    pub fn player_move(&mut self, player: usize) {
-        self.runtime.player_move(player);
+    fn fresh_runtime(&self, seed: Option<u64>) -> Runtime {
+        let mut runtime = Runtime::new(self.initial_values.clone(), self.callbacks.clone());
+        if let Some(seed) = seed {
+            runtime.set_seed(seed);
+        }
+        for breakpoint in self.breakpoints.iter() {
+            runtime.debugger().add_breakpoint(breakpoint);
+        }
+        runtime.set_tracing(self.tracing);
+        runtime.set_coverage(self.coverage.clone());
+        runtime.set_host(self.host.clone());
+        runtime.set_builtins(self.builtins.clone());
+        runtime
     }
 
-    fn check_exploded_show(&self, key: &str) -> String {
-        let instructions: Vec<&str> = key.split(" ").collect();
-        match instructions[0] {
-            "player" => self.handle_show_player(instructions),
-            key => self.find_custom_item(key)
-        }
+    pub fn save(&self, path: &str) -> Result<(), CardlangError> {
+        fs::write(path, format!("{}\n", self.runtime.to_save()))
+            .map_err(|_| CardlangError::UnwritableFile(path.to_string()))
     }
 
-    fn handle_show_player(&self, args: Vec<&str>) -> String {
-        let player_num = args[1].parse::<usize>().unwrap_or(1) - 1;
-        Self::display_list(&self.runtime.get_player(player_num).get_hand())
+    // saves only hold the state, so they load into a game built from the same source
+    pub fn load_save(&mut self, path: &str) -> Result<(), CardlangError> {
+        let source = fs::read_to_string(path).map_err(|_| CardlangError::UnreadableFile(path.to_string()))?;
+        let save = Json::parse(&source).map_err(|_| CardlangError::InvalidFile(path.to_string()))?;
+
+        let mut runtime = self.fresh_runtime(self.seed);
+        runtime.load_save(&save).ok_or_else(|| CardlangError::InvalidFile(path.to_string()))?;
+        self.runtime = runtime;
+        self.replay_seed = None;
+        self.history = vec!();
+        self.turn_started = Instant::now();
+        self.notify_host();
+        Ok(())
     }
 
-    fn display_name(&self) -> String {
-        match &self.name {
-            Some(name) => name.to_string(),
-            None => "Name not initalised!".to_string() // TODO - Error? Default? 
-         }
+    // overrides the game's turn_time, or turns the timer off
+    pub fn set_turn_limit(&mut self, limit: Option<Duration>) {
+        self.turn_limit = limit;
+        self.turn_started = Instant::now();
     }
 
-    fn display_list<D: Display>(list: &Vec<D>) -> String {
-        list.iter().map(|x|x.to_string()).collect::<Vec<String>>().join(", ")
+    pub fn turn_limit(&self) -> Option<Duration> {
+        self.turn_limit
     }
 
-    fn find_custom_item(&self, key: &str) -> String {
-        match self.runtime.find_custom_item(key) {
-            Some(v) => Self::display_list(&v),
-            _ => format!("{} not found", key)
+    // when the current player has run out of time, moves for them if they can and passes if not
+    pub fn check_turn_timer(&mut self) -> Option<String> {
+        let limit = self.turn_limit?;
+        if !self.runtime.is_active() || self.turn_started.elapsed() < limit {
+            return None;
         }
+
+        let player = self.runtime.get_current_player();
+        let message = if self.legal_moves().contains(&player) && self.try_move(player).is_ok() {
+            format!("player {} ran out of time, so a move was made for them", player)
+        } else {
+            self.runtime.pass_turn();
+            self.notify_host();
+            format!("player {} ran out of time and passes", player)
+        };
+        self.turn_started = Instant::now();
+        Some(message)
     }
 
-}
+    // collects the statements run from now on, across restarts, but not by forks or hints
+    pub fn track_coverage(&mut self) {
+        let coverage = Coverage::default();
+        self.runtime.set_coverage(Some(coverage.clone()));
+        self.coverage = Some(coverage);
+    }
 
+    pub fn coverage_report(&self) -> Option<CoverageReport> {
+        let definitions: Vec<&Definition> = [&self.callbacks.setup, &self.callbacks.player_move].iter()
+            .filter_map(|d| d.as_deref())
+            .collect();
+        self.coverage.as_ref().map(|c| c.report(&definitions))
+    }
 
-/*
+    pub fn set_host(&mut self, host: SharedHost) {
+        self.host = Some(HostHandle::new(host));
+        self.runtime.set_host(self.host.clone());
+    }
 
+    // a host specific function the game's source can call by name, kept across restarts
+    pub fn register_builtin<F>(&mut self, name: &str, function: F)
+    where F: Fn(&mut Runtime, &[PrimitiveValue]) -> Option<PrimitiveValue> + Send + Sync + 'static {
+        let function: Arc<NativeFunction> = Arc::new(function);
+        self.builtins.register(name, Arc::clone(&function));
+        self.runtime.register_shared_builtin(name, function);
+    }
 
-######################################
-//////////////////////////////////////
-///////////// TESTS //////////////////
-//////////////////////////////////////
-######################################
+    pub fn clear_host(&mut self) {
+        self.host = None;
+        self.runtime.set_host(None);
+    }
 
+    // hands control to the host, which picks each move until it stops or the game ends
+    pub fn play(&mut self) -> usize {
+        let mut moves_made = 0;
+        while self.runtime.is_active() {
+            let legal_moves = self.legal_moves();
+            let host = match &self.host {
+                Some(h) => h.clone(),
+                None => break
+            };
+            let state = self.state();
+            let choice = host.with(|h| h.request_move(&state, &legal_moves)).flatten();
+            match choice {
+                Some(player) => {
+                    if self.try_move(player).is_ok() {
+                        moves_made += 1;
+                    }
+                },
+                None => break
+            }
+        }
+        moves_made
+    }
 
+    fn notify_host(&self) {
+        if let Some(host) = &self.host {
+            let state = self.state();
+            host.with(|h| h.state_changed(&state));
+        }
+    }
 
-*/
+    fn message_host(&self, message: &str) {
+        if let Some(host) = &self.host {
+            host.with(|h| h.message(message));
+        }
+    }
 
-#[cfg(test)]
-mod test{
-    use super::*;
-    use crate::cards::standard_deck;
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.tracing = tracing;
+        self.runtime.set_tracing(tracing);
+    }
 
-    #[test]
-    fn it_can_display_a_deck() {
-        let ast = vec!(
-            Statement::Declaration(
-                Declaration {
-                    key: GlobalKey::Deck,
-                    value: Expression::Symbol("StandardDeck".to_string())
-                }
-            )
-        );
+    pub fn snapshot(&self) -> Snapshot {
+        self.runtime.snapshot()
+    }
 
-        let game = Game::new(ast);
-        let deck = game.show("deck");
-        let split_deck: Vec<&str> = deck.split(",").collect();
+    pub fn take_trace(&mut self) -> Vec<String> {
+        self.runtime.take_trace()
+    }
 
-        assert_eq!(split_deck[0], "ace spades");
-        assert_eq!(split_deck.len(), 52);
+    pub fn add_breakpoint(&mut self, name: &str) {
+        self.breakpoints.push(name.to_string());
+        self.runtime.debugger().add_breakpoint(name);
     }
 
-    #[test]
-    fn it_can_display_a_name() {
-        let ast = vec!(
-            Statement::Declaration(
-                Declaration {
-                    key: GlobalKey::Name,
-                    value: Expression::Symbol("turns".to_string())
-                }
-            )
-        );
+    pub fn debugger(&mut self) -> &mut Debugger {
+        self.runtime.debugger()
+    }
 
-        let game = Game::new(ast);
-        let name = game.show("name");
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
 
-        assert_eq!(name, "turns".to_string());
+    pub fn player_move(&mut self, player: usize) -> bool {
+        self.try_move(player).is_ok()
     }
 
-    #[test]
-    fn it_can_display_players() {
-        let ast = vec!(
-            Statement::Declaration(
-                Declaration {
-                    key: GlobalKey::Players,
-                    value: Expression::Number(3.0)
-                }
-            )
-        );
+    // as player_move, but says why a move was rejected
+    pub fn try_move(&mut self, player: usize) -> Result<(), CardlangError> {
+        let previous_state = self.runtime.clone();
+        self.runtime.record_clone();
+        if let Err(e) = self.runtime.try_player_move(player) {
+            let e = CardlangError::from(e);
+            self.message_host(&format!("move rejected: {}", e));
+            return Err(e);
+        }
 
-        let game = Game::new(ast);
-        let players = game.show("players");
+        self.history.push(HistoryEntry{ player, previous_state });
+        self.turn_started = Instant::now();
+        if !self.runtime.is_active() {
+            self.message_host(&self.show("game"));
+        }
+        self.notify_host();
+        Ok(())
+    }
 
-        assert_eq!(players, "player 1 (cards: 0), player 2 (cards: 0), player 3 (cards: 0)".to_string());
+    // the work done since the game started: statements run, copies made and how long moves took
+    pub fn metrics(&self) -> Metrics {
+        self.runtime.metrics()
     }
 
-    #[test]
-    fn it_can_display_a_single_player() {
-        let ast = vec!(
-            Statement::Declaration (
-                Declaration {
-                    key: GlobalKey::Players,
-                    value: Expression::Number(1.0)
-                }
-            )
-        );
+    // every reason the last move went wrong, not only the one try_move returned
+    pub fn violations(&self) -> Vec<Violation> {
+        self.runtime.violations().to_vec()
+    }
 
-        let game = Game::new(ast);
-        let players = game.show("players");
+    pub fn legal_moves(&self) -> Vec<usize> {
+        Self::legal_moves_from(&self.runtime)
+    }
 
-        assert_eq!(players, "player 1 (cards: 0)".to_string());
+    fn legal_moves_from(runtime: &Runtime) -> Vec<usize> {
+        (1..=runtime.players().len()).filter(|&player| {
+            let mut trial = runtime.fork();
+            trial.player_move(player)
+        }).collect()
     }
 
-    #[test]
-    fn it_can_start_a_game() {
-        let mut ast = vec!(
-            Statement::Declaration(
-                Declaration {
-                    key: GlobalKey::Players,
-                    value: Expression::Number(3.0)
-                }
-            )
-        );
-        let from = "deck".to_owned();
-        let to = "players".to_owned();
-        let modifier = None;
-        let count = None;
-        let transfer = Transfer{ from, to, modifier, count };
-        let transfer_statement = Statement::Transfer(transfer);
+    // plays each legal move out at random and picks the one whose mover wins most often
+    pub fn suggest_move(&self, playouts: usize) -> Option<Suggestion> {
+        let playouts = playouts.max(1);
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy()
+        };
 
-        let name = "setup".to_owned();
-        let body = vec!(transfer_statement);
-        let definition = Definition{ arguments: vec!(), name, body };
-        let statement = Statement::Definition(definition);
+        let mut best: Option<Suggestion> = None;
+        for (player, position) in self.enumerate_moves() {
+            let wins = (0..playouts).filter(|_| {
+                let mut trial = position.runtime.fork();
+                trial.set_seed(rng.gen());
+                Self::playout(&mut trial).contains(&(player as f64))
+            }).count();
+
+            let win_rate = wins as f64 / playouts as f64;
+            if best.as_ref().is_none_or(|b| win_rate > b.win_rate) {
+                best = Some(Suggestion { player_move: player, win_rate, playouts });
+            }
+        }
+        best
+    }
 
-        ast.push(statement);
+    fn playout(runtime: &mut Runtime) -> Vec<f64> {
+        for _ in 0..PLAYOUT_DEPTH {
+            if !runtime.is_active() {
+                break;
+            }
 
-        let mut game = Game::new(ast);
-        game.start();
+            let legal_moves = Self::legal_moves_from(runtime);
+            if legal_moves.is_empty() {
+                break;
+            }
 
-        let deck = game.show("deck");
-        let split_deck: Vec<&str> = deck.split(",").collect();
+            let choice = runtime.random_index(legal_moves.len());
+            runtime.player_move(legal_moves[choice]);
+        }
+        runtime.winners().to_vec()
+    }
 
-        assert_eq!(split_deck.len(), 49);
+    // a copy of the current position for search, sharing the game's definitions and
+    // leaving behind the history, host, breakpoints and trace
+    pub fn fork(&self) -> Game {
+        self.fork_with(self.runtime.fork())
     }
 
-    #[test]
-    fn second_start_restarts() {
-        let mut ast = vec!(
-            Statement::Declaration(
-                Declaration {
-                    key: GlobalKey::Players,
-                    value: Expression::Number(3.0)
-                }
-            )
-        );
-        let from = "deck".to_owned();
-        let to = "players".to_owned();
-        let modifier = None;
-        let count = None;
-        let transfer = Transfer{ from, to, modifier, count };
-        let transfer_statement = Statement::Transfer(transfer);
+    // every legal move along with the position it leads to
+    pub fn enumerate_moves(&self) -> Vec<(usize, Game)> {
+        (1..=self.runtime.players().len()).filter_map(|player| {
+            let mut trial = self.runtime.fork();
+            if trial.player_move(player) {
+                Some((player, self.fork_with(trial)))
+            } else {
+                None
+            }
+        }).collect()
+    }
 
-        let name = "setup".to_owned();
-        let body = vec!(transfer_statement);
-        let definition = Definition{ arguments: vec!(), name, body };
-        let statement = Statement::Definition(definition);
+    fn fork_with(&self, runtime: Runtime) -> Game {
+        Game {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            ast: Arc::clone(&self.ast),
+            runtime,
+            initial_values: self.initial_values.clone(),
+            callbacks: self.callbacks.clone(),
+            seed: self.seed,
+            history: vec!(),
+            breakpoints: vec!(),
+            tracing: false,
+            host: None,
+            builtins: self.builtins.clone(),
+            coverage: None,
+            turn_limit: None,
+            turn_started: Instant::now(),
+            formatter: Arc::clone(&self.formatter),
+            session: SessionTally::default(),
+            replay_seed: None
+        }
+    }
+
+    pub fn auto(&mut self, limit: Option<usize>) -> String {
+        let limit = limit.unwrap_or(AUTO_MOVE_LIMIT);
+        let mut moves_made = 0;
+
+        while moves_made < limit && self.runtime.is_active() {
+            let legal_moves = self.legal_moves();
+            if legal_moves.is_empty() {
+                break;
+            }
+
+            if let Some(choice) = self.runtime.choose_move(&legal_moves) {
+                self.player_move(choice);
+            }
+            moves_made += 1;
+        }
+
+        format!("moves made: {}\n{}", moves_made, self.show("game"))
+    }
+
+    // `cardlang/1 game=<hash> seed=<seed> moves=1,2,1` - everything needed to play the game again
+    // exactly, as long as it's against the same rules. None for a game loaded from a save
+    pub fn export_replay(&self) -> Option<String> {
+        let seed = self.replay_seed?;
+        let moves: Vec<String> = self.history.iter().map(|entry| entry.player.to_string()).collect();
+        Some(format!("{} game={:016x} seed={} moves={}", REPLAY_PREFIX, self.rules_hash(), seed, moves.join(",")))
+    }
+
+    pub fn import_replay(&mut self, notation: &str) -> Result<(), CardlangError> {
+        let invalid = |reason: &str| CardlangError::InvalidReplay(reason.to_string());
+        let mut fields = notation.split_whitespace();
+        if fields.next() != Some(REPLAY_PREFIX) {
+            return Err(invalid("it isn't in the cardlang/1 notation"));
+        }
+
+        let mut hash = None;
+        let mut seed = None;
+        let mut moves = vec!();
+        for field in fields {
+            match field.split_once('=') {
+                Some(("game", h)) => hash = u64::from_str_radix(h, 16).ok(),
+                Some(("seed", s)) => seed = s.parse::<u64>().ok(),
+                Some(("moves", "")) => (),
+                Some(("moves", m)) => {
+                    moves = m.split(',').map(|n| n.parse::<usize>()).collect::<Result<Vec<usize>, _>>()
+                        .map_err(|_| invalid("its moves aren't player numbers"))?;
+                },
+                _ => return Err(invalid(&format!("'{}' isn't part of the notation", field)))
+            }
+        }
+
+        match hash {
+            Some(h) if h == self.rules_hash() => (),
+            Some(_) => return Err(invalid("it was recorded against different rules")),
+            None => return Err(invalid("it doesn't say which game it's for"))
+        }
+        let seed = seed.ok_or_else(|| invalid("it has no seed"))?;
+
+        let chosen_seed = self.seed.replace(seed);
+        self.start();
+        self.seed = chosen_seed;
+
+        for (turn, player) in moves.into_iter().enumerate() {
+            self.try_move(player).map_err(|e| invalid(&format!("move {} (player {}) was rejected, {}", turn + 1, player, e)))?;
+        }
+        Ok(())
+    }
+
+    // fnv-1a over the game's canonical source, which unlike std's hasher is the same on every
+    // machine and release. Comments, blank lines and indenting don't change it, the rules do
+    fn rules_hash(&self) -> u64 {
+        crate::cache::fnv1a(crate::ast::to_source(&self.ast).as_bytes())
+    }
+
+    pub fn show_history(&self) -> String {
+        if self.history.is_empty() {
+            return "no moves made".to_string();
+        }
+
+        self.history.iter().enumerate().map(|(turn, entry)| {
+            format!("{}: move {}", turn + 1, entry.player)
+        }).collect::<Vec<String>>().join("\n")
+    }
+
+    pub fn undo(&mut self, moves: usize) -> String {
+        let mut rolled_back = vec!();
+
+        for _ in 0..moves {
+            let turn = self.history.len();
+            match self.history.pop() {
+                Some(entry) => {
+                    rolled_back.push(format!("undid {}: move {}", turn, entry.player));
+                    self.runtime = entry.previous_state;
+                },
+                None => break
+            }
+        }
+
+        if rolled_back.is_empty() {
+            return "nothing to undo".to_string();
+        }
+
+        self.notify_host();
+        rolled_back.join("\n")
+    }
+
+    fn check_exploded_show(&self, key: &str, seat: Option<usize>) -> String {
+        let instructions: Vec<&str> = key.split(" ").collect();
+        if let [pile @ .., "summary"] = instructions.as_slice() {
+            if !pile.is_empty() {
+                return self.display_summary(pile, seat);
+            }
+        }
+        match instructions[0] {
+            "player" => self.handle_show_player(instructions, seat),
+            key => self.find_custom_item(key)
+        }
+    }
+
+    fn handle_show_player(&self, args: Vec<&str>, seat: Option<usize>) -> String {
+        let player_num = args.get(1).and_then(|n| n.parse::<usize>().ok()).unwrap_or(1);
+        match args.get(2) {
+            Some(&stack) if stack != "hand" => self.display_player_stack(player_num, stack, seat),
+            _ => self.display_hand(player_num, seat)
+        }
+    }
+
+    // players are numbered from 1, and anyone can ask for one that isn't there
+    fn seated_player(&self, player_num: usize) -> Option<&Player> {
+        self.runtime.players().get(player_num.checked_sub(1)?)
+    }
+
+    fn display_hand(&self, player_num: usize, seat: Option<usize>) -> String {
+        let hand = match self.seated_player(player_num) {
+            Some(player) => player.get_hand(),
+            None => return format!("player {} not found", player_num)
+        };
+        match seat {
+            Some(s) if s != player_num => self.display_hidden(&hand),
+            _ => self.formatter.cards(&hand)
+        }
+    }
+
+    // hidden from other seats, like a hand
+    fn display_player_stack(&self, player_num: usize, stack: &str, seat: Option<usize>) -> String {
+        let player = match self.seated_player(player_num) {
+            Some(player) => player,
+            None => return format!("player {} not found", player_num)
+        };
+        match (player.stack(stack), seat) {
+            (None, _) => format!("player {} {} not found", player_num, stack),
+            (Some(cards), _) if self.is_face_down(&format!("player:{}", stack)) => self.formatter.face_down(cards.len()),
+            (Some(cards), Some(s)) if s != player_num => self.display_hidden(cards),
+            (Some(cards), _) => self.formatter.cards(cards)
+        }
+    }
+
+    // counts by rank and suit, for any pile the seat could read card by card
+    fn display_summary(&self, pile: &[&str], seat: Option<usize>) -> String {
+        let player = |n: &str| n.parse::<usize>().ok().filter(|n| *n > 0 && *n <= self.runtime.players().len());
+        let face_down = match pile {
+            ["player", _, stack] => self.is_face_down(&format!("player:{}", stack)),
+            [stack] => self.is_face_down(stack),
+            _ => false
+        };
+        let (cards, visible) = match pile {
+            ["deck"] => (Some(self.runtime.get_deck()), seat.is_none()),
+            ["player", n] | ["player", n, "hand"] => match player(n) {
+                Some(n) => (Some(self.runtime.get_player(n - 1).get_hand()), seat.is_none_or(|s| s == n)),
+                None => (None, false)
+            },
+            ["player", n, stack] => match player(n) {
+                Some(n) => (self.runtime.get_player(n - 1).stack(stack).map(|c| c.to_vec()), seat.is_none_or(|s| s == n)),
+                None => (None, false)
+            },
+            [stack] => (self.runtime.find_custom_item(stack), seat.is_none() || *stack != BURN_PILE),
+            _ => (None, false)
+        };
+        match cards {
+            Some(cards) if face_down => self.formatter.face_down(cards.len()),
+            Some(cards) if visible => self.formatter.table(&summary(&cards)),
+            Some(cards) => self.display_hidden(&cards),
+            None => format!("{} not found", pile.join(" "))
+        }
+    }
+
+    fn display_hidden(&self, cards: &[Card]) -> String {
+        self.formatter.hidden(cards.len())
+    }
+
+    fn is_face_down(&self, stack: &str) -> bool {
+        self.initial_values.face_down.iter().any(|s| s == stack)
+    }
+
+    fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.to_string(),
+            None => "Name not initalised!".to_string() // TODO - Error? Default? 
+         }
+    }
+
+    pub fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    pub fn get_status(&self) -> String {
+        self.runtime.get_status()
+    }
+
+    pub fn get_deck(&self) -> Vec<Card> {
+        self.runtime.get_deck()
+    }
+
+    pub fn get_players(&self) -> Vec<Player> {
+        self.runtime.get_players()
+    }
+
+    pub fn get_current_player(&self) -> usize {
+        self.runtime.get_current_player()
+    }
+
+    pub fn deck(&self) -> &[Card] {
+        self.runtime.deck()
+    }
+
+    // players are numbered from 1, as in the language
+    pub fn player(&self, n: usize) -> Option<PlayerView<'_>> {
+        let player = self.runtime.players().get(n.checked_sub(1)?)?;
+        Some(PlayerView {
+            id: n,
+            hand: player.hand(),
+            is_current: n == self.runtime.get_current_player(),
+            is_winner: self.runtime.winners().contains(&(n as f64))
+        })
+    }
+
+    pub fn stack(&self, name: &str) -> Option<&[Card]> {
+        self.runtime.stack(name)
+    }
+
+    pub fn state(&self) -> GameStateView<'_> {
+        GameStateView {
+            name: self.name.as_deref(),
+            status: self.runtime.status(),
+            current_player: self.runtime.get_current_player(),
+            player_count: self.runtime.players().len(),
+            winners: self.runtime.winners().iter().map(|w| *w as usize).collect()
+        }
+    }
+
+    // the state visible to a player as numbers, the same length for every call on a game:
+    // their hand one-hot by card, deck, stack and hand sizes as fractions of a deck,
+    // then the current player and the game's status one-hot
+    pub fn encode_observation(&self, player: usize) -> Vec<f64> {
+        let mut encoded = vec!(0.0; DECK_SIZE);
+        if let Some(view) = self.player(player) {
+            for card in view.hand.iter() {
+                encoded[card.index()] = 1.0;
+            }
+        }
+
+        let size = |cards: &[Card]| cards.len() as f64 / DECK_SIZE as f64;
+        encoded.push(size(self.deck()));
+        for (_, cards) in self.get_stacks().iter() {
+            encoded.push(size(cards));
+        }
+        for p in self.runtime.players().iter() {
+            encoded.push(size(p.hand()));
+        }
+
+        let current_player = self.runtime.get_current_player();
+        encoded.extend((1..=self.runtime.players().len()).map(|n| if n == current_player { 1.0 } else { 0.0 }));
+
+        let status = self.runtime.status();
+        encoded.extend([GameState::Pending, GameState::Active, GameState::GameOver].iter()
+            .map(|s| if *s == status { 1.0 } else { 0.0 }));
+        encoded
+    }
+
+    pub fn observation_size(&self) -> usize {
+        let players = self.runtime.players().len();
+        DECK_SIZE + 1 + self.initial_values.card_stacks.len() + players * 2 + 3
+    }
+
+    pub fn get_stacks(&self) -> Vec<(String, Vec<Card>)> {
+        self.initial_values.card_stacks.iter().map(|stack| {
+            (stack.to_string(), self.runtime.find_custom_item(stack).unwrap_or_default())
+        }).collect()
+    }
+
+    fn display_stacks(&self) -> String {
+        self.initial_values.card_stacks.iter().map(|stack| {
+            let count = self.runtime.find_custom_item(stack).unwrap_or_default().len();
+            format!("{} (cards: {})", stack, count)
+        }).collect::<Vec<String>>().join(", ")
+    }
+
+    fn display_chips(&self) -> String {
+        let rows: Vec<(String, String)> = self.runtime.players().iter().map(|p| {
+            let out = if p.eliminated() { " (eliminated)" } else { "" };
+            (format!("player {}", p.get_id()), format!("{}{}", p.chips(), out))
+        }).collect();
+        self.formatter.table(&rows)
+    }
+
+    fn display_scores(&self) -> String {
+        let rows: Vec<(String, String)> = self.runtime.players().iter().map(|p| {
+            (format!("player {}", p.get_id()), p.score().to_string())
+        }).collect();
+        self.formatter.table(&rows)
+    }
+
+    // a finished game counts straight away, rather than waiting for the next start
+    fn session_tally(&self) -> SessionTally {
+        let mut tally = self.session.clone();
+        if self.runtime.status() != GameState::GameOver {
+            return tally;
+        }
+
+        let players = self.runtime.players();
+        tally.scores.resize(players.len(), 0.0);
+        tally.wins.resize(players.len(), 0);
+        tally.games += 1;
+        for (i, player) in players.iter().enumerate() {
+            tally.scores[i] += player.score();
+        }
+        for winner in self.runtime.get_winners() {
+            if winner >= 1.0 && winner as usize <= players.len() {
+                tally.wins[winner as usize - 1] += 1;
+            }
+        }
+        tally
+    }
+
+    fn display_session(&self) -> String {
+        let tally = self.session_tally();
+        let mut rows = vec!(("games".to_string(), tally.games.to_string()));
+        for (i, (score, wins)) in tally.scores.iter().zip(tally.wins.iter()).enumerate() {
+            rows.push((format!("player {}", i + 1), format!("{} wins, {} points", wins, score)));
+        }
+        self.formatter.table(&rows)
+    }
+
+    fn display_all(&self, seat: Option<usize>) -> String {
+        let mut rows = vec!(("deck".to_string(), self.show_for_seat("deck", seat)));
+
+        for stack in self.initial_values.card_stacks.iter() {
+            rows.push((stack.to_string(), self.show_for_seat(stack, seat)));
+        }
+
+        for player in self.runtime.get_players().iter() {
+            let id = player.get_id() as usize;
+            rows.push((format!("player {}", id), self.display_hand(id, seat)));
+        }
+
+        self.formatter.table(&rows)
+    }
+
+    // what anyone watching can see: the shared stacks, and for each player only how many cards
+    // they hold and their score
+    fn display_table(&self) -> String {
+        let mut rows = vec!(("deck".to_string(), self.display_hidden(&self.runtime.get_deck())));
+
+        for stack in self.initial_values.card_stacks.iter() {
+            let shown = match stack.as_str() {
+                BURN_PILE => self.display_hidden(&self.runtime.find_custom_item(BURN_PILE).unwrap_or_default()),
+                _ => self.find_custom_item(stack)
+            };
+            rows.push((stack.to_string(), shown));
+        }
+
+        rows.push(("current player".to_string(), self.runtime.get_current_player().to_string()));
+        for player in self.runtime.players().iter() {
+            let id = player.get_id() as usize;
+            let mut public = vec!(format!("{} cards", player.hand().len()), format!("score {}", player.score()));
+            if self.initial_values.chips.is_some() {
+                public.push(format!("{} chips", player.chips()));
+            }
+            rows.push((format!("player {}", id), public.join(", ")));
+            for stack in self.initial_values.player_stacks.iter() {
+                // seat 0 is nobody's, so every player's own stacks stay hidden
+                rows.push((format!("player {} {}", id, stack), self.display_player_stack(id, stack, Some(0))));
+            }
+        }
+
+        self.formatter.table(&rows)
+    }
+
+    fn display_list<D: Display>(list: &Vec<D>) -> String {
+        list.iter().map(|x|x.to_string()).collect::<Vec<String>>().join(", ")
+    }
+
+    fn find_custom_item(&self, key: &str) -> String {
+        match self.runtime.find_custom_item(key) {
+            Some(v) if self.is_face_down(key) => self.formatter.face_down(v.len()),
+            Some(v) => self.formatter.cards(&v),
+            _ => format!("{} not found", key)
+        }
+    }
+
+}
+
+// anywhere it could run, by its own name or as `std:name`
+fn calls_function(statements: &[Statement], name: &str) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::FunctionCall(f) => crate::diagnostics::builtin_name(&f.name) == name,
+        Statement::Definition(d) => calls_function(&d.body, name),
+        Statement::IfStatement(i) => calls_function(&i.body, name),
+        Statement::WhileStatement(w) => calls_function(&w.body, name),
+        Statement::ForeachStatement(f) => calls_function(&f.body, name),
+        Statement::MatchStatement(m) => {
+            m.arms.iter().any(|arm| calls_function(&arm.body, name))
+                || m.otherwise.as_ref().is_some_and(|body| calls_function(body, name))
+        },
+        _ => false
+    })
+}
+
+/*
+
+
+######################################
+//////////////////////////////////////
+///////////// TESTS //////////////////
+//////////////////////////////////////
+######################################
+
+
+
+*/
+
+#[cfg(test)]
+mod test{
+    use super::*;
+    use crate::cards::standard_deck;
+    use crate::error::{RuntimeError, Span};
+    use crate::host::TransferDecision;
+    use std::{sync::{Mutex, OnceLock}, thread::{self, ThreadId}};
+
+    #[derive(Default)]
+    struct RecordingHost {
+        states: Vec<(String, usize)>,
+        messages: Vec<String>
+    }
+
+    impl crate::host::GameHost for RecordingHost {
+        fn state_changed(&mut self, state: &GameStateView) {
+            self.states.push((state.status.to_string(), state.current_player));
+        }
+
+        fn message(&mut self, message: &str) {
+            self.messages.push(message.to_string());
+        }
+
+        fn request_move(&mut self, _state: &GameStateView, legal_moves: &[usize]) -> Option<usize> {
+            if self.states.len() > 2 {
+                return None;
+            }
+            legal_moves.first().copied()
+        }
+    }
+
+    struct HouseRules;
+
+    // nobody may take a card from the middle
+    impl crate::host::GameHost for HouseRules {
+        fn before_transfer(&mut self, from: &str, _to: &str, _cards: &[Card]) -> TransferDecision {
+            if from == "middle" { TransferDecision::Deny } else { TransferDecision::Allow }
+        }
+    }
+
+    #[test]
+    fn hosts_can_deny_transfers() {
+        let source = "players 2\nstack middle\ndefine setup(){\ndeck > middle\n}\ndefine player_move(player){\nmiddle > player:hand\ndeck > player:hand\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.set_host(std::sync::Arc::new(std::sync::Mutex::new(HouseRules)));
+        game.set_tracing(true);
+        game.start();
+        game.take_trace();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("middle"), "king diamonds".to_string());
+        assert_eq!(game.show("player 1"), "queen diamonds".to_string());
+        assert_eq!(game.take_trace()[0], "middle > player:hand (denied by the host)".to_string());
+    }
+
+    #[test]
+    fn every_card_from_several_decks_has_its_own_id() {
+        let source = "decks 2\nplayers 1\ndefine setup(){\ndeck > players end\n}\ndefine is_first_deck(card){\nreturn(card:id between 0 and 51)\n}\ndefine player_move(player){\ncheck(count_where(player:hand, is_first_deck) is 52)\nend()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        let hand = game.player(1).unwrap().hand.to_vec();
+        let mut ids: Vec<u32> = hand.iter().map(|c| c.id()).collect();
+        ids.sort();
+        ids.dedup();
+
+        assert_eq!(hand.len(), 104);
+        assert_eq!(ids.len(), 104);
+        assert_eq!(hand.iter().filter(|c| c.to_string() == "ace spades").count(), 2);
+        assert!(game.player_move(1));
+    }
+
+    #[test]
+    fn piles_can_be_summarised_by_rank_and_suit() {
+        let mut game = crate::load_game("players 2\ndefine setup(){\ndeck > players\n}").unwrap();
+        game.start();
+
+        assert_eq!(game.show("player 2 summary"), "cards: 1\nsuits: diamonds 1\nranks: queen 1".to_string());
+        assert_eq!(game.show("deck summary").lines().next(), Some("cards: 50"));
+        assert_eq!(game.show_for_seat("player 2 summary", Some(1)), "hidden (1 cards)".to_string());
+        assert_eq!(game.show_for_seat("deck summary", Some(1)), "hidden (50 cards)".to_string());
+        assert_eq!(game.show("middle summary"), "middle not found".to_string());
+    }
+
+    // the logger is global, so each test only looks at the records from its own thread
+    #[derive(Default)]
+    struct Capture(Mutex<Vec<(ThreadId, String, Vec<(String, String)>)>>);
+
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            let mut fields = Fields::default();
+            let _ = record.key_values().visit(&mut fields);
+            if let Ok(mut records) = self.0.lock() {
+                records.push((thread::current().id(), record.args().to_string(), fields.0));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[derive(Default)]
+    struct Fields(Vec<(String, String)>);
+
+    impl<'kvs> log::kv::VisitSource<'kvs> for Fields {
+        fn visit_pair(&mut self, name: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+            self.0.push((name.to_string(), value.to_string()));
+            Ok(())
+        }
+    }
+
+    fn capture() -> &'static Capture {
+        static CAPTURE: OnceLock<&'static Capture> = OnceLock::new();
+        CAPTURE.get_or_init(|| {
+            let capture: &'static Capture = Box::leak(Box::default());
+            let _ = log::set_logger(capture);
+            log::set_max_level(log::LevelFilter::Trace);
+            capture
+        })
+    }
+
+    #[test]
+    fn moves_are_logged_as_spans() {
+        let capture = capture();
+        let source = "players 2\ndefine player_move(player){\ndeck > player:hand\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        game.legal_moves();
+
+        assert!(game.player_move(1));
+        let id = thread::current().id();
+        let records: Vec<_> = capture.0.lock().unwrap().iter().filter(|(t, ..)| *t == id).map(|(_, m, f)| (m.clone(), f.clone())).collect();
+        let messages: Vec<&str> = records.iter().map(|(m, _)| m.as_str()).collect();
+        let span = |fields: &[(String, String)]| fields.iter().find(|(name, _)| name == "span").map(|(_, value)| value.clone());
+
+        assert_eq!(messages, vec!("move started", "transfer", "function call", "move"));
+        assert!(span(&records[0].1).unwrap().parse::<u64>().is_ok());
+        assert!(records.iter().all(|(_, f)| span(f) == span(&records[0].1)));
+        assert_eq!(records[1].1[..3], [
+            ("from".to_string(), "deck".to_string()),
+            ("to".to_string(), "player:hand".to_string()),
+            ("moved".to_string(), "1".to_string())
+        ]);
+        assert!(records[3].1.contains(&("result".to_string(), "accepted".to_string())));
+    }
+
+    #[test]
+    fn registered_builtins_survive_a_restart() {
+        let source = "players 2\ndefine player_move(player){\ncheck(house_allows(player:id))\nadd_score(player:id, 1)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.register_builtin("house_allows", |_, arguments| Some(PrimitiveValue::Bool(arguments == [PrimitiveValue::Number(1.0)])));
+        game.start();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert!(!game.player_move(2));
+    }
+
+    #[test]
+    fn every_failed_check_is_reported() {
+        let source = "players 2\ndefine player_move(player){\nnowhere > player:hand\ncheck(player:id is current_player)\ncheck(count(player:hand) is 1)\ncheck(count(player:hand) is 0)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(!game.player_move(2));
+        assert_eq!(game.violations().iter().map(|v| v.to_string()).collect::<Vec<String>>(), vec!(
+            "nowhere > player:hand couldn't move any cards at line 3, column 1".to_string(),
+            "a check failed in player_move at line 4, column 1".to_string(),
+            "a check failed in player_move at line 5, column 1".to_string()
+        ));
+
+        assert!(!game.player_move(3));
+        assert_eq!(game.violations(), vec!(Violation::Rejected(RuntimeError::UnknownPlayer(3))));
+    }
+
+    #[test]
+    fn hosts_are_told_about_every_change() {
+        let source = "players 2\ndefine player_move(player){\ncheck(player:id is current_player)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        let host = std::sync::Arc::new(std::sync::Mutex::new(RecordingHost::default()));
+        game.set_host(host.clone());
+
+        game.start();
+        game.player_move(2);
+        assert_eq!(game.play(), 2);
+
+        let host = host.lock().unwrap();
+        assert_eq!(host.states, vec!(
+            ("active".to_string(), 1),
+            ("active".to_string(), 2),
+            ("active".to_string(), 1)
+        ));
+        assert_eq!(host.messages, vec!("move rejected: a check failed in player_move at line 3, column 1".to_string()));
+    }
+
+    #[test]
+    fn play_without_a_host_makes_no_moves() {
+        let source = "players 2\ndefine player_move(player){\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(game.play(), 0);
+    }
+
+    #[test]
+    fn observations_encode_what_a_player_can_see() {
+        let source = "players 2\nstack middle\ndefine setup(){\ndeck > players\ndeck > middle\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        let encoded = game.encode_observation(1);
+        let hand = game.player(1).unwrap().hand[0];
+
+        assert_eq!(encoded.len(), game.observation_size());
+        assert_eq!(encoded[..DECK_SIZE].iter().sum::<f64>(), 1.0);
+        assert_eq!(encoded[hand.index()], 1.0);
+        assert_eq!(encoded[DECK_SIZE..DECK_SIZE + 4].to_vec(), vec!(49.0 / 52.0, 1.0 / 52.0, 1.0 / 52.0, 1.0 / 52.0));
+        assert_eq!(encoded[DECK_SIZE + 4..].to_vec(), vec!(1.0, 0.0, 0.0, 1.0, 0.0));
+        assert_eq!(game.encode_observation(3).len(), game.observation_size());
+    }
+
+    #[test]
+    fn forks_are_independent_of_the_original() {
+        let source = "players 2\ndefine setup(){\ndeck > players\n}\ndefine player_move(player){\ncheck(player:id is current_player)\nplayer:hand > deck\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.set_tracing(true);
+        game.start();
+        game.take_trace();
+
+        game.player_move(1);
+        game.player_move(2);
+
+        let mut fork = game.fork();
+        assert_eq!(fork.show_history(), "no moves made".to_string());
+        assert!(fork.player_move(1));
+        assert_eq!(fork.get_current_player(), 2);
+        assert_eq!(fork.take_trace(), Vec::<String>::new());
+
+        assert_eq!(game.get_current_player(), 1);
+        assert_eq!(game.deck().len(), 52);
+    }
+
+    #[test]
+    fn it_enumerates_moves_with_their_positions() {
+        let source = "players 3\ndefine player_move(player){\ncheck(player:id is current_player)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        let moves = game.enumerate_moves();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0, 1);
+        assert_eq!(moves[0].1.get_current_player(), 2);
+        assert_eq!(moves[0].1.legal_moves(), vec!(2));
+        assert_eq!(game.get_current_player(), 1);
+    }
+
+    #[test]
+    fn hints_prefer_the_move_that_wins() {
+        // any player can move, but only player 2 wins by doing so
+        let source = "players 2\ndefine player_move(player){\nif(player:id is 2){\nwinner(player:id)\nend()\n}\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.set_seed(3);
+        game.start();
+
+        let suggestion = game.suggest_move(10).unwrap();
+
+        assert_eq!(suggestion, Suggestion { player_move: 2, win_rate: 1.0, playouts: 10 });
+    }
+
+    #[test]
+    fn there_are_no_hints_without_legal_moves() {
+        let game = crate::load_game("players 2").unwrap();
+
+        assert_eq!(game.suggest_move(10), None);
+    }
+
+    #[test]
+    fn rejected_moves_say_why() {
+        let source = "players 2\ndefine player_move(player){\ncheck(player:id is current_player)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+
+        assert_eq!(game.try_move(1), Err(CardlangError::Runtime(RuntimeError::NotActive)));
+
+        game.start();
+        assert_eq!(game.try_move(3), Err(CardlangError::Runtime(RuntimeError::UnknownPlayer(3))));
+        assert_eq!(
+            game.try_move(2),
+            Err(CardlangError::Runtime(RuntimeError::CheckFailed("player_move".to_string(), Span{ line: 3, column: 1 })))
+        );
+        assert_eq!(game.try_move(2).unwrap_err().to_string(), "a check failed in player_move at line 3, column 1".to_string());
+        assert_eq!(game.try_move(1), Ok(()));
+        assert_eq!(game.show_history(), "1: move 1".to_string());
+    }
+
+    #[test]
+    fn it_exposes_typed_views_of_the_game() {
+        let source = "name turns\nplayers 2\nstack middle\ndefine setup(){\ndeck > players\ndeck > middle\n}\ndefine player_move(player){\ncheck(player:id is current_player)\nwinner(player:id)\nend()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(game.deck().len(), 49);
+        assert_eq!(game.stack("middle").map(|s| s.len()), Some(1));
+        assert_eq!(game.stack("pile"), None);
+
+        let player = game.player(2).unwrap();
+        assert_eq!(player.hand, &standard_deck()[50..51]);
+        assert!(!player.is_current);
+        assert!(game.player(0).is_none());
+        assert!(game.player(3).is_none());
+
+        game.player_move(1);
+        assert_eq!(game.state(), GameStateView {
+            name: Some("turns"),
+            status: GameState::GameOver,
+            current_player: 1,
+            player_count: 2,
+            winners: vec!(1)
+        });
+        assert!(game.player(1).unwrap().is_winner);
+    }
+
+    #[test]
+    fn it_can_display_a_deck() {
+        let ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Deck,
+                    value: Expression::Symbol("StandardDeck".to_string()),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+
+        let game = Game::new(ast);
+        let deck = game.show("deck");
+        let split_deck: Vec<&str> = deck.split(",").collect();
+
+        assert_eq!(split_deck[0], "ace spades");
+        assert_eq!(split_deck.len(), 52);
+    }
+
+    #[test]
+    fn it_can_display_a_name() {
+        let ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Name,
+                    value: Expression::Symbol("turns".to_string()),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+
+        let game = Game::new(ast);
+        let name = game.show("name");
+
+        assert_eq!(name, "turns".to_string());
+    }
+
+    #[test]
+    fn it_can_display_players() {
+        let ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(3.0),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+
+        let game = Game::new(ast);
+        let players = game.show("players");
+
+        assert_eq!(players, "player 1 (cards: 0), player 2 (cards: 0), player 3 (cards: 0)".to_string());
+    }
+
+    #[test]
+    fn it_can_display_a_single_player() {
+        let ast = vec!(
+            Statement::Declaration (
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(1.0),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+
+        let game = Game::new(ast);
+        let players = game.show("players");
+
+        assert_eq!(players, "player 1 (cards: 0)".to_string());
+    }
+
+    #[test]
+    fn it_can_start_a_game() {
+        let mut ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(3.0),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+        let from = "deck".to_owned();
+        let to = "players".to_owned();
+        let modifier = None;
+        let count = None;
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
+        let transfer_statement = Statement::Transfer(transfer);
+
+        let name = "setup".to_owned();
+        let body = vec!(transfer_statement);
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let statement = Statement::Definition(definition);
+
+        ast.push(statement);
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        let deck = game.show("deck");
+        let split_deck: Vec<&str> = deck.split(",").collect();
+
+        assert_eq!(split_deck.len(), 49);
+    }
+
+    #[test]
+    fn second_start_restarts() {
+        let mut ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(3.0),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+        let from = "deck".to_owned();
+        let to = "players".to_owned();
+        let modifier = None;
+        let count = None;
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
+        let transfer_statement = Statement::Transfer(transfer);
+
+        let name = "setup".to_owned();
+        let body = vec!(transfer_statement);
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let statement = Statement::Definition(definition);
+
+        ast.push(statement);
+
+        let mut game = Game::new(ast);
+        game.start();
+        game.start();
+
+        let deck = game.show("deck");
+        let split_deck: Vec<&str> = deck.split(",").collect();
+
+        assert_eq!(split_deck.len(), 49);
+    }
+
+    #[test]
+    fn it_deals_to_the_end_with_the_count_modifier() {
+        let mut ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(3.0),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+        let from = "deck".to_owned();
+        let to = "players".to_owned();
+        let modifier = None; //Some(TransferModifier::Alternate);
+        let count = Some(Expression::Symbol("end".to_string()));
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
+        let transfer_statement = Statement::Transfer(transfer);
+
+        let name = "setup".to_owned();
+        let body = vec!(transfer_statement);
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let statement = Statement::Definition(definition);
+
+        ast.push(statement);
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        let deck = game.show("deck");
+        assert_eq!(&deck, "");
+    }
+
+    #[test]
+    fn it_can_show_player_hand(){
+        let mut ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(1.0),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+        let from = "deck".to_owned();
+        let to = "players".to_owned();
+        let modifier = None; //Some(TransferModifier::Alternate);
+        let count = None;
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
+        let transfer_statement = Statement::Transfer(transfer);
+
+        let name = "setup".to_owned();
+        let body = vec!(transfer_statement);
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let statement = Statement::Definition(definition);
+
+        ast.push(statement);
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        let hand = game.show("player 1 hand");
+        assert_eq!(&hand, "king diamonds");
+    }
+
+    #[test]
+    fn it_can_show_multiple_player_hand(){
+        let mut ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+        let from = "deck".to_owned();
+        let to = "players".to_owned();
+        let modifier = None; //Some(TransferModifier::Alternate);
+        let count = None;
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
+        let transfer_statement = Statement::Transfer(transfer);
+
+        let name = "setup".to_owned();
+        let body = vec!(transfer_statement);
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let statement = Statement::Definition(definition);
+
+        ast.push(statement);
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        let show_players = game.show("players");
+        assert_eq!(&show_players, "player 1 (cards: 1), player 2 (cards: 1)");
+
+        let hand = game.show("player 2 hand");
+        assert_eq!(&hand, "queen diamonds");
+    }
+
+    #[test]
+    fn it_can_access_built_in_functions() {
+        let body = vec!(
+            Statement::FunctionCall(
+                FunctionCall{
+                    name: "shuffle".to_string(),
+                    arguments: vec!(Expression::Symbol("deck".to_string())),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+
+        let name = "setup".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let statement = Statement::Definition(definition);
+        let ast = vec!(statement);
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        let usual_order = Game::display_list(&standard_deck());
+        let deck = game.show("deck");
+
+        assert_ne!(deck, usual_order);
+    }
+
+    #[test]
+    fn a_seed_makes_shuffles_repeatable() {
+        let body = vec!(
+            Statement::FunctionCall(
+                FunctionCall{
+                    name: "shuffle".to_string(),
+                    arguments: vec!(Expression::Symbol("deck".to_string())),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+
+        let name = "setup".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let statement = Statement::Definition(definition);
+        let ast = vec!(statement);
+
+        let mut game = Game::new(ast.clone());
+        game.set_seed(1234);
+        game.start();
+        let first_deck = game.show("deck");
+        game.start();
+        let second_deck = game.show("deck");
+
+        let mut other_game = Game::new(ast);
+        other_game.set_seed(1234);
+        other_game.start();
+
+        assert_eq!(first_deck, second_deck);
+        assert_eq!(first_deck, other_game.show("deck"));
+    }
+
+    #[test]
+    fn it_can_make_a_move() {
+        let body = vec!(
+            Statement::FunctionCall(
+                FunctionCall{
+                    name: "shuffle".to_string(),
+                    arguments: vec!(Expression::Symbol("deck".to_string())),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let statement = Statement::Definition(definition);
+
+        let ast = vec!(statement);
+
+        let mut game = Game::new(ast);
+        game.start();
+        game.player_move(1);
+
+        let usual_order = Game::display_list(&standard_deck());
+        let deck = game.show("deck");
+
+        assert_ne!(deck, usual_order);
+    }
+
+    #[test]
+    fn it_records_moves_in_the_history() {
+        let players = Statement::Declaration(
+            Declaration {
+                key: GlobalKey::Players,
+                value: Expression::Number(2.0),
+                span: SourceSpan::default()
+            }
+        );
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body: vec!(), span: SourceSpan::default() };
+        let ast = vec!(players, Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.player_move(1);
+        game.start();
+        game.player_move(1);
+        game.player_move(2);
+
+        assert_eq!(game.show_history(), "1: move 1\n2: move 2".to_string());
+    }
+
+    #[test]
+    fn starting_clears_the_history() {
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body: vec!(), span: SourceSpan::default() };
+        let ast = vec!(Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.start();
+        game.player_move(1);
+        game.start();
+
+        assert_eq!(game.show_history(), "no moves made".to_string());
+    }
+
+    #[test]
+    fn undo_reverts_the_last_move() {
+        let players = Statement::Declaration(
+            Declaration {
+                key: GlobalKey::Players,
+                value: Expression::Number(1.0),
+                span: SourceSpan::default()
+            }
+        );
+        let body = vec!(
+            Statement::Transfer(Transfer{
+                from: "deck".to_owned(),
+                to: "players".to_owned(),
+                modifier: None,
+                count: None,
+                span: SourceSpan::default()
+            })
+        );
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let ast = vec!(players, Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.start();
+        game.player_move(1);
+        game.player_move(1);
+
+        let result = game.undo(1);
+
+        assert_eq!(result, "undid 2: move 1".to_string());
+        assert_eq!(game.show("player 1 hand"), "king diamonds".to_string());
+        assert_eq!(game.show_history(), "1: move 1".to_string());
+    }
+
+    #[test]
+    fn undo_can_revert_several_moves() {
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body: vec!(), span: SourceSpan::default() };
+        let ast = vec!(Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.start();
+        game.player_move(1);
+        game.player_move(1);
+
+        assert_eq!(game.undo(3), "undid 2: move 1\nundid 1: move 1".to_string());
+        assert_eq!(game.undo(1), "nothing to undo".to_string());
+    }
+
+    #[test]
+    fn only_accepted_moves_are_recorded() {
+        let check = Statement::CheckStatement(CheckStatement{
+            expression: Expression::Bool(false),
+            span: SourceSpan::default()
+        });
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body: vec!(check), span: SourceSpan::default() };
+        let ast = vec!(Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        assert!(!game.player_move(1));
+        assert_eq!(game.show_history(), "no moves made".to_string());
+    }
+
+    #[test]
+    fn auto_plays_until_the_game_ends() {
+        let players = Statement::Declaration(
+            Declaration {
+                key: GlobalKey::Players,
+                value: Expression::Number(2.0),
+                span: SourceSpan::default()
+            }
+        );
+        let body = vec!(
+            Statement::CheckStatement(CheckStatement{
+                expression: Expression::Comparison(Box::new(Comparison{
+                    left: Expression::Symbol("player:id".to_string()),
+                    right: Expression::Symbol("current_player".to_string()),
+                    negative: false,
+                    span: SourceSpan::default()
+                })),
+                span: SourceSpan::default()
+            }),
+            Statement::Transfer(Transfer{
+                from: "deck".to_owned(),
+                to: "player:hand".to_owned(),
+                modifier: None,
+                count: None,
+                span: SourceSpan::default()
+            }),
+            Statement::IfStatement(IfStatement{
+                expression: Expression::Comparison(Box::new(Comparison{
+                    left: Expression::FunctionCall(FunctionCall{
+                        name: "count".to_string(),
+                        arguments: vec!(Expression::Symbol("player:hand".to_string())),
+                        span: SourceSpan::default()
+                    }),
+                    right: Expression::Number(2.0),
+                    negative: false,
+                    span: SourceSpan::default()
+                })),
+                body: vec!(Statement::FunctionCall(FunctionCall{
+                    name: "end".to_string(),
+                    arguments: vec!(),
+                    span: SourceSpan::default()
+                })),
+                span: SourceSpan::default()
+            }),
+            Statement::FunctionCall(FunctionCall{
+                name: "next_player".to_string(),
+                arguments: vec!(),
+                span: SourceSpan::default()
+            })
+        );
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!("player".to_string()), name, body, span: SourceSpan::default() };
+        let ast = vec!(players, Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        assert_eq!(game.auto(None), "moves made: 5\ngame over".to_string());
+        assert_eq!(game.show_history(), "1: move 1\n2: move 2\n3: move 1\n4: move 2\n5: move 1".to_string());
+    }
+
+    #[test]
+    fn auto_can_be_limited_to_a_number_of_moves() {
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!(), name, body: vec!(), span: SourceSpan::default() };
+        let ast = vec!(Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        assert_eq!(game.auto(Some(10)), "moves made: 10\nactive".to_string());
+    }
+
+    #[test]
+    fn a_breakpoint_pauses_on_each_statement_of_a_move() {
+        let body = vec!(
+            Statement::Transfer(Transfer{
+                from: "deck".to_owned(),
+                to: "player:hand".to_owned(),
+                modifier: None,
+                count: None,
+                span: SourceSpan::default()
+            }),
+            Statement::FunctionCall(FunctionCall{
+                name: "next_player".to_string(),
+                arguments: vec!(),
+                span: SourceSpan::default()
+            })
+        );
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!("player".to_string()), name, body, span: SourceSpan::default() };
+        let ast = vec!(Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.add_breakpoint("player_move");
+        game.start();
+        game.player_move(1);
+
+        assert_eq!(game.debugger().current(), "paused in player_move: deck > player:hand".to_string());
+        assert_eq!(game.debugger().stack(), "0: player_move".to_string());
+        assert_eq!(game.debugger().locals(), "player:bet = 0\nplayer:chips = 0\nplayer:eliminated = false\nplayer:folded = false\nplayer:hand = []\nplayer:id = 1\nplayer:is_current = true\nplayer:score = 0\nplayer:team = 1".to_string());
+        assert_eq!(game.debugger().step(), "deck: -king diamonds\nplayer 1 hand: +king diamonds\npaused in player_move: next_player(..)".to_string());
+        assert_eq!(game.debugger().step(), "move complete".to_string());
+    }
+
+    #[test]
+    fn the_debugger_can_step_back_through_a_move() {
+        let body = vec!(
+            Statement::Transfer(Transfer{
+                from: "deck".to_string(),
+                to: "player:hand".to_string(),
+                modifier: None,
+                count: None,
+                span: SourceSpan::default()
+            }),
+            Statement::FunctionCall(FunctionCall{
+                name: "next_player".to_string(),
+                arguments: vec!(),
+                span: SourceSpan::default()
+            })
+        );
+        let definition = Definition{ arguments: vec!("player".to_string()), name: "player_move".to_string(), body, span: SourceSpan::default() };
+        let mut game = Game::new(vec!(Statement::Definition(definition)));
+        game.add_breakpoint("player_move");
+        game.start();
+        game.player_move(1);
+        game.debugger().resume();
+
+        assert_eq!(game.debugger().back(), "paused in player_move: next_player(..)".to_string());
+        assert_eq!(game.debugger().back(), "deck: +king diamonds\nplayer 1 hand: -king diamonds\npaused in player_move: deck > player:hand".to_string());
+        // the recording moves back, the table doesn't
+        assert_eq!(game.show("player 1"), "king diamonds".to_string());
+        assert_eq!(game.debugger().step(), "deck: -king diamonds\nplayer 1 hand: +king diamonds\npaused in player_move: next_player(..)".to_string());
+    }
+
+    #[test]
+    fn breakpoints_survive_a_restart() {
+        let name = "setup".to_owned();
+        let body = vec!(Statement::FunctionCall(FunctionCall{
+            name: "shuffle".to_string(),
+            arguments: vec!(Expression::Symbol("deck".to_string())),
+            span: SourceSpan::default()
+        }));
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
+        let ast = vec!(Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.add_breakpoint("setup");
+        game.start();
+        game.debugger().resume();
+        game.start();
+
+        assert!(game.debugger().is_paused());
+    }
+
+    #[test]
+    fn trace_mode_logs_executed_statements() {
+        let body = vec!(
+            Statement::CheckStatement(CheckStatement{
+                expression: Expression::Bool(true),
+                span: SourceSpan::default()
+            }),
+            Statement::Transfer(Transfer{
+                from: "deck".to_owned(),
+                to: "player:hand".to_owned(),
+                modifier: None,
+                count: None,
+                span: SourceSpan::default()
+            }),
+            Statement::IfStatement(IfStatement{
+                expression: Expression::Bool(false),
+                body: vec!(),
+                span: SourceSpan::default()
+            })
+        );
+        let name = "player_move".to_owned();
+        let definition = Definition{ arguments: vec!("player".to_string()), name, body, span: SourceSpan::default() };
+        let ast = vec!(Statement::Definition(definition));
+
+        let mut game = Game::new(ast);
+        game.set_tracing(true);
+        game.start();
+        game.player_move(1);
+
+        let expected = vec!(
+            "check(..) -> true".to_string(),
+            "deck > player:hand (1 cards moved)".to_string(),
+            "  deck: -king diamonds".to_string(),
+            "  player 1 hand: +king diamonds".to_string(),
+            "if(..) -> false".to_string()
+        );
+
+        assert_eq!(game.take_trace(), expected);
+        assert!(game.take_trace().is_empty());
+    }
+
+    #[test]
+    fn traces_point_out_unknown_functions() {
+        let mut game = crate::load_game("players 2\ndefine player_move(player){\nnext_playr()\n}").unwrap();
+        game.set_tracing(true);
+        game.start();
+        game.player_move(1);
+
+        assert_eq!(game.take_trace(), vec!("next_playr(..) - unknown function, ignored, did you mean 'next_player'?".to_string()));
+    }
+
+    #[test]
+    fn deal_each_deals_without_a_setup() {
+        let mut game = crate::load_game("players 3\ndeal 7 each").unwrap();
+        game.start();
+
+        let hands: Vec<usize> = game.get_players().iter().map(|p| p.get_hand().len()).collect();
+        assert_eq!(hands, vec!(7, 7, 7));
+        assert_eq!(game.get_deck().len(), 31);
+    }
+
+    #[test]
+    fn transfers_can_draw_from_and_put_under_the_bottom() {
+        let mut game = crate::load_game("stack middle\ndefine setup(){\ndeck bottom > middle\ndeck > bottom middle\ndeck > middle\n}").unwrap();
+        game.start();
+
+        assert_eq!(game.show("middle"), "king diamonds, ace spades, queen diamonds".to_string());
+    }
+
+    #[test]
+    fn deal_each_happens_after_setup() {
+        let mut game = crate::load_game("players 2\nstack middle\ndeal 2 each\ndefine setup(){\ndeck > middle\n}").unwrap();
+        game.start();
+
+        assert_eq!(game.show("middle"), "king diamonds".to_string());
+        assert_eq!(game.show("player 1"), "queen diamonds, ten diamonds".to_string());
+    }
+
+    #[test]
+    fn shuffle_into_empties_one_stack_into_another() {
+        let source = "players 1\nstack discard\ndefine setup(){\ndeck > discard end\ndeck > discard\n}\ndefine player_move(p){\ndiscard > p:hand\nshuffle_into(discard, deck)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        assert_eq!(game.get_deck().len(), 0);
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("discard"), "".to_string());
+        assert_eq!(game.show("player 1"), "ace spades".to_string());
+        assert_eq!(game.get_deck().len(), 51);
+    }
+
+    #[test]
+    fn move_all_matching_sweeps_the_cards_a_definition_keeps() {
+        let source = "players 1\nstack sets\ndefine setup(){\ndeck > players end\n}\ndefine is_ace(card){\nreturn(card:rank is Ace)\n}\ndefine player_move(p){\nmove_all_matching(p:hand, sets, is_ace)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("sets"), "ace diamonds, ace clubs, ace hearts, ace spades".to_string());
+        assert_eq!(game.get_players()[0].get_hand().len(), 48);
+    }
+
+    #[test]
+    fn exchange_trades_cards_between_two_players() {
+        let source = "players 3\ndefine setup(){\ndeck > players\ndeck > players\n}\ndefine player_move(p){\nexchange(p, 3, 1)\nexchange(2, 3)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        assert_eq!(game.show("player 1"), "king diamonds, ten diamonds".to_string());
+        assert_eq!(game.show("player 2"), "queen diamonds, nine diamonds".to_string());
+        assert_eq!(game.show("player 3"), "jack diamonds, eight diamonds".to_string());
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("player 1"), "king diamonds, eight diamonds".to_string());
+        assert_eq!(game.show("player 2"), "jack diamonds, ten diamonds".to_string());
+        assert_eq!(game.show("player 3"), "queen diamonds, nine diamonds".to_string());
+    }
+
+    #[test]
+    fn every_player_passes_the_cards_they_chose_at_once() {
+        let source = "players 3\nstack player:passing\ndefine setup(){\ndeck > players\ndeck > players\npass(1, left)\n}\ndefine is_court(card){\nreturn(card:value between 11 and 13)\n}\ndefine passing:player_move(player){\nmove_all_matching(player:hand, player:passing, is_court)\n}\ndefine player_move(player){\ncheck(player:id is current_player)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(game.show("phase"), "passing".to_string());
+        assert!(game.player_move(3));
+        assert!(game.player_move(1));
+        assert_eq!(game.show("player 2"), "queen diamonds, nine diamonds".to_string());
+        assert_eq!(game.try_move(1), Err(CardlangError::Runtime(RuntimeError::AlreadyMoved(1))));
+
+        assert!(game.player_move(2));
+        assert_eq!(game.show("phase"), "play".to_string());
+        assert_eq!(game.show("player 1"), "ten diamonds, jack diamonds".to_string());
+        assert_eq!(game.show("player 2"), "nine diamonds, king diamonds".to_string());
+        assert_eq!(game.show("player 3"), "eight diamonds, queen diamonds".to_string());
+        assert_eq!(game.show("player 1 passing"), "".to_string());
+    }
+
+    #[test]
+    fn passing_right_goes_back_round_the_table() {
+        let source = "players 3\nstack player:passing\ndefine setup(){\ndeck > players\npass(1, right)\n}\ndefine passing:player_move(player){\nplayer:hand > player:passing\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        for player in 1..=3 {
+            game.player_move(player);
+        }
+
+        assert_eq!(game.show("player 1"), "queen diamonds".to_string());
+        assert_eq!(game.show("player 3"), "king diamonds".to_string());
+    }
+
+    #[test]
+    fn a_pass_takes_exactly_its_count() {
+        let source = "players 2\nstack player:passing\ndefine setup(){\ndeck > players\ndeck > players\ndeck > players\npass(2, left)\n}\ndefine passing:player_move(player){\nplayer:hand > player:passing\nif(player:id is 2){\nplayer:hand > player:passing\n}\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(game.try_move(1), Err(CardlangError::Runtime(RuntimeError::WrongPass(1, 1, 2))));
+        assert_eq!(game.show("player 1"), "king diamonds, jack diamonds, nine diamonds".to_string());
+        assert!(game.player_move(2));
+        assert_eq!(game.show("phase"), "passing".to_string());
+    }
+
+    #[test]
+    fn bets_go_into_the_pot_until_it_is_awarded() {
+        let source = "players 3\ndefine player_move(p){\nbet(1, 5)\nraise(2, 5)\nfold(3)\nif(bets_settled()){\nend()\n}\ncall(1)\nif(bets_settled()){\naward_pot(2)\n}\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("game"), "active".to_string());
+        assert_eq!(game.show("pot"), "0".to_string());
+        let chips: Vec<f64> = game.runtime.players().iter().map(|p| p.chips()).collect();
+        assert_eq!(chips, vec!(-10.0, 10.0, 0.0));
+    }
+
+    #[test]
+    fn betting_builtins_can_be_called_through_std() {
+        let chips = |source: &str| {
+            let mut game = crate::load_game(source).unwrap();
+            game.start();
+            assert!(game.player_move(1));
+            let folded: Vec<bool> = game.runtime.players().iter().map(|p| p.folded()).collect();
+            (game.runtime.players().iter().map(|p| p.chips()).collect::<Vec<f64>>(), game.show("pot"), folded)
+        };
+
+        assert_eq!(
+            chips("players 3\ndefine player_move(p){\nstd:bet(1, 5)\nstd:raise(2, 5)\nstd:fold(3)\nstd:call(1)\n}"),
+            (vec!(-10.0, -10.0, 0.0), "20".to_string(), vec!(false, false, true))
+        );
+        assert_eq!(
+            chips("players 2\ndefine player_move(p){\nstd:bet(1, 4)\nstd:award_pot(2)\n}"),
+            (vec!(-4.0, 4.0), "0".to_string(), vec!(false, false))
+        );
+    }
+
+    #[test]
+    fn folded_players_are_skipped() {
+        let source = "players 3\ndefine player_move(p){\ncheck(p:id is current_player)\nbet(p, 2)\nfold(2)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("current_player"), "3".to_string());
+        assert_eq!(game.show("pot"), "2".to_string());
+    }
+
+    #[test]
+    fn players_start_with_the_declared_chips() {
+        let source = "players 3\nchips 10\ndefine player_move(p){\npay(p:id, 2, 4)\npay(3, p, 1)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("chips"), "player 1: 7\nplayer 2: 14\nplayer 3: 9".to_string());
+    }
+
+    #[test]
+    fn bankrupt_players_are_eliminated() {
+        let source = "players 3\nchips 5\ndefine player_move(p){\ncheck(p:id is current_player)\npay(p:id, 3, 5)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("chips"), "player 1: 0 (eliminated)\nplayer 2: 5\nplayer 3: 10".to_string());
+        assert_eq!(game.try_move(1).unwrap_err().to_string(), "player 1 has been eliminated".to_string());
+
+        assert!(game.player_move(2));
+        assert_eq!(game.show("game"), "game over\nwinners: 3".to_string());
+    }
+
+    #[test]
+    fn scores_are_kept_for_each_player() {
+        let source = "players 2\ndefine player_move(p){\nadd_score(p, 3)\nadd_score(p:id, get_score(p) * 2)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("scores"), "player 1: 9\nplayer 2: 0".to_string());
+        assert_eq!(game.show_json("scores").to_string(), "{\"scores\":[{\"id\":1,\"score\":9},{\"id\":2,\"score\":0}]}".to_string());
+    }
+
+    #[test]
+    fn turns_and_rounds_are_counted() {
+        let source = "players 2\ndefine player_move(p){\nadd_score(p, turn)\nnext_player()\nif(round is 3){\nend()\n}\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert!(game.player_move(2));
+        assert_eq!(game.show("turn"), "turn: 3\nround: 2".to_string());
+        assert!(game.player_move(1));
+        assert!(game.player_move(2));
+        assert_eq!(game.show("scores"), "player 1: 4\nplayer 2: 6".to_string());
+        assert_eq!(game.show_json("turn").to_string(), "{\"turn\":5,\"round\":3}".to_string());
+        assert_eq!(game.show("game"), "game over".to_string());
+    }
+
+    #[test]
+    fn phases_have_their_own_moves() {
+        let source = "players 2\nstack middle\ndefine player_move(p){\nadd_score(p, 1)\nif(turn is 2){\nphase(scoring)\n}\n}\ndefine scoring:setup(){\ndeck > middle\n}\ndefine scoring:player_move(p){\ncheck(phase() is scoring)\nadd_score(p, 10)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("phase"), "play".to_string());
+        assert!(game.player_move(2));
+        assert_eq!(game.show("phase"), "scoring".to_string());
+        assert_ne!(game.show("middle"), "".to_string());
+        assert!(game.player_move(1));
+        assert_eq!(game.show("scores"), "player 1: 11\nplayer 2: 1".to_string());
+        assert_eq!(game.show_json("phase").to_string(), "{\"phase\":\"scoring\"}".to_string());
+    }
+
+    #[test]
+    fn chips_go_negative_without_a_bank() {
+        let source = "players 2\ndefine player_move(p){\npay(1, 2, 5)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("chips"), "player 1: -5\nplayer 2: 5".to_string());
+    }
+
+    #[test]
+    fn simultaneous_rounds_resolve_once_everyone_has_moved() {
+        let source = "players 2\nturns simultaneous\nstack middle\nstack player:played\ndefine setup(){\ndeck > players\n}\ndefine player_move(p){\np:hand > p:played\n}\ndefine resolve(){\ndeck > middle\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(2));
+        assert_eq!(game.try_move(2).unwrap_err().to_string(), "player 2 has already moved this round".to_string());
+        assert_eq!(game.show("middle"), "".to_string());
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("middle"), "jack diamonds".to_string());
+        assert_eq!(game.show("player 1 played"), "king diamonds".to_string());
+        assert!(game.player_move(2));
+    }
+
+    #[test]
+    fn players_out_of_time_have_a_move_made_for_them() {
+        let source = "players 2\nturn_time 0\ndefine player_move(p){\ncheck(p:id is current_player)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        assert_eq!(game.check_turn_timer(), None);
+        game.start();
+
+        assert_eq!(game.check_turn_timer(), Some("player 1 ran out of time, so a move was made for them".to_string()));
+        assert_eq!(game.show("current_player"), "2".to_string());
+    }
+
+    #[test]
+    fn players_out_of_time_without_a_legal_move_pass() {
+        let source = "players 2\ndefine player_move(p){\ncheck(p:id is 2)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        assert_eq!(game.check_turn_timer(), None);
+
+        game.set_turn_limit(Some(Duration::from_secs(0)));
+        assert_eq!(game.check_turn_timer(), Some("player 1 ran out of time and passes".to_string()));
+        assert_eq!(game.show("current_player"), "2".to_string());
+    }
+
+    #[test]
+    fn auto_moves_as_bot_move_prefers() {
+        let source = "players 3\ndefine setup(){\ndeck > players end\n}\ndefine player_move(p){\np:hand > deck end\nend()\n}\ndefine bot_move(p){\nreturn(p:id is 2)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        game.auto(None);
+
+        assert_eq!(game.show_history(), "1: move 2".to_string());
+        assert_eq!(game.get_deck().len(), 17);
+    }
+
+    #[test]
+    fn metrics_count_the_work_each_move_does() {
+        let mut game = crate::load_game("players 2\ndefine player_move(p){\ncheck(p:id is current_player)\nnext_player()\n}").unwrap();
+        game.start();
+        assert!(game.player_move(1));
+        assert!(!game.player_move(1));
+
+        let metrics = game.metrics();
+        assert_eq!(metrics.moves, 2);
+        assert_eq!(metrics.statements, 3);
+        assert_eq!(metrics.clones, 2);
+        assert!(metrics.expressions >= 4);
+        assert!(game.show("perf").starts_with("statements executed: 3\nexpressions resolved: "));
+    }
+
+    #[test]
+    fn formatters_change_how_cards_are_shown() {
+        let mut game = crate::load_game("players 2\ndefine setup(){\ndeck > players\n}").unwrap();
+        game.start();
+        game.set_formatter(Arc::new(crate::format::Compact));
+
+        assert_eq!(game.show("player 1"), "KD".to_string());
+        assert_eq!(game.show_for_seat("player 2", Some(1)), "[1]".to_string());
+
+        game.set_formatter(Arc::new(crate::format::JsonFormatter));
+        assert_eq!(game.show_for_seat("all", Some(1)), r#"{"deck":{"hidden":50},"player 1":["king diamonds"],"player 2":{"hidden":1}}"#.to_string());
+    }
+
+    #[test]
+    fn snapshots_show_what_a_move_changed() {
+        let mut game = crate::load_game("players 2\nstack middle\ndefine setup(){\ndeck > players\n}\ndefine player_move(p){\np:hand > middle\nnext_player()\n}").unwrap();
+        game.start();
+        let before = game.snapshot();
+        assert!(game.player_move(1));
+
+        let changes: Vec<String> = crate::diff(&before, &game.snapshot()).iter().map(|c| c.to_string()).collect();
+        assert_eq!(changes, vec!(
+            "current_player: 1 -> 2".to_string(),
+            "middle: +king diamonds".to_string(),
+            "player 1 hand: -king diamonds".to_string()
+        ));
+    }
+
+    #[test]
+    fn replays_play_the_game_again_exactly() {
+        let source = "players 2\ndefine setup(){\nshuffle(deck)\ndeck > players\n}\ndefine player_move(p){\ncheck(p:id is current_player)\np:hand > deck\nshuffle(deck)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        game.auto(Some(4));
+        let replay = game.export_replay().unwrap();
+
+        let mut replayed = crate::load_game(source).unwrap();
+        replayed.import_replay(&replay).unwrap();
+
+        assert!(replay.starts_with("cardlang/1 game="));
+        assert!(replay.ends_with(" moves=1,2,1,2"));
+        assert_eq!(replayed.show("all"), game.show("all"));
+        assert_eq!(replayed.export_replay(), Some(replay));
+    }
+
+    #[test]
+    fn replays_survive_the_rules_being_reformatted() {
+        let source = "players 2\ndefine player_move(p){\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        assert!(game.player_move(1));
+        let replay = game.export_replay().unwrap();
+
+        let reformatted = ".( the same game )\n\nplayers 2\n\ndefine player_move(p){\n    next_player()\n}\n";
+        let mut other = crate::load_game(reformatted).unwrap();
+
+        assert_eq!(other.import_replay(&replay), Ok(()));
+    }
+
+    #[test]
+    fn replays_must_be_for_the_same_rules() {
+        let mut game = crate::load_game("players 2\ndefine player_move(p){\ncheck(p:id is current_player)\nnext_player()\n}").unwrap();
+        game.start();
+        assert!(game.player_move(1));
+        let replay = game.export_replay().unwrap();
+
+        let mut other = crate::load_game("players 3\ndefine player_move(p){\nnext_player()\n}").unwrap();
+        assert_eq!(
+            other.import_replay(&replay),
+            Err(CardlangError::InvalidReplay("it was recorded against different rules".to_string()))
+        );
+        assert_eq!(
+            game.import_replay(&replay.replace("moves=1", "moves=2")).unwrap_err().to_string(),
+            "unable to replay the game: move 1 (player 2) was rejected, a check failed in player_move at line 3, column 1".to_string()
+        );
+    }
+
+    #[test]
+    fn saved_games_carry_on_where_they_left_off() {
+        let path = std::env::temp_dir().join("cardlang_saved_game.json");
+        let path = path.to_str().unwrap();
+        let mut game = crate::load_game("players 2\ndefine setup(){\ndeck > players\n}\ndefine player_move(p){\np:hand > deck\nnext_player()\n}").unwrap();
+        game.start();
+        assert!(game.player_move(1));
+        game.save(path).unwrap();
+
+        let mut loaded = crate::load_game("players 2\ndefine setup(){\ndeck > players\n}\ndefine player_move(p){\np:hand > deck\nnext_player()\n}").unwrap();
+        loaded.load_save(path).unwrap();
+
+        assert_eq!(loaded.show("all"), game.show("all"));
+        assert_eq!(loaded.get_current_player(), 2);
+        assert!(loaded.player_move(2));
+        assert_eq!(crate::load_game("players 3").unwrap().load_save(path), Err(CardlangError::InvalidFile(path.to_string())));
+    }
+
+    #[test]
+    fn sessions_keep_totals_across_starts() {
+        let source = "players 2\ndefine player_move(p){\nadd_score(p:id, 5)\nwinner(p:id)\nend()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        assert_eq!(game.show("session"), "games: 0".to_string());
+
+        assert!(game.player_move(2));
+        assert_eq!(game.show("session"), "games: 1\nplayer 1: 0 wins, 0 points\nplayer 2: 1 wins, 5 points".to_string());
+
+        game.start();
+        assert!(game.player_move(1));
+        game.start();
+        assert!(game.player_move(2));
+        assert_eq!(game.show("session"), "games: 3\nplayer 1: 1 wins, 5 points\nplayer 2: 2 wins, 10 points".to_string());
+    }
+
+    #[test]
+    fn matches_replay_hands_until_a_player_reaches_the_target() {
+        let source = "players 2\nmatch to 3\ndefine setup(){\ndeck > players\n}\ndefine player_move(p){\nadd_score(p:id, 2)\nend()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(2));
+        assert_eq!(game.show("game"), "active\nhand: 2".to_string());
+        assert_eq!(game.get_players()[0].get_hand().len(), 1);
+        assert_eq!(game.get_deck().len(), 50);
+
+        assert!(game.player_move(1));
+        assert!(game.player_move(2));
+        assert_eq!(game.show("game"), "game over\nhand: 3\nwinners: 2".to_string());
+    }
+
+    #[test]
+    fn burnt_cards_are_hidden_from_the_players() {
+        let source = "players 2\ndefine setup(){\nburn(3)\ndeck > players\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(game.show("burn"), "?? (3 cards)".to_string());
+        assert_eq!(game.show_for_seat("burn", Some(1)), "hidden (3 cards)".to_string());
+        assert_eq!(game.show("player 1"), "ten diamonds".to_string());
+        assert_eq!(game.get_stacks().len(), 1);
+    }
+
+    #[test]
+    fn burning_anywhere_creates_the_burn_pile() {
+        let game = crate::load_game("define setup(){\nif(true){\nburn(1)\n}\n}").unwrap();
+
+        assert_eq!(game.get_stacks().len(), 1);
+
+        let looped = crate::load_game("define setup(){\nforeach card in deck {\nwhile(false){\nstd:burn(1)\n}\n}\n}").unwrap();
+        assert_eq!(looped.get_stacks().len(), 1);
+    }
+
+    #[test]
+    fn every_stack_in_a_declaration_is_created() {
+        let game = crate::load_game("stack middle, discard, crib").unwrap();
+
+        let stacks: Vec<String> = game.get_stacks().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(stacks, vec!("middle", "discard", "crib"));
+    }
+
+    #[test]
+    fn hands_are_dealt_up_to_the_hand_size() {
+        let mut game = crate::load_game("players 3\nhand_size 5\ndeal 2 each").unwrap();
+        game.start();
+
+        let hands: Vec<usize> = game.get_players().iter().map(|p| p.get_hand().len()).collect();
+        assert_eq!(hands, vec!(5, 5, 5));
+        assert_eq!(game.get_deck().len(), 37);
+    }
+
+    #[test]
+    fn hands_are_refilled_after_a_move() {
+        let source = "players 2\nhand_size 3\nstack middle\ndefine player_move(p){\np:hand > middle\np:hand > middle\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.get_players()[0].get_hand().len(), 3);
+        assert_eq!(game.get_deck().len(), 44);
+    }
+
+    #[test]
+    fn moves_cannot_leave_a_hand_over_the_hand_size() {
+        let source = "players 2\nhand_size 3\ndefine player_move(p){\ndeck > p:hand\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(
+            game.try_move(1).unwrap_err().to_string(),
+            "player 1 would end the turn with 4 cards, more than the hand size of 3".to_string()
+        );
+        assert_eq!(game.get_players()[0].get_hand().len(), 3);
+        assert_eq!(game.get_deck().len(), 46);
+    }
+
+    #[test]
+    fn players_have_their_own_declared_stacks() {
+        let source = "players 2\nstack player:tricks\ndefine player_move(p){\ndeck > p:tricks\nif(count(p:tricks) is 0){\nnext_player()\n}\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        game.player_move(1);
+
+        assert_eq!(game.show("player 1 tricks"), "king diamonds".to_string());
+        assert_eq!(game.show("player 2 tricks"), "".to_string());
+        assert_eq!(game.show_for_seat("player 1 tricks", Some(2)), "hidden (1 cards)".to_string());
+        assert_eq!(game.show("player 1 discard"), "player 1 discard not found".to_string());
+        assert_eq!(game.show("player 1 hand"), "".to_string());
+        assert_eq!(game.get_current_player(), 2);
+    }
+
+    #[test]
+    fn face_down_stacks_only_show_how_many_cards_they_hold() {
+        let source = "cardlang 2\nplayers 2\nstack middle facedown\nstack table\nstack player:tricks facedown\ndefine setup(){\ndeck > middle\ndeck > middle\ndeck > players\n}\ndefine player_move(p){\np > p:tricks\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        game.player_move(1);
+
+        assert_eq!(game.show("middle"), "?? (2 cards)".to_string());
+        assert_eq!(game.show("player 1 tricks"), "?? (1 cards)".to_string());
+        assert_eq!(game.show_for_seat("player 1 tricks", Some(1)), "?? (1 cards)".to_string());
+        assert_eq!(game.show("middle summary"), "?? (2 cards)".to_string());
+        assert!(game.show("all").contains("middle: ?? (2 cards)"));
+        assert_eq!(game.show_json("middle").to_string(), r#"{"middle":{"facedown":2}}"#.to_string());
+
+        game.set_formatter(Arc::new(crate::format::Compact));
+        assert_eq!(game.show("middle"), "?? ??".to_string());
+    }
+
+    #[test]
+    fn cards_know_which_way_up_they_are() {
+        let source = "cardlang 2\nplayers 2\nstack middle facedown\nstack table\nstack player:tricks facedown\ndefine setup(){\ndeck > middle 2\ndeck > players 2\n}\n\
+            define player_move(p){\np > p:tricks\nforeach card in p:tricks {\nif(card:face is \"down\"){\nadd_score(p:id, 1)\n}\n}\n\
+            foreach card in p:hand {\nif(card:face is \"up\"){\nadd_score(p:id, 10)\n}\n}\nmove_all_matching(middle, table, is_down)\nadd_score(p:id, count(table))\n}\n\
+            define is_down(card){\nreturn(card:face is \"down\")\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        game.player_move(1);
+
+        assert_eq!(game.show("scores"), "player 1: 13\nplayer 2: 0".to_string());
+    }
+
+    #[test]
+    fn transfers_move_as_many_cards_as_the_language_version_allows() {
+        let source = "players 2\ndefine setup(){\ndeck > players 3\n}";
+        let hand = |source: &str| {
+            let mut game = crate::load_game(source).unwrap();
+            game.start();
+            game.get_players()[0].get_hand().len()
+        };
+
+        assert_eq!(hand(source), 1);
+        assert_eq!(hand(&format!("cardlang 2\n{}", source)), 3);
+        assert_eq!(
+            crate::load_game(&format!("cardlang 3\n{}", source)).unwrap_err(),
+            CardlangError::UnsupportedVersion(3)
+        );
+    }
+
+    #[test]
+    fn games_play_with_the_deck_they_declare() {
+        let deck_size = |source: &str| crate::load_game(source).map(|game| game.runtime.get_deck().len());
+
+        assert_eq!(deck_size("deck StandardDeck\nplayers 2"), Ok(52));
+        assert_eq!(deck_size("deck PiquetDeck\nplayers 2"), Ok(32));
+        assert_eq!(deck_size("deck euchre {\nranks nine, ten, jack, queen, king, ace\n}\ndecks 2"), Ok(48));
+        assert_eq!(deck_size("deck reds {\nsuits hearts, diamonds\n}"), Ok(26));
+        assert_eq!(deck_size("players 2\ndeck StandardDek"), Err(CardlangError::UnknownDeck("StandardDek".to_string(), Span{ line: 2, column: 1 })));
+        assert_eq!(
+            crate::load_game("players 2\ndeck StandardDek").unwrap_err().to_string(),
+            "there's no deck called 'StandardDek' at line 2, column 1".to_string()
+        );
+        assert_eq!(
+            deck_size("deck euchre {\nranks nine, tne\n}"),
+            Err(CardlangError::InvalidDeck("euchre".to_string(), "tne".to_string(), Span{ line: 1, column: 1 }))
+        );
+    }
+
+    #[test]
+    fn cards_are_worth_what_the_game_values_them_at() {
+        let source = "players 2\nstack middle\nvalues { ace 11, face 10, 2 0 }\ndefine setup(){\ndeck > middle\ndeck > middle\ndeck bottom > middle\n}\ndefine player_move(p){\nforeach card in middle {\ncheck(card:value is value(card))\nadd_score(p, value(card))\n}\nadd_score(p, value(middle) * 100)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.show("scores"), "player 1: 3131\nplayer 2: 0".to_string());
+        assert_eq!(
+            crate::load_game("values { knave 10 }").unwrap_err(),
+            CardlangError::UnknownRank("knave".to_string(), Span{ line: 1, column: 1 })
+        );
+    }
+
+    #[test]
+    fn transfer_counts_are_worked_out_when_they_run() {
+        let source = "cardlang 2\nplayers 2\nstack middle\ndefine setup(){\ndeck > middle 3\ndeck > players count(middle) - 1\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        assert_eq!(game.get_players()[0].get_hand().len(), 2);
+
+        let mut game = crate::load_game("cardlang 2\nplayers 2\nhand_size 4\ndefine setup(){\ndeck > players hand_size\n}").unwrap();
+        game.start();
+        assert_eq!(game.get_players()[1].get_hand().len(), 4);
+    }
+
+    #[test]
+    fn defines_override_builtins_but_not_std() {
+        let source = "players 2\ndefine count(cards){\nreturn(99)\n}\ndefine is_first(n){\nreturn(n is 1)\n}\ndefine player_move(p){\ncheck(count(p:hand) is 99)\ncheck(std:count(p:hand) is 0)\ncheck(is_first(p:id))\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert!(!game.player_move(2));
+    }
+
+    #[test]
+    fn helpers_run_in_their_own_frame() {
+        let source = "players 2\nstack middle\ndefine deal_to(p, n){\nif(n is 2){\ndeck > p:hand\n}\ndeck > middle\n}\ndefine player_move(player){\ndeal_to(player, 1 + 1)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(game.player_move(1));
+        assert_eq!(game.stack("middle").map(|s| s.len()), Some(1));
+        assert_eq!(game.get_players()[0].get_hand().len(), 1);
+    }
+
+    #[test]
+    fn while_loops_run_until_their_condition_fails() {
+        let mut game = crate::load_game("stack middle\ndefine setup(){\nwhile(count(middle) between 0 and 6){\ndeck > middle\n}\n}").unwrap();
+        game.start();
+
+        assert_eq!(game.stack("middle").map(|s| s.len()), Some(7));
+    }
+
+    #[test]
+    fn foreach_runs_once_for_each_card() {
+        let source = "stack middle\ndefine setup(){\nforeach card in deck {\nif(card:suit is Hearts){\ndeck > middle\n}\n}\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(game.stack("middle").map(|s| s.len()), Some(13));
+        assert_eq!(game.get_deck().len(), 39);
+    }
 
-        ast.push(statement);
+    #[test]
+    fn endless_while_loops_are_stopped() {
+        let mut game = crate::load_game("define setup(){\nwhile(true){\nshuffle(deck)\n}\nend()\n}").unwrap();
+        game.start();
 
-        let mut game = Game::new(ast);
+        assert_eq!(game.show("game"), "active");
+        assert_eq!(game.violations(), vec!(Violation::Rejected(RuntimeError::OutOfSteps("setup".to_string(), 100_000))));
+    }
+
+    #[test]
+    fn nested_loops_share_one_budget() {
+        let source = "players 2\ndefine player_move(p){\nwhile(true){\nwhile(true){\nwhile(true){\n}\n}\n}\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
         game.start();
+
+        assert_eq!(game.try_move(1), Err(CardlangError::Runtime(RuntimeError::OutOfSteps("player_move".to_string(), 100_000))));
+        assert_eq!(game.get_current_player(), 1);
+    }
+
+    #[test]
+    fn defines_calling_themselves_give_up() {
+        let source = "players 2\ndefine forever(){\nreturn(forever())\n}\ndefine player_move(p){\nforever()\nstd:next_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
         game.start();
 
-        let deck = game.show("deck");
-        let split_deck: Vec<&str> = deck.split(",").collect();
+        assert_eq!(game.try_move(1), Err(CardlangError::Runtime(RuntimeError::TooDeep("forever".to_string(), 64))));
+        assert_eq!(game.get_current_player(), 1);
+        assert_eq!(game.show("game"), "active");
+    }
 
-        assert_eq!(split_deck.len(), 49);
+    #[test]
+    fn defines_calling_themselves_twice_over_run_out_of_steps() {
+        let source = "players 2\ndefine twice(n){\nif(n between 1 and 40){\ntwice(n + 1)\ntwice(n + 1)\n}\n}\ndefine player_move(p){\ntwice(1)\nstd:next_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(game.try_move(1), Err(CardlangError::Runtime(RuntimeError::OutOfSteps("twice".to_string(), 100_000))));
+        assert_eq!(game.get_current_player(), 1);
     }
 
     #[test]
-    fn it_deals_to_the_end_with_the_count_modifier() {
-        let mut ast = vec!(
-            Statement::Declaration(
-                Declaration {
-                    key: GlobalKey::Players,
-                    value: Expression::Number(3.0)
-                }
-            )
-        );
-        let from = "deck".to_owned();
-        let to = "players".to_owned();
-        let modifier = None; //Some(TransferModifier::Alternate);
-        let count = Some(TransferCount::End);
-        let transfer = Transfer{ from, to, modifier, count };
-        let transfer_statement = Statement::Transfer(transfer);
+    fn shuffles_share_the_budget() {
+        let source = "players 2\nstack middle\ndefine player_move(p){\nshuffle(middle, riffle 1000000000)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
 
-        let name = "setup".to_owned();
-        let body = vec!(transfer_statement);
-        let definition = Definition{ arguments: vec!(), name, body };
-        let statement = Statement::Definition(definition);
+        assert_eq!(game.try_move(1), Err(CardlangError::Runtime(RuntimeError::OutOfSteps("player_move".to_string(), 100_000))));
+        assert_eq!(game.get_current_player(), 1);
+    }
 
-        ast.push(statement);
+    #[test]
+    fn shuffles_can_name_a_stack_and_a_method() {
+        let source = "players 1\nstack middle\ndefine setup(){\ndeck > middle end\nshuffle(middle, perfect 8)\nshuffle(deck)\n}";
+        let middle = |source: &str| {
+            let mut game = crate::load_game(source).unwrap();
+            game.start();
+            game.show("middle")
+        };
+        let unshuffled = middle(&source.replace("perfect 8", "sideways"));
 
-        let mut game = Game::new(ast);
+        assert_eq!(middle(source), unshuffled);
+        assert_ne!(middle(&source.replace("perfect 8", "perfect 1")), unshuffled);
+        assert!(unshuffled.starts_with("king diamonds, queen diamonds"));
+    }
+
+    #[test]
+    fn the_table_only_shows_what_spectators_could_see() {
+        let source = "players 2\nstack middle\nstack player:tricks\ndefine setup(){\ndeck > players\ndeck > middle\n}\ndefine player_move(p){\np > p:tricks\nadd_score(p:id, 2)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
         game.start();
+        game.player_move(1);
 
-        let deck = game.show("deck");
-        assert_eq!(&deck, "");
+        assert_eq!(
+            game.show("table"),
+            "deck: hidden (49 cards)\nmiddle: jack diamonds\ncurrent player: 2\nplayer 1: 0 cards, score 2\nplayer 1 tricks: hidden (1 cards)\nplayer 2: 1 cards, score 0\nplayer 2 tricks: hidden (0 cards)".to_string()
+        );
+        assert_eq!(game.show_for_seat("table", Some(1)), game.show("table"));
+        assert_eq!(
+            game.show_json("table").to_string(),
+            r#"{"deck":49,"stacks":{"middle":[{"rank":"jack","suit":"diamonds"}]},"current_player":2,"players":[{"id":1,"cards":0,"stacks":{"tricks":1},"score":2},{"id":2,"cards":1,"stacks":{"tricks":0},"score":0}]}"#.to_string()
+        );
     }
 
     #[test]
-    fn it_can_show_player_hand(){
-        let mut ast = vec!(
-            Statement::Declaration(
-                Declaration {
-                    key: GlobalKey::Players,
-                    value: Expression::Number(1.0)
-                }
-            )
-        );
-        let from = "deck".to_owned();
-        let to = "players".to_owned();
-        let modifier = None; //Some(TransferModifier::Alternate);
-        let count = None;
-        let transfer = Transfer{ from, to, modifier, count };
-        let transfer_statement = Statement::Transfer(transfer);
+    fn teams_are_dealt_round_the_table() {
+        let source = "players 4\nteams 2\ndefine player_move(player){\ncheck(player:team is 1)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        let accepted: Vec<usize> = (1..=4).filter(|&n| game.player_move(n)).collect();
 
-        let name = "setup".to_owned();
-        let body = vec!(transfer_statement);
-        let definition = Definition{ arguments: vec!(), name, body };
-        let statement = Statement::Definition(definition);
+        assert_eq!(accepted, vec!(1, 3));
+    }
 
-        ast.push(statement);
+    #[test]
+    fn players_know_whether_it_is_their_turn() {
+        let source = "players 2\ndefine player_move(player){\ncheck(player:is_current is true)\ncheck(player:score is 0)\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
 
-        let mut game = Game::new(ast);
+        assert!(!game.player_move(2));
+        assert!(game.player_move(1));
+        assert!(game.player_move(2));
+    }
+
+    #[test]
+    fn count_where_counts_the_cards_a_definition_keeps() {
+        let source = "players 1\ndefine setup(){\ndeck > players end\n}\ndefine is_heart(card){\nreturn(card:suit is Hearts)\n}\ndefine player_move(player){\ncheck(count_where(player:hand, is_heart) is 13)\nend()\n}";
+        let mut game = crate::load_game(source).unwrap();
         game.start();
 
-        let hand = game.show("player 1 hand");
-        assert_eq!(&hand, "king diamonds");
+        assert!(game.player_move(1));
+        assert_eq!(game.get_status(), "game over".to_string());
     }
 
     #[test]
-    fn it_can_show_multiple_player_hand(){
-        let mut ast = vec!(
-            Statement::Declaration(
-                Declaration {
-                    key: GlobalKey::Players,
-                    value: Expression::Number(2.0)
-                }
-            )
-        );
-        let from = "deck".to_owned();
-        let to = "players".to_owned();
-        let modifier = None; //Some(TransferModifier::Alternate);
-        let count = None;
-        let transfer = Transfer{ from, to, modifier, count };
-        let transfer_statement = Statement::Transfer(transfer);
+    fn count_where_is_zero_for_unknown_definitions() {
+        let source = "players 1\ndefine setup(){\ndeck > players end\n}\ndefine player_move(player){\ncheck(count_where(player:hand, is_heart) is 0)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
 
-        let name = "setup".to_owned();
-        let body = vec!(transfer_statement);
-        let definition = Definition{ arguments: vec!(), name, body };
-        let statement = Statement::Definition(definition);
+        assert!(game.player_move(1));
+    }
 
-        ast.push(statement);
+    #[test]
+    fn chance_is_the_fraction_of_cards_a_definition_keeps() {
+        let source = "players 1\nstack middle\ndefine is_red(card){\nreturn(card:color is Red)\n}\ndefine player_move(player){\ncheck(chance(deck, is_red) is 0.5)\ncheck(chance(middle, is_red) is 0)\ncheck(chance(player:hand, is_red) is 0)\nend()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
 
-        let mut game = Game::new(ast);
+        assert!(game.player_move(1));
+        assert_eq!(game.get_status(), "game over".to_string());
+    }
+
+    #[test]
+    fn between_includes_both_ends() {
+        let source = "players 2\ndefine setup(){\ndeck > players end\n}\ndefine player_move(player){\ncheck(count(player:hand) between 20 and 26)\nif(count(player:hand) between 27 and 30){\nend()\n}\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
         game.start();
 
-        let show_players = game.show("players");
-        assert_eq!(&show_players, "player 1 (cards: 1), player 2 (cards: 1)");
+        assert!(game.player_move(1));
+        assert_eq!(game.get_status(), "active".to_string());
+    }
 
-        let hand = game.show("player 2 hand");
-        assert_eq!(&hand, "queen diamonds");
+    #[test]
+    fn between_is_false_for_values_that_are_not_numbers() {
+        let source = "players 2\ndefine player_move(player){\ncheck(player:hand between 0 and 52)\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert!(!game.player_move(1));
     }
 
     #[test]
-    fn it_can_access_built_in_functions() {
-        let body = vec!(
-            Statement::FunctionCall(
-                FunctionCall{
-                    name: "shuffle".to_string(),
-                    arguments: vec!(Expression::Symbol("deck".to_string()))
-                }
-            )
-        );
+    fn strings_can_hold_spaces_and_punctuation() {
+        let source = "name \"Crazy Eights!\"\nplayers 1\ndefine setup(){\ndeck > players end\n}\ndefine is_ace(card){\nreturn(card:rank is \"Ace\")\n}\ndefine player_move(player){\ncheck(count_where(player:hand, is_ace) is 4)\nend()\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
 
-        let name = "setup".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
-        let statement = Statement::Definition(definition);
-        let ast = vec!(statement);
+        assert_eq!(game.get_name(), Some("Crazy Eights!".to_string()));
+        assert!(game.player_move(1));
+    }
 
-        let mut game = Game::new(ast);
+    #[test]
+    fn strings_fill_in_what_is_between_their_braces() {
+        let source = "players 2\ndefine setup(){\ndeck > players\n}\ndefine player_move(player){\nphase(\"player {player:id} on turn {turn + 1}, {count(player:hand)} in hand {not here\")\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
         game.start();
+        game.player_move(1);
 
-        let usual_order = Game::display_list(&standard_deck());
-        let deck = game.show("deck");
+        assert_eq!(game.show("phase"), "player 1 on turn 2, 1 in hand {not here".to_string());
+    }
 
-        assert_ne!(deck, usual_order);
+    #[test]
+    fn strings_keep_braces_that_hold_no_expression() {
+        let source = "players 1\ndefine setup(){\nphase(\"{} {nowhere:at all} \\\"quoted\\\"\\nnext\")\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+
+        assert_eq!(game.show("phase"), "{} {nowhere:at all} \"quoted\"\nnext".to_string());
     }
 
     #[test]
-    fn it_can_make_a_move() {
-        let body = vec!(
-            Statement::FunctionCall(
-                FunctionCall{
-                    name: "shuffle".to_string(),
-                    arguments: vec!(Expression::Symbol("deck".to_string()))
-                }
-            )
-        );
+    fn match_runs_the_first_arm_that_fits() {
+        let source = "players 2\nstack middle\ndefine setup(){\ndeck > players\n}\ndefine player_move(player){\nmatch count(player:hand) {\n0 {\nend()\n}\n1 {\nplayer:hand > middle\n}\nother {\nnext_player()\n}\n}\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        game.set_tracing(true);
 
-        let name = "player_move".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
-        let statement = Statement::Definition(definition);
+        game.player_move(1);
+        assert_eq!(game.take_trace(), vec!(
+            "match(..) -> 1".to_string(),
+            "player:hand > middle (1 cards moved)".to_string(),
+            "  middle: +king diamonds".to_string(),
+            "  player 1 hand: -king diamonds".to_string()
+        ));
 
-        let ast = vec!(statement);
+        game.player_move(1);
+        assert_eq!(game.get_status(), "game over".to_string());
+    }
 
-        let mut game = Game::new(ast);
+    #[test]
+    fn match_falls_back_to_the_other_arm() {
+        let source = "players 2\ndefine setup(){\ndeck > players end\n}\ndefine player_move(player){\nmatch count(player:hand) {\n0 {\nend()\n}\nother {\nnext_player()\n}\n}\n}";
+        let mut game = crate::load_game(source).unwrap();
         game.start();
         game.player_move(1);
 
-        let usual_order = Game::display_list(&standard_deck());
-        let deck = game.show("deck");
-
-        assert_ne!(deck, usual_order);
+        assert_eq!(game.get_current_player(), 2);
+        assert_eq!(game.get_status(), "active".to_string());
     }
 
     #[test]
@@ -455,7 +3009,8 @@ mod test{
         let players = Statement::Declaration(
             Declaration {
                 key: GlobalKey::Players,
-                value: Expression::Number(3.0)
+                value: Expression::Number(3.0),
+                span: SourceSpan::default()
             }
         );
 
@@ -465,13 +3020,14 @@ mod test{
                     from: "deck".to_string(),
                     to: "player:hand".to_string(),
                     modifier: None,
-                    count: None
+                    count: None,
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "player_move".to_owned();
-        let definition = Definition{ arguments: vec!("player".to_string()), name, body };
+        let definition = Definition{ arguments: vec!("player".to_string()), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
 
         let ast = vec!(
@@ -493,7 +3049,8 @@ mod test{
         let players = Statement::Declaration(
             Declaration {
                 key: GlobalKey::Players,
-                value: Expression::Number(3.0)
+                value: Expression::Number(3.0),
+                span: SourceSpan::default()
             }
         );
 
@@ -503,13 +3060,14 @@ mod test{
                     from: "deck".to_string(),
                     to: "player:hand".to_string(),
                     modifier: None,
-                    count: None
+                    count: None,
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "player_move".to_owned();
-        let definition = Definition{ arguments: vec!("player".to_string()), name, body };
+        let definition = Definition{ arguments: vec!("player".to_string()), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
 
         let ast = vec!(
@@ -534,13 +3092,15 @@ mod test{
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::Players,
-                    value: Expression::Number(1.0)
+                    value: Expression::Number(1.0),
+                    span: SourceSpan::default()
                 }
             ),
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::Stack,
-                    value: Expression::Symbol("middle".to_string())
+                    value: Expression::Symbol("middle".to_string()),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -548,12 +3108,12 @@ mod test{
         let to = "middle".to_owned();
         let modifier = None;
         let count = None;
-        let transfer = Transfer{ from, to, modifier, count };
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
         let transfer_statement = Statement::Transfer(transfer);
 
         let name = "setup".to_owned();
         let body = vec!(transfer_statement);
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
 
         ast.push(statement);
@@ -566,13 +3126,170 @@ mod test{
         assert_eq!(&middle, "king diamonds");
     }
 
+    #[test]
+    fn it_can_show_all_stacks_with_counts() {
+        let ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Stack,
+                    value: Expression::Symbol("middle".to_string()),
+                    span: SourceSpan::default()
+                }
+            ),
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Stack,
+                    value: Expression::Symbol("discard".to_string()),
+                    span: SourceSpan::default()
+                }
+            ),
+            Statement::Definition(Definition{
+                name: "setup".to_owned(),
+                arguments: vec!(),
+                body: vec!(Statement::Transfer(Transfer{
+                    from: "deck".to_owned(),
+                    to: "middle".to_owned(),
+                    modifier: None,
+                    count: None,
+                    span: SourceSpan::default()
+                })),
+                span: SourceSpan::default()
+            })
+        );
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        assert_eq!(game.show("stacks"), "middle (cards: 1), discard (cards: 0)".to_string());
+    }
+
+    #[test]
+    fn it_can_show_the_whole_table() {
+        let ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
+                }
+            ),
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Stack,
+                    value: Expression::Symbol("middle".to_string()),
+                    span: SourceSpan::default()
+                }
+            ),
+            Statement::Definition(Definition{
+                name: "setup".to_owned(),
+                arguments: vec!(),
+                body: vec!(Statement::Transfer(Transfer{
+                    from: "deck".to_owned(),
+                    to: "players".to_owned(),
+                    modifier: None,
+                    count: None,
+                    span: SourceSpan::default()
+                })),
+                span: SourceSpan::default()
+            })
+        );
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        let display = game.show("all");
+        let lines: Vec<&str> = display.split("\n").collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("deck: ace spades"));
+        assert_eq!(lines[1], "middle: ");
+        assert_eq!(lines[2], "player 1: king diamonds");
+        assert_eq!(lines[3], "player 2: queen diamonds");
+    }
+
+    #[test]
+    fn it_can_show_keys_as_json() {
+        let ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Name,
+                    value: Expression::Symbol("turns".to_string()),
+                    span: SourceSpan::default()
+                }
+            ),
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
+                }
+            )
+        );
+
+        let game = Game::new(ast);
+
+        assert_eq!(game.show_json("name").to_string(), r#"{"name":"turns"}"#.to_string());
+        assert_eq!(
+            game.show_json("players").to_string(),
+            r#"{"players":[{"id":1,"cards":0},{"id":2,"cards":0}]}"#.to_string()
+        );
+        assert_eq!(game.show_json("game").to_string(), r#"{"status":"pending","winners":[]}"#.to_string());
+        assert_eq!(game.show_json("player 2 hand").to_string(), r#"{"player":2,"hand":[]}"#.to_string());
+        assert_eq!(game.show_json("middle").to_string(), r#"{"error":"middle not found"}"#.to_string());
+    }
+
+    #[test]
+    fn it_can_show_cards_as_json() {
+        let game = Game::new(vec!());
+        let deck = game.show_json("deck").to_string();
+
+        assert!(deck.starts_with(r#"{"deck":[{"rank":"ace","suit":"spades"},"#));
+    }
+
+    #[test]
+    fn a_seated_view_hides_other_players_cards() {
+        let ast = vec!(
+            Statement::Declaration(
+                Declaration {
+                    key: GlobalKey::Players,
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
+                }
+            ),
+            Statement::Definition(Definition{
+                name: "setup".to_owned(),
+                arguments: vec!(),
+                body: vec!(Statement::Transfer(Transfer{
+                    from: "deck".to_owned(),
+                    to: "players".to_owned(),
+                    modifier: None,
+                    count: None,
+                    span: SourceSpan::default()
+                })),
+                span: SourceSpan::default()
+            })
+        );
+
+        let mut game = Game::new(ast);
+        game.start();
+
+        assert_eq!(game.show_for_seat("player 1 hand", Some(1)), "king diamonds".to_string());
+        assert_eq!(game.show_for_seat("player 2 hand", Some(1)), "hidden (1 cards)".to_string());
+        assert_eq!(game.show_for_seat("deck", Some(1)), "hidden (50 cards)".to_string());
+        assert_eq!(
+            game.show_for_seat("all", Some(2)),
+            "deck: hidden (50 cards)\nplayer 1: hidden (1 cards)\nplayer 2: queen diamonds".to_string()
+        );
+    }
+
     #[test]
     fn it_can_show_info_about_the_game() {
         let ast = vec!(
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::Deck,
-                    value: Expression::Symbol("StandardDeck".to_string())
+                    value: Expression::Symbol("StandardDeck".to_string()),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -589,13 +3306,14 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -612,7 +3330,8 @@ mod test{
         let players = Statement::Declaration(
             Declaration {
                 key: GlobalKey::Players,
-                value: Expression::Number(3.0)
+                value: Expression::Number(3.0),
+                span: SourceSpan::default()
             }
         );
 
@@ -622,13 +3341,14 @@ mod test{
                     from: "deck".to_string(),
                     to: "player hand".to_string(),
                     modifier: None,
-                    count: None
+                    count: None,
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "player_move".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
 
         let ast = vec!(
@@ -649,7 +3369,8 @@ mod test{
         let players = Statement::Declaration(
             Declaration {
                 key: GlobalKey::Players,
-                value: Expression::Number(3.0)
+                value: Expression::Number(3.0),
+                span: SourceSpan::default()
             }
         );
 
@@ -659,25 +3380,27 @@ mod test{
                     from: "deck".to_string(),
                     to: "player hand".to_string(),
                     modifier: None,
-                    count: None
+                    count: None,
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "player_move".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
 
         let body = vec!(
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_owned(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
         let name = "setup".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let setup = Statement::Definition(definition);
 
         let ast = vec!(
@@ -701,13 +3424,14 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "winner".to_string(),
-                    arguments: vec!(Expression::Number(1.0))
+                    arguments: vec!(Expression::Number(1.0)),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -724,20 +3448,22 @@ mod test{
         let declaration = Statement::Declaration(
             Declaration {
                 key: GlobalKey::Players,
-                value: Expression::Number(1.0)
+                value: Expression::Number(1.0),
+                span: SourceSpan::default()
             }
         );
         let body = vec!(
             Statement::FunctionCall(
                 FunctionCall{
                     name: "winner".to_string(),
-                    arguments: vec!(Expression::Symbol("player:id".to_string()))
+                    arguments: vec!(Expression::Symbol("player:id".to_string())),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "player_move".to_owned();
-        let definition = Definition{ arguments: vec!("player".to_string()), name, body };
+        let definition = Definition{ arguments: vec!("player".to_string()), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(declaration, statement);
 
@@ -756,19 +3482,21 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "winner".to_string(),
-                    arguments: vec!(Expression::Number(1.0))
+                    arguments: vec!(Expression::Number(1.0)),
+                    span: SourceSpan::default()
                 }
             ),
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -786,14 +3514,16 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let if_statement = IfStatement{
             expression: Expression::Bool(true),
-            body: if_body
+            body: if_body,
+            span: SourceSpan::default()
         };
 
         let body = vec!(
@@ -801,7 +3531,7 @@ mod test{
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -819,14 +3549,16 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let if_statement = IfStatement{
             expression: Expression::Bool(false),
-            body: if_body
+            body: if_body,
+            span: SourceSpan::default()
         };
 
         let body = vec!(
@@ -834,7 +3566,7 @@ mod test{
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -852,7 +3584,8 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -860,12 +3593,14 @@ mod test{
         let comparison = Comparison{
             left: Expression::Number(1.0),
             right: Expression::Number(1.0),
-            negative: false
+            negative: false,
+            span: SourceSpan::default()
         };
 
         let if_statement = IfStatement{
             expression: Expression::Comparison(Box::new(comparison)),
-            body: if_body
+            body: if_body,
+            span: SourceSpan::default()
         };
 
         let body = vec!(
@@ -873,7 +3608,7 @@ mod test{
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ name, body, arguments: vec!() };
+        let definition = Definition{ name, body, arguments: vec!(), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -891,7 +3626,8 @@ mod test{
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::Players,
-                    value: Expression::Number(2.0)
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -899,7 +3635,8 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -908,18 +3645,21 @@ mod test{
             name: "count".to_string(),
             arguments: vec!(
                 Expression::Symbol("player:hand".to_string())
-            )
+            ),
+            span: SourceSpan::default()
         };
 
         let comparison = Comparison{
             left: Expression::FunctionCall(count_call),
             right: Expression::Number(0.0),
-            negative: false
+            negative: false,
+            span: SourceSpan::default()
         };
 
         let if_statement = IfStatement{
             expression: Expression::Comparison(Box::new(comparison)),
-            body: if_body
+            body: if_body,
+            span: SourceSpan::default()
         };
 
         let body = vec!(
@@ -927,7 +3667,7 @@ mod test{
         );
 
         let name = "player_move".to_owned();
-        let definition = Definition{ name, body, arguments: vec!() };
+        let definition = Definition{ name, body, arguments: vec!(), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         ast.push(statement);
 
@@ -946,20 +3686,21 @@ mod test{
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::Players,
-                    value: Expression::Number(2.0)
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
                 }
             )
         );
         let from = "deck".to_owned();
         let to = "players".to_owned();
         let modifier = None; //Some(TransferModifier::Alternate);
-        let count = Some(TransferCount::End);
-        let transfer = Transfer{ from, to, modifier, count };
+        let count = Some(Expression::Symbol("end".to_string()));
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
         let transfer_statement = Statement::Transfer(transfer);
 
         let name = "setup".to_owned();
         let body = vec!(transfer_statement);
-        let definition = Definition{ name, body, arguments: vec!() };
+        let definition = Definition{ name, body, arguments: vec!(), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
 
         ast.push(statement);
@@ -968,7 +3709,8 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -977,18 +3719,21 @@ mod test{
             name: "count".to_string(),
             arguments: vec!(
                 Expression::Symbol("player:hand".to_string())
-            )
+            ),
+            span: SourceSpan::default()
         };
 
         let comparison = Comparison{
             left: Expression::FunctionCall(count_call),
             right: Expression::Number(26.0),
-            negative: false
+            negative: false,
+            span: SourceSpan::default()
         };
 
         let if_statement = IfStatement{
             expression: Expression::Comparison(Box::new(comparison)),
-            body: if_body
+            body: if_body,
+            span: SourceSpan::default()
         };
 
         let body = vec!(
@@ -996,7 +3741,7 @@ mod test{
         );
 
         let name = "player_move".to_owned();
-        let definition = Definition{ name, body, arguments: vec!("player".to_string()) };
+        let definition = Definition{ name, body, arguments: vec!("player".to_string()), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         ast.push(statement);
 
@@ -1013,24 +3758,27 @@ mod test{
     fn check_stops_a_function_executing_when_expression_is_false() {
         let body = vec!(
             Statement::CheckStatement(CheckStatement{
-                expression: Expression::Bool(false)
+                expression: Expression::Bool(false),
+                span: SourceSpan::default()
             }),
             Statement::FunctionCall(
                 FunctionCall{
                     name: "winner".to_string(),
-                    arguments: vec!(Expression::Number(1.0))
+                    arguments: vec!(Expression::Number(1.0)),
+                    span: SourceSpan::default()
                 }
             ),
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ name, body, arguments: vec!() };
+        let definition = Definition{ name, body, arguments: vec!(), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -1046,24 +3794,27 @@ mod test{
     fn check_passes_through_when_expression_is_true() {
         let body = vec!(
             Statement::CheckStatement(CheckStatement{
-                expression: Expression::Bool(true)
+                expression: Expression::Bool(true),
+                span: SourceSpan::default()
             }),
             Statement::FunctionCall(
                 FunctionCall{
                     name: "winner".to_string(),
-                    arguments: vec!(Expression::Number(1.0))
+                    arguments: vec!(Expression::Number(1.0)),
+                    span: SourceSpan::default()
                 }
             ),
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ name, body, arguments: vec!() };
+        let definition = Definition{ name, body, arguments: vec!(), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -1081,7 +3832,8 @@ mod test{
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::CurrentPlayer,
-                    value: Expression::Number(1.0)
+                    value: Expression::Number(1.0),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -1098,7 +3850,8 @@ mod test{
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::CurrentPlayer,
-                    value: Expression::Number(2.0)
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -1115,25 +3868,28 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "next_player".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ name, body,  arguments: vec!(), };
+        let definition = Definition{ name, body,  arguments: vec!(), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::Players,
-                    value: Expression::Number(3.0)
+                    value: Expression::Number(3.0),
+                    span: SourceSpan::default()
                 },
             ),
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::CurrentPlayer,
-                    value: Expression::Number(1.0)
+                    value: Expression::Number(1.0),
+                    span: SourceSpan::default()
                 }
             ),
             statement
@@ -1152,25 +3908,28 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "next_player".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ name, body, arguments: vec!() };
+        let definition = Definition{ name, body, arguments: vec!(), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::Players,
-                    value: Expression::Number(2.0)
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
                 },
             ),
             Statement::Declaration(
                 Declaration {
                     key: GlobalKey::CurrentPlayer,
-                    value: Expression::Number(2.0)
+                    value: Expression::Number(2.0),
+                    span: SourceSpan::default()
                 }
             ),
             statement
@@ -1189,19 +3948,22 @@ mod test{
             Statement::FunctionCall(
                 FunctionCall{
                     name: "end".to_string(),
-                    arguments: vec!()
+                    arguments: vec!(),
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let and = And{
             left: Expression::Bool(true),
-            right: Expression::Bool(true)
+            right: Expression::Bool(true),
+            span: SourceSpan::default()
         };
 
         let if_statement = IfStatement{
             expression: Expression::And(Box::new(and)),
-            body: if_body
+            body: if_body,
+            span: SourceSpan::default()
         };
 
         let body = vec!(
@@ -1209,7 +3971,7 @@ mod test{
         );
 
         let name = "setup".to_owned();
-        let definition = Definition{ name, body, arguments: vec!() };
+        let definition = Definition{ name, body, arguments: vec!(), span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let ast = vec!(statement);
 
@@ -1221,12 +3983,45 @@ mod test{
         assert_eq!(display, "game over");
     }
 
+    #[test]
+    fn sums_are_resolved_as_numbers() {
+        let mut game = crate::load_game("define setup(){\nif(current_player + 2 - 1 is 2){\nend()\n}\n}").unwrap();
+        game.start();
+
+        assert_eq!(game.show("game"), "game over");
+    }
+
+    #[test]
+    fn products_are_resolved_as_numbers() {
+        let mut game = crate::load_game("define setup(){\nif(current_player * 6 / 4 + -3 % 4 is 2.5){\nend()\n}\n}").unwrap();
+        game.start();
+
+        assert_eq!(game.show("game"), "game over");
+    }
+
+    #[test]
+    fn nothing_divides_by_zero() {
+        let mut game = crate::load_game("define setup(){\nif(current_player / 0 is false){\nend()\n}\n}").unwrap();
+        game.start();
+
+        assert_eq!(game.show("game"), "game over");
+    }
+
+    #[test]
+    fn either_side_of_an_or_can_be_true() {
+        let mut game = crate::load_game("define setup(){\nif(current_player is 2 | current_player is 1){\nend()\n}\n}").unwrap();
+        game.start();
+
+        assert_eq!(game.show("game"), "game over");
+    }
+
     #[test]
     fn it_passes_the_player_to_the_move_with_the_specified_argument_label() {
         let players = Statement::Declaration(
             Declaration {
                 key: GlobalKey::Players,
-                value: Expression::Number(3.0)
+                value: Expression::Number(3.0),
+                span: SourceSpan::default()
             }
         );
 
@@ -1236,13 +4031,14 @@ mod test{
                     from: "deck".to_string(),
                     to: "pl:hand".to_string(),
                     modifier: None,
-                    count: None
+                    count: None,
+                    span: SourceSpan::default()
                 }
             )
         );
 
         let name = "player_move".to_owned();
-        let definition = Definition{ arguments: vec!("pl".to_string()), name, body };
+        let definition = Definition{ arguments: vec!("pl".to_string()), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
 
         let ast = vec!(
@@ -0,0 +1,325 @@
+use std::fmt;
+
+// arrays and objects nested any deeper are refused, rather than parsed until the stack runs out
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+impl Json {
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn string(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+
+impl Json {
+    pub fn parse(source: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser { chars: source.chars().collect(), position: 0, depth: 0 };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(JsonError{ position: parser.position });
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct JsonError {
+    pub position: usize
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize
+}
+
+impl Parser {
+    fn error<T>(&self) -> Result<T, JsonError> {
+        Err(JsonError{ position: self.position })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.chars.get(self.position) {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.position).copied();
+        self.position += 1;
+        c
+    }
+
+    fn expect_word(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        for expected in word.chars() {
+            if self.chars.get(self.position) != Some(&expected) {
+                return self.error();
+            }
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.chars.get(self.position) {
+            Some('n') => self.expect_word("null", Json::Null),
+            Some('t') => self.expect_word("true", Json::Bool(true)),
+            Some('f') => self.expect_word("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.parse_string()?)),
+            Some('[') => self.nested(Self::parse_array),
+            Some('{') => self.nested(Self::parse_object),
+            Some('-') | Some('0'..='9') => self.parse_number(),
+            _ => self.error()
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Json, JsonError>) -> Result<Json, JsonError> {
+        if self.depth == MAX_DEPTH {
+            return self.error();
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_number(&mut self) -> Result<Json, JsonError> {
+        let start = self.position;
+        while let Some(c) = self.chars.get(self.position) {
+            match c {
+                '-' | '+' | '.' | 'e' | 'E' | '0'..='9' => self.position += 1,
+                _ => break
+            }
+        }
+        let number: String = self.chars[start..self.position].iter().collect();
+        match number.parse::<f64>() {
+            Ok(n) => Ok(Json::Number(n)),
+            _ => Err(JsonError{ position: start })
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        // opening quote
+        self.next();
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let start = self.position;
+                        let end = start + 4;
+                        if end > self.chars.len() {
+                            return self.error();
+                        }
+                        let hex: String = self.chars[start..end].iter().collect();
+                        self.position = end;
+                        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                            Some(c) => s.push(c),
+                            None => return self.error()
+                        }
+                    },
+                    Some(c) => s.push(c),
+                    None => return self.error()
+                },
+                Some(c) => s.push(c),
+                None => return self.error()
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, JsonError> {
+        // opening bracket
+        self.next();
+        let mut items = vec!();
+        self.skip_whitespace();
+        if self.chars.get(self.position) == Some(&']') {
+            self.next();
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => (),
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return self.error()
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, JsonError> {
+        // opening brace
+        self.next();
+        let mut fields = vec!();
+        self.skip_whitespace();
+        if self.chars.get(self.position) == Some(&'}') {
+            self.next();
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.chars.get(self.position) != Some(&'"') {
+                return self.error();
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if self.next() != Some(':') {
+                return self.error();
+            }
+            let value = self.parse_value()?;
+            fields.push((key, value));
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => (),
+                Some('}') => return Ok(Json::Object(fields)),
+                _ => return self.error()
+            }
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(s) => write!(f, "\"{}\"", escape(s)),
+            Json::Array(items) => {
+                let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+                write!(f, "[{}]", items.join(","))
+            },
+            Json::Object(fields) => {
+                let fields: Vec<String> = fields.iter()
+                    .map(|(k, v)| format!("\"{}\":{}", escape(k), v))
+                    .collect();
+                write!(f, "{{{}}}", fields.join(","))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn it_serialises_nested_values() {
+        let json = Json::object(vec!(
+            ("name", Json::string("turns")),
+            ("players", Json::Array(vec!(Json::Number(1.0), Json::Number(2.0)))),
+            ("over", Json::Bool(false)),
+            ("winner", Json::Null)
+        ));
+
+        assert_eq!(json.to_string(), r#"{"name":"turns","players":[1,2],"over":false,"winner":null}"#);
+    }
+
+    #[test]
+    fn it_parses_what_it_serialises() {
+        let source = r#"{"method":"move","params":{"player":2,"tags":["a\"b",true,null]}}"#;
+        let json = Json::parse(source).unwrap();
+
+        assert_eq!(json.get("method").and_then(|m| m.as_str()), Some("move"));
+        assert_eq!(json.to_string(), source.to_string());
+    }
+
+    #[test]
+    fn it_reports_the_position_of_parse_errors() {
+        let result = Json::parse("{\"a\": tru}");
+
+        assert_eq!(result, Err(JsonError{ position: 9 }));
+    }
+
+    #[test]
+    fn deep_nesting_is_refused() {
+        let deep = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        let flood = "[".repeat(1_000_000);
+
+        assert!(Json::parse(&deep).is_ok());
+        assert_eq!(Json::parse(&format!("[{}]", deep)), Err(JsonError{ position: MAX_DEPTH }));
+        assert_eq!(Json::parse(&flood), Err(JsonError{ position: MAX_DEPTH }));
+    }
+
+    #[test]
+    fn it_escapes_strings() {
+        let json = Json::string("say \"hi\"\n");
+
+        assert_eq!(json.to_string(), r#""say \"hi\"\n""#);
+    }
+}
@@ -1,8 +1,9 @@
+use std::{error::Error, fmt};
 use crate::token::{Token, SourceToken};
 
-enum TokenResult {
-    Token(Token),
-    PartialToken(String),
+enum TokenResult<'a> {
+    Token(Token<'a>),
+    PartialToken,
     Empty,
     Error
 }
@@ -16,144 +17,218 @@ pub enum LexErrorType{
 #[derive(Debug, PartialEq, Clone)]
 pub struct LexError{
     pub error_type: LexErrorType,
-    pub line_number: u32
+    pub line_number: u32,
+    pub column: u32
 }
 
 impl LexError{
-    pub fn new(error_type: LexErrorType, line_number: u32) -> LexError {
-        LexError{ error_type, line_number }
+    pub fn new(error_type: LexErrorType, line_number: u32, column: u32) -> LexError {
+        LexError{ error_type, line_number, column }
     }
 }
 
-pub fn lexer(source: &str) -> Result<Vec<SourceToken>, LexError> {
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error_type {
+            LexErrorType::EmptySpecification => write!(f, "empty specification"),
+            LexErrorType::ParseError => write!(f, "unrecognised token")
+        }
+    }
+}
+
+impl Error for LexError {}
+
+// tokens borrow their text from the source, a partial token is just the offset it started at
+pub fn lexer(source: &str) -> Result<Vec<SourceToken<'_>>, LexError> {
     let mut line_number = 1;
+    let mut column = 0;
+    let mut token_column = 0;
     let mut tokens = vec!();
-    let mut chars = source.chars().peekable();
+    let mut chars = source.char_indices().peekable();
 
-    let mut partial_token: Option<String> = None;
+    let mut partial_start: Option<usize> = None;
+    let mut previous_char = None;
 
-    loop {
-        let current_char_result = chars.next();
-        if current_char_result.is_none() {
-            break;
+    while let Some((offset, current_char)) = chars.next() {
+        column += 1;
+        if partial_start.is_none() {
+            token_column = column;
         }
 
-        let current_char = current_char_result.expect("expected a char");
-        let next_char = chars.peek();
-        let result = handle_char(&partial_token, current_char, next_char);
+        let next_char = chars.peek().map(|(_, c)| *c);
+        let end = offset + current_char.len_utf8();
+        let result = match partial_start {
+            None if current_char == '-' && is_minus(previous_char, next_char) => TokenResult::Token(Token::Minus),
+            None => match handle_single_chars(current_char) {
+                Some(single_char) => single_char,
+                None => handle_partial(&source[offset..end], next_char)
+            },
+            Some(start) => handle_partial(&source[start..end], next_char)
+        };
+        let token_line = line_number;
+        previous_char = Some(current_char);
+
+        // newlines inside comments still count towards the line number
+        if current_char == '\n' {
+            line_number += 1;
+            column = 0;
+        }
 
         match result {
             TokenResult::Token(t) => {
-                if t == Token::Newline {
-                    line_number += 1;
-                }
-
-                partial_token = None;
+                partial_start = None;
                 let source_token = SourceToken{
                     token: t,
-                    line_number
+                    line_number: token_line,
+                    column: token_column
                 };
                 tokens.push(source_token);
             },
-            TokenResult::PartialToken(s) => {
-                partial_token = Some(s);
+            TokenResult::PartialToken => {
+                partial_start.get_or_insert(offset);
             },
             TokenResult::Empty => {
-                partial_token = None;
+                partial_start = None;
             },
             TokenResult::Error => {
-                let lex_error = LexError::new(LexErrorType::ParseError, line_number);
+                let lex_error = LexError::new(LexErrorType::ParseError, token_line, token_column);
                 return Err(lex_error);
             }
         }
     }
 
-    if tokens.len() == 0 {
-        let lex_error = LexError::new(LexErrorType::EmptySpecification, line_number);
+    // a string still open at the end of the source is an error, where an unclosed comment is just dropped
+    if tokens.is_empty() {
+        let lex_error = LexError::new(LexErrorType::EmptySpecification, line_number, 0);
         Err(lex_error)
+    } else if partial_start.is_some_and(|start| source[start..].starts_with('"')) {
+        Err(LexError::new(LexErrorType::ParseError, line_number, token_column))
     } else {
         Ok(tokens)
     }
 }
 
-
-fn handle_char(partial_token: &Option<String>, current_char: char, next_char: Option<&char>) -> TokenResult {
-    match partial_token {
-        None => {
-            let single_char = handle_single_chars(current_char);
-            if single_char.is_some() {
-                return single_char.expect("should have a single char");
-            }
-            
-            handle_partial(current_char.to_string(), next_char)
-        },
-        Some(x) => {
-            let new_partial = format!("{}{}", x, current_char);
-            handle_partial(new_partial, next_char)
-        }
-    }
-}
-
-fn handle_single_chars(current_char: char) -> Option<TokenResult> {
+fn handle_single_chars<'a>(current_char: char) -> Option<TokenResult<'a>> {
     match current_char {
         '(' => Some(TokenResult::Token(Token::OpenParens)),
         ')' => Some(TokenResult::Token(Token::CloseParens)),
-        ' ' => Some(TokenResult::Empty),
+        ' ' | '\t' | '\r' => Some(TokenResult::Empty),
         ',' => Some(TokenResult::Token(Token::Comma)),
         '{' => Some(TokenResult::Token(Token::OpenBracket)),
         '}' => Some(TokenResult::Token(Token::CloseBracket)),
         '>' => Some(TokenResult::Token(Token::Transfer)),
         '\n' => Some(TokenResult::Token(Token::Newline)),
-        '.' => Some(TokenResult::PartialToken(current_char.to_string())),
+        '.' => Some(TokenResult::PartialToken),
         '&' => Some(TokenResult::Token(Token::Ampersand)),
+        '|' => Some(TokenResult::Token(Token::Pipe)),
+        '+' => Some(TokenResult::Token(Token::Plus)),
+        '*' => Some(TokenResult::Token(Token::Star)),
+        '/' => Some(TokenResult::Token(Token::Slash)),
+        '%' => Some(TokenResult::Token(Token::Percent)),
+        '"' => Some(TokenResult::PartialToken),
         _ => None
     }
 }
 
-fn handle_partial(current_partial: String, next_char: Option<&char>) -> TokenResult {
-    let keyword_result = handle_keyword(&current_partial, next_char);
+// `-3` is a number, but `x - 3` and `count(x)-3` take it away
+fn is_minus(previous_char: Option<char>, next_char: Option<char>) -> bool {
+    let number_follows = matches!(next_char, Some('0'..='9'));
+    let value_before = matches!(previous_char, Some('A'..='z') | Some('0'..='9') | Some(':') | Some(')'));
+    !number_follows || value_before
+}
+
+// an odd run of backslashes escapes whatever follows, an even one only escapes itself
+fn is_escaped(text: &str) -> bool {
+    text.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+// a string's text as the game means it, with `\n` a new line and `\"` a quote
+pub fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let escaped = match (c, chars.peek().copied()) {
+            ('\\', Some('n')) => Some('\n'),
+            ('\\', Some(quoted @ ('"' | '\\'))) => Some(quoted),
+            _ => None
+        };
+        match escaped {
+            Some(e) => {
+                chars.next();
+                unescaped.push(e);
+            },
+            None => unescaped.push(c)
+        }
+    }
+    unescaped
+}
+
+fn handle_partial(current_partial: &str, next_char: Option<char>) -> TokenResult<'_> {
+    // `"Crazy Eights"` is taken as written up to the closing quote, on one line, and `\"` doesn't close it
+    if let Some(text) = current_partial.strip_prefix('"') {
+        return match text.strip_suffix('"') {
+            Some(text) if !is_escaped(text) => TokenResult::Token(Token::String(text)),
+            _ if text.ends_with('\n') => TokenResult::Error,
+            _ => TokenResult::PartialToken
+        };
+    }
 
-    if keyword_result.is_some() {
-        return keyword_result.expect("should be a keyword");
+    if let Some(keyword) = handle_keyword(current_partial, next_char) {
+        return keyword;
     }
 
-    if is_word_finished(next_char) {
+    if is_word_finished(next_char) && !is_decimal_point(current_partial, next_char) {
         return resolve_partial(current_partial);
     }
 
-    TokenResult::PartialToken(current_partial)
+    TokenResult::PartialToken
+}
+
+// 3.5 keeps going past the point, where a . would otherwise start a new token
+fn is_decimal_point(partial_token: &str, next_char: Option<char>) -> bool {
+    let digits = partial_token.strip_prefix('-').unwrap_or(partial_token);
+    next_char == Some('.') && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
 }
 
-fn handle_keyword(partial_token: &str, next_char: Option<&char>) -> Option<TokenResult> {
+// words that are always keywords, and can't name a stack or a definition
+pub const KEYWORDS: [(&str, Token<'static>); 20] = [
+    ("name", Token::Name),
+    ("stack", Token::Stack),
+    ("deck", Token::Deck),
+    ("players", Token::Players),
+    ("teams", Token::Teams),
+    ("hand_size", Token::HandSize),
+    ("current_player", Token::CurrentPlayer),
+    ("define", Token::Define),
+    ("check", Token::Check),
+    ("is", Token::Is),
+    ("if", Token::If),
+    ("while", Token::While),
+    ("foreach", Token::Foreach),
+    ("true", Token::True),
+    ("false", Token::False),
+    ("return", Token::Return),
+    (".test", Token::Test),
+    ("not", Token::Not),
+    ("match", Token::Match),
+    ("between", Token::Between)
+];
+
+fn handle_keyword<'a>(partial_token: &str, next_char: Option<char>) -> Option<TokenResult<'a>> {
     if !is_word_finished(next_char) {
         return None
     }
 
-    match partial_token {
-        "name" => Some(TokenResult::Token(Token::Name)),
-        "stack" => Some(TokenResult::Token(Token::Stack)),
-        "deck" => Some(TokenResult::Token(Token::Deck)),
-        "players" => Some(TokenResult::Token(Token::Players)),
-        "current_player" => Some(TokenResult::Token(Token::CurrentPlayer)),
-        "define" => Some(TokenResult::Token(Token::Define)),
-        "check" => Some(TokenResult::Token(Token::Check)),
-        "is" => Some(TokenResult::Token(Token::Is)),
-        "if" => Some(TokenResult::Token(Token::If)),
-        "true" => Some(TokenResult::Token(Token::True)),
-        "false" => Some(TokenResult::Token(Token::False)),
-        "return" => Some(TokenResult::Token(Token::Return)),
-        ".test" => Some(TokenResult::Token(Token::Test)),
-        "not" => Some(TokenResult::Token(Token::Not)),
-        _ => None
-    }
+    KEYWORDS.iter()
+        .find(|(keyword, _)| *keyword == partial_token)
+        .map(|(_, token)| TokenResult::Token(token.clone()))
 }
 
-fn resolve_partial(partial_token: String) -> TokenResult {
+fn resolve_partial(partial_token: &str) -> TokenResult<'_> {
     let mut chars = partial_token.chars();
-    let first = chars.next().expect("unable to find first char in partial token");
-    match first {
-        'A'..='z' => TokenResult::Token(Token::Symbol(partial_token)),
-        '.' => {
+    match chars.next() {
+        Some('A'..='z') => TokenResult::Token(Token::Symbol(partial_token)),
+        Some('.') => {
             match chars.next() {
                 // comments
                 Some('(') => {
@@ -172,30 +247,22 @@ fn resolve_partial(partial_token: String) -> TokenResult {
                             _ => ()
                         }
                     }
-                    TokenResult::PartialToken(partial_token)
+                    TokenResult::PartialToken
                 },
                 _ => TokenResult::Error
             }
         },
         _ => {
-            let parse_result = partial_token.parse::<f64>();
-            match parse_result {
-                Ok(float) => TokenResult::Token(Token::Number(float)),
+            match partial_token.parse::<f64>() {
+                Ok(float) => TokenResult::Token(Token::Number(float, partial_token.chars().count() as u32)),
                 _ => TokenResult::Error
             }
-            
         }
     }
-    
 }
 
-fn is_word_finished(next_char: Option<&char>) -> bool {
-    match next_char {
-        Some('A'..='z') | Some('0'..='9') | Some(':') => {
-            false
-        },
-        _ => true
-    }
+fn is_word_finished(next_char: Option<char>) -> bool {
+    !matches!(next_char, Some('A'..='z') | Some('0'..='9') | Some(':'))
 }
 
 #[cfg(test)]
@@ -254,7 +321,7 @@ mod test{
         let src = "scopa";
         let result = lexer(&src).unwrap();
 
-        assert_eq!(result[0].token, Token::Symbol("scopa".to_owned()));
+        assert_eq!(result[0].token, Token::Symbol("scopa"));
     }
 
     #[test]
@@ -262,7 +329,7 @@ mod test{
         let src = "StandardDeck";
         let result = lexer(&src).unwrap();
 
-        assert_eq!(result[0].token, Token::Symbol("StandardDeck".to_owned()));
+        assert_eq!(result[0].token, Token::Symbol("StandardDeck"));
     }
 
     #[test]
@@ -271,7 +338,7 @@ mod test{
         let result = lexer(&src).unwrap();
         let expected = vec!(
             Token::Deck,
-            Token::Symbol("StandardDeck".to_owned())
+            Token::Symbol("StandardDeck")
         );
         assert_eq!(result[0].token, expected[0]);
         assert_eq!(result[1].token, expected[1]);
@@ -323,8 +390,8 @@ mod test{
         let src = "check cards is fun";
         let result = lexer(&src).unwrap();
         let expected = vec!(
-            Token::Check, Token::Symbol("cards".to_owned()),
-            Token::Is, Token::Symbol("fun".to_owned())
+            Token::Check, Token::Symbol("cards"),
+            Token::Is, Token::Symbol("fun")
         );
         assert_eq!(result[0].token, expected[0]);
         assert_eq!(result[1].token, expected[1]);
@@ -342,7 +409,7 @@ mod test{
     fn it_handles_numbers(){
         let src ="1";
         let result = lexer(&src).unwrap();
-        let expected = Token::Number(1.0);
+        let expected = Token::Number(1.0, 1);
         assert_eq!(result[0].token, expected);
     }
 
@@ -359,7 +426,7 @@ mod test{
         let src = "foo1";
         let result = lexer(&src).unwrap();
 
-        assert_eq!(result[0].token, Token::Symbol("foo1".to_owned()));
+        assert_eq!(result[0].token, Token::Symbol("foo1"));
     }
 
     #[test]
@@ -374,7 +441,7 @@ mod test{
     fn it_ignores_comments() {
         let src = "name .( this is a comment ) test1";
         let result = lexer(&src).unwrap();
-        let expected = vec!(Token::Name, Token::Symbol("test1".to_owned()));
+        let expected = vec!(Token::Name, Token::Symbol("test1"));
         assert_eq!(result[0].token, expected[0]);
         assert_eq!(result[1].token, expected[1]);
     }
@@ -384,7 +451,7 @@ mod test{
         let src = "name .( 
 this is a comment ) test2";
         let result = lexer(&src).unwrap();
-        let expected = vec!(Token::Name, Token::Symbol("test2".to_owned()));
+        let expected = vec!(Token::Name, Token::Symbol("test2"));
         assert_eq!(result[0].token, expected[0]);
         assert_eq!(result[1].token, expected[1]);
     }
@@ -393,7 +460,7 @@ this is a comment ) test2";
     fn comments_can_contain_parens() {
         let src = "name .(()) test2";
         let result = lexer(&src).unwrap();
-        let expected = vec!(Token::Name, Token::Symbol("test2".to_owned()));
+        let expected = vec!(Token::Name, Token::Symbol("test2"));
         assert_eq!(result[0].token, expected[0]);
         assert_eq!(result[1].token, expected[1]);
     }
@@ -403,7 +470,7 @@ this is a comment ) test2";
         let src = "hello_world";
         let result = lexer(&src).unwrap();
 
-        assert_eq!(result[0].token, Token::Symbol("hello_world".to_owned()));
+        assert_eq!(result[0].token, Token::Symbol("hello_world"));
     }
 
     #[test]
@@ -411,7 +478,7 @@ this is a comment ) test2";
         let src = "shuffle(deck)";
         let result = lexer(&src).unwrap();
         let expected = vec!(
-            Token::Symbol("shuffle".to_string()), Token::OpenParens,
+            Token::Symbol("shuffle"), Token::OpenParens,
             Token::Deck, Token::CloseParens
         );
         assert_eq!(result[0].token, expected[0]);
@@ -424,7 +491,7 @@ this is a comment ) test2";
     fn a_symbol_can_contain_an_attribute() {
         let src = "player:hand";
         let result = lexer(&src).unwrap();
-        let expected = Token::Symbol("player:hand".to_owned());
+        let expected = Token::Symbol("player:hand");
         assert_eq!(result[0].token, expected)
     }
 
@@ -453,6 +520,34 @@ this is a comment ) test2";
         assert_eq!(result.line_number, 2);
     }
 
+    #[test]
+    fn tokens_report_their_starting_column() {
+        let src = "deck > players\n  shuffle(deck)";
+        let result = lexer(src).unwrap();
+
+        assert_eq!((result[0].line_number, result[0].column), (1, 1));
+        assert_eq!((result[1].line_number, result[1].column), (1, 6));
+        assert_eq!((result[2].line_number, result[2].column), (1, 8));
+        assert_eq!((result[4].line_number, result[4].column), (2, 3));
+        assert_eq!((result[6].line_number, result[6].column), (2, 11));
+    }
+
+    #[test]
+    fn lex_errors_report_columns() {
+        let src = "name  1foo";
+        let result = lexer(src).unwrap_err();
+
+        assert_eq!(result.column, 7);
+    }
+
+    #[test]
+    fn multiline_comments_count_towards_line_numbers() {
+        let src = ".( a\ncomment )\nname";
+        let result = lexer(src).unwrap();
+
+        assert_eq!(result[1].line_number, 3);
+    }
+
     #[test]
     fn it_recognises_the_ampersand() {
         let src = "&";
@@ -461,6 +556,49 @@ this is a comment ) test2";
         assert_eq!(result[0].token, Token::Ampersand);
     }
 
+    #[test]
+    fn a_minus_after_a_value_subtracts() {
+        let tokens: Vec<Token> = lexer("count(x)-1 - 2 + -3").unwrap().into_iter().map(|t| t.token).collect();
+
+        assert_eq!(&tokens[4..], &[Token::Minus, Token::Number(1.0, 1), Token::Minus, Token::Number(2.0, 1), Token::Plus, Token::Number(-3.0, 2)]);
+    }
+
+    #[test]
+    fn it_recognises_multiplication_division_and_modulo() {
+        let tokens: Vec<Token> = lexer("a * 2 / b % 4").unwrap().into_iter().map(|t| t.token).collect();
+
+        assert_eq!((&tokens[1], &tokens[3], &tokens[5]), (&Token::Star, &Token::Slash, &Token::Percent));
+    }
+
+    #[test]
+    fn it_recognises_string_literals() {
+        let tokens: Vec<Token> = lexer("name \"Crazy Eights!\" \"\"").unwrap().into_iter().map(|t| t.token).collect();
+
+        assert_eq!(tokens, vec!(Token::Name, Token::String("Crazy Eights!"), Token::String("")));
+        assert_eq!(lexer("name \"Crazy").unwrap_err().error_type, LexErrorType::ParseError);
+        assert_eq!(lexer("name \"Crazy\nEights\"").unwrap_err().line_number, 1);
+    }
+
+    #[test]
+    fn escaped_quotes_dont_end_a_string() {
+        let tokens: Vec<Token> = lexer(r#"name "say \"hi\"" "back\\""#).unwrap().into_iter().map(|t| t.token).collect();
+
+        assert_eq!(tokens, vec!(Token::Name, Token::String(r#"say \"hi\""#), Token::String(r"back\\")));
+        assert_eq!(lexer(r#"name "open \""#).unwrap_err().error_type, LexErrorType::ParseError);
+    }
+
+    #[test]
+    fn strings_unescape_new_lines_and_quotes() {
+        assert_eq!(unescape(r#"one\ntwo \"three\" \\n \t"#), "one\ntwo \"three\" \\n \\t".to_string());
+    }
+
+    #[test]
+    fn it_recognises_the_pipe() {
+        let result = lexer("a | b").unwrap();
+
+        assert_eq!(result[1].token, Token::Pipe);
+    }
+
     #[test]
     fn it_recognises_the_return_keyword() {
         let src = "return";
@@ -495,4 +633,56 @@ this is a comment ) test2";
 
         assert_eq!(result[0].token, expected[0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn windows_line_endings_and_tabs_are_whitespace() {
+        let src = "name turns\r\n\tstack\tmiddle\r\nplayers 2";
+        let result = lexer(src).unwrap();
+        let expected = vec!(
+            Token::Name, Token::Symbol("turns"), Token::Newline,
+            Token::Stack, Token::Symbol("middle"), Token::Newline,
+            Token::Players, Token::Number(2.0, 1)
+        );
+
+        assert_eq!(result.iter().map(|t| t.token.clone()).collect::<Vec<Token>>(), expected);
+        assert_eq!((result[4].line_number, result[4].column), (2, 8));
+        assert_eq!(result[6].line_number, 3);
+    }
+
+    #[test]
+    fn it_handles_decimal_and_negative_numbers() {
+        let src = "3.5 -2 -0.25 10";
+        let result = lexer(src).unwrap();
+        let expected = vec!(Token::Number(3.5, 3), Token::Number(-2.0, 2), Token::Number(-0.25, 5), Token::Number(10.0, 2));
+
+        assert_eq!(result.iter().map(|t| t.token.clone()).collect::<Vec<Token>>(), expected);
+        assert_eq!(result[2].column, 8);
+        assert_eq!(lexer("3.50").unwrap()[0].token.width(), 4);
+    }
+
+    #[test]
+    fn numbers_can_only_have_one_point() {
+        let result = lexer("1.2.3").unwrap_err();
+
+        assert_eq!(result.error_type, LexErrorType::ParseError);
+    }
+
+    #[test]
+    fn symbols_are_slices_of_the_source() {
+        let src = "stack pile:hand";
+        let result = lexer(src).unwrap();
+
+        match result[1].token {
+            Token::Symbol(s) => assert!(std::ptr::eq(s, &src[6..])),
+            _ => panic!("expected a symbol")
+        }
+    }
+
+    #[test]
+    fn symbols_can_follow_multibyte_characters() {
+        let result = lexer(".(é) turns").unwrap();
+
+        assert_eq!(result[0].token, Token::Symbol("turns"));
+        assert_eq!(result[0].column, 6);
+    }
+}
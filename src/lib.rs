@@ -0,0 +1,94 @@
+//! cardlang - a language for describing card games, and an interpreter to play them
+//!
+//! ```
+//! let source = cardlang::examples::find("war").unwrap();
+//! let mut game = cardlang::load_game(source).unwrap();
+//! game.start();
+//!
+//! assert!(game.player_move(1));
+//! ```
+
+use std::fs;
+
+pub mod lex;
+pub mod parse;
+pub mod token;
+pub mod ast;
+pub mod interpreter;
+pub mod cards;
+pub mod runtime;
+pub mod json;
+pub mod server;
+pub mod tui;
+pub mod session;
+pub mod diagnostics;
+pub mod doc;
+pub mod examples;
+pub mod error;
+pub mod host;
+pub mod env;
+pub mod bot;
+pub mod tournament;
+pub mod rating;
+pub mod profile;
+pub mod simulate;
+pub mod highlight;
+pub mod grammar;
+pub mod format;
+pub mod incremental;
+pub mod cache;
+pub mod version;
+pub mod options;
+mod suggest;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod generators;
+
+pub use interpreter::{Game, PlayerView, GameStateView, Suggestion};
+pub use runtime::{Runtime, GameState, Snapshot, Change, diff, Metrics, PrimitiveValue};
+pub use lex::{lexer, LexError, LexErrorType};
+pub use parse::{parse, ParseError, ParseErrorType};
+pub use cards::{Card, Player, Rank, Suit};
+pub use diagnostics::Diagnostic;
+pub use highlight::{semantic_tokens, SemanticKind, SemanticToken};
+pub use error::{CardlangError, RuntimeError, Span, Violation};
+pub use host::{GameHost, TransferDecision};
+pub use env::{Env, Observation};
+pub use bot::{Bot, RandomBot, GameView};
+pub use tournament::Tournament;
+pub use rating::Ratings;
+pub use profile::{Profiles, ProfileStore};
+pub use format::Formatter;
+
+// a path on disk, or one of the bundled games as `example:<name>`
+pub fn read_source(path: &str) -> Result<String, CardlangError> {
+    if let Some(name) = path.strip_prefix(examples::PREFIX) {
+        return examples::find(name)
+            .map(|source| source.to_string())
+            .ok_or_else(|| CardlangError::UnknownExample(name.to_string()));
+    }
+
+    fs::read_to_string(path).map_err(|_| CardlangError::UnreadableFile(path.to_string()))
+}
+
+pub fn load_game(source: &str) -> Result<Game, CardlangError> {
+    let mut game = parse_source(source).map(Game::new)?;
+    game.set_description(doc::description(source));
+    Ok(game)
+}
+
+pub fn parse_source(source: &str) -> Result<Vec<ast::Statement>, CardlangError> {
+    let tokens = lex::lexer(source)?;
+    let ast = parse::parse(&tokens)?;
+    check_source(ast)
+}
+
+// the checks that need the whole file, as a deck can be declared after the stack that uses it
+pub(crate) fn check_source(ast: Vec<ast::Statement>) -> Result<Vec<ast::Statement>, CardlangError> {
+    match version::language_version(&ast) {
+        v if v > version::LANGUAGE_VERSION => Err(CardlangError::UnsupportedVersion(v)),
+        _ => match diagnostics::card_error(&ast) {
+            Some(e) => Err(e),
+            None => Ok(ast)
+        }
+    }
+}
@@ -1,22 +1,71 @@
-use std::{fs, env, io::{stdin, stdout, Write}};
+use std::{env, fs, process, io::{stdin, stdout, Write}, time::{Duration, SystemTime}};
 
-mod lex;
-mod parse;
-mod token;
-mod ast;
-mod interpreter;
-mod cards;
-mod runtime;
+use cardlang::{
+    Game, load_game, read_source, parse_source,
+    diagnostics, doc, examples, grammar, server, session, tui, bot, tournament::Tournament, rating::Ratings, simulate,
+    interpreter::REPLAY_PREFIX, format, incremental::IncrementalParser, cache, options, ProfileStore, json::Json as JsonValue
+};
 
-use interpreter::Game;
+const HINT_PLAYOUTS: usize = 20;
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputMode {
+    Text,
+    Json,
+    Tui
+}
 
 enum CommandResult {
-    Game(Game),
-    CommandFailed,
+    Game(String, Game, Vec<String>),
+    Use(String),
+    Games,
+    CommandFailed(String),
     Exit,
     Show(String),
     Start,
-    Move(usize)
+    Move(usize),
+    Seed(u64),
+    History,
+    Undo(usize),
+    Auto(Option<usize>),
+    Break(String),
+    Step,
+    Back,
+    Continue,
+    Locals,
+    Stack,
+    Trace(bool),
+    Seat(Option<usize>),
+    Bot(usize, Option<String>),
+    Hint(usize),
+    Timer(Option<Duration>),
+    Save(String),
+    Load(String),
+    Export,
+    Replay(String),
+    Format(String),
+    Watch(String),
+    Profiles(Option<String>),
+    Name(usize, String)
+}
+
+struct Repl {
+    game: Option<Game>,
+    // what the active game was built as, with the others built this session kept aside by name
+    name: Option<String>,
+    shelved: Vec<(String, Game)>,
+    mode: OutputMode,
+    seat: Option<usize>,
+    bots: bot::Seats,
+    watched: Option<Watched>,
+    profiles: Option<ProfileStore>
+}
+
+// a game file rebuilt whenever it changes, between commands
+struct Watched {
+    path: String,
+    modified: Option<SystemTime>,
+    parser: IncrementalParser
 }
 
 fn main() {
@@ -24,134 +73,721 @@ fn main() {
     let default_command = "".to_string();
     let args: Vec<String> = env::args().collect();
     let command = args.get(1).unwrap_or(&default_command);
+    let mode = if args.iter().any(|arg| arg == "--json") {
+        OutputMode::Json
+    } else if args.iter().any(|arg| arg == "--tui") {
+        OutputMode::Tui
+    } else {
+        OutputMode::Text
+    };
+    install_logger(command == "serve", mode == OutputMode::Json);
     match command as &str {
-        "test"    => test(args.get(2)),
-        _         => interactive()
+        "test"    => test(args.get(2), mode == OutputMode::Json),
+        "lint"    => lint(args.get(2), mode == OutputMode::Json),
+        "doc"     => document(args.get(2)),
+        "examples" => println!("{}", examples::list()),
+        "grammar" if mode == OutputMode::Json => println!("{}", grammar::to_json()),
+        "grammar" => print!("{}", grammar::ebnf()),
+        "tournament" => run_tournament(&args[2..]),
+        "simulate" => run_simulation(&args[2..]),
+        "serve"   => {
+            let tcp = args.iter().position(|arg| arg == "--tcp");
+            if args.iter().any(|arg| arg == "--stdio") {
+                server::serve_stdio()
+            } else if let Some(i) = tcp {
+                match (args.get(i + 1), args.get(i + 2)) {
+                    (Some(address), Some(path)) => session::serve_tcp(address, path),
+                    _ => println!("usage: cardlang serve --tcp <address> <game file>")
+                }
+            } else {
+                println!("serve requires --stdio or --tcp");
+            }
+        },
+        _         => interactive(mode)
     }
 }
 
-fn test(cmd: Option<&String>) {
-    match cmd {
-        Some(path) => {
-            let file_result = fs::read_to_string(path);
+// CARDLANG_LOG=debug logs to stderr from any command, servers log at info without it
+fn install_logger(serving: bool, json: bool) {
+    let level = match env::var("CARDLANG_LOG") {
+        Ok(name) => name.parse::<log::Level>().ok(),
+        Err(_) if serving => Some(log::Level::Info),
+        Err(_) => None
+    };
+    if let Some(level) = level {
+        if log::set_logger(Box::leak(Box::new(StderrLogger{ json }))).is_ok() {
+            log::set_max_level(level.to_level_filter());
+        }
+    }
+}
 
-            if file_result.is_err() {
-                println!("unable to read '{}'", path);
-                return;
+// one line per record, `INFO cardlang::runtime: move player=1 result=accepted span=3`
+struct StderrLogger {
+    json: bool
+}
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let mut fields = Fields::default();
+        let _ = record.key_values().visit(&mut fields);
+        if self.json {
+            let mut object = vec!(
+                ("level", JsonValue::String(record.level().to_string())),
+                ("target", JsonValue::string(record.target())),
+                ("message", JsonValue::String(record.args().to_string()))
+            );
+            object.extend(fields.0.iter().map(|(name, value)| (name.as_str(), JsonValue::string(value))));
+            eprintln!("{}", JsonValue::object(object));
+        } else {
+            let mut line = format!("{} {}: {}", record.level(), record.target(), record.args());
+            for (name, value) in fields.0.iter() {
+                if value.contains(' ') {
+                    line.push_str(&format!(" {}={:?}", name, value));
+                } else {
+                    line.push_str(&format!(" {}={}", name, value));
+                }
             }
+            eprintln!("{}", line);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[derive(Default)]
+struct Fields(Vec<(String, String)>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, name: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+        self.0.push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+// exits with 2 when there's nothing to check
+fn read_checked_source(cmd: Option<&String>) -> (&String, String) {
+    let path = match cmd {
+        Some(path) => path,
+        _ => {
+            println!("no file specified!");
+            process::exit(2);
+        }
+    };
+
+    match read_source(path) {
+        Ok(source) => (path, source),
+        Err(e) => {
+            println!("{}", e);
+            process::exit(2);
+        }
+    }
+}
+
+fn test(cmd: Option<&String>, json: bool) {
+    let (path, source) = read_checked_source(cmd);
+    let diagnostics = diagnostics::diagnose(&source);
+    let success = !diagnostics::has_errors(&diagnostics);
+    if json {
+        println!("{}", diagnostics::report(path, &diagnostics));
+    } else {
+        for diagnostic in diagnostics.iter() {
+            println!("{}: {}", path, diagnostic);
+        }
+        println!("{}", if success { "success!" } else { "failed to parse!" });
+    }
+
+    if !success {
+        process::exit(1);
+    }
+}
+
+// like test, but for tidying up - warnings are counted and only errors fail
+fn lint(cmd: Option<&String>, json: bool) {
+    let (path, source) = read_checked_source(cmd);
+    let diagnostics = diagnostics::diagnose(&source);
+    if json {
+        println!("{}", diagnostics::report(path, &diagnostics));
+    } else {
+        for diagnostic in diagnostics.iter() {
+            println!("{}: {}", path, diagnostic);
+        }
+        let warnings = diagnostics.iter().filter(|d| d.severity == diagnostics::Severity::Warning).count();
+        println!("warnings: {}", warnings);
+    }
+
+    if diagnostics::has_errors(&diagnostics) {
+        process::exit(1);
+    }
+}
+
+fn document(cmd: Option<&String>) {
+    let path = match cmd {
+        Some(path) => path,
+        _ => {
+            println!("no file specified!");
+            process::exit(2);
+        }
+    };
+
+    let ast = read_source(path).and_then(|source| parse_source(&source));
+
+    match ast {
+        Ok(ast) => print!("{}", doc::document(&ast)),
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// cardlang simulate <game file> [--games n] [--seed n]
+fn run_simulation(args: &[String]) {
+    let option = |name: &str, default: u64| {
+        args.iter().position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(default)
+    };
+
+    let path = match args.first() {
+        Some(path) if !path.starts_with("--") => path,
+        _ => {
+            println!("usage: cardlang simulate <game file> [--games n] [--seed n]");
+            process::exit(2);
+        }
+    };
+
+    match read_source(path).and_then(|source| load_game(&source)) {
+        Ok(mut game) => println!("{}", simulate::simulate(&mut game, option("--games", 100) as usize, option("--seed", 0))),
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// cardlang tournament <game file> <bot> <bot>.. [--games n] [--seed n] [--ratings file]
+fn run_tournament(args: &[String]) {
+    let option = |name: &str, default: u64| {
+        args.iter().position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(default)
+    };
+    let positional: Vec<&String> = args.iter().enumerate()
+        .filter(|(i, a)| !a.starts_with("--") && (*i == 0 || !args[i - 1].starts_with("--")))
+        .map(|(_, a)| a)
+        .collect();
+
+    if positional.len() < 2 {
+        println!("usage: cardlang tournament <game file> <bot> <bot>.. [--games n] [--seed n] [--ratings file]");
+        process::exit(2);
+    }
+
+    let game = match read_source(positional[0]).and_then(|source| load_game(&source)) {
+        Ok(g) => g,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut tournament = Tournament::new(game, option("--games", 100) as usize, option("--seed", 0));
+    for kind in positional[1..].iter() {
+        if !tournament.add_builtin(kind) {
+            println!("unknown bot '{}'", kind);
+            process::exit(2);
+        }
+    }
 
-            let game = parse_game(file_result.expect("unable to read file"));
-            match game {
-                Some(_g) => println!("success!"),
-                _       => println!("failed to parse!")
+    let ratings_path = args.iter().position(|a| a == "--ratings").and_then(|i| args.get(i + 1));
+    let results = match ratings_path {
+        Some(path) => {
+            let saved = Ratings::load(path).and_then(|mut ratings| {
+                let results = tournament.run_rated(&mut ratings);
+                ratings.save(path).map(|_| results)
+            });
+            match saved {
+                Ok(results) => results,
+                Err(e) => {
+                    println!("{}", e);
+                    process::exit(1);
+                }
             }
         },
-        _ => println!("no file specified!")
-    }
+        None => tournament.run()
+    };
+
+    println!("{}", results);
 }
 
-fn interactive() {
-    println!("Cardlang interpreter");
-    let mut game: Option<Game> = None;
+fn interactive(mode: OutputMode) {
+    let mut repl = Repl { game: None, name: None, shelved: vec!(), mode, seat: None, bots: vec!(), watched: None, profiles: None };
+    let mut output = match mode {
+        OutputMode::Json => String::new(),
+        _ => "Cardlang interpreter".to_string()
+    };
+
     loop {
-        print!("> ");
-        let _ = stdout().flush();
+        match mode {
+            OutputMode::Tui => print!("{}", tui::render_screen(repl.game.as_ref(), repl.seat, &output)),
+            _ if !output.is_empty() => println!("{}", output),
+            _ => ()
+        }
+
+        if mode != OutputMode::Json {
+            print!("> ");
+            let _ = stdout().flush();
+        }
 
         let mut input = String::new();
-        stdin().read_line(&mut input).unwrap();
+        if stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            break;
+        }
+
+        // the timer can only be checked between commands, as reading one blocks
+        let timed_out = repl.game.as_mut().and_then(|g| g.check_turn_timer()).unwrap_or_default();
+        let reloaded = repl.reload_watched().unwrap_or_default();
 
         let command = input.trim().split(' ').collect();
         let command_result = translate_command(command);
 
         // handle global commands
-        match command_result {
-            CommandResult::Game(ref g) => game = Some(g.clone()),
+        output = match command_result {
+            CommandResult::Game(name, g, warnings) => {
+                repl.set_game(&name, g);
+                let mut lines = vec!(format!("Game loaded as {}", name));
+                lines.extend(warnings);
+                lines.join("\n")
+            },
+            CommandResult::CommandFailed(message) => message,
+            CommandResult::Watch(path) => {
+                repl.watched = Some(Watched { path, modified: None, parser: IncrementalParser::default() });
+                repl.reload_watched().unwrap_or_default()
+            },
+            CommandResult::Use(name) => repl.use_game(&name),
+            CommandResult::Games => repl.list_games(),
+            CommandResult::Profiles(path) => repl.open_profiles(path),
+            CommandResult::Name(seat, name) => match repl.profiles.as_mut() {
+                Some(store) => {
+                    store.name_seat(seat, &name);
+                    format!("player {} is {}", seat, name)
+                },
+                None => "open a profile store first, with profiles <file>".to_string()
+            },
             CommandResult::Exit => break,
-            _ => ()
-        }
-
-        handle_game_command(command_result, &mut game);
+            command_result => repl.handle_game_command(command_result)
+        };
+        let recorded = repl.record_profiles();
+        output = join_output(vec!(timed_out, reloaded, output, recorded));
     }
 }
 
+fn expected(message: &str) -> CommandResult {
+    CommandResult::CommandFailed(format!("expected {}!", message))
+}
+
 fn translate_command(command: Vec<&str>) -> CommandResult {
     match command[0] {
         "exit" => CommandResult::Exit,
         "build" => build_game(command),
+        "use" => {
+            match command.get(1) {
+                Some(name) => CommandResult::Use(name.to_string()),
+                None => expected("the name of a game")
+            }
+        },
+        "games" => CommandResult::Games,
+        "watch" => {
+            match command.get(1) {
+                Some(path) => CommandResult::Watch(path.to_string()),
+                None => CommandResult::CommandFailed("no source file specified in watch".to_string())
+            }
+        },
         "show" => {
             let display_list = &command[1..];
             CommandResult::Show(display_list.join(" "))
         },
         "start" => CommandResult::Start,
+        "history" => CommandResult::History,
+        "undo" => CommandResult::Undo(command.get(1).and_then(|n| n.parse().ok()).unwrap_or(1)),
+        "auto" => CommandResult::Auto(command.get(1).and_then(|n| n.parse().ok())),
+        "hint" => CommandResult::Hint(command.get(1).and_then(|n| n.parse().ok()).unwrap_or(HINT_PLAYOUTS)),
+        "break" => {
+            match command.get(1) {
+                Some(name) => CommandResult::Break(name.to_string()),
+                None => expected("a definition name")
+            }
+        },
+        "step" | "forward" => CommandResult::Step,
+        "back" => CommandResult::Back,
+        "continue" => CommandResult::Continue,
+        "locals" => CommandResult::Locals,
+        "stack" => CommandResult::Stack,
+        "trace" => {
+            match command.get(1) {
+                Some(&"on") => CommandResult::Trace(true),
+                Some(&"off") => CommandResult::Trace(false),
+                _ => expected("on or off")
+            }
+        },
         "move" => {
             if command.len() < 2 {
-                println!("expected argument!");
-                CommandResult::CommandFailed
+                expected("argument")
             } else {
                 CommandResult::Move(command[1].parse().unwrap_or(1))
             }
         },
+        "seat" => {
+            match command.get(1) {
+                Some(&"off") => CommandResult::Seat(None),
+                Some(n) => match n.parse::<usize>() {
+                    Ok(seat) => CommandResult::Seat(Some(seat)),
+                    _ => expected("a player number or off")
+                },
+                None => expected("a player number or off")
+            }
+        },
+        "bot" => {
+            match (command.get(1).map(|n| n.parse::<usize>()), command.get(2)) {
+                (Some(Ok(seat)), Some(&"off")) => CommandResult::Bot(seat, None),
+                (Some(Ok(seat)), Some(kind)) => CommandResult::Bot(seat, Some(kind.to_string())),
+                _ => expected("a player number and a bot, or off")
+            }
+        },
+        "timer" => {
+            match command.get(1).map(|s| (*s, s.parse::<u64>())) {
+                Some(("off", _)) => CommandResult::Timer(None),
+                Some((_, Ok(seconds))) => CommandResult::Timer(Some(Duration::from_secs(seconds))),
+                _ => expected("a number of seconds or off")
+            }
+        },
+        "save" => {
+            match command.get(1) {
+                Some(path) => CommandResult::Save(path.to_string()),
+                None => expected("a file to save to")
+            }
+        },
+        "load" => {
+            match command.get(1) {
+                Some(path) => CommandResult::Load(path.to_string()),
+                None => expected("a saved game")
+            }
+        },
+        "export" => CommandResult::Export,
+        "profiles" => CommandResult::Profiles(command.get(1).map(|path| path.to_string())),
+        "name" => {
+            match (command.get(1).map(|n| n.parse::<usize>()), command.get(2)) {
+                (Some(Ok(seat)), Some(name)) => CommandResult::Name(seat, name.to_string()),
+                _ => expected("a player number and a name")
+            }
+        },
+        "format" => {
+            match command.get(1) {
+                Some(name) => CommandResult::Format(name.to_string()),
+                None => expected(&format!("a format, one of {}", format::FORMATTERS.join(", ")))
+            }
+        },
+        // the notation itself, or a file it was saved in
+        "replay" => {
+            match command.get(1) {
+                Some(_) => CommandResult::Replay(command[1..].join(" ")),
+                None => expected("a replay or a file holding one")
+            }
+        },
+        "seed" => {
+            match command.get(1).map(|s| s.parse::<u64>()) {
+                Some(Ok(seed)) => CommandResult::Seed(seed),
+                _ => expected("a numeric seed")
+            }
+        },
         _ => unrecognised_command()
     }
 }
 
-fn handle_game_command(command: CommandResult, game: &mut Option<Game>) {
-    if let Some(ref mut g) = game { 
-        match command {
-            CommandResult::Show(c) => println!("{}", g.show(&c)),
-            CommandResult::Start => g.start(),
-            CommandResult::Move(n) => g.player_move(n),
-            _ => ()
+impl Repl {
+    // a game built under another name is kept, so `use` can switch back to it
+    fn set_game(&mut self, name: &str, game: Game) {
+        self.shelved.retain(|(n, _)| n != name);
+        if let (Some(previous), Some(active)) = (self.name.take(), self.game.take()) {
+            if previous != name {
+                self.shelved.push((previous, active));
+            }
         }
+        self.name = Some(name.to_string());
+        self.game = Some(game);
     }
-}
 
-fn build_game(command: Vec<&str>) -> CommandResult {
-    if command.len() < 2 {
-        println!("no source file specified in build");
-        return CommandResult::CommandFailed;
+    fn use_game(&mut self, name: &str) -> String {
+        if self.name.as_deref() == Some(name) {
+            return format!("already using {}", name);
+        }
+        match self.shelved.iter().position(|(n, _)| n == name) {
+            Some(i) => {
+                let (name, game) = self.shelved.remove(i);
+                self.set_game(&name, game);
+                format!("using {}", name)
+            },
+            None => format!("no game called '{}', try games", name)
+        }
+    }
+
+    fn list_games(&self) -> String {
+        let mut names: Vec<String> = self.name.iter().map(|n| format!("{} (in use)", n)).collect();
+        names.extend(self.shelved.iter().map(|(n, _)| n.to_string()));
+        if names.is_empty() {
+            "no games built".to_string()
+        } else {
+            names.join("\n")
+        }
+    }
+
+    // `profiles <file>` opens a store, and `profiles` alone lists who is in it
+    fn open_profiles(&mut self, path: Option<String>) -> String {
+        match (path, self.profiles.as_ref()) {
+            (Some(path), _) => match ProfileStore::open(&path) {
+                Ok(store) => {
+                    let listed = store.profiles().to_string();
+                    self.profiles = Some(store);
+                    join_output(vec!(format!("profiles from {}", path), listed))
+                },
+                Err(e) => e.to_string()
+            },
+            (None, Some(store)) => store.profiles().to_string(),
+            (None, None) => "no profile store open".to_string()
+        }
+    }
+
+    fn record_profiles(&mut self) -> String {
+        match (self.profiles.as_mut(), self.game.as_ref()) {
+            (Some(store), Some(game)) => match store.update(game) {
+                Ok(recorded) => recorded.unwrap_or_default(),
+                Err(e) => e.to_string()
+            },
+            _ => String::new()
+        }
+    }
+
+    // only the statements that changed since the last load are parsed again
+    fn reload_watched(&mut self) -> Option<String> {
+        let watched = self.watched.as_mut()?;
+        let modified = fs::metadata(&watched.path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified == watched.modified {
+            return None;
+        }
+        watched.modified = modified;
+
+        let source = match read_source(&watched.path) {
+            Ok(source) => source,
+            Err(e) => return Some(e.to_string())
+        };
+        match watched.parser.parse(&source) {
+            Ok(ast) => {
+                let mut game = Game::new(ast);
+                game.set_description(doc::description(&source));
+                let mut lines = vec!(format!(
+                    "Game loaded from {} ({} of {} sections parsed again)",
+                    watched.path, watched.parser.reparsed(), watched.parser.chunks()
+                ));
+                lines.extend(diagnostics::diagnose(&source).iter().map(|d| d.to_string()));
+                let name = game_name(&watched.path);
+                self.set_game(&name, game);
+                Some(lines.join("\n"))
+            },
+            Err(e) => Some(e.to_string())
+        }
     }
 
-    let file_result = fs::read_to_string(command[1]);
+    fn handle_game_command(&mut self, command: CommandResult) -> String {
+        let mode = self.mode;
+        let g = match self.game {
+            Some(ref mut g) => g,
+            None => return "no game built".to_string()
+        };
 
-    if file_result.is_err() {
-        println!("unable to read '{}'", command[1]);
-        return CommandResult::CommandFailed;
+        match command {
+            CommandResult::Show(c) if mode == OutputMode::Json => g.show_json(&c).to_string(),
+            CommandResult::Show(c) => g.show_for_seat(&c, self.seat),
+            CommandResult::Seat(seat) => {
+                self.seat = seat;
+                match seat {
+                    Some(n) => format!("seated as player {}", n),
+                    None => "seat cleared".to_string()
+                }
+            },
+            CommandResult::Move(n) if self.seat.is_some() && self.seat != Some(n) => {
+                format!("seated as player {} - can't move for player {}", self.seat.unwrap_or(0), n)
+            },
+            CommandResult::Bot(seat, None) => {
+                self.bots.retain(|(s, _)| *s != seat);
+                format!("player {} is no longer a bot", seat)
+            },
+            CommandResult::Bot(seat, Some(kind)) => {
+                match bot::create(&kind, None) {
+                    Some(b) => {
+                        self.bots.retain(|(s, _)| *s != seat);
+                        self.bots.push((seat, b));
+                        let played = bot::play_bots(g, &mut self.bots);
+                        join_output(vec!(format!("player {} is a {} bot", seat, kind), played.join("\n")))
+                    },
+                    None => format!("unknown bot '{}'", kind)
+                }
+            },
+            CommandResult::Start => {
+                g.start();
+                let played = bot::play_bots(g, &mut self.bots);
+                join_output(vec!(runtime_output(g), played.join("\n")))
+            },
+            CommandResult::Move(n) => {
+                let result = g.try_move(n);
+                let mut output = vec!(runtime_output(g));
+                match result {
+                    _ if mode == OutputMode::Json => output.push(g.move_json(n, result.is_ok()).to_string()),
+                    Err(e) => {
+                        output.push(format!("move rejected: {}", e));
+                        let reason = e.to_string();
+                        output.extend(g.violations().iter().map(|v| v.to_string()).filter(|v| *v != reason).map(|v| format!("also: {}", v)));
+                    },
+                    Ok(()) => ()
+                }
+                output.push(bot::play_bots(g, &mut self.bots).join("\n"));
+                join_output(output)
+            },
+            CommandResult::Seed(n) => {
+                g.set_seed(n);
+                String::new()
+            },
+            CommandResult::History => g.show_history(),
+            CommandResult::Undo(n) => g.undo(n),
+            CommandResult::Auto(n) => {
+                let summary = g.auto(n);
+                join_output(vec!(runtime_output(g), summary))
+            },
+            CommandResult::Hint(playouts) => match g.suggest_move(playouts) {
+                Some(s) => format!(
+                    "try move {} (won {:.0}% of {} playouts)", s.player_move, s.win_rate * 100.0, s.playouts
+                ),
+                None => "no legal moves".to_string()
+            },
+            CommandResult::Break(name) => {
+                g.add_breakpoint(&name);
+                String::new()
+            },
+            CommandResult::Step => g.debugger().step(),
+            CommandResult::Back => g.debugger().back(),
+            CommandResult::Continue => g.debugger().resume(),
+            CommandResult::Locals => g.debugger().locals(),
+            CommandResult::Stack => g.debugger().stack(),
+            CommandResult::Trace(on) => {
+                g.set_tracing(on);
+                String::new()
+            },
+            CommandResult::Save(path) => match g.save(&path) {
+                Ok(()) => format!("saved to {}", path),
+                Err(e) => e.to_string()
+            },
+            CommandResult::Load(path) => match g.load_save(&path) {
+                Ok(()) => format!("loaded {}", path),
+                Err(e) => e.to_string()
+            },
+            CommandResult::Format(name) => match format::by_name(&name) {
+                Some(formatter) => {
+                    g.set_formatter(formatter);
+                    format!("showing cards as {}", name)
+                },
+                None => format!("unknown format '{}', try one of {}", name, format::FORMATTERS.join(", "))
+            },
+            CommandResult::Export => g.export_replay().unwrap_or_else(|| "only started games can be exported".to_string()),
+            CommandResult::Replay(replay) => {
+                let notation = if replay.starts_with(REPLAY_PREFIX) {
+                    Ok(replay)
+                } else {
+                    read_source(&replay)
+                };
+                match notation.and_then(|n| g.import_replay(&n)) {
+                    Ok(()) => join_output(vec!(runtime_output(g), g.show("game"))),
+                    Err(e) => e.to_string()
+                }
+            },
+            CommandResult::Timer(limit) => {
+                g.set_turn_limit(limit);
+                match limit {
+                    Some(l) => format!("turns are limited to {} seconds", l.as_secs()),
+                    None => "turn timer off".to_string()
+                }
+            },
+            _ => String::new()
+        }
     }
+}
 
-    let game = parse_game(file_result.expect("unable to read file"));
+fn join_output(output: Vec<String>) -> String {
+    output.into_iter().filter(|o| !o.is_empty()).collect::<Vec<String>>().join("\n")
+}
+
+fn runtime_output(game: &mut Game) -> String {
+    let mut output = game.take_trace();
 
-    match game {
-        Some(g) => CommandResult::Game(g),
-        None => CommandResult::CommandFailed 
+    if game.debugger().is_paused() {
+        output.push(game.debugger().current());
     }
+
+    join_output(output)
 }
 
-fn parse_game(source: String) -> Option<Game> {
-    let lex_result = lex::lexer(&source);
-    if lex_result.is_err() {
-        println!("parse error: {:?}", lex_result.unwrap_err());
-        return None;
+fn build_game(command: Vec<&str>) -> CommandResult {
+    if command.len() < 2 {
+        return CommandResult::CommandFailed("no source file specified in build".to_string());
     }
 
-    let tokens = lex_result.expect("unable to unwrap tokens");
+    let source = match read_source(command[1]) {
+        Ok(source) => source,
+        Err(e) => return CommandResult::CommandFailed(e.to_string())
+    };
 
-    let parse_result = parse::parse(&tokens);
+    // `build war.card as war` names the game to switch back to it, by default it's named after the file
+    let name = match command.iter().position(|arg| *arg == "as") {
+        Some(i) => match command.get(i + 1) {
+            Some(name) => name.to_string(),
+            None => return expected("a name after as")
+        },
+        None => game_name(command[1])
+    };
 
-    if parse_result.is_err() {
-        println!("parse error: {:?}", parse_result.unwrap_err());
-        // maybe this should be an additional debug flag?
-        //println!("{:?}", &tokens);
-        return None;
+    // `build rummy.card --opt stock_size=21` changes an option the game declares
+    let mut overrides = vec!();
+    for (i, arg) in command.iter().enumerate().skip(2) {
+        if *arg != "--opt" {
+            continue;
+        }
+        match command.get(i + 1).and_then(|setting| options::parse_override(setting)) {
+            Some(setting) => overrides.push(setting),
+            None => return expected("an option as name=value after --opt")
+        }
     }
 
-    let ast = parse_result.expect("unable to unwrap ast!");
-    let game = Game::new(ast);
-    println!("Game loaded");
-    Some(game)
+    // unchanged files are read from the cache beside them rather than parsed again
+    match cache::parse_cached(command[1], &source).and_then(|ast| options::override_options(ast, &overrides)) {
+        Ok(ast) => {
+            let warnings = diagnostics::check(&ast).iter().map(|d| d.to_string()).collect();
+            let mut game = Game::new(ast);
+            game.set_description(doc::description(&source));
+            CommandResult::Game(name, game, warnings)
+        },
+        Err(e) => CommandResult::CommandFailed(e.to_string())
+    }
+}
+
+// `games/war.card` and `example:war` are both war
+fn game_name(path: &str) -> String {
+    let file = path.rsplit(['/', ':']).next().unwrap_or(path);
+    file.strip_suffix(".card").unwrap_or(file).to_string()
 }
 
 fn unrecognised_command() -> CommandResult {
-    println!("unrecognised command");
-    CommandResult::CommandFailed
-}
\ No newline at end of file
+    CommandResult::CommandFailed("unrecognised command".to_string())
+}
@@ -0,0 +1,171 @@
+use crate::ast::*;
+use crate::error::CardlangError;
+
+// `options { stock_size 31 default }` gives the rules a name for a value, so one file can hold house rules
+// as `build rummy.card --opt stock_size=21` rather than as copies of it
+pub fn options(ast: &[Statement]) -> Vec<&GameOption> {
+    ast.iter().filter_map(|statement| match statement {
+        Statement::GameOption(o) => Some(o),
+        _ => None
+    }).collect()
+}
+
+// `stock_size=21` from the command line, read as the option would be written in the game
+pub fn parse_override(setting: &str) -> Option<(String, Expression)> {
+    let (name, value) = setting.split_once('=')?;
+    let value = match value {
+        "true" => Expression::Bool(true),
+        "false" => Expression::Bool(false),
+        value => match value.parse() {
+            Ok(n) => Expression::Number(n),
+            Err(_) if !value.is_empty() => Expression::Symbol(value.to_string()),
+            Err(_) => return None
+        }
+    };
+    Some((name.to_string(), value))
+}
+
+// only options the game declares can be changed, so a misspelt name isn't quietly ignored
+pub fn override_options(mut ast: Vec<Statement>, overrides: &[(String, Expression)]) -> Result<Vec<Statement>, CardlangError> {
+    for (name, value) in overrides.iter() {
+        let option = ast.iter_mut().find_map(|statement| match statement {
+            Statement::GameOption(o) if o.name == *name => Some(o),
+            _ => None
+        });
+        match option {
+            Some(o) => o.value = value.clone(),
+            None => return Err(CardlangError::UnknownOption(name.to_string()))
+        }
+    }
+    Ok(ast)
+}
+
+// the rules with every option replaced by its value, leaving alone any definition or loop whose argument shares its name
+pub fn apply(ast: Vec<Statement>) -> Vec<Statement> {
+    let values: Vec<(String, Expression)> = options(&ast).iter().map(|o| (o.name.to_string(), o.value.clone())).collect();
+    if values.is_empty() {
+        return ast;
+    }
+    apply_statements(ast, &values)
+}
+
+fn apply_statements(statements: Vec<Statement>, values: &[(String, Expression)]) -> Vec<Statement> {
+    statements.into_iter().map(|s| apply_statement(s, values)).collect()
+}
+
+fn apply_statement(statement: Statement, values: &[(String, Expression)]) -> Statement {
+    match statement {
+        Statement::Definition(d) => {
+            let values: Vec<(String, Expression)> = values.iter().filter(|(name, _)| !d.arguments.contains(name)).cloned().collect();
+            Statement::Definition(Definition{ body: apply_statements(d.body, &values), ..d })
+        },
+        Statement::FunctionCall(f) => Statement::FunctionCall(apply_call(f, values)),
+        Statement::Transfer(t) => Statement::Transfer(Transfer{ count: t.count.map(|c| apply_expression(c, values)), ..t }),
+        Statement::IfStatement(i) => Statement::IfStatement(IfStatement{
+            expression: apply_expression(i.expression, values),
+            body: apply_statements(i.body, values),
+            ..i
+        }),
+        Statement::WhileStatement(w) => Statement::WhileStatement(WhileStatement{
+            expression: apply_expression(w.expression, values),
+            body: apply_statements(w.body, values),
+            ..w
+        }),
+        Statement::ForeachStatement(f) => {
+            let values: Vec<(String, Expression)> = values.iter().filter(|(name, _)| *name != f.variable).cloned().collect();
+            Statement::ForeachStatement(ForeachStatement{ body: apply_statements(f.body, &values), ..f })
+        },
+        Statement::MatchStatement(m) => Statement::MatchStatement(MatchStatement{
+            expression: apply_expression(m.expression, values),
+            arms: m.arms.into_iter().map(|a| MatchArm{
+                pattern: apply_expression(a.pattern, values),
+                body: apply_statements(a.body, values)
+            }).collect(),
+            otherwise: m.otherwise.map(|o| apply_statements(o, values)),
+            ..m
+        }),
+        Statement::CheckStatement(c) => Statement::CheckStatement(CheckStatement{ expression: apply_expression(c.expression, values), ..c }),
+        Statement::ReturnStatement(r) => Statement::ReturnStatement(ReturnStatement{ expression: apply_expression(r.expression, values), ..r }),
+        statement => statement
+    }
+}
+
+fn apply_call(f: FunctionCall, values: &[(String, Expression)]) -> FunctionCall {
+    FunctionCall{ arguments: f.arguments.into_iter().map(|a| apply_expression(a, values)).collect(), ..f }
+}
+
+fn apply_expression(expression: Expression, values: &[(String, Expression)]) -> Expression {
+    match expression {
+        Expression::Symbol(s) => match values.iter().find(|(name, _)| *name == s) {
+            Some((_, value)) => value.clone(),
+            None => Expression::Symbol(s)
+        },
+        Expression::FunctionCall(f) => Expression::FunctionCall(apply_call(f, values)),
+        Expression::Comparison(c) => Expression::Comparison(Box::new(Comparison{
+            left: apply_expression(c.left, values),
+            right: apply_expression(c.right, values),
+            ..*c
+        })),
+        Expression::And(a) => Expression::And(Box::new(And{
+            left: apply_expression(a.left, values),
+            right: apply_expression(a.right, values),
+            ..*a
+        })),
+        Expression::Or(o) => Expression::Or(Box::new(Or{
+            left: apply_expression(o.left, values),
+            right: apply_expression(o.right, values),
+            ..*o
+        })),
+        Expression::Arithmetic(a) => Expression::Arithmetic(Box::new(Arithmetic{
+            left: apply_expression(a.left, values),
+            right: apply_expression(a.right, values),
+            ..*a
+        })),
+        Expression::Between(b) => Expression::Between(Box::new(Between{
+            value: apply_expression(b.value, values),
+            low: apply_expression(b.low, values),
+            high: apply_expression(b.high, values),
+            ..*b
+        })),
+        expression => expression
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    const SOURCE: &str = "options { stock_size 31 default, jokers false }\ndefine setup(){\ncheck(count(deck) is stock_size)\n}";
+
+    fn checked(ast: Vec<Statement>) -> Option<Expression> {
+        match apply(ast).last() {
+            Some(Statement::Definition(d)) => match &d.body[0] {
+                Statement::CheckStatement(CheckStatement{ expression: Expression::Comparison(c), .. }) => Some(c.right.clone()),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    #[test]
+    fn options_are_read_as_their_values() {
+        let ast = crate::parse_source(SOURCE).unwrap();
+
+        assert_eq!(options(&ast).len(), 2);
+        assert_eq!(checked(ast), Some(Expression::Number(31.0)));
+    }
+
+    #[test]
+    fn options_can_be_changed_when_the_game_is_built() {
+        let ast = crate::parse_source(SOURCE).unwrap();
+        let overrides = vec!(parse_override("stock_size=21").unwrap());
+
+        assert_eq!(checked(override_options(ast.clone(), &overrides).unwrap()), Some(Expression::Number(21.0)));
+        assert_eq!(
+            override_options(ast, &[parse_override("stocksize=21").unwrap()]),
+            Err(CardlangError::UnknownOption("stocksize".to_string()))
+        );
+        assert_eq!(parse_override("jokers=true"), Some(("jokers".to_string(), Expression::Bool(true))));
+        assert_eq!(parse_override("jokers"), None);
+    }
+}
@@ -1,7 +1,9 @@
+use std::{error::Error, fmt};
 use crate::token::{Token, SourceToken};
+use crate::lex::unescape;
 use crate::ast::*;
+use crate::error::Span;
 
-use std::iter::Peekable;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum ParseErrorType{
@@ -14,221 +16,506 @@ pub enum ParseErrorType{
 #[derive(Debug, PartialEq, Clone)]
 pub struct ParseError {
     pub error_type: ParseErrorType,
-    pub line_number: u32
+    pub line_number: u32,
+    pub column: u32
 }
 
 impl ParseError {
     pub fn new(error_type: ParseErrorType, line_number: u32) -> ParseError {
         ParseError {
             error_type,
-            line_number
+            line_number,
+            column: 0
+        }
+    }
+
+    pub fn at(error_type: ParseErrorType, token: &SourceToken) -> ParseError {
+        ParseError {
+            error_type,
+            line_number: token.line_number,
+            column: token.column
         }
     }
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error_type {
+            ParseErrorType::ExpectedSymbol => write!(f, "expected a symbol"),
+            ParseErrorType::UnexpectedEndOfStream => write!(f, "unexpected end of file"),
+            ParseErrorType::UnexpectedToken => write!(f, "unexpected token")
+        }
+    }
+}
+
+impl Error for ParseError {}
+
 pub fn parse(tokens: &Vec<SourceToken>) -> Result<Vec<Statement>, ParseError> {
+    let mut ast = vec!();
+    parse_statements(&mut tokens.iter(), &mut ast, None)?;
+    Ok(ast)
+}
+
+// a lone expression, as written between the braces of a string
+pub fn parse_expression(tokens: &[SourceToken]) -> Result<Expression, ParseError> {
+    let mut tokens_iter = tokens.iter();
+    let expression = build_expression(&mut tokens_iter)?;
+    match tokens_iter.next() {
+        Some(t) => Err(ParseError::at(ParseErrorType::UnexpectedToken, t)),
+        None => Ok(expression)
+    }
+}
+
+// carries on past errors, skipping to the next statement, so every error in a file can be reported at once
+pub fn parse_all(tokens: &[SourceToken]) -> (Vec<Statement>, Vec<ParseError>) {
+    let mut errors = vec!();
+    let ast = parse_recovering(tokens, &mut errors);
+    (ast, errors)
+}
+
+fn parse_recovering(tokens: &[SourceToken], errors: &mut Vec<ParseError>) -> Vec<Statement> {
     let mut ast = vec!();
     let mut tokens_iter = tokens.iter();
+    while let Err(e) = parse_statements(&mut tokens_iter, &mut ast, Some(errors)) {
+        errors.push(e);
+        skip_to_recovery_point(&mut tokens_iter);
+    }
+    ast
+}
+
+// the end of the line, or the end of a block opened since the error
+fn skip_to_recovery_point(tokens_iter: &mut std::slice::Iter<SourceToken>) {
+    let mut depth = 0;
+    for source_token in tokens_iter.by_ref() {
+        match source_token.token {
+            Token::OpenBracket => depth += 1,
+            Token::CloseBracket if depth <= 1 => break,
+            Token::CloseBracket => depth -= 1,
+            Token::Newline if depth == 0 => break,
+            _ => ()
+        }
+    }
+}
 
+// errors are only collected when recovering - otherwise the first one ends the parse
+fn parse_statements(
+    tokens_iter: &mut std::slice::Iter<SourceToken>,
+    ast: &mut Vec<Statement>,
+    mut errors: Option<&mut Vec<ParseError>>
+) -> Result<(), ParseError> {
     loop {
+        let before = tokens_iter.as_slice();
+        let statement_count = ast.len();
         let current_token = tokens_iter.next();
         match current_token {
-            Some(SourceToken{ token: Token::Name, .. }) 
-                | Some(SourceToken{ token: Token::Players, ..}) 
-                | Some(SourceToken{ token: Token::CurrentPlayer, ..})
-                | Some(SourceToken{ token: Token::Stack, ..}) => {
-                let unwrapped_token = current_token.expect("unable to unwrap token");
-                let key = get_key(&unwrapped_token.token).expect("unable to find key");
-                let next_token = tokens_iter.next().expect("unable to find next token");
-                let value = get_value(&next_token.token).expect("unable to find expression");
-                let declaration = Declaration{ key, value };
-                let statement = Statement::Declaration(declaration);
+            Some(key_token @ SourceToken{ token: Token::Name | Token::Players | Token::Teams | Token::HandSize | Token::CurrentPlayer | Token::Stack, .. }) => {
+                let statement = create_declaration(key_token, next_token(tokens_iter, key_token)?)?;
                 ast.push(statement);
+
+                // `stack middle, discard, crib` declares each of them
+                if key_token.token == Token::Stack {
+                    while let Some(comma_token @ SourceToken{ token: Token::Comma, .. }) = tokens_iter.as_slice().first() {
+                        tokens_iter.next();
+                        ast.push(create_declaration(key_token, next_token(tokens_iter, comma_token)?)?);
+                    }
+
+                    // `stack discard facedown` - show hides its cards from everyone
+                    if let Some(SourceToken{ token: Token::Symbol("facedown"), .. }) = tokens_iter.as_slice().first() {
+                        tokens_iter.next();
+                        let stacks: Vec<Statement> = ast[statement_count..].iter().filter_map(|s| match s {
+                            Statement::Declaration(d) => Some(Statement::Declaration(Declaration{ key: GlobalKey::FaceDown, ..d.clone() })),
+                            _ => None
+                        }).collect();
+                        ast.extend(stacks);
+                    }
+                }
             },
-            Some(SourceToken{ token: Token::Deck, line_number }) => {
-                let deck_token = current_token.expect("unable to unwrap token");
-                let next_token_result = tokens_iter.next();
-                match next_token_result {
-                    Some(SourceToken{ token: Token::Symbol(_), ..}) => {
-                        let key = get_key(&deck_token.token).expect("unable to find key");
-                        let next_token = next_token_result.expect("unable to find next token");
-                        let value = get_value(&next_token.token).expect("unable to find expression");
-                        let declaration = Declaration{ key, value };
-                        let statement = Statement::Declaration(declaration);
-                        ast.push(statement);
+            Some(deck_token @ SourceToken{ token: Token::Deck, .. }) => {
+                match tokens_iter.next() {
+                    Some(SourceToken{ token: Token::Symbol(end @ ("top" | "bottom")), ..}) if next_is_transfer(tokens_iter) => {
+                        let transfer_token = next_token(tokens_iter, deck_token)?;
+                        ast.push(create_transfer("deck", *end == "bottom", transfer_token, tokens_iter)?);
                     },
-                    Some(SourceToken{ token: Token::Transfer, ..}) => {
-                        let transfer_result = create_transfer("deck", &mut tokens_iter);
-                        if transfer_result.is_err() {
-                            return Err(transfer_result.unwrap_err());
-                        }
-                        ast.push(transfer_result.unwrap())
+                    Some(SourceToken{ token: Token::Symbol(name), ..}) if matches!(tokens_iter.as_slice().first(), Some(SourceToken{ token: Token::OpenBracket, .. })) => {
+                        tokens_iter.next();
+                        ast.push(create_deck_definition(name, deck_token, tokens_iter)?);
+                    },
+                    Some(value_token @ SourceToken{ token: Token::Symbol(_), ..}) => {
+                        ast.push(create_declaration(deck_token, value_token)?);
+                    },
+                    Some(transfer_token @ SourceToken{ token: Token::Transfer, ..}) => {
+                        ast.push(create_transfer("deck", false, transfer_token, tokens_iter)?);
                     },
                     _ => {
-                        return Err(ParseError::new(ParseErrorType::UnexpectedToken, *line_number)); 
+                        return Err(ParseError::at(ParseErrorType::UnexpectedToken, deck_token));
                     }
                 }
             },
-            Some(SourceToken{ token: Token::Define, ..}) => {
-                let next_token = tokens_iter.next().expect("unable to find next token");
-                let name = match &next_token.token {
-                    Token::Symbol(s) => s.to_owned(),
+            Some(define_token @ SourceToken{ token: Token::Define, ..}) => {
+                let name_token = next_token(tokens_iter, define_token)?;
+                let name = match &name_token.token {
+                    Token::Symbol(s) => s.to_string(),
                     _ => {
-                        return Err(
-                            ParseError::new(
-                                ParseErrorType::ExpectedSymbol, next_token.line_number
-                            )
-                        )
+                        return Err(ParseError::at(ParseErrorType::ExpectedSymbol, name_token))
                     }
                 };
 
                 // parens
                 tokens_iter.next();
 
-                let arguments = match build_args_list(&mut tokens_iter){
-                    Ok(args) => args,
-                    Err(e) => return Err(e)
-                };
+                let arguments = build_args_list(tokens_iter)?;
 
-                let body = match build_block(&mut tokens_iter) {
-                    Ok(b) => b,
-                    Err(e) => return Err(e)
-                };
+                let body = build_block(tokens_iter, errors.as_deref_mut())?;
 
-                let definition = Definition{ arguments, name, body };
+                let definition = Definition{ arguments, name, body, span: SourceSpan::default() };
                 let statement = Statement::Definition(definition);
                 ast.push(statement);
             },
-            Some(SourceToken{token: Token::Symbol(name), line_number }) => {
+            Some(symbol_token @ SourceToken{token: Token::Symbol(name), .. }) => {
                 match tokens_iter.next() {
                     Some(SourceToken{ token: Token::OpenParens, ..}) => {
-                        let func_result = create_function(name, &mut tokens_iter);
-                        if func_result.is_err() {
-                            return Err(func_result.unwrap_err());
-                        }
-                        ast.push(func_result.unwrap());
+                        ast.push(create_function(name, tokens_iter)?);
+                    },
+                    Some(transfer_token @ SourceToken{ token: Token::Transfer, ..}) => {
+                        ast.push(create_transfer(name, false, transfer_token, tokens_iter)?);
+                    },
+                    Some(SourceToken{ token: Token::Symbol(end @ ("top" | "bottom")), ..}) if next_is_transfer(tokens_iter) => {
+                        let transfer_token = next_token(tokens_iter, symbol_token)?;
+                        ast.push(create_transfer(name, *end == "bottom", transfer_token, tokens_iter)?);
+                    },
+                    Some(count_token @ SourceToken{ token: Token::Number(..), ..}) if *name == "deal" => {
+                        ast.push(create_deal(count_token, tokens_iter)?);
+                    },
+                    Some(SourceToken{ token: Token::OpenBracket, ..}) if *name == "options" => {
+                        ast.extend(create_options(symbol_token, tokens_iter)?);
+                    },
+                    Some(SourceToken{ token: Token::OpenBracket, ..}) if *name == "values" => {
+                        ast.push(create_value_table(symbol_token, tokens_iter)?);
                     },
-                    Some(SourceToken{ token: Token::Transfer, ..}) => {
-                        let transfer_result = create_transfer(name, &mut tokens_iter);
-                        if transfer_result.is_err() {
-                            return Err(transfer_result.unwrap_err());
+                    Some(SourceToken{ token: Token::Symbol(stack), ..}) if *name == "facedown" => {
+                        let value = Expression::Symbol(stack.to_string());
+                        ast.push(Statement::Declaration(Declaration{ key: GlobalKey::FaceDown, value, span: SourceSpan::default() }));
+                    },
+                    Some(SourceToken{ token: Token::Symbol("simultaneous"), ..}) if *name == "turns" => {
+                        let value = Expression::Symbol("simultaneous".to_string());
+                        let declaration = Declaration{ key: GlobalKey::Turns, value, span: SourceSpan::default() };
+                        ast.push(Statement::Declaration(declaration));
+                    },
+                    Some(SourceToken{ token: Token::Number(n, _), ..}) if get_contextual_key(name).is_some() => {
+                        if let Some(key) = get_contextual_key(name) {
+                            let declaration = Declaration{ key, value: Expression::Number(*n), span: SourceSpan::default() };
+                            ast.push(Statement::Declaration(declaration));
                         }
-                        ast.push(transfer_result.unwrap())
-
                     },
-                    _ => return Err(ParseError::new(ParseErrorType::UnexpectedToken, *line_number))
+                    _ => return Err(ParseError::at(ParseErrorType::UnexpectedToken, symbol_token))
                 }
 
- 
             },
             Some(SourceToken{ token: Token::If, ..}) => {
                 tokens_iter.next(); // assuming open parens?
 
-                let expression = match build_expression(&mut tokens_iter) {
-                    Ok(ex) => ex,
-                    Err(e) => return Err(e)
-                };
+                let expression = build_expression(tokens_iter)?;
 
-                let body = match build_block(&mut tokens_iter) {
-                    Ok(b) => b,
-                    Err(e) => return Err(e)
-                };
+                let body = build_block(tokens_iter, errors.as_deref_mut())?;
 
-                let if_statement = IfStatement{ expression, body };
+                let if_statement = IfStatement{ expression, body, span: SourceSpan::default() };
                 let statement = Statement::IfStatement(if_statement);
                 ast.push(statement);
             },
-            Some(SourceToken{ token: Token::Check, line_number}) => {
+            Some(SourceToken{ token: Token::While, ..}) => {
+                tokens_iter.next();
+                let expression = build_expression(tokens_iter)?;
+                let body = build_block(tokens_iter, errors.as_deref_mut())?;
+                ast.push(Statement::WhileStatement(WhileStatement{ expression, body, span: SourceSpan::default() }));
+            },
+            Some(foreach_token @ SourceToken{ token: Token::Foreach, ..}) => {
+                let variable = match next_token(tokens_iter, foreach_token)? {
+                    SourceToken{ token: Token::Symbol(s), .. } => s.to_string(),
+                    t => return Err(ParseError::at(ParseErrorType::ExpectedSymbol, t))
+                };
+                match next_token(tokens_iter, foreach_token)? {
+                    SourceToken{ token: Token::Symbol("in"), .. } => (),
+                    t => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+                }
+                let stack = match next_token(tokens_iter, foreach_token)? {
+                    SourceToken{ token: token @ (Token::Symbol(_) | Token::Deck), .. } => get_transfer_value(token),
+                    t => return Err(ParseError::at(ParseErrorType::ExpectedSymbol, t))
+                };
+                let body = build_block(tokens_iter, errors.as_deref_mut())?;
+                ast.push(Statement::ForeachStatement(ForeachStatement{ variable, stack, body, span: SourceSpan::default() }));
+            },
+            // `match to 500`, rather than a match statement on something called `to`
+            Some(SourceToken{ token: Token::Match, ..}) if is_match_target(tokens_iter.as_slice()) => {
+                tokens_iter.next();
+                if let Some(SourceToken{ token: Token::Number(n, _), .. }) = tokens_iter.next() {
+                    let declaration = Declaration{ key: GlobalKey::MatchTo, value: Expression::Number(*n), span: SourceSpan::default() };
+                    ast.push(Statement::Declaration(declaration));
+                }
+            },
+            Some(match_token @ SourceToken{ token: Token::Match, ..}) => {
+                let expression = build_match_subject(tokens_iter, match_token)?;
+                let (arms, otherwise) = build_match_arms(tokens_iter, match_token, errors.as_deref_mut())?;
+
+                let match_statement = MatchStatement{ expression, arms, otherwise, span: SourceSpan::default() };
+                let statement = Statement::MatchStatement(match_statement);
+                ast.push(statement);
+            },
+            Some(check_token @ SourceToken{ token: Token::Check, ..}) => {
                 match tokens_iter.next() {
                     Some(SourceToken{ token: Token::OpenParens, ..}) => (),
-                    _ => return Err(ParseError{
-                        error_type: ParseErrorType::UnexpectedToken,
-                        line_number: *line_number
-                    })
+                    _ => return Err(ParseError::at(ParseErrorType::UnexpectedToken, check_token))
                 }
 
-                let expression = match build_expression(&mut tokens_iter) {
-                    Ok(ex) => ex,
-                    Err(e) => return Err(e)
-                };
+                let expression = build_expression(tokens_iter)?;
 
-                let check_statement = CheckStatement{ expression };
+                let check_statement = CheckStatement{ expression, span: SourceSpan::default() };
                 let statement = Statement::CheckStatement(check_statement);
                 ast.push(statement);
             },
-            Some(SourceToken{ token: Token::Return, line_number}) => {
+            Some(return_token @ SourceToken{ token: Token::Return, ..}) => {
                 match tokens_iter.next() {
                     Some(SourceToken{ token: Token::OpenParens, ..}) => (),
-                    _ => return Err(ParseError{
-                        error_type: ParseErrorType::UnexpectedToken,
-                        line_number: *line_number
-                    })
+                    _ => return Err(ParseError::at(ParseErrorType::UnexpectedToken, return_token))
                 }
 
-                let expression = match build_expression(&mut tokens_iter) {
-                    Ok(ex) => ex,
-                    Err(e) => return Err(e)
-                };
+                let expression = build_expression(tokens_iter)?;
 
-                let check_statement = ReturnStatement{ expression };
+                let check_statement = ReturnStatement{ expression, span: SourceSpan::default() };
                 let statement = Statement::ReturnStatement(check_statement);
                 ast.push(statement);
             },
             None => { break; },
             _ => (),
         }
+
+        if ast.len() > statement_count {
+            let span = span_of(before, tokens_iter.as_slice().len());
+            for statement in ast[statement_count..].iter_mut() {
+                set_span(statement, span);
+            }
+        }
     }
 
-    Ok(ast)
+    Ok(())
+}
+
+// covers the tokens consumed since `before`, leaving out trailing newlines
+// and a close parens that ends an enclosing construct rather than the node itself
+fn span_of(before: &[SourceToken], remaining: usize) -> SourceSpan {
+    let mut consumed = &before[..before.len() - remaining];
+    while let Some((SourceToken{ token: Token::Newline, .. }, rest)) = consumed.split_last() {
+        consumed = rest;
+    }
+
+    let opened = consumed.iter().filter(|t| t.token == Token::OpenParens).count();
+    let closed = consumed.iter().filter(|t| t.token == Token::CloseParens).count();
+    if closed > opened {
+        consumed = &consumed[..consumed.len() - 1];
+    }
+
+    match (consumed.first(), consumed.last()) {
+        (Some(first), Some(last)) => SourceSpan{
+            start: Span{ line: first.line_number, column: first.column },
+            end: Span{ line: last.line_number, column: last.column + last.token.width() }
+        },
+        _ => SourceSpan::default()
+    }
+}
+
+fn set_span(statement: &mut Statement, span: SourceSpan) {
+    match statement {
+        Statement::Declaration(d) => d.span = span,
+        Statement::Definition(d) => d.span = span,
+        Statement::Transfer(t) => t.span = span,
+        Statement::FunctionCall(f) => f.span = span,
+        Statement::IfStatement(i) => i.span = span,
+        Statement::WhileStatement(w) => w.span = span,
+        Statement::ForeachStatement(f) => f.span = span,
+        Statement::MatchStatement(m) => m.span = span,
+        Statement::CheckStatement(c) => c.span = span,
+        Statement::ReturnStatement(r) => r.span = span,
+        Statement::GameOption(o) => o.span = span,
+        Statement::DeckDefinition(d) => d.span = span,
+        Statement::ValueTable(v) => v.span = span
+    }
 }
 
 fn create_function(name: &str, tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Statement, ParseError> {
     let mut arguments = vec!();
 
-    match tokens_iter.next() {
-        Some(SourceToken{ token: Token::Deck, ..}) => {
-            arguments.push(Expression::Symbol("deck".to_string()));
-        },
-        Some(SourceToken{ token: Token::Symbol(s), ..}) => {
-            arguments.push(Expression::Symbol(s.to_string()));
-        },
-        _ => ()
+    let before = tokens_iter.as_slice();
+    let first = match tokens_iter.next() {
+        Some(SourceToken{ token: Token::Deck, ..}) => Some(Expression::Symbol("deck".to_string())),
+        Some(SourceToken{ token: Token::Symbol(s), ..}) => Some(Expression::Symbol(s.to_string())),
+        Some(SourceToken{ token: Token::Number(n, _), ..}) => Some(Expression::Number(*n)),
+        Some(SourceToken{ token: Token::String(s), ..}) => Some(Expression::String(unescape(s))),
+        _ => None
     };
+    if let Some(first) = first {
+        arguments.push(build_sum(tokens_iter, first, before)?);
+    }
 
-    //close parens
-    //tokens_iter.next();
+    if !arguments.is_empty() {
+        while let Some(SourceToken{ token: Token::Comma, .. }) = tokens_iter.as_slice().first() {
+            tokens_iter.next();
+            let before = tokens_iter.as_slice();
+            let value = build_value(tokens_iter)?;
+            let value = build_sum(tokens_iter, value, before)?;
+            // a symbol can take a count, as in `shuffle(deck, riffle 7)`
+            let counted = matches!(value, Expression::Symbol(_));
+            arguments.push(value);
+            if let (true, Some(SourceToken{ token: Token::Number(n, _), .. })) = (counted, tokens_iter.as_slice().first()) {
+                arguments.push(Expression::Number(*n));
+                tokens_iter.next();
+            }
+        }
+        if let Some(SourceToken{ token: Token::CloseParens, .. }) = tokens_iter.as_slice().first() {
+            tokens_iter.next();
+        }
+    }
 
-    let function_call = FunctionCall { name: name.to_string(), arguments };
+    let function_call = FunctionCall { name: name.to_string(), arguments, span: SourceSpan::default() };
     Ok(Statement::FunctionCall(function_call))
 }
 
 
-fn create_transfer(from: &str, tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Statement, ParseError> {
-    let transfer_target = tokens_iter.next().expect("unable to find next token");
-    let from = get_transfer_value(&Token::Symbol(from.to_string()));
+// the token after `previous`, which a statement can't end without
+fn next_token<'a, 'b>(tokens_iter: &mut std::slice::Iter<'a, SourceToken<'b>>, previous: &SourceToken) -> Result<&'a SourceToken<'b>, ParseError> {
+    tokens_iter.next().ok_or_else(|| ParseError::new(ParseErrorType::UnexpectedEndOfStream, previous.line_number))
+}
+
+fn create_declaration(key_token: &SourceToken, value_token: &SourceToken) -> Result<Statement, ParseError> {
+    let key = get_key(&key_token.token).ok_or_else(|| ParseError::at(ParseErrorType::UnexpectedToken, key_token))?;
+    let value = get_value(&value_token.token).ok_or_else(|| ParseError::at(ParseErrorType::UnexpectedToken, value_token))?;
+    let declaration = Declaration{ key, value, span: SourceSpan::default() };
+    Ok(Statement::Declaration(declaration))
+}
+
+fn next_is_transfer(tokens_iter: &std::slice::Iter<SourceToken>) -> bool {
+    matches!(tokens_iter.as_slice().first(), Some(SourceToken{ token: Token::Transfer, .. }))
+}
+
+// `deck bottom > bottom discard 3`, where `top` or `bottom` beside a stack says which end the cards go from or to
+fn create_transfer(
+    from: &str,
+    from_bottom: bool,
+    transfer_token: &SourceToken,
+    tokens_iter: &mut std::slice::Iter<SourceToken>
+) -> Result<Statement, ParseError> {
+    let mut transfer_target = next_token(tokens_iter, transfer_token)?;
+    let mut to_bottom = false;
+    if let Token::Symbol(end @ ("top" | "bottom")) = transfer_target.token {
+        if let Some(target @ SourceToken{ token: Token::Symbol(_) | Token::Deck | Token::Players, .. }) = tokens_iter.as_slice().first() {
+            tokens_iter.next();
+            to_bottom = end == "bottom";
+            transfer_target = target;
+        }
+    }
+    let from = get_transfer_value(&Token::Symbol(from));
     let to = get_transfer_value(&transfer_target.token);
-    let modifier = None;
-    let count = match tokens_iter.next() {
-        Some(SourceToken{ token: Token::Symbol(s), ..}) => {
-            if s == "end" {
-                Some(TransferCount::End)
-            } else {
-                None
-            }
-        },
-        _ => None
+    let modifier = TransferModifier::new(from_bottom, to_bottom);
+    let count = match tokens_iter.as_slice().first() {
+        None | Some(SourceToken{ token: Token::Newline | Token::CloseBracket, .. }) => None,
+        Some(_) => {
+            let before = tokens_iter.as_slice();
+            let value = build_value(tokens_iter)?;
+            Some(build_sum(tokens_iter, value, before)?)
+        }
     };
 
-    let transfer = Transfer{ from, to, modifier, count };
+    let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
     let statement = Statement::Transfer(transfer);
     Ok(statement)
 }
 
 
+// `options { stock_size 31 default, jokers false }`, the `default` is optional and only for reading
+fn create_options(options_token: &SourceToken, tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Vec<Statement>, ParseError> {
+    let mut options = vec!();
+    loop {
+        match next_token(tokens_iter, options_token)? {
+            SourceToken{ token: Token::Newline | Token::Comma, .. } => (),
+            SourceToken{ token: Token::CloseBracket, .. } => break,
+            name_token @ SourceToken{ token: Token::Symbol(name), .. } => {
+                let value = match next_token(tokens_iter, name_token)? {
+                    SourceToken{ token: Token::Number(n, _), .. } => Expression::Number(*n),
+                    SourceToken{ token: Token::True, .. } => Expression::Bool(true),
+                    SourceToken{ token: Token::False, .. } => Expression::Bool(false),
+                    SourceToken{ token: Token::Symbol(s), .. } if *s != "default" => Expression::Symbol(s.to_string()),
+                    SourceToken{ token: Token::String(s), .. } => Expression::String(unescape(s)),
+                    t => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+                };
+                if let Some(SourceToken{ token: Token::Symbol("default"), .. }) = tokens_iter.as_slice().first() {
+                    tokens_iter.next();
+                }
+                options.push(Statement::GameOption(GameOption{ name: name.to_string(), value, span: SourceSpan::default() }));
+            },
+            t => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+        }
+    }
+    Ok(options)
+}
+
+// `deck euchre { ranks nine, ten, jack, queen, king, ace }`, where `suits` lists the suits the same way
+fn create_deck_definition(name: &str, deck_token: &SourceToken, tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Statement, ParseError> {
+    let mut ranks = vec!();
+    let mut suits = vec!();
+    let mut list: Option<&mut Vec<String>> = None;
+    loop {
+        match next_token(tokens_iter, deck_token)? {
+            SourceToken{ token: Token::Newline | Token::Comma, .. } => (),
+            SourceToken{ token: Token::CloseBracket, .. } => break,
+            SourceToken{ token: Token::Symbol("ranks"), .. } => list = Some(&mut ranks),
+            SourceToken{ token: Token::Symbol("suits"), .. } => list = Some(&mut suits),
+            t @ SourceToken{ token: Token::Symbol(_) | Token::Number(..), .. } => match (list.as_mut(), &t.token) {
+                (Some(list), Token::Symbol(s)) => list.push(s.to_string()),
+                (Some(list), Token::Number(n, _)) => list.push(n.to_string()),
+                _ => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+            },
+            t => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+        }
+    }
+    Ok(Statement::DeckDefinition(DeckDefinition{ name: name.to_string(), ranks, suits, span: SourceSpan::default() }))
+}
+
+// `values { ace 11, face 10 }`, each rank by name or number followed by what it's worth
+fn create_value_table(values_token: &SourceToken, tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Statement, ParseError> {
+    let mut values = vec!();
+    loop {
+        let rank_token = next_token(tokens_iter, values_token)?;
+        let rank = match rank_token {
+            SourceToken{ token: Token::Newline | Token::Comma, .. } => continue,
+            SourceToken{ token: Token::CloseBracket, .. } => break,
+            SourceToken{ token: Token::Symbol(s), .. } => s.to_string(),
+            SourceToken{ token: Token::Number(n, _), .. } => n.to_string(),
+            t => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+        };
+        match next_token(tokens_iter, rank_token)? {
+            SourceToken{ token: Token::Number(n, _), .. } => values.push((rank, *n)),
+            t => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+        }
+    }
+    Ok(Statement::ValueTable(ValueTable{ values, span: SourceSpan::default() }))
+}
+
+// `deal 7 each` - deal is only a keyword here, so it can still name a definition
+fn create_deal(count_token: &SourceToken, tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Statement, ParseError> {
+    match next_token(tokens_iter, count_token)? {
+        SourceToken{ token: Token::Symbol("each"), .. } => (),
+        t => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+    }
+
+    let value = get_value(&count_token.token).ok_or_else(|| ParseError::at(ParseErrorType::UnexpectedToken, count_token))?;
+    let declaration = Declaration{ key: GlobalKey::Deal, value, span: SourceSpan::default() };
+    Ok(Statement::Declaration(declaration))
+}
+
 fn get_key(token: &Token) -> Option<GlobalKey> {
     match token {
         Token::Name => Some(GlobalKey::Name),
         Token::Players => Some(GlobalKey::Players),
+        Token::Teams => Some(GlobalKey::Teams),
+        Token::HandSize => Some(GlobalKey::HandSize),
         Token::Deck => Some(GlobalKey::Deck),
         Token::CurrentPlayer => Some(GlobalKey::CurrentPlayer),
         Token::Stack => Some(GlobalKey::Stack),
@@ -236,10 +523,31 @@ fn get_key(token: &Token) -> Option<GlobalKey> {
     }
 }
 
+fn is_match_target(tokens: &[SourceToken]) -> bool {
+    matches!(
+        tokens,
+        [SourceToken{ token: Token::Symbol("to"), .. }, SourceToken{ token: Token::Number(..), .. }]
+            | [SourceToken{ token: Token::Symbol("to"), .. }, SourceToken{ token: Token::Number(..), .. }, SourceToken{ token: Token::Newline, .. }, ..]
+    )
+}
+
+// `chips 100` - like deal, these are only keywords when followed by a number
+pub const CONTEXTUAL_KEYS: [(&str, GlobalKey); 4] = [
+    ("chips", GlobalKey::Chips),
+    ("turn_time", GlobalKey::TurnTime),
+    ("decks", GlobalKey::Decks),
+    ("cardlang", GlobalKey::Language)
+];
+
+fn get_contextual_key(name: &str) -> Option<GlobalKey> {
+    CONTEXTUAL_KEYS.iter().find(|(key, _)| *key == name).map(|(_, key)| key.clone())
+}
+
 fn get_value(token: &Token) -> Option<Expression> {
     match token {
-        Token::Symbol(a) => Some(Expression::Symbol(a.to_owned())),
-        Token::Number(a) => Some(Expression::Number(*a)),
+        Token::Symbol(a) => Some(Expression::Symbol(a.to_string())),
+        Token::String(a) => Some(Expression::String(unescape(a))),
+        Token::Number(a, _) => Some(Expression::Number(*a)),
         _ => None
     }
 }
@@ -248,24 +556,25 @@ fn get_transfer_value(token: &Token) -> String {
     match token {
         Token::Deck => "deck".to_owned(),
         Token::Players => "players".to_owned(),
-        Token::Symbol(s) => s.to_owned(),
+        Token::Symbol(s) => s.to_string(),
         _ => "".to_owned() // todo - handle errors
     }
 }
 
-fn build_block(tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Vec<Statement>, ParseError> {
+fn build_block(
+    tokens_iter: &mut std::slice::Iter<SourceToken>,
+    errors: Option<&mut Vec<ParseError>>
+) -> Result<Vec<Statement>, ParseError> {
     let mut body_tokens = vec!();
     let mut line_number = 0;
     let mut open_bracket_count = 0;
 
     loop {
         match tokens_iter.next() {
-            Some(SourceToken{ token: Token::CloseBracket, line_number }) => {
+            Some(t @ SourceToken{ token: Token::CloseBracket, .. }) => {
                 if open_bracket_count > 1 {
                     open_bracket_count -= 1;
-                    body_tokens.push(
-                        SourceToken{ token: Token::CloseBracket, line_number: *line_number }
-                    );
+                    body_tokens.push(t.clone());
                 } else {
                     break;
                 }
@@ -281,77 +590,261 @@ fn build_block(tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Vec<St
         }
     }
 
-    return parse(&body_tokens)
+    match errors {
+        Some(errors) => Ok(parse_recovering(&body_tokens, errors)),
+        None => parse(&body_tokens)
+    }
+}
+
+// everything up to the bracket that opens the arms, which isn't wrapped in parens like an if
+fn build_match_subject(tokens_iter: &mut std::slice::Iter<SourceToken>, match_token: &SourceToken) -> Result<Expression, ParseError> {
+    let remaining = tokens_iter.as_slice();
+    let length = remaining.iter()
+        .position(|t| t.token == Token::OpenBracket)
+        .ok_or_else(|| ParseError::new(ParseErrorType::UnexpectedEndOfStream, match_token.line_number))?;
+
+    if length == 0 {
+        return Err(ParseError::at(ParseErrorType::ExpectedSymbol, &remaining[0]));
+    }
+
+    let expression = build_expression(&mut remaining[..length].iter())?;
+    tokens_iter.nth(length);
+    Ok(expression)
+}
+
+fn build_match_arms(
+    tokens_iter: &mut std::slice::Iter<SourceToken>,
+    match_token: &SourceToken,
+    mut errors: Option<&mut Vec<ParseError>>
+) -> Result<(Vec<MatchArm>, Option<Vec<Statement>>), ParseError> {
+    let mut arms = vec!();
+    let mut otherwise = None;
+
+    loop {
+        let pattern_token = next_token(tokens_iter, match_token)?;
+        let pattern = match pattern_token.token {
+            Token::Newline => continue,
+            Token::CloseBracket => break,
+            Token::Symbol(s) => Expression::Symbol(s.to_string()),
+            Token::String(s) => Expression::String(unescape(s)),
+            Token::Number(n, _) => Expression::Number(n),
+            _ => return Err(ParseError::at(ParseErrorType::UnexpectedToken, pattern_token))
+        };
+
+        match tokens_iter.as_slice().first() {
+            Some(SourceToken{ token: Token::OpenBracket, .. }) => (),
+            Some(t) => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t)),
+            None => return Err(ParseError::new(ParseErrorType::UnexpectedEndOfStream, pattern_token.line_number))
+        }
+
+        let body = build_block(tokens_iter, errors.as_deref_mut())?;
+        if pattern == Expression::Symbol("other".to_string()) {
+            otherwise = Some(body);
+        } else {
+            arms.push(MatchArm{ pattern, body });
+        }
+    }
+
+    Ok((arms, otherwise))
 }
 
 fn build_expression(tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Expression, ParseError> {
-    let left = match tokens_iter.next() {
+    let before = tokens_iter.as_slice();
+    let left = build_value(tokens_iter)?;
+    combine_expression(tokens_iter, left, before)
+}
+
+// a single value, without anything combined onto it
+fn build_value(tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Expression, ParseError> {
+    let value = match tokens_iter.next() {
         Some(SourceToken{ token: Token::True, ..}) => Expression::Bool(true),
         Some(SourceToken{ token: Token::False, ..}) => Expression::Bool(false),
         Some(SourceToken{ token: Token::Symbol(s), ..}) => Expression::Symbol(s.to_string()),
-        Some(SourceToken{ token: Token::Number(n), ..}) => Expression::Number(*n),
+        Some(SourceToken{ token: Token::String(s), ..}) => Expression::String(unescape(s)),
+        Some(SourceToken{ token: Token::Number(n, _), ..}) => Expression::Number(*n),
         Some(SourceToken{ token: Token::CurrentPlayer, ..}) => Expression::Symbol("current_player".to_string()),
+        Some(SourceToken{ token: Token::Deck, ..}) => Expression::Symbol("deck".to_string()),
+        Some(SourceToken{ token: Token::HandSize, ..}) => Expression::Symbol("hand_size".to_string()),
         None => return Err(ParseError::new(ParseErrorType::UnexpectedEndOfStream, 0)),
-        _ => return Err(ParseError::new(ParseErrorType::UnexpectedToken, 0))
+        Some(t) => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
     };
-    combine_expression(tokens_iter, left)
+    Ok(value)
 }
 
-fn combine_expression(tokens_iter: &mut std::slice::Iter<SourceToken>, left: Expression) -> Result<Expression, ParseError> {
-    match tokens_iter.next() {
-        None | Some(SourceToken{ token: Token::CloseParens, ..}) => Ok(left),
-        Some(SourceToken{ token: Token::Is, ..}) => {
-            let mut peekable = tokens_iter.clone().peekable();
-            let negative = match peekable.peek() {
-                Some(SourceToken{ token: Token::Not, .. }) => {
-                    tokens_iter.next();
-                    true
-                },
-                _ => false
-            };
-            let right = build_expression(tokens_iter).expect("bad right expression for comparison");
-            let comparison = Comparison {
-                left,
-                right,
-                negative
-            };
-            Ok(Expression::Comparison(Box::new(comparison)))
+// `before` starts at the first token of `left`, so spans can reach back to it.
+// `|` joins the loosest, then `&`, then `is`, then `+` and `-`, then `*`, `/` and `%`, so `a is 1 | b is 2 & c` is `(a is 1) | ((b is 2) & c)`
+fn combine_expression<'a>(
+    tokens_iter: &mut std::slice::Iter<'a, SourceToken>,
+    left: Expression,
+    before: &'a [SourceToken]
+) -> Result<Expression, ParseError> {
+    let mut expression = build_and(tokens_iter, left, before)?;
+    while let Some(SourceToken{ token: Token::Pipe, .. }) = tokens_iter.as_slice().first() {
+        tokens_iter.next();
+        let right = build_and_from_start(tokens_iter)?;
+        let or = Or { left: expression, right, span: span_of(before, tokens_iter.as_slice().len()) };
+        expression = Expression::Or(Box::new(or));
+    }
+
+    // a comma ends an argument, and is left for the call it belongs to
+    match tokens_iter.as_slice().first() {
+        None | Some(SourceToken{ token: Token::Comma, .. }) => Ok(expression),
+        Some(SourceToken{ token: Token::CloseParens, .. }) => {
+            tokens_iter.next();
+            Ok(expression)
         },
-        Some(SourceToken{ token: Token::Ampersand, ..}) => {
-            let right = build_expression(tokens_iter).expect("bad right expression");
-            let and = And {
-                left,
-                right
+        Some(t) => Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+    }
+}
+
+fn build_and_from_start(tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Expression, ParseError> {
+    let before = tokens_iter.as_slice();
+    let left = build_value(tokens_iter)?;
+    build_and(tokens_iter, left, before)
+}
+
+fn build_and<'a>(
+    tokens_iter: &mut std::slice::Iter<'a, SourceToken>,
+    left: Expression,
+    before: &'a [SourceToken]
+) -> Result<Expression, ParseError> {
+    let mut expression = build_comparison(tokens_iter, left, before)?;
+    while let Some(SourceToken{ token: Token::Ampersand, .. }) = tokens_iter.as_slice().first() {
+        tokens_iter.next();
+        let right_before = tokens_iter.as_slice();
+        let right_value = build_value(tokens_iter)?;
+        let right = build_comparison(tokens_iter, right_value, right_before)?;
+        let and = And { left: expression, right, span: span_of(before, tokens_iter.as_slice().len()) };
+        expression = Expression::And(Box::new(and));
+    }
+    Ok(expression)
+}
+
+fn build_comparison<'a>(
+    tokens_iter: &mut std::slice::Iter<'a, SourceToken>,
+    left: Expression,
+    before: &'a [SourceToken]
+) -> Result<Expression, ParseError> {
+    let left = build_sum(tokens_iter, left, before)?;
+    if let Some(SourceToken{ token: Token::Is, .. }) = tokens_iter.as_slice().first() {
+        tokens_iter.next();
+        let negative = match tokens_iter.as_slice().first() {
+            Some(SourceToken{ token: Token::Not, .. }) => {
+                tokens_iter.next();
+                true
+            },
+            _ => false
+        };
+        let right_before = tokens_iter.as_slice();
+        let right_value = build_value(tokens_iter)?;
+        let right = build_sum(tokens_iter, right_value, right_before)?;
+        let comparison = Comparison { left, right, negative, span: span_of(before, tokens_iter.as_slice().len()) };
+        return Ok(Expression::Comparison(Box::new(comparison)));
+    }
+    Ok(left)
+}
+
+fn build_sum<'a>(
+    tokens_iter: &mut std::slice::Iter<'a, SourceToken>,
+    left: Expression,
+    before: &'a [SourceToken]
+) -> Result<Expression, ParseError> {
+    let mut expression = build_product(tokens_iter, left, before)?;
+    loop {
+        let operator = match tokens_iter.as_slice().first() {
+            Some(SourceToken{ token: Token::Plus, .. }) => Operator::Add,
+            Some(SourceToken{ token: Token::Minus, .. }) => Operator::Subtract,
+            _ => return Ok(expression)
+        };
+        tokens_iter.next();
+        let right_before = tokens_iter.as_slice();
+        let right_value = build_value(tokens_iter)?;
+        let right = build_product(tokens_iter, right_value, right_before)?;
+        let arithmetic = Arithmetic { left: expression, operator, right, span: span_of(before, tokens_iter.as_slice().len()) };
+        expression = Expression::Arithmetic(Box::new(arithmetic));
+    }
+}
+
+fn build_product<'a>(
+    tokens_iter: &mut std::slice::Iter<'a, SourceToken>,
+    left: Expression,
+    before: &'a [SourceToken]
+) -> Result<Expression, ParseError> {
+    let mut expression = build_operand(tokens_iter, left, before)?;
+    loop {
+        let operator = match tokens_iter.as_slice().first() {
+            Some(SourceToken{ token: Token::Star, .. }) => Operator::Multiply,
+            Some(SourceToken{ token: Token::Slash, .. }) => Operator::Divide,
+            Some(SourceToken{ token: Token::Percent, .. }) => Operator::Modulo,
+            _ => return Ok(expression)
+        };
+        tokens_iter.next();
+        let right_before = tokens_iter.as_slice();
+        let right_value = build_value(tokens_iter)?;
+        let right = build_operand(tokens_iter, right_value, right_before)?;
+        let arithmetic = Arithmetic { left: expression, operator, right, span: span_of(before, tokens_iter.as_slice().len()) };
+        expression = Expression::Arithmetic(Box::new(arithmetic));
+    }
+}
+
+// a value with any calls and `between`s that follow it
+fn build_operand<'a>(
+    tokens_iter: &mut std::slice::Iter<'a, SourceToken>,
+    left: Expression,
+    before: &'a [SourceToken]
+) -> Result<Expression, ParseError> {
+    match tokens_iter.as_slice().first() {
+        Some(between_token @ SourceToken{ token: Token::Between, ..}) => {
+            tokens_iter.next();
+            let low = build_value(tokens_iter)?;
+            match next_token(tokens_iter, between_token)? {
+                SourceToken{ token: Token::Symbol("and"), .. } => (),
+                t => return Err(ParseError::at(ParseErrorType::UnexpectedToken, t))
+            }
+            let high = build_value(tokens_iter)?;
+            let between = Between {
+                value: left,
+                low,
+                high,
+                span: span_of(before, tokens_iter.as_slice().len())
             };
-            Ok(Expression::And(Box::new(and)))
+            build_operand(tokens_iter, Expression::Between(Box::new(between)), before)
         },
         Some(SourceToken{ token: Token::OpenParens, ..}) => {
+            tokens_iter.next();
             match left {
                 Expression::Symbol(s) => {
-                    let arguments = vec!(build_expression(tokens_iter).expect("bad args!"));
+                    let mut arguments = vec!();
+                    match tokens_iter.as_slice().first() {
+                        Some(SourceToken{ token: Token::CloseParens, .. }) => { tokens_iter.next(); },
+                        _ => arguments.push(build_expression(tokens_iter)?)
+                    }
+                    while let Some(SourceToken{ token: Token::Comma, .. }) = tokens_iter.as_slice().first() {
+                        tokens_iter.next();
+                        arguments.push(build_expression(tokens_iter)?);
+                    }
                     let function = FunctionCall{
                         name: s.to_string(),
-                        arguments
+                        arguments,
+                        span: span_of(before, tokens_iter.as_slice().len())
                     };
-                    combine_expression(tokens_iter, Expression::FunctionCall(function))
+                    build_operand(tokens_iter, Expression::FunctionCall(function), before)
                 },
                 _ => Err(ParseError::new(ParseErrorType::UnexpectedToken, 0))
             }
         },
-        _ => Err(ParseError::new(ParseErrorType::UnexpectedToken, 0))
+        _ => Ok(left)
     }
 }
-    
 
 fn build_args_list(tokens_iter: &mut std::slice::Iter<SourceToken>) -> Result<Vec<String>, ParseError> {
     let mut args_list = vec!();
     loop {
         match tokens_iter.next() {
             Some(SourceToken{ token: Token::Symbol(s), ..}) => args_list.push(s.to_string()),
+            Some(SourceToken{ token: Token::Comma, ..}) => (),
             Some(SourceToken{ token: Token::CloseParens, ..}) => break,
-            Some(SourceToken{ line_number, .. }) => {
-                return Err(ParseError::new(ParseErrorType::ExpectedSymbol, *line_number))
-            },
+            Some(t) => return Err(ParseError::at(ParseErrorType::ExpectedSymbol, t)),
             None => return Err(ParseError::new(ParseErrorType::UnexpectedEndOfStream, 0))
         }
     }
@@ -364,19 +857,19 @@ mod test{
     use super::*;
 
     fn get_source_tokens(tokens: Vec<Token>) -> Vec<SourceToken> {
-        tokens.iter().map(|t| SourceToken{ token: t.to_owned(), line_number: 0 }).collect()
+        tokens.iter().map(|t| SourceToken{ token: t.to_owned(), line_number: 0, column: 0 }).collect()
     }
 
     #[test]
     fn it_can_handle_a_single_declaration() {
         let tokens = get_source_tokens(vec!(
             Token::Name,
-            Token::Symbol("turns".to_string())
+            Token::Symbol("turns")
         ));
         let mut expected = vec!();
         let key = GlobalKey::Name;
         let value = Expression::Symbol("turns".to_string());
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
@@ -390,12 +883,12 @@ mod test{
     fn it_can_handle_numerical_declaration(){ 
         let tokens = get_source_tokens(vec!(
             Token::Players,
-            Token::Number(2.0)
+            Token::Number(2.0, 1)
         ));
         let mut expected = vec!();
         let key = GlobalKey::Players;
         let value = Expression::Number(2.0);
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
@@ -409,22 +902,22 @@ mod test{
     fn it_can_handle_newlines(){ 
         let tokens = get_source_tokens(vec!(
             Token::Name,
-            Token::Symbol("turns".to_string()),
+            Token::Symbol("turns"),
             Token::Newline,
             Token::Players,
-            Token::Number(2.0)
+            Token::Number(2.0, 1)
         ));
         let mut expected = vec!();
         let key = GlobalKey::Name;
         let value = Expression::Symbol("turns".to_string());
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
 
         let key = GlobalKey::Players;
         let value = Expression::Number(2.0);
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
@@ -438,50 +931,50 @@ mod test{
     fn it_can_setup_a_simple_game() {
         let tokens = get_source_tokens(vec!(
             Token::Name,
-            Token::Symbol("turns".to_string()),
+            Token::Symbol("turns"),
             Token::Newline,
             Token::Players,
-            Token::Number(2.0),
+            Token::Number(2.0, 1),
             Token::Deck,
-            Token::Symbol("StandardDeck".to_string()),
+            Token::Symbol("StandardDeck"),
             Token::CurrentPlayer,
-            Token::Number(1.0),
+            Token::Number(1.0, 1),
             Token::Stack,
-            Token::Symbol("middle".to_owned())
+            Token::Symbol("middle")
         ));
 
         let mut expected = vec!();
         let key = GlobalKey::Name;
         let value = Expression::Symbol("turns".to_string());
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
 
         let key = GlobalKey::Players;
         let value = Expression::Number(2.0);
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
 
         let key = GlobalKey::Deck;
         let value = Expression::Symbol("StandardDeck".to_string());
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
 
         let key = GlobalKey::CurrentPlayer;
         let value = Expression::Number(1.0);
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
 
         let key = GlobalKey::Stack;
         let value = Expression::Symbol("middle".to_string());
-        let declaration = Declaration{ key, value };
+        let declaration = Declaration{ key, value, span: SourceSpan::default() };
 
         let statement = Statement::Declaration(declaration);
         expected.push(statement);
@@ -494,7 +987,7 @@ mod test{
     fn it_recognises_a_function_definition() {
         let tokens = get_source_tokens(vec!(
             Token::Define,
-            Token::Symbol("setup".to_owned()),
+            Token::Symbol("setup"),
             Token::OpenParens,
             Token::CloseParens,
             Token::OpenBracket,
@@ -503,7 +996,7 @@ mod test{
 
         let name = "setup".to_owned();
         let body = vec!();
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let expected = vec!(statement);
         let result = parse(&tokens);
@@ -515,7 +1008,7 @@ mod test{
     fn it_returns_a_parse_error_when_function_not_defined_correctly() {
         let tokens = get_source_tokens(vec!(
             Token::Define,
-            Token::Number(1.0),
+            Token::Number(1.0, 1),
             Token::OpenParens,
             Token::CloseParens,
             Token::OpenBracket,
@@ -541,7 +1034,7 @@ mod test{
         let to = "players".to_owned();
         let modifier = None;
         let count = None;
-        let transfer = Transfer{ from, to, modifier, count };
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
         let statement = Statement::Transfer(transfer);
         let expected = Ok(vec!(statement));
         let result = parse(&tokens);
@@ -553,7 +1046,7 @@ mod test{
     fn it_can_handle_function_body() {
         let tokens = get_source_tokens(vec!(
             Token::Define,
-            Token::Symbol("setup".to_owned()),
+            Token::Symbol("setup"),
             Token::OpenParens,
             Token::CloseParens,
             Token::OpenBracket,
@@ -568,12 +1061,12 @@ mod test{
         let to = "players".to_owned();
         let modifier = None;
         let count = None;
-        let transfer = Transfer{ from, to, modifier, count };
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
         let transfer_statement = Statement::Transfer(transfer);
 
         let name = "setup".to_owned();
         let body = vec!(transfer_statement);
-        let definition = Definition{ arguments: vec!(), name, body };
+        let definition = Definition{ arguments: vec!(), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let expected = vec!(statement);
         let result = parse(&tokens);
@@ -585,7 +1078,7 @@ mod test{
     fn it_returns_error_for_incomplete_function_body() {
         let tokens = get_source_tokens(vec!(
             Token::Define,
-            Token::Symbol("setup".to_owned()),
+            Token::Symbol("setup"),
             Token::OpenParens,
             Token::CloseParens,
             Token::OpenBracket
@@ -602,7 +1095,7 @@ mod test{
     fn it_returns_error_for_invalid_function_body() {
         let tokens = get_source_tokens(vec!(
             Token::Define,
-            Token::Symbol("setup".to_owned()),
+            Token::Symbol("setup"),
             Token::OpenParens,
             Token::CloseParens,
             Token::OpenBracket,
@@ -633,13 +1126,14 @@ mod test{
     #[test]
     fn it_can_recognise_function_calls() {
         let tokens = get_source_tokens(vec!(
-            Token::Symbol("shuffle".to_string()), Token::OpenParens,
+            Token::Symbol("shuffle"), Token::OpenParens,
             Token::Deck, Token::CloseParens
         ));
 
         let function_call = FunctionCall{
             name: "shuffle".to_string(),
-            arguments: vec!(Expression::Symbol("deck".to_string()))
+            arguments: vec!(Expression::Symbol("deck".to_string())),
+            span: SourceSpan::default()
         };
         let statement = Statement::FunctionCall(function_call);
         let expected = Ok(vec!(statement));
@@ -652,7 +1146,7 @@ mod test{
     #[test]
     fn it_recognises_player_hand_to_deck_transfer() {
         let tokens = get_source_tokens(vec!(
-            Token::Symbol("player:hand".to_string()),
+            Token::Symbol("player:hand"),
             Token::Transfer,
             Token::Deck
         ));
@@ -661,7 +1155,7 @@ mod test{
         let to = "deck".to_owned();
         let modifier = None;
         let count = None;
-        let transfer = Transfer{ from, to, modifier, count };
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
         let statement = Statement::Transfer(transfer);
         let expected = Ok(vec!(statement));
         
@@ -672,17 +1166,17 @@ mod test{
     #[test]
     fn it_can_pass_a_count_to_transfer() {
         let tokens = get_source_tokens(vec!(
-            Token::Symbol("player:hand".to_string()),
+            Token::Symbol("player:hand"),
             Token::Transfer,
             Token::Deck,
-            Token::Symbol("end".to_string())
+            Token::Symbol("end")
         ));
 
         let from = "player:hand".to_owned();
         let to = "deck".to_owned();
         let modifier = None;
-        let count = Some(TransferCount::End);
-        let transfer = Transfer{ from, to, modifier, count };
+        let count = Some(Expression::Symbol("end".to_string()));
+        let transfer = Transfer{ from, to, modifier, count, span: SourceSpan::default() };
         let statement = Statement::Transfer(transfer);
         let expected = Ok(vec!(statement));
         
@@ -690,17 +1184,90 @@ mod test{
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn it_can_parse_a_while_loop() {
+        let ast = parse_source("define setup(){\nwhile(count(middle) is 0){\ndeck > middle\n}\n}").unwrap();
+
+        let body = match &ast[0] {
+            Statement::Definition(d) => d.body.clone(),
+            _ => vec!()
+        };
+        assert!(matches!(&body[0], Statement::WhileStatement(w) if w.body.len() == 1));
+        assert_eq!(body[0].to_string(), "while(count(middle) is 0){\n    deck > middle\n}");
+    }
+
+    #[test]
+    fn it_can_parse_string_literals() {
+        let ast = parse_source("name \"Crazy Eights\"\ndefine is_ace(card){\nreturn(card:rank is \"Ace\")\n}").unwrap();
+
+        assert_eq!(ast[0], Statement::Declaration(Declaration{ key: GlobalKey::Name, value: Expression::String("Crazy Eights".to_string()), span: SourceSpan::default() }));
+        assert_eq!(ast[0].to_string(), "name \"Crazy Eights\"".to_string());
+        assert!(matches!(&ast[1], Statement::Definition(d) if d.body[0].to_string() == "return(card:rank is \"Ace\")"));
+    }
+
+    #[test]
+    fn string_literals_keep_their_escapes_when_written_back() {
+        let ast = parse_source(r#"name "say \"hi\"\nthen \\ bye""#).unwrap();
+
+        assert_eq!(ast[0], Statement::Declaration(Declaration{ key: GlobalKey::Name, value: Expression::String("say \"hi\"\nthen \\ bye".to_string()), span: SourceSpan::default() }));
+        assert_eq!(ast[0].to_string(), r#"name "say \"hi\"\nthen \\ bye""#.to_string());
+    }
+
+    #[test]
+    fn it_can_parse_a_foreach_loop() {
+        let ast = parse_source("define player_move(player){\nforeach card in player:hand {\ncheck(card:value is 2)\n}\n}").unwrap();
+
+        let body = match &ast[0] {
+            Statement::Definition(d) => d.body.clone(),
+            _ => vec!()
+        };
+        assert!(matches!(&body[0], Statement::ForeachStatement(f) if f.variable == "card" && f.stack == "player:hand" && f.body.len() == 1));
+        assert_eq!(body[0].to_string(), "foreach card in player:hand {\n    check(card:value is 2)\n}");
+        assert!(parse_source("define setup(){\nforeach card player:hand {\n}\n}").is_err());
+    }
+
+    #[test]
+    fn transfers_can_use_the_bottom_of_a_stack() {
+        let ast = parse_source("define setup(){\ndeck bottom > players\ndiscard > bottom deck end\nmiddle top > top discard\n}").unwrap();
+
+        let transfers: Vec<(Option<TransferModifier>, String)> = match &ast[0] {
+            Statement::Definition(d) => d.body.iter().filter_map(|s| match s {
+                Statement::Transfer(t) => Some((t.modifier, t.to.to_string())),
+                _ => None
+            }).collect(),
+            _ => vec!()
+        };
+        assert_eq!(transfers, vec!(
+            (Some(TransferModifier::FromBottom), "players".to_string()),
+            (Some(TransferModifier::ToBottom), "deck".to_string()),
+            (None, "discard".to_string())
+        ));
+        assert!(matches!(parse_source("deck bottom").unwrap()[0], Statement::Declaration(_)));
+    }
+
+    #[test]
+    fn transfer_counts_can_be_expressions() {
+        let ast = parse_source("define setup(){\ndeck > players count(middle) - 1\ndeck > player:hand hand_size\n}").unwrap();
+
+        let body = match &ast[0] {
+            Statement::Definition(d) => d.body.iter().map(|s| s.to_string()).collect::<Vec<String>>(),
+            _ => vec!()
+        };
+        assert_eq!(body, vec!("deck > players count(middle) - 1", "deck > player:hand hand_size"));
+    }
+
     #[test]
     fn it_can_recognise_function_calls_with_no_arguments() {
         let tokens = get_source_tokens(vec!(
-            Token::Symbol("end".to_string()),
+            Token::Symbol("end"),
             Token::OpenParens,
             Token::CloseParens
         ));
 
         let function_call = FunctionCall{
             name: "end".to_string(),
-            arguments: vec!()
+            arguments: vec!(),
+            span: SourceSpan::default()
         };
 
         let statement = Statement::FunctionCall(function_call);
@@ -714,15 +1281,16 @@ mod test{
     #[test]
     fn does_it_recognise_win_player_id() {
         let tokens = get_source_tokens(vec!(
-            Token::Symbol("winner".to_string()),
+            Token::Symbol("winner"),
             Token::OpenParens,
-            Token::Symbol("player:id".to_string()),
+            Token::Symbol("player:id"),
             Token::CloseParens
         ));
 
         let function_call = FunctionCall{
             name: "winner".to_string(),
-            arguments: vec!(Expression::Symbol("player:id".to_string()))
+            arguments: vec!(Expression::Symbol("player:id".to_string())),
+            span: SourceSpan::default()
         };
 
         let statement = Statement::FunctionCall(function_call);
@@ -745,7 +1313,7 @@ mod test{
         ));
         let expression = Expression::Bool(true);
         let body = vec!();
-        let if_statement = IfStatement{ expression, body };
+        let if_statement = IfStatement{ expression, body, span: SourceSpan::default() };
         let statement = Statement::IfStatement(if_statement);
         let expected = vec!(statement);
         let result = parse(&tokens);
@@ -765,7 +1333,7 @@ mod test{
         ));
         let expression = Expression::Bool(false);
         let body = vec!();
-        let if_statement = IfStatement{ expression, body };
+        let if_statement = IfStatement{ expression, body, span: SourceSpan::default() };
         let statement = Statement::IfStatement(if_statement);
         let expected = vec!(statement);
         let result = parse(&tokens);
@@ -778,9 +1346,9 @@ mod test{
         let tokens = get_source_tokens(vec!(
             Token::If,
             Token::OpenParens,
-            Token::Symbol("player:id".to_string()),
+            Token::Symbol("player:id"),
             Token::Is,
-            Token::Number(1.0),
+            Token::Number(1.0, 1),
             Token::CloseParens,
             Token::OpenBracket,
             Token::CloseBracket
@@ -789,11 +1357,12 @@ mod test{
         let comparison = Comparison {
             left: Expression::Symbol("player:id".to_string()),
             right: Expression::Number(1.0),
-            negative: false
+            negative: false,
+            span: SourceSpan::default()
         };
         let expression = Expression::Comparison(Box::new(comparison));
         let body = vec!();
-        let if_statement = IfStatement{ expression, body };
+        let if_statement = IfStatement{ expression, body, span: SourceSpan::default() };
         let statement = Statement::IfStatement(if_statement);
         let expected = vec!(statement);
         let result = parse(&tokens);
@@ -809,7 +1378,7 @@ mod test{
             Token::True,
             Token::CloseParens,
             Token::OpenBracket,
-            Token::Symbol("shuffle".to_string()),
+            Token::Symbol("shuffle"),
             Token::OpenParens,
             Token::Deck,
             Token::CloseParens,
@@ -818,10 +1387,11 @@ mod test{
         let expression = Expression::Bool(true);
         let function_call = FunctionCall{
             name: "shuffle".to_string(),
-            arguments: vec!(Expression::Symbol("deck".to_string()))
+            arguments: vec!(Expression::Symbol("deck".to_string())),
+            span: SourceSpan::default()
         };
         let body = vec!(Statement::FunctionCall(function_call));
-        let if_statement = IfStatement{ expression, body };
+        let if_statement = IfStatement{ expression, body, span: SourceSpan::default() };
         let statement = Statement::IfStatement(if_statement);
         let expected = vec!(statement);
         let result = parse(&tokens);
@@ -835,12 +1405,12 @@ mod test{
         let tokens = get_source_tokens(vec!(
             Token::If,
             Token::OpenParens,
-            Token::Symbol("count".to_string()),
+            Token::Symbol("count"),
             Token::OpenParens,
-            Token::Symbol("player:hand".to_string()),
+            Token::Symbol("player:hand"),
             Token::CloseParens,
             Token::Is,
-            Token::Number(0.0),
+            Token::Number(0.0, 1),
             Token::CloseParens,
             Token::OpenBracket,
             Token::CloseBracket
@@ -850,17 +1420,19 @@ mod test{
             name: "count".to_string(),
             arguments: vec!(
                 Expression::Symbol("player:hand".to_string())
-            )
+            ),
+            span: SourceSpan::default()
         };
 
         let comparison = Comparison {
             left: Expression::FunctionCall(function_call),
             right: Expression::Number(0.0),
-            negative: false
+            negative: false,
+            span: SourceSpan::default()
         };
         let expression = Expression::Comparison(Box::new(comparison));
         let body = vec!();
-        let if_statement = IfStatement{ expression, body };
+        let if_statement = IfStatement{ expression, body, span: SourceSpan::default() };
         let statement = Statement::IfStatement(if_statement);
         let expected = vec!(statement);
         let result = parse(&tokens);
@@ -871,12 +1443,12 @@ mod test{
     #[test]
     fn it_returns_a_line_number_on_errors() {
         let tokens = vec!(
-            SourceToken{ token: Token::Define, line_number: 1 },
-            SourceToken{ token: Token::Number(1.0), line_number: 1 },
-            SourceToken{ token: Token::OpenParens, line_number: 1 },
-            SourceToken{ token: Token::CloseParens, line_number: 1 },
-            SourceToken{ token: Token::OpenBracket, line_number: 1 },
-            SourceToken{ token: Token::CloseBracket, line_number: 1 },
+            SourceToken{ token: Token::Define, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Number(1.0, 1), line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenBracket, line_number: 1, column: 0 },
+            SourceToken{ token: Token::CloseBracket, line_number: 1, column: 0 },
         );
 
         let expected = ParseError::new(ParseErrorType::ExpectedSymbol, 1);
@@ -888,8 +1460,8 @@ mod test{
     #[test]
     fn it_returns_a_line_number_on_more_errors() {
         let tokens = vec!(
-            SourceToken{ token: Token::Deck, line_number: 2 },
-            SourceToken{ token: Token::CloseBracket, line_number: 2 },
+            SourceToken{ token: Token::Deck, line_number: 2, column: 0 },
+            SourceToken{ token: Token::CloseBracket, line_number: 2, column: 0 },
         );
 
         let expected = ParseError::new(ParseErrorType::UnexpectedToken, 2);
@@ -901,8 +1473,8 @@ mod test{
     #[test]
     fn it_returns_a_line_number_on_unexpected_token_after_symbol() {
         let tokens = vec!(
-            SourceToken{ token: Token::Symbol("foo".to_string()), line_number: 3 },
-            SourceToken{ token: Token::Symbol("bar".to_string()), line_number: 3 },
+            SourceToken{ token: Token::Symbol("foo"), line_number: 3, column: 0 },
+            SourceToken{ token: Token::Symbol("bar"), line_number: 3, column: 0 },
         );
 
         let expected = ParseError::new(ParseErrorType::UnexpectedToken, 3);
@@ -914,14 +1486,14 @@ mod test{
     #[test]
     fn it_returns_a_line_number_on_unexpected_end_of_stream() {
         let tokens = vec!(
-            SourceToken{ token: Token::If, line_number: 4 },
-            SourceToken{ token: Token::OpenParens, line_number: 4 },
-            SourceToken{ token: Token::Symbol("player:id".to_string()), line_number: 4 },
-            SourceToken{ token: Token::Is, line_number: 4 },
-            SourceToken{ token: Token::Number(1.0), line_number: 4 },
-            SourceToken{ token: Token::CloseParens, line_number: 4 },
-            SourceToken{ token: Token::Newline, line_number: 4 },
-            SourceToken{ token: Token::OpenBracket, line_number: 5 }
+            SourceToken{ token: Token::If, line_number: 4, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 4, column: 0 },
+            SourceToken{ token: Token::Symbol("player:id"), line_number: 4, column: 0 },
+            SourceToken{ token: Token::Is, line_number: 4, column: 0 },
+            SourceToken{ token: Token::Number(1.0, 1), line_number: 4, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 4, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 4, column: 0 },
+            SourceToken{ token: Token::OpenBracket, line_number: 5, column: 0 }
         );
 
         let expected = ParseError::new(ParseErrorType::UnexpectedEndOfStream, 5);
@@ -940,28 +1512,28 @@ mod test{
         */
 
         let tokens = vec!(
-            SourceToken{ token: Token::If, line_number: 0 },
-            SourceToken{ token: Token::OpenParens, line_number: 0 },
-            SourceToken{ token: Token::Symbol("count".to_string()), line_number: 0 },
-            SourceToken{ token: Token::OpenParens, line_number: 0 },
-            SourceToken{ token: Token::Symbol("player:hand".to_string()), line_number: 0 },
-            SourceToken{ token: Token::CloseParens, line_number: 0 },
-            SourceToken{ token: Token::Is, line_number: 0 },
-            SourceToken{ token: Token::Number(0.0), line_number: 0 },
-            SourceToken{ token: Token::CloseParens, line_number: 0 },
-            SourceToken{ token: Token::OpenBracket, line_number: 0 },
-            SourceToken{ token: Token::Newline, line_number: 0 },
-            SourceToken{ token: Token::Symbol("winner".to_string()), line_number: 1 },
-            SourceToken{ token: Token::OpenParens, line_number: 1 },
-            SourceToken{ token: Token::Symbol("player:id".to_string()), line_number: 1 },
-            SourceToken{ token: Token::CloseParens, line_number: 1 },
-            SourceToken{ token: Token::Newline, line_number: 1 },
-            SourceToken{ token: Token::Symbol("end".to_string()), line_number: 2 },
-            SourceToken{ token: Token::OpenParens, line_number: 2 },
-            SourceToken{ token: Token::CloseParens, line_number: 2 },
-            SourceToken{ token: Token::Newline, line_number: 2 },
-            SourceToken{ token: Token::CloseBracket, line_number: 3 },
-            SourceToken{ token: Token::Newline, line_number: 3 },
+            SourceToken{ token: Token::If, line_number: 0, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 0, column: 0 },
+            SourceToken{ token: Token::Symbol("count"), line_number: 0, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 0, column: 0 },
+            SourceToken{ token: Token::Symbol("player:hand"), line_number: 0, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 0, column: 0 },
+            SourceToken{ token: Token::Is, line_number: 0, column: 0 },
+            SourceToken{ token: Token::Number(0.0, 1), line_number: 0, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 0, column: 0 },
+            SourceToken{ token: Token::OpenBracket, line_number: 0, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 0, column: 0 },
+            SourceToken{ token: Token::Symbol("winner"), line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Symbol("player:id"), line_number: 1, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Symbol("end"), line_number: 2, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 2, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 2, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 2, column: 0 },
+            SourceToken{ token: Token::CloseBracket, line_number: 3, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 3, column: 0 },
         );
 
         let expected = vec!(
@@ -972,21 +1544,26 @@ mod test{
                             name: "count".to_string(),
                             arguments: vec!(
                                 Expression::Symbol("player:hand".to_string())
-                            )
+                            ),
+                            span: SourceSpan::default()
                         }),
                         right: Expression::Number(0.0),
-                        negative:false
+                        negative:false,
+                        span: SourceSpan::default()
                     })),
                     body: vec!(
                         Statement::FunctionCall(FunctionCall{
                             name: "winner".to_string(),
-                            arguments: vec!(Expression::Symbol("player:id".to_string()))
+                            arguments: vec!(Expression::Symbol("player:id".to_string())),
+                            span: SourceSpan::default()
                         }),
                         Statement::FunctionCall(FunctionCall{
                             name: "end".to_string(),
-                            arguments: vec!()
+                            arguments: vec!(),
+                            span: SourceSpan::default()
                         })
-                    )
+                    ),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -1007,37 +1584,37 @@ mod test{
         */
 
         let tokens = vec!(
-            SourceToken{ token: Token::Define, line_number: 1 },
-            SourceToken{ token: Token::Symbol("player_move".to_string()), line_number: 1 },
-            SourceToken{ token: Token::OpenParens, line_number: 1 },
-            SourceToken{ token: Token::Symbol("player".to_string()), line_number: 1 },
-            SourceToken{ token: Token::CloseParens, line_number: 1 },
-            SourceToken{ token: Token::OpenBracket, line_number: 1 },
-            SourceToken{ token: Token::Newline, line_number: 1 },
-            SourceToken{ token: Token::If, line_number: 2 },
-            SourceToken{ token: Token::OpenParens, line_number: 2 },
-            SourceToken{ token: Token::Symbol("count".to_string()), line_number: 2 },
-            SourceToken{ token: Token::OpenParens, line_number: 2 },
-            SourceToken{ token: Token::Symbol("player:hand".to_string()), line_number: 2 },
-            SourceToken{ token: Token::CloseParens, line_number: 2 },
-            SourceToken{ token: Token::Is, line_number: 2 },
-            SourceToken{ token: Token::Number(0.0), line_number: 2 },
-            SourceToken{ token: Token::CloseParens, line_number: 2 },
-            SourceToken{ token: Token::OpenBracket, line_number: 2 },
-            SourceToken{ token: Token::Newline, line_number: 2 },
-            SourceToken{ token: Token::Symbol("winner".to_string()), line_number: 3 },
-            SourceToken{ token: Token::OpenParens, line_number: 3 },
-            SourceToken{ token: Token::Symbol("player:id".to_string()), line_number: 3 },
-            SourceToken{ token: Token::CloseParens, line_number: 3 },
-            SourceToken{ token: Token::Newline, line_number: 3 },
-            SourceToken{ token: Token::Symbol("end".to_string()), line_number: 4 },
-            SourceToken{ token: Token::OpenParens, line_number: 4 },
-            SourceToken{ token: Token::CloseParens, line_number: 4 },
-            SourceToken{ token: Token::Newline, line_number: 4 },
-            SourceToken{ token: Token::CloseBracket, line_number: 5 },
-            SourceToken{ token: Token::Newline, line_number: 5 },
-            SourceToken{ token: Token::CloseBracket, line_number: 6 },
-            SourceToken{ token: Token::Newline, line_number: 6 },
+            SourceToken{ token: Token::Define, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Symbol("player_move"), line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Symbol("player"), line_number: 1, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenBracket, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 1, column: 0 },
+            SourceToken{ token: Token::If, line_number: 2, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 2, column: 0 },
+            SourceToken{ token: Token::Symbol("count"), line_number: 2, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 2, column: 0 },
+            SourceToken{ token: Token::Symbol("player:hand"), line_number: 2, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 2, column: 0 },
+            SourceToken{ token: Token::Is, line_number: 2, column: 0 },
+            SourceToken{ token: Token::Number(0.0, 1), line_number: 2, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 2, column: 0 },
+            SourceToken{ token: Token::OpenBracket, line_number: 2, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 2, column: 0 },
+            SourceToken{ token: Token::Symbol("winner"), line_number: 3, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 3, column: 0 },
+            SourceToken{ token: Token::Symbol("player:id"), line_number: 3, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 3, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 3, column: 0 },
+            SourceToken{ token: Token::Symbol("end"), line_number: 4, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 4, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 4, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 4, column: 0 },
+            SourceToken{ token: Token::CloseBracket, line_number: 5, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 5, column: 0 },
+            SourceToken{ token: Token::CloseBracket, line_number: 6, column: 0 },
+            SourceToken{ token: Token::Newline, line_number: 6, column: 0 },
         );
 
         let body = vec!(
@@ -1048,21 +1625,26 @@ mod test{
                             name: "count".to_string(),
                             arguments: vec!(
                                 Expression::Symbol("player:hand".to_string())
-                            )
+                            ),
+                            span: SourceSpan::default()
                         }),
                         right: Expression::Number(0.0),
-                        negative: false
+                        negative: false,
+                        span: SourceSpan::default()
                     })),
                     body: vec!(
                         Statement::FunctionCall(FunctionCall{
                             name: "winner".to_string(),
-                            arguments: vec!(Expression::Symbol("player:id".to_string()))
+                            arguments: vec!(Expression::Symbol("player:id".to_string())),
+                            span: SourceSpan::default()
                         }),
                         Statement::FunctionCall(FunctionCall{
                             name: "end".to_string(),
-                            arguments: vec!()
+                            arguments: vec!(),
+                            span: SourceSpan::default()
                         })
-                    )
+                    ),
+                    span: SourceSpan::default()
                 }
             )
         );
@@ -1072,6 +1654,7 @@ mod test{
                 name: "player_move".to_string(),
                 body,
                 arguments: vec!("player".to_string()),
+                span: SourceSpan::default(),
             })
         );
         let result = parse(&tokens);
@@ -1082,15 +1665,16 @@ mod test{
     #[test]
     fn it_can_parse_a_check_statement() {
         let tokens = vec!(
-            SourceToken{ token: Token::Check, line_number: 1 },
-            SourceToken{ token: Token::OpenParens, line_number: 1 },
-            SourceToken{ token: Token::True, line_number: 1 },
-            SourceToken{ token: Token::CloseParens, line_number: 1 },
+            SourceToken{ token: Token::Check, line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::True, line_number: 1, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 1, column: 0 },
         );
 
         let expected = vec!(
             Statement::CheckStatement(CheckStatement{
-                expression: Expression::Bool(true)
+                expression: Expression::Bool(true),
+                span: SourceSpan::default()
             })
         );
 
@@ -1102,13 +1686,14 @@ mod test{
     #[test]
     fn it_insists_on_an_open_parens_for_check_condition() {
         let tokens = vec!(
-            SourceToken{ token: Token::Check, line_number: 1 },
-            SourceToken{ token: Token::True, line_number: 1 }
+            SourceToken{ token: Token::Check, line_number: 1, column: 0 },
+            SourceToken{ token: Token::True, line_number: 1, column: 0 }
         );
 
         let expected = ParseError{
             error_type: ParseErrorType::UnexpectedToken,
-            line_number: 1
+            line_number: 1,
+            column: 0
         };
 
         let result = parse(&tokens);
@@ -1119,22 +1704,23 @@ mod test{
     #[test]
     fn it_can_parse_a_check_statement_with_current_player() {
         let tokens = vec!(
-            SourceToken{ token: Token::Check, line_number: 1 },
-            SourceToken{ token: Token::OpenParens, line_number: 1 },
-            SourceToken{ token: Token::CurrentPlayer, line_number: 1 },
-            SourceToken{ token: Token::Is, line_number: 1 },
-            SourceToken{ token: Token::Symbol("player:id".to_string()), line_number: 1 },
-            SourceToken{ token: Token::CloseParens, line_number: 1 },
+            SourceToken{ token: Token::Check, line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::CurrentPlayer, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Is, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Symbol("player:id"), line_number: 1, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 1, column: 0 },
         );
 
         let expression = Expression::Comparison(Box::new(Comparison{
             left: Expression::Symbol("current_player".to_string()),
             right: Expression::Symbol("player:id".to_string()),
-            negative: false
+            negative: false,
+            span: SourceSpan::default()
         }));
 
         let expected = vec!(
-            Statement::CheckStatement(CheckStatement{ expression })
+            Statement::CheckStatement(CheckStatement{ expression, span: SourceSpan::default() })
         );
 
         let result = parse(&tokens);
@@ -1145,15 +1731,16 @@ mod test{
     #[test]
     fn it_can_parse_a_return_statement() {
         let tokens = vec!(
-            SourceToken{ token: Token::Return, line_number: 1 },
-            SourceToken{ token: Token::OpenParens, line_number: 1 },
-            SourceToken{ token: Token::True, line_number: 1 },
-            SourceToken{ token: Token::CloseParens, line_number: 1 },
+            SourceToken{ token: Token::Return, line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::True, line_number: 1, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 1, column: 0 },
         );
 
         let expected = vec!(
             Statement::ReturnStatement(ReturnStatement{
-                expression: Expression::Bool(true)
+                expression: Expression::Bool(true),
+                span: SourceSpan::default()
             })
         );
 
@@ -1165,20 +1752,22 @@ mod test{
     #[test]
     fn it_can_parse_an_and_statement() {
         let tokens = vec!(
-            SourceToken{ token: Token::Return, line_number: 1 },
-            SourceToken{ token: Token::OpenParens, line_number: 1 },
-            SourceToken{ token: Token::True, line_number: 1 },
-            SourceToken{ token: Token::Ampersand, line_number: 1 },
-            SourceToken{ token: Token::True, line_number: 1 },
-            SourceToken{ token: Token::CloseParens, line_number: 1 },
+            SourceToken{ token: Token::Return, line_number: 1, column: 0 },
+            SourceToken{ token: Token::OpenParens, line_number: 1, column: 0 },
+            SourceToken{ token: Token::True, line_number: 1, column: 0 },
+            SourceToken{ token: Token::Ampersand, line_number: 1, column: 0 },
+            SourceToken{ token: Token::True, line_number: 1, column: 0 },
+            SourceToken{ token: Token::CloseParens, line_number: 1, column: 0 },
         );
 
         let expected = vec!(
             Statement::ReturnStatement(ReturnStatement{
                 expression: Expression::And(Box::new(And{
                     left: Expression::Bool(true),
-                    right: Expression::Bool(true)
-                }))
+                    right: Expression::Bool(true),
+                    span: SourceSpan::default()
+                })),
+                span: SourceSpan::default()
             })
         );
 
@@ -1192,9 +1781,9 @@ mod test{
     fn it_parses_the_argument_of_a_function() {
         let tokens = get_source_tokens(vec!(
             Token::Define,
-            Token::Symbol("not_royal".to_owned()),
+            Token::Symbol("not_royal"),
             Token::OpenParens,
-            Token::Symbol("card".to_string()),
+            Token::Symbol("card"),
             Token::CloseParens,
             Token::OpenBracket,
             Token::CloseBracket
@@ -1202,7 +1791,7 @@ mod test{
 
         let name = "not_royal".to_owned();
         let body = vec!();
-        let definition = Definition{ arguments: vec!("card".to_string()), name, body };
+        let definition = Definition{ arguments: vec!("card".to_string()), name, body, span: SourceSpan::default() };
         let statement = Statement::Definition(definition);
         let expected = vec!(statement);
         let result = parse(&tokens);
@@ -1215,10 +1804,10 @@ mod test{
         let tokens = get_source_tokens(vec!(
             Token::If,
             Token::OpenParens,
-            Token::Symbol("player:id".to_string()),
+            Token::Symbol("player:id"),
             Token::Is,
             Token::Not,
-            Token::Number(1.0),
+            Token::Number(1.0, 1),
             Token::CloseParens,
             Token::OpenBracket,
             Token::CloseBracket
@@ -1227,16 +1816,411 @@ mod test{
         let comparison = Comparison {
             left: Expression::Symbol("player:id".to_string()),
             right: Expression::Number(1.0),
-            negative: true
+            negative: true,
+            span: SourceSpan::default()
         };
         let expression = Expression::Comparison(Box::new(comparison));
         let body = vec!();
-        let if_statement = IfStatement{ expression, body };
+        let if_statement = IfStatement{ expression, body, span: SourceSpan::default() };
         let statement = Statement::IfStatement(if_statement);
         let expected = vec!(statement);
         let result = parse(&tokens);
 
         assert_eq!(Ok(expected), result);
     }
+
+    fn span(start: (u32, u32), end: (u32, u32)) -> (Span, Span) {
+        (Span{ line: start.0, column: start.1 }, Span{ line: end.0, column: end.1 })
+    }
+
+    #[test]
+    fn statements_carry_their_source_span() {
+        let tokens = crate::lex::lexer("name turns\ndefine setup(){\n  deck > players end\n  shuffle(deck)\n}").unwrap();
+        let ast = parse(&tokens).unwrap();
+        let setup = match &ast[1] {
+            Statement::Definition(d) => d,
+            _ => panic!("expected a definition")
+        };
+
+        assert_eq!((ast[0].span().start, ast[0].span().end), span((1, 1), (1, 11)));
+        assert_eq!((setup.span.start, setup.span.end), span((2, 1), (5, 2)));
+        assert_eq!((setup.body[0].span().start, setup.body[0].span().end), span((3, 3), (3, 21)));
+        assert_eq!((setup.body[1].span().start, setup.body[1].span().end), span((4, 3), (4, 16)));
+    }
+
+    #[test]
+    fn expressions_carry_their_source_span() {
+        let tokens = crate::lex::lexer("if(count(player:hand) is 0){\n}").unwrap();
+        let ast = parse(&tokens).unwrap();
+        let comparison = match &ast[0] {
+            Statement::IfStatement(IfStatement{ expression: Expression::Comparison(c), .. }) => c,
+            _ => panic!("expected a comparison")
+        };
+        let count = comparison.left.span().unwrap();
+
+        assert_eq!((comparison.span.start, comparison.span.end), span((1, 4), (1, 27)));
+        assert_eq!((count.start, count.end), span((1, 4), (1, 22)));
+        assert_eq!(comparison.right.span(), None);
+    }
+
+    #[test]
+    fn recovering_keeps_the_statements_around_an_error() {
+        let tokens = crate::lex::lexer("name turns\ndefine setup(){\ncheck(>)\nshuffle(deck)\n}\ndeck 3\nplayers 2").unwrap();
+        let (ast, errors) = parse_all(&tokens);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(ast.len(), 3);
+        match &ast[1] {
+            Statement::Definition(d) => assert_eq!(d.body.len(), 1),
+            _ => panic!("expected a definition")
+        }
+        assert_eq!(parse(&tokens), Err(errors[0].clone()));
+    }
+
+    fn parse_source(source: &str) -> Result<Vec<Statement>, ParseError> {
+        parse(&crate::lex::lexer(source).unwrap())
+    }
+
+    #[test]
+    fn unfinished_statements_are_errors() {
+        assert_eq!(parse_source("players").unwrap_err().error_type, ParseErrorType::UnexpectedEndOfStream);
+        assert_eq!(parse_source("name\n").unwrap_err().error_type, ParseErrorType::UnexpectedToken);
+        assert_eq!(parse_source("stack deck").unwrap_err().error_type, ParseErrorType::UnexpectedToken);
+        assert_eq!(parse_source("deck >").unwrap_err().error_type, ParseErrorType::UnexpectedEndOfStream);
+        assert_eq!(parse_source("define").unwrap_err().error_type, ParseErrorType::UnexpectedEndOfStream);
+        assert_eq!(parse_source("pile > ").unwrap_err().line_number, 1);
+    }
+
+    #[test]
+    fn the_deck_can_be_counted() {
+        assert!(parse_source("if(count(deck) is 0){\n}").is_ok());
+    }
+
+    #[test]
+    fn it_can_parse_a_match_statement() {
+        let ast = parse_source("match card:rank {\nAce {\nend()\n}\n10 {\n}\nother {\nnext_player()\n}\n}").unwrap();
+        let end = Statement::FunctionCall(FunctionCall{ name: "end".to_string(), arguments: vec!(), span: SourceSpan::default() });
+        let next_player = Statement::FunctionCall(FunctionCall{ name: "next_player".to_string(), arguments: vec!(), span: SourceSpan::default() });
+        let expected = Statement::MatchStatement(MatchStatement{
+            expression: Expression::Symbol("card:rank".to_string()),
+            arms: vec!(
+                MatchArm{ pattern: Expression::Symbol("Ace".to_string()), body: vec!(end) },
+                MatchArm{ pattern: Expression::Number(10.0), body: vec!() }
+            ),
+            otherwise: Some(vec!(next_player)),
+            span: SourceSpan::default()
+        });
+
+        assert_eq!(ast, vec!(expected));
+        assert_eq!((ast[0].span().start, ast[0].span().end), span((1, 1), (10, 2)));
+    }
+
+    #[test]
+    fn match_subjects_can_be_function_calls() {
+        let ast = parse_source("match count(player:hand) {\n0 {\n}\n}").unwrap();
+
+        match &ast[0] {
+            Statement::MatchStatement(m) => assert_eq!(m.expression.to_string(), "count(player:hand)".to_string()),
+            _ => panic!("expected a match statement")
+        }
+    }
+
+    #[test]
+    fn match_arms_need_a_block() {
+        assert_eq!(parse_source("match card:rank {\nAce end()\n}").unwrap_err().error_type, ParseErrorType::UnexpectedToken);
+        assert_eq!(parse_source("match {\n}").unwrap_err().error_type, ParseErrorType::ExpectedSymbol);
+        assert_eq!(parse_source("match card:rank {\nAce {\n}").unwrap_err().error_type, ParseErrorType::UnexpectedEndOfStream);
+    }
+
+    #[test]
+    fn it_can_parse_a_between_comparison() {
+        let ast = parse_source("check(count(player:hand) between 2 and 10 & true)").unwrap();
+        let between = Between{
+            value: Expression::FunctionCall(FunctionCall{
+                name: "count".to_string(),
+                arguments: vec!(Expression::Symbol("player:hand".to_string())),
+                span: SourceSpan::default()
+            }),
+            low: Expression::Number(2.0),
+            high: Expression::Number(10.0),
+            span: SourceSpan::default()
+        };
+        let expected = Statement::CheckStatement(CheckStatement{
+            expression: Expression::And(Box::new(And{
+                left: Expression::Between(Box::new(between)),
+                right: Expression::Bool(true),
+                span: SourceSpan::default()
+            })),
+            span: SourceSpan::default()
+        });
+
+        assert_eq!(ast, vec!(expected));
+    }
+
+    #[test]
+    fn or_joins_looser_than_and_and_is() {
+        let ast = parse_source("check(x is 1 | y is 2 & z)").unwrap();
+        let comparison = |name: &str, n: f64| Expression::Comparison(Box::new(Comparison{
+            left: Expression::Symbol(name.to_string()),
+            right: Expression::Number(n),
+            negative: false,
+            span: SourceSpan::default()
+        }));
+        let expected = Statement::CheckStatement(CheckStatement{
+            expression: Expression::Or(Box::new(Or{
+                left: comparison("x", 1.0),
+                right: Expression::And(Box::new(And{
+                    left: comparison("y", 2.0),
+                    right: Expression::Symbol("z".to_string()),
+                    span: SourceSpan::default()
+                })),
+                span: SourceSpan::default()
+            })),
+            span: SourceSpan::default()
+        });
+
+        assert_eq!(ast, vec!(expected));
+    }
+
+    #[test]
+    fn sums_are_worked_out_left_to_right_before_comparing() {
+        let ast = parse_source("check(count(player:hand)-1 + x is 3)").unwrap();
+        let count = Expression::FunctionCall(FunctionCall{
+            name: "count".to_string(),
+            arguments: vec!(Expression::Symbol("player:hand".to_string())),
+            span: SourceSpan::default()
+        });
+        let difference = Expression::Arithmetic(Box::new(Arithmetic{
+            left: count,
+            operator: Operator::Subtract,
+            right: Expression::Number(1.0),
+            span: SourceSpan::default()
+        }));
+        let expected = Statement::CheckStatement(CheckStatement{
+            expression: Expression::Comparison(Box::new(Comparison{
+                left: Expression::Arithmetic(Box::new(Arithmetic{
+                    left: difference,
+                    operator: Operator::Add,
+                    right: Expression::Symbol("x".to_string()),
+                    span: SourceSpan::default()
+                })),
+                right: Expression::Number(3.0),
+                negative: false,
+                span: SourceSpan::default()
+            })),
+            span: SourceSpan::default()
+        });
+
+        assert_eq!(ast, vec!(expected));
+    }
+
+    #[test]
+    fn products_are_worked_out_before_sums() {
+        let ast = parse_source("return(a + b * 2 % c)").unwrap();
+        let arithmetic = |left, operator, right| Expression::Arithmetic(Box::new(Arithmetic{ left, operator, right, span: SourceSpan::default() }));
+        let symbol = |s: &str| Expression::Symbol(s.to_string());
+        let product = arithmetic(arithmetic(symbol("b"), Operator::Multiply, Expression::Number(2.0)), Operator::Modulo, symbol("c"));
+        let expected = Statement::ReturnStatement(ReturnStatement{
+            expression: arithmetic(symbol("a"), Operator::Add, product),
+            span: SourceSpan::default()
+        });
+
+        assert_eq!(ast, vec!(expected));
+    }
+
+    #[test]
+    fn between_needs_an_and() {
+        assert_eq!(parse_source("check(x between 2 10)").unwrap_err().error_type, ParseErrorType::UnexpectedToken);
+        assert_eq!(parse_source("check(x between 2 and)").unwrap_err().error_type, ParseErrorType::UnexpectedToken);
+    }
+
+    #[test]
+    fn calls_in_expressions_can_take_several_arguments() {
+        let ast = parse_source("if(count_where(player:hand, is_heart) is 2){\n}").unwrap();
+        let call = FunctionCall{
+            name: "count_where".to_string(),
+            arguments: vec!(Expression::Symbol("player:hand".to_string()), Expression::Symbol("is_heart".to_string())),
+            span: SourceSpan::default()
+        };
+
+        match &ast[0] {
+            Statement::IfStatement(IfStatement{ expression: Expression::Comparison(c), .. }) => {
+                assert_eq!(c.left, Expression::FunctionCall(call));
+                assert_eq!(c.right, Expression::Number(2.0));
+                assert_eq!((c.span.start, c.span.end), span((1, 4), (1, 43)));
+            },
+            _ => panic!("expected a comparison")
+        }
+    }
+
+    #[test]
+    fn function_statements_can_take_several_arguments() {
+        let ast = parse_source("define setup(){\nshuffle_into(discard, deck)\nburn(2)\n}").unwrap();
+        let body = match &ast[0] {
+            Statement::Definition(d) => &d.body,
+            _ => panic!("expected a definition")
+        };
+
+        assert_eq!(body[0].to_string(), "shuffle_into(discard, deck)".to_string());
+        assert_eq!(body[1].to_string(), "burn(2)".to_string());
+    }
+
+    #[test]
+    fn expression_calls_can_take_no_arguments() {
+        let ast = parse_source("define player_move(p){\nif(bets_settled()){\nend()\n}\n}").unwrap();
+        let body = match &ast[0] {
+            Statement::Definition(d) => &d.body,
+            _ => panic!("expected a definition")
+        };
+
+        match &body[0] {
+            Statement::IfStatement(i) => {
+                assert_eq!(i.expression, Expression::FunctionCall(FunctionCall{ name: "bets_settled".to_string(), arguments: vec!(), span: SourceSpan::default() }));
+                assert_eq!(i.body.len(), 1);
+            },
+            _ => panic!("expected an if statement")
+        }
+    }
+
+    #[test]
+    fn it_can_parse_a_deal_declaration() {
+        let ast = parse_source("deal 7 each\ndefine setup(){\ndeal()\n}").unwrap();
+        let deal = Statement::Declaration(Declaration{ key: GlobalKey::Deal, value: Expression::Number(7.0), span: SourceSpan::default() });
+
+        assert_eq!(ast[0], deal);
+        assert_eq!((ast[0].span().start, ast[0].span().end), span((1, 1), (1, 12)));
+        assert!(matches!(&ast[1], Statement::Definition(d) if matches!(&d.body[0], Statement::FunctionCall(_))));
+    }
+
+    #[test]
+    fn one_declaration_can_name_several_stacks() {
+        let ast = parse_source("stack middle, discard, crib\nplayers 2").unwrap();
+        let stack = |name: &str| Statement::Declaration(Declaration{ key: GlobalKey::Stack, value: Expression::Symbol(name.to_string()), span: SourceSpan::default() });
+
+        assert_eq!(ast.len(), 4);
+        assert_eq!(ast[..3], [stack("middle"), stack("discard"), stack("crib")]);
+        assert_eq!((ast[2].span().start, ast[2].span().end), span((1, 1), (1, 28)));
+        assert_eq!(parse_source("stack middle,").unwrap_err().error_type, ParseErrorType::UnexpectedEndOfStream);
+    }
+
+    #[test]
+    fn arguments_can_take_a_count() {
+        let ast = parse_source("define setup(){\nshuffle(deck, riffle 7)\n}").unwrap();
+        let body = match &ast[0] {
+            Statement::Definition(d) => &d.body,
+            _ => panic!("expected a definition")
+        };
+
+        assert_eq!(body[0].to_string(), "shuffle(deck, riffle, 7)".to_string());
+        assert_eq!(parse_source("define setup(){\nshuffle(deck, riffle, 7)\n}").unwrap(), ast);
+    }
+
+    #[test]
+    fn stacks_can_be_declared_face_down() {
+        let ast = parse_source("stack middle, discard facedown
+facedown player:tricks").unwrap();
+        let face_down = |name: &str| Statement::Declaration(Declaration{ key: GlobalKey::FaceDown, value: Expression::Symbol(name.to_string()), span: SourceSpan::default() });
+
+        assert_eq!(ast.len(), 5);
+        assert_eq!(ast[2..], [face_down("middle"), face_down("discard"), face_down("player:tricks")]);
+        assert_eq!(ast[4].to_string(), "facedown player:tricks".to_string());
+    }
+
+    #[test]
+    fn it_can_parse_a_hand_size_declaration() {
+        let ast = parse_source("hand_size 7").unwrap();
+
+        assert_eq!(ast, vec!(Statement::Declaration(Declaration{ key: GlobalKey::HandSize, value: Expression::Number(7.0), span: SourceSpan::default() })));
+        assert_eq!(ast[0].to_string(), "hand_size 7".to_string());
+    }
+
+    #[test]
+    fn it_can_parse_a_chips_declaration() {
+        let ast = parse_source("chips 100\ndefine chips(){\n}").unwrap();
+
+        assert_eq!(ast[0], Statement::Declaration(Declaration{ key: GlobalKey::Chips, value: Expression::Number(100.0), span: SourceSpan::default() }));
+        assert_eq!(ast[0].to_string(), "chips 100".to_string());
+        assert!(matches!(&ast[1], Statement::Definition(d) if d.name == "chips"));
+    }
+
+    #[test]
+    fn it_can_parse_a_match_target() {
+        let ast = parse_source("match to 500\ndefine player_move(to){\nmatch to {\n1 {\nend()\n}\n}\n}").unwrap();
+
+        assert_eq!(ast[0], Statement::Declaration(Declaration{ key: GlobalKey::MatchTo, value: Expression::Number(500.0), span: SourceSpan::default() }));
+        assert_eq!(ast[0].to_string(), "match to 500".to_string());
+        assert!(matches!(&ast[1], Statement::Definition(d) if matches!(&d.body[0], Statement::MatchStatement(_))));
+    }
+
+    #[test]
+    fn it_can_parse_a_turn_time() {
+        let ast = parse_source("turn_time 30").unwrap();
+
+        assert_eq!(ast[0], Statement::Declaration(Declaration{ key: GlobalKey::TurnTime, value: Expression::Number(30.0), span: SourceSpan::default() }));
+        assert_eq!(ast[0].to_string(), "turn_time 30".to_string());
+    }
+
+    #[test]
+    fn it_can_parse_a_deck_count() {
+        let ast = parse_source("decks 2").unwrap();
+
+        assert_eq!(ast[0], Statement::Declaration(Declaration{ key: GlobalKey::Decks, value: Expression::Number(2.0), span: SourceSpan::default() }));
+        assert_eq!(ast[0].to_string(), "decks 2".to_string());
+    }
+
+    #[test]
+    fn it_can_parse_a_deck_of_its_own() {
+        let ast = parse_source("deck euchre {\n    ranks 9, 10, jack, queen, king, ace\n    suits hearts\n}\nplayers 4").unwrap();
+
+        let ranks: Vec<String> = ["9", "10", "jack", "queen", "king", "ace"].iter().map(|r| r.to_string()).collect();
+        assert_eq!(ast[0], Statement::DeckDefinition(DeckDefinition{
+            name: "euchre".to_string(), ranks, suits: vec!("hearts".to_string()), span: SourceSpan::default()
+        }));
+        assert_eq!(ast[0].to_string(), "deck euchre {\n    ranks 9, 10, jack, queen, king, ace\n    suits hearts\n}".to_string());
+        assert_eq!(ast.len(), 2);
+        assert_eq!(parse_source("deck euchre {\nnine\n}").unwrap_err().error_type, ParseErrorType::UnexpectedToken);
+    }
+
+    #[test]
+    fn it_can_parse_card_values() {
+        let ast = parse_source("values {\nace 11, face 10\n9 0\n}").unwrap();
+
+        let values = vec!(("ace".to_string(), 11.0), ("face".to_string(), 10.0), ("9".to_string(), 0.0));
+        assert_eq!(ast, vec!(Statement::ValueTable(ValueTable{ values, span: SourceSpan::default() })));
+        assert_eq!(ast[0].to_string(), "values { ace 11, face 10, 9 0 }".to_string());
+        assert_eq!(parse_source("values { ace }").unwrap_err().error_type, ParseErrorType::UnexpectedToken);
+    }
+
+    #[test]
+    fn it_can_parse_a_language_version() {
+        let ast = parse_source("cardlang 2\ndefine setup(){\ndeck > middle 4\n}").unwrap();
+
+        assert_eq!(ast[0], Statement::Declaration(Declaration{ key: GlobalKey::Language, value: Expression::Number(2.0), span: SourceSpan::default() }));
+        assert_eq!(ast[0].to_string(), "cardlang 2".to_string());
+        assert!(matches!(&ast[1], Statement::Definition(d) if d.body[0].to_string() == "deck > middle 4"));
+    }
+
+    #[test]
+    fn it_can_parse_simultaneous_turns() {
+        let ast = parse_source("turns simultaneous").unwrap();
+
+        assert_eq!(ast, vec!(Statement::Declaration(Declaration{ key: GlobalKey::Turns, value: Expression::Symbol("simultaneous".to_string()), span: SourceSpan::default() })));
+        assert_eq!(ast[0].to_string(), "turns simultaneous".to_string());
+    }
+
+    #[test]
+    fn deals_say_how_they_are_shared() {
+        assert_eq!(parse_source("deal 7").unwrap_err().error_type, ParseErrorType::UnexpectedEndOfStream);
+        assert_eq!(parse_source("deal 7 all").unwrap_err().error_type, ParseErrorType::UnexpectedToken);
+    }
+
+    #[test]
+    fn random_tokens_never_panic() {
+        let mut generator = crate::generators::Generator::new(5);
+        for _ in 0..500 {
+            let tokens = generator.noise_tokens(30);
+            let _ = parse(&tokens);
+            let _ = parse_all(&tokens);
+        }
+    }
 }
-        
\ No newline at end of file
@@ -0,0 +1,211 @@
+use std::fs;
+use crate::error::CardlangError;
+use crate::interpreter::Game;
+use crate::json::Json;
+use crate::rating::{read_existing, Rating, Ratings, INITIAL_RATING};
+use crate::runtime::GameState;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub games: usize,
+    pub wins: usize,
+    pub rating: f64
+}
+
+// people who come back to play again, kept by name in a json file so their history carries between sessions
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Profiles {
+    entries: Vec<Profile>
+}
+
+impl Profiles {
+    // a missing file is an empty store
+    pub fn load(path: &str) -> Result<Profiles, CardlangError> {
+        let source = match read_existing(path)? {
+            Some(s) => s,
+            None => return Ok(Profiles::default())
+        };
+
+        let invalid = || CardlangError::InvalidFile(path.to_string());
+        let fields = match Json::parse(&source) {
+            Ok(Json::Object(fields)) => fields,
+            _ => return Err(invalid())
+        };
+
+        let mut entries = vec!();
+        for (name, value) in fields.iter() {
+            let number = |key: &str| value.get(key).and_then(|n| n.as_f64()).ok_or_else(invalid);
+            entries.push(Profile {
+                name: name.to_string(),
+                games: number("games")? as usize,
+                wins: number("wins")? as usize,
+                rating: number("rating")?
+            });
+        }
+        Ok(Profiles { entries })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), CardlangError> {
+        fs::write(path, format!("{}\n", self.to_json()))
+            .map_err(|_| CardlangError::UnwritableFile(path.to_string()))
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::Object(self.entries.iter().map(|p| (p.name.to_string(), Json::object(vec!(
+            ("games", Json::Number(p.games as f64)),
+            ("wins", Json::Number(p.wins as f64)),
+            ("rating", Json::Number(p.rating))
+        )))).collect())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.entries.iter().find(|p| p.name == name)
+    }
+
+    pub fn entries(&self) -> &[Profile] {
+        &self.entries
+    }
+
+    // each named player and whether they won, rated against each other as a tournament game would be
+    pub fn record_game(&mut self, players: &[(String, bool)]) {
+        let mut ratings = Ratings::from(self.entries.iter().map(|p| Rating {
+            name: p.name.to_string(), rating: p.rating, games: p.games
+        }).collect::<Vec<Rating>>());
+        let scores: Vec<(String, f64)> = players.iter().map(|(name, won)| (name.to_string(), if *won { 1.0 } else { 0.0 })).collect();
+        ratings.record_game(&scores);
+
+        for (name, won) in players.iter() {
+            let index = match self.entries.iter().position(|p| &p.name == name) {
+                Some(i) => i,
+                None => {
+                    self.entries.push(Profile { name: name.to_string(), games: 0, wins: 0, rating: INITIAL_RATING });
+                    self.entries.len() - 1
+                }
+            };
+            let profile = &mut self.entries[index];
+            profile.games += 1;
+            profile.wins += *won as usize;
+            profile.rating = ratings.get(name);
+        }
+    }
+}
+
+impl std::fmt::Display for Profiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.entries.is_empty() {
+            return write!(f, "no profiles");
+        }
+        let lines: Vec<String> = self.entries.iter().map(|p| {
+            format!("{}: {} wins from {} games, rated {:.0}", p.name, p.wins, p.games, p.rating)
+        }).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// a store opened by the repl or a server, with the seats its players have taken.
+// Every finished game is recorded once, and saved straight away
+#[derive(Debug, Clone)]
+pub struct ProfileStore {
+    path: String,
+    profiles: Profiles,
+    seats: Vec<(usize, String)>,
+    recorded: bool
+}
+
+impl ProfileStore {
+    pub fn open(path: &str) -> Result<ProfileStore, CardlangError> {
+        Ok(ProfileStore { path: path.to_string(), profiles: Profiles::load(path)?, seats: vec!(), recorded: false })
+    }
+
+    pub fn profiles(&self) -> &Profiles {
+        &self.profiles
+    }
+
+    pub fn name_seat(&mut self, seat: usize, name: &str) {
+        self.seats.retain(|(s, _)| *s != seat);
+        self.seats.push((seat, name.to_string()));
+    }
+
+    // unnamed seats play without being recorded
+    pub fn update(&mut self, game: &Game) -> Result<Option<String>, CardlangError> {
+        let state = game.state();
+        if state.status != GameState::GameOver {
+            self.recorded = false;
+            return Ok(None);
+        }
+        if self.recorded || self.seats.is_empty() {
+            return Ok(None);
+        }
+        self.recorded = true;
+
+        let players: Vec<(String, bool)> = self.seats.iter()
+            .filter(|(seat, _)| *seat <= state.player_count)
+            .map(|(seat, name)| (name.to_string(), state.winners.contains(seat)))
+            .collect();
+        self.profiles.record_game(&players);
+        self.profiles.save(&self.path)?;
+        Ok(Some(format!("recorded the game for {}", players.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>().join(", "))))
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn games_accumulate_wins_and_ratings() {
+        let mut profiles = Profiles::default();
+        profiles.record_game(&[("ada".to_string(), true), ("bo".to_string(), false)]);
+        profiles.record_game(&[("ada".to_string(), true), ("bo".to_string(), false)]);
+
+        let ada = profiles.get("ada").unwrap();
+        assert_eq!((ada.games, ada.wins), (2, 2));
+        assert!(ada.rating > INITIAL_RATING);
+        assert_eq!(profiles.get("bo").map(|p| p.wins), Some(0));
+    }
+
+    #[test]
+    fn profiles_survive_a_round_trip_to_disk() {
+        let path = std::env::temp_dir().join(format!("cardlang-profiles-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut profiles = Profiles::default();
+        profiles.record_game(&[("ada".to_string(), false), ("bo".to_string(), true)]);
+
+        profiles.save(path).unwrap();
+        let loaded = Profiles::load(path).unwrap();
+        let _ = fs::remove_file(path);
+
+        assert_eq!(loaded, profiles);
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let path = std::env::temp_dir();
+        let path = path.to_str().unwrap();
+
+        assert_eq!(Profiles::load("/nonexistent/profiles.json"), Ok(Profiles::default()));
+        assert_eq!(Profiles::load(path), Err(CardlangError::UnreadableFile(path.to_string())));
+    }
+
+    #[test]
+    fn finished_games_are_recorded_once() {
+        let path = std::env::temp_dir().join(format!("cardlang-profile-store-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut game = crate::load_game("players 2\ndefine player_move(player){\nwinner(player:id)\nend()\n}").unwrap();
+        let mut store = ProfileStore::open(path).unwrap();
+        store.name_seat(1, "ada");
+        store.name_seat(2, "bo");
+
+        game.start();
+        assert_eq!(store.update(&game), Ok(None));
+        game.player_move(1);
+        assert_eq!(store.update(&game), Ok(Some("recorded the game for ada, bo".to_string())));
+        assert_eq!(store.update(&game), Ok(None));
+        let saved = Profiles::load(path).unwrap();
+        let _ = fs::remove_file(path);
+
+        assert_eq!(saved.get("ada").map(|p| p.wins), Some(1));
+        assert_eq!(saved.get("bo").map(|p| p.games), Some(1));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{fs, io};
+use crate::error::CardlangError;
+use crate::json::Json;
+
+pub const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rating {
+    pub name: String,
+    pub rating: f64,
+    pub games: usize
+}
+
+// elo ratings for bots, kept between tournaments in a json file
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Ratings {
+    entries: Vec<Rating>
+}
+
+impl Ratings {
+    // a missing file is a fresh set of ratings
+    pub fn load(path: &str) -> Result<Ratings, CardlangError> {
+        let source = match read_existing(path)? {
+            Some(s) => s,
+            None => return Ok(Ratings::default())
+        };
+
+        let invalid = || CardlangError::InvalidFile(path.to_string());
+        let fields = match Json::parse(&source) {
+            Ok(Json::Object(fields)) => fields,
+            _ => return Err(invalid())
+        };
+
+        let mut entries = vec!();
+        for (name, value) in fields.iter() {
+            let rating = value.get("rating").and_then(|r| r.as_f64()).ok_or_else(invalid)?;
+            let games = value.get("games").and_then(|g| g.as_f64()).ok_or_else(invalid)?;
+            entries.push(Rating { name: name.to_string(), rating, games: games as usize });
+        }
+        Ok(Ratings { entries })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), CardlangError> {
+        fs::write(path, format!("{}\n", self.to_json()))
+            .map_err(|_| CardlangError::UnwritableFile(path.to_string()))
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::Object(self.entries.iter().map(|r| (r.name.to_string(), Json::object(vec!(
+            ("rating", Json::Number(r.rating)),
+            ("games", Json::Number(r.games as f64))
+        )))).collect())
+    }
+
+    pub fn get(&self, name: &str) -> f64 {
+        self.entries.iter().find(|r| r.name == name).map(|r| r.rating).unwrap_or(INITIAL_RATING)
+    }
+
+    pub fn entries(&self) -> &[Rating] {
+        &self.entries
+    }
+
+    // scores are 1 for a win, 0.5 for a draw and 0 for a loss, and every pair of players
+    // in the game is rated as its own match, sharing out the k factor between them
+    pub fn record_game(&mut self, scores: &[(String, f64)]) {
+        if scores.len() < 2 {
+            return;
+        }
+
+        let k = K_FACTOR / (scores.len() - 1) as f64;
+        let changes: Vec<f64> = scores.iter().map(|(name, score)| {
+            let rating = self.get(name);
+            scores.iter().filter(|(other, _)| other != name).map(|(other, other_score)| {
+                let expected = 1.0 / (1.0 + 10f64.powf((self.get(other) - rating) / 400.0));
+                let actual = if score > other_score { 1.0 } else if score < other_score { 0.0 } else { 0.5 };
+                k * (actual - expected)
+            }).sum()
+        }).collect();
+
+        for ((name, _), change) in scores.iter().zip(changes) {
+            let index = match self.entries.iter().position(|r| &r.name == name) {
+                Some(i) => i,
+                None => {
+                    self.entries.push(Rating { name: name.to_string(), rating: INITIAL_RATING, games: 0 });
+                    self.entries.len() - 1
+                }
+            };
+            self.entries[index].rating += change;
+            self.entries[index].games += 1;
+        }
+    }
+}
+
+// only a file that isn't there yet reads as nothing, one that can't be read is an error.
+// Profiles are kept the same way
+pub(crate) fn read_existing(path: &str) -> Result<Option<String>, CardlangError> {
+    match fs::read_to_string(path) {
+        Ok(source) => Ok(Some(source)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(_) => Err(CardlangError::UnreadableFile(path.to_string()))
+    }
+}
+
+impl From<Vec<Rating>> for Ratings {
+    fn from(entries: Vec<Rating>) -> Ratings {
+        Ratings { entries }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn winners_take_points_from_losers() {
+        let mut ratings = Ratings::default();
+        ratings.record_game(&[("a".to_string(), 1.0), ("b".to_string(), 0.0)]);
+
+        assert_eq!(ratings.get("a"), 1516.0);
+        assert_eq!(ratings.get("b"), 1484.0);
+        assert_eq!(ratings.get("c"), INITIAL_RATING);
+    }
+
+    #[test]
+    fn draws_between_equals_change_nothing() {
+        let mut ratings = Ratings::default();
+        ratings.record_game(&[("a".to_string(), 0.5), ("b".to_string(), 0.5)]);
+
+        assert_eq!(ratings.get("a"), INITIAL_RATING);
+        assert_eq!(ratings.entries()[1].games, 1);
+    }
+
+    #[test]
+    fn ratings_survive_a_round_trip_to_disk() {
+        let path = std::env::temp_dir().join(format!("cardlang-ratings-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut ratings = Ratings::default();
+        ratings.record_game(&[("a".to_string(), 1.0), ("b".to_string(), 0.0)]);
+
+        ratings.save(path).unwrap();
+        let loaded = Ratings::load(path).unwrap();
+        let _ = fs::remove_file(path);
+
+        assert_eq!(loaded, ratings);
+    }
+
+    #[test]
+    fn missing_files_start_fresh() {
+        assert_eq!(Ratings::load("/nonexistent/ratings.json"), Ok(Ratings::default()));
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let path = std::env::temp_dir();
+        let path = path.to_str().unwrap();
+
+        assert_eq!(Ratings::load(path), Err(CardlangError::UnreadableFile(path.to_string())));
+    }
+}
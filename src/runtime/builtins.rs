@@ -0,0 +1,119 @@
+use super::*;
+
+pub type NativeFunction = dyn Fn(&mut Runtime, &[PrimitiveValue]) -> Option<PrimitiveValue> + Send + Sync;
+
+// functions an embedder has added to the language, shared so forks and fresh games keep them
+#[derive(Clone, Default)]
+pub struct Builtins(HashMap<String, Arc<NativeFunction>>);
+
+impl Builtins {
+    // the language's own functions can't be replaced, a name they already use is ignored
+    pub fn register(&mut self, name: &str, function: Arc<NativeFunction>) {
+        if !BUILT_IN_FUNCTIONS.contains(&name) {
+            self.0.insert(name.to_string(), function);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<NativeFunction>> {
+        self.0.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+}
+
+impl fmt::Debug for Builtins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.0.keys().collect();
+        names.sort();
+        write!(f, "Builtins({:?})", names)
+    }
+}
+
+impl Runtime {
+    pub fn register_builtin<F>(&mut self, name: &str, function: F)
+    where F: Fn(&mut Runtime, &[PrimitiveValue]) -> Option<PrimitiveValue> + Send + Sync + 'static {
+        self.register_shared_builtin(name, Arc::new(function));
+    }
+
+    pub fn register_shared_builtin(&mut self, name: &str, function: Arc<NativeFunction>) {
+        self.builtins.register(name, function);
+    }
+
+    pub fn set_builtins(&mut self, builtins: Builtins) {
+        self.builtins = builtins;
+    }
+
+    // with or without `std:`
+    pub(super) fn is_builtin(&self, name: &str) -> bool {
+        let name = builtin_name(name);
+        BUILT_IN_FUNCTIONS.contains(&name) || self.builtins.contains(name)
+    }
+
+    pub(super) fn call_native(&mut self, name: &str, f: &FunctionCall) -> Option<PrimitiveValue> {
+        let function = self.builtins.get(name)?;
+        let arguments: Vec<PrimitiveValue> = f.arguments.iter().map(|a| self.resolve_expression(a)).collect();
+        function(self, &arguments)
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn runtime() -> Runtime {
+        let initial_values = InitialValues{
+            players: 2,
+            card_stacks: vec!(),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
+            current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
+        };
+        let callbacks = Callbacks{ player_move: None, setup: None, definitions: HashMap::new() };
+        Runtime::new(initial_values, callbacks)
+    }
+
+    fn call(name: &str, arguments: Vec<Expression>) -> FunctionCall {
+        FunctionCall{ name: name.to_string(), arguments, span: SourceSpan::default() }
+    }
+
+    #[test]
+    fn registered_functions_can_be_called_and_change_the_game() {
+        let mut runtime = runtime();
+        runtime.register_builtin("double_score", |runtime, arguments| match arguments {
+            [PrimitiveValue::Number(p)] => {
+                let player = &mut Arc::make_mut(&mut runtime.players)[*p as usize - 1];
+                player.set_score(player.score() * 2.0 + 1.0);
+                Some(PrimitiveValue::Number(player.score()))
+            },
+            _ => None
+        });
+
+        let result = runtime.handle_function_call(&call("double_score", vec!(Expression::Number(2.0))));
+
+        assert_eq!(result, Some(PrimitiveValue::Number(1.0)));
+        assert_eq!(runtime.fork().players[1].score(), 1.0);
+        assert!(runtime.fork().is_builtin("double_score"));
+    }
+
+    #[test]
+    fn the_language_functions_cannot_be_replaced() {
+        let mut runtime = runtime();
+        runtime.register_builtin("count", |_, _| Some(PrimitiveValue::Number(99.0)));
+
+        let result = runtime.handle_function_call(&call("count", vec!(Expression::Symbol("player:hand".to_string()))));
+
+        assert!(!runtime.builtins.contains("count"));
+        assert_ne!(result, Some(PrimitiveValue::Number(99.0)));
+    }
+}
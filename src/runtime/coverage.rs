@@ -0,0 +1,103 @@
+use crate::ast::*;
+use super::debug::describe_statement;
+use ::std::{collections::HashSet, fmt, sync::{Arc, Mutex}};
+
+// statements are told apart by address, which is stable because every runtime of a game
+// shares the same callback definitions
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    executed: Arc<Mutex<HashSet<usize>>>
+}
+
+impl Coverage {
+    pub fn record(&self, statement: &Statement) {
+        if let Ok(mut executed) = self.executed.lock() {
+            executed.insert(statement as *const Statement as usize);
+        }
+    }
+
+    pub fn has_run(&self, statement: &Statement) -> bool {
+        match self.executed.lock() {
+            Ok(executed) => executed.contains(&(statement as *const Statement as usize)),
+            Err(_) => false
+        }
+    }
+
+    pub fn report(&self, definitions: &[&Definition]) -> CoverageReport {
+        let mut report = CoverageReport::default();
+        for definition in definitions.iter() {
+            self.walk(&definition.name, &definition.body, &mut report);
+        }
+        report
+    }
+
+    fn walk(&self, name: &str, statements: &[Statement], report: &mut CoverageReport) {
+        for statement in statements.iter() {
+            report.total += 1;
+            if self.has_run(statement) {
+                report.covered += 1;
+            } else {
+                report.uncovered.push(format!("{}: {}", name, describe_statement(statement)));
+            }
+
+            match statement {
+                Statement::IfStatement(i) => self.walk(name, &i.body, report),
+                Statement::WhileStatement(w) => self.walk(name, &w.body, report),
+                Statement::ForeachStatement(f) => self.walk(name, &f.body, report),
+                Statement::MatchStatement(m) => {
+                    for arm in m.arms.iter() {
+                        self.walk(name, &arm.body, report);
+                    }
+                    if let Some(body) = &m.otherwise {
+                        self.walk(name, body, report);
+                    }
+                },
+                _ => ()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct CoverageReport {
+    pub total: usize,
+    pub covered: usize,
+    pub uncovered: Vec<String>
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "coverage: {}/{} statements", self.covered, self.total)?;
+        for statement in self.uncovered.iter() {
+            write!(f, "\nnever ran in {}", statement)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn it_reports_statements_that_never_ran() {
+        let definition = Definition {
+            name: "player_move".to_string(),
+            arguments: vec!(),
+            body: vec!(
+                Statement::IfStatement(IfStatement {
+                    expression: Expression::Bool(false),
+                    body: vec!(Statement::FunctionCall(FunctionCall{ name: "end".to_string(), arguments: vec!(), span: SourceSpan::default() })),
+                    span: SourceSpan::default()
+                })
+            ),
+            span: SourceSpan::default()
+        };
+        let coverage = Coverage::default();
+        coverage.record(&definition.body[0]);
+
+        let report = coverage.report(&[&definition]);
+
+        assert_eq!(report.to_string(), "coverage: 1/2 statements\nnever ran in player_move: end(..)".to_string());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::ast::*;
+use super::{ArgumentValue, INTERNAL_REF, Snapshot, diff};
+use ::std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct StackFrame {
+    pub name: String,
+    pub values: HashMap<String, ArgumentValue>
+}
+
+impl StackFrame {
+    pub fn new(name: &str) -> StackFrame {
+        StackFrame { name: name.to_string(), values: HashMap::new() }
+    }
+
+    fn display_locals(&self) -> Vec<String> {
+        let mut locals = vec!();
+        for (key, value) in self.values.iter() {
+            match value {
+                ArgumentValue::Obj(o) => {
+                    for (attribute, v) in o.iter().filter(|(a, _)| a.as_str() != INTERNAL_REF) {
+                        locals.push(format!("{}:{} = {}", key, attribute, v));
+                    }
+                },
+                ArgumentValue::Value(v) => locals.push(format!("{} = {}", key, v))
+            }
+        }
+        locals.sort();
+        locals
+    }
+}
+
+// the state of the call stack just before a statement was executed
+#[derive(Clone, Debug)]
+struct PausePoint {
+    statement: String,
+    line: u32,
+    frames: Vec<StackFrame>,
+    state: Option<Snapshot>
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+    breakpoints: Vec<String>,
+    pause_points: Vec<PausePoint>,
+    // the table once the move finished, to show what the last statement changed
+    end_state: Option<Snapshot>,
+    position: usize
+}
+
+impl Debugger {
+    pub fn add_breakpoint(&mut self, name: &str) {
+        if !self.breakpoints.iter().any(|b| b == name) {
+            self.breakpoints.push(name.to_string());
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.pause_points = vec!();
+        self.end_state = None;
+        self.position = 0;
+    }
+
+    pub fn watching(&self, call_stack: &[StackFrame]) -> bool {
+        call_stack.iter().any(|frame| self.breakpoints.contains(&frame.name))
+    }
+
+    pub fn record(&mut self, statement: &Statement, call_stack: &[StackFrame], state: Option<Snapshot>) {
+        if !self.watching(call_stack) {
+            return;
+        }
+
+        self.pause_points.push(PausePoint {
+            statement: describe_statement(statement),
+            line: statement.span().start.line,
+            frames: call_stack.to_vec(),
+            state
+        });
+    }
+
+    pub fn finish(&mut self, state: Snapshot) {
+        if !self.pause_points.is_empty() {
+            self.end_state = Some(state);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.position < self.pause_points.len()
+    }
+
+    pub fn current(&self) -> String {
+        match self.pause_points.get(self.position) {
+            Some(point) => {
+                let frame = point.frames.last().map(|f| f.name.as_str()).unwrap_or("");
+                match point.line {
+                    0 => format!("paused in {}: {}", frame, point.statement),
+                    line => format!("paused in {} at line {}: {}", frame, line, point.statement)
+                }
+            },
+            None => "not paused".to_string()
+        }
+    }
+
+    pub fn step(&mut self) -> String {
+        if !self.is_paused() {
+            return "not paused".to_string();
+        }
+
+        let before = self.state_at(self.position);
+        self.position += 1;
+        let next = if self.is_paused() { self.current() } else { "move complete".to_string() };
+
+        // what the statement just stepped over did to the table
+        describe_changes(before, self.state_at(self.position), next)
+    }
+
+    // back over the statement before this one, or the last of the move once it's complete,
+    // listing what it changed. Only the position in the recording moves: the move has already
+    // run, so the table stays as it left it and just `locals` and `stack` follow
+    pub fn back(&mut self) -> String {
+        if self.pause_points.is_empty() {
+            return "not paused".to_string();
+        }
+        if self.position == 0 {
+            return format!("already at the start of the move\n{}", self.current());
+        }
+
+        let after = self.state_at(self.position);
+        self.position -= 1;
+        describe_changes(after, self.state_at(self.position), self.current())
+    }
+
+    // the table just before the statement at `position`, or as the move left it
+    fn state_at(&self, position: usize) -> Option<Snapshot> {
+        match self.pause_points.get(position) {
+            Some(point) => point.state.clone(),
+            None => self.end_state.clone()
+        }
+    }
+
+    pub fn resume(&mut self) -> String {
+        if !self.is_paused() {
+            return "not paused".to_string();
+        }
+
+        self.position = self.pause_points.len();
+        "move complete".to_string()
+    }
+
+    pub fn locals(&self) -> String {
+        match self.pause_points.get(self.position).and_then(|p| p.frames.last()) {
+            Some(frame) => {
+                let locals = frame.display_locals();
+                if locals.is_empty() {
+                    "no locals".to_string()
+                } else {
+                    locals.join("\n")
+                }
+            },
+            None => "not paused".to_string()
+        }
+    }
+
+    pub fn stack(&self) -> String {
+        match self.pause_points.get(self.position) {
+            Some(point) => point.frames.iter().rev().enumerate().map(|(depth, frame)| {
+                format!("{}: {}", depth, frame.name)
+            }).collect::<Vec<String>>().join("\n"),
+            None => "not paused".to_string()
+        }
+    }
+}
+
+fn describe_changes(from: Option<Snapshot>, to: Option<Snapshot>, position: String) -> String {
+    let mut lines: Vec<String> = match (from, to) {
+        (Some(from), Some(to)) => diff(&from, &to).iter().map(|c| c.to_string()).collect(),
+        _ => vec!()
+    };
+    lines.push(position);
+    lines.join("\n")
+}
+
+pub fn describe_statement(statement: &Statement) -> String {
+    match statement {
+        Statement::Transfer(t) => format!("{} > {}", t.from, t.to),
+        Statement::FunctionCall(f) => format!("{}(..)", f.name),
+        Statement::IfStatement(_) => "if(..)".to_string(),
+        Statement::WhileStatement(_) => "while(..)".to_string(),
+        Statement::ForeachStatement(f) => format!("foreach {} in {}", f.variable, f.stack),
+        Statement::MatchStatement(_) => "match(..)".to_string(),
+        Statement::CheckStatement(_) => "check(..)".to_string(),
+        Statement::ReturnStatement(_) => "return(..)".to_string(),
+        Statement::Declaration(_) => "declaration".to_string(),
+        Statement::GameOption(o) => format!("option {}", o.name),
+        Statement::DeckDefinition(d) => format!("deck {}", d.name),
+        Statement::ValueTable(_) => "values".to_string(),
+        Statement::Definition(d) => format!("define {}", d.name)
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn shuffle_statement() -> Statement {
+        Statement::FunctionCall(FunctionCall{
+            name: "shuffle".to_string(),
+            arguments: vec!(),
+            span: SourceSpan::default()
+        })
+    }
+
+    #[test]
+    fn it_only_records_statements_inside_breakpoints() {
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint("player_move");
+
+        debugger.record(&shuffle_statement(), &[StackFrame::new("setup")], None);
+        assert!(!debugger.is_paused());
+
+        debugger.record(&shuffle_statement(), &[StackFrame::new("player_move")], None);
+        assert_eq!(debugger.current(), "paused in player_move: shuffle(..)".to_string());
+    }
+
+    #[test]
+    fn stepping_past_the_last_statement_completes_the_move() {
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint("player_move");
+        debugger.record(&shuffle_statement(), &[StackFrame::new("player_move")], None);
+
+        assert_eq!(debugger.step(), "move complete".to_string());
+        assert_eq!(debugger.step(), "not paused".to_string());
+    }
+
+    #[test]
+    fn stepping_back_returns_to_earlier_statements() {
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint("player_move");
+        debugger.record(&shuffle_statement(), &[StackFrame::new("player_move")], None);
+        debugger.resume();
+
+        assert_eq!(debugger.back(), "paused in player_move: shuffle(..)".to_string());
+        assert_eq!(debugger.back(), "already at the start of the move\npaused in player_move: shuffle(..)".to_string());
+        assert_eq!(debugger.step(), "move complete".to_string());
+    }
+}
@@ -0,0 +1,62 @@
+use ::std::{fmt, time::Duration};
+
+// how much work a game has done since it started, to measure the interpreter against real games
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metrics {
+    pub statements: u64,
+    pub expressions: u64,
+    // copies of the whole runtime, for undo history and for trying out moves
+    pub clones: u64,
+    // every attempt, including the ones that were rejected
+    pub moves: u64,
+    pub move_time: Duration,
+    pub slowest_move: Duration
+}
+
+impl Metrics {
+    pub fn average_move_time(&self) -> Duration {
+        match self.moves {
+            0 => Duration::default(),
+            n => self.move_time / n as u32
+        }
+    }
+
+    pub fn record_move(&mut self, time: Duration) {
+        self.moves += 1;
+        self.move_time += time;
+        self.slowest_move = self.slowest_move.max(time);
+    }
+
+    pub fn rows(&self) -> Vec<(String, String)> {
+        vec!(
+            ("statements executed".to_string(), self.statements.to_string()),
+            ("expressions resolved".to_string(), self.expressions.to_string()),
+            ("clones".to_string(), self.clones.to_string()),
+            ("moves".to_string(), self.moves.to_string()),
+            ("time per move".to_string(), format!("{:?} (slowest {:?})", self.average_move_time(), self.slowest_move))
+        )
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.rows().iter().map(|(label, value)| format!("{}: {}", label, value)).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn move_times_are_averaged() {
+        let mut metrics = Metrics::default();
+        metrics.record_move(Duration::from_millis(2));
+        metrics.record_move(Duration::from_millis(4));
+
+        assert_eq!(metrics.average_move_time(), Duration::from_millis(3));
+        assert_eq!(metrics.slowest_move, Duration::from_millis(4));
+        assert_eq!(Metrics::default().average_move_time(), Duration::default());
+    }
+}
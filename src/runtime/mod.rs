@@ -1,11 +1,45 @@
 mod transfer;
+mod debug;
+mod coverage;
+mod save;
+mod snapshot;
+mod metrics;
+mod builtins;
 pub mod std;
 
 use self::std::*;
 use crate::ast::*;
-use crate::cards::{standard_deck, Card, Player};
-use ::std::{fmt, collections::HashMap};
+use crate::cards::{Card, DeckSpec, Player, RankValues};
+use crate::error::{RuntimeError, Violation};
+use crate::diagnostics::{builtin_name, BUILT_IN_FUNCTIONS};
+use crate::host::{HostHandle, TransferDecision};
+use ::std::{fmt, cell::Cell, collections::HashMap, sync::{Arc, atomic::{AtomicU64, Ordering}}, time::Instant};
 use transfer::{transfer, TransferTarget};
+use debug::{StackFrame, describe_statement};
+pub use debug::Debugger;
+pub use coverage::{Coverage, CoverageReport};
+pub use save::SAVE_VERSION;
+pub use snapshot::{Snapshot, Change, diff};
+pub use metrics::Metrics;
+pub use builtins::{Builtins, NativeFunction};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Direction {
+    Left,
+    Right
+}
+
+// a pass waiting on every player to choose their cards, which they do on their move by putting
+// them in their own `passing` stack
+#[derive(Clone, Debug)]
+struct Pass {
+    count: usize,
+    direction: Direction,
+    chosen: Vec<usize>,
+    // where play goes back to once the cards are handed on
+    phase: String
+}
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum GameState {
@@ -32,36 +66,147 @@ pub enum PrimitiveValue {
     String(String)
 }
 
+impl fmt::Display for PrimitiveValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrimitiveValue::Bool(b) => write!(f, "{}", b),
+            PrimitiveValue::Number(n) => write!(f, "{}", n),
+            PrimitiveValue::Stack(s) => {
+                let cards = s.iter().map(|c| c.to_string()).collect::<Vec<String>>();
+                write!(f, "[{}]", cards.join(", "))
+            },
+            PrimitiveValue::String(s) => write!(f, "{}", s)
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ArgumentValue {
-    Obj(HashMap<String, PrimitiveValue>)
+    Obj(HashMap<String, PrimitiveValue>),
+    // a number or anything else without properties, passed to a define
+    Value(PrimitiveValue)
+}
+
+enum BlockResult {
+    Completed,
+    Returned(PrimitiveValue),
+    CheckFailed(SourceSpan),
+    // out of steps or too deep in calls, with the reason kept in `abandoned`
+    Abandoned
 }
 
 #[derive(Clone, Debug)]
 pub struct InitialValues {
     pub players: u32,
     pub card_stacks: Vec<String>,
+    // stacks every player gets, by the name after `player:`
+    pub player_stacks: Vec<String>,
+    pub teams: Option<u32>,
+    // cards dealt to each player once setup has run
+    pub deal: Option<u32>,
+    // hands are dealt up to this after setup, refilled after each move, and can't end a move any bigger
+    pub hand_size: Option<u32>,
+    // a bank every player starts with - without one, chips can go negative and nobody is eliminated
+    pub chips: Option<f64>,
+    // every player moves once, in any order, before the round is resolved
+    pub simultaneous: bool,
+    // hands are replayed, keeping scores, until a player reaches this
+    pub match_to: Option<f64>,
     pub current_player: usize,
+    // standard decks shuffled together, every card keeping an id of its own
+    pub decks: u32,
+    // the ranks and suits each of those decks is cut down to
+    pub deck: DeckSpec,
+    // what `value(card)` reads for each rank
+    pub values: RankValues,
+    // stacks declared `facedown`, with a player's own stacks as `player:tricks`
+    pub face_down: Vec<String>
 }
 
 #[derive(Clone, Debug)]
+// shared between every copy of a runtime, so forking doesn't copy the game's definitions
 pub struct Callbacks {
-    pub player_move: Option<Definition>,
-    pub setup: Option<Definition>
+    pub player_move: Option<Arc<Definition>>,
+    pub setup: Option<Arc<Definition>>,
+    // every other definition, called by name or handed to builtins that take one
+    pub definitions: HashMap<String, Arc<Definition>>
 }
 
 const INTERNAL_REF: &str = "_ref";
+// the phase every hand starts in, until `phase(scoring)` moves play on
+const FIRST_PHASE: &str = "play";
+// the phase players choose what to pass in, and the stack each of them puts the cards in
+const PASSING: &str = "passing";
+// defines calling defines, deep enough for any real game but short of overflowing the stack
+const MAX_CALL_DEPTH: usize = 64;
+// statements and passes through loops a move, or setup, can take before it's taken to be stuck,
+// counted across every loop in it so nested ones can't multiply, and far more than dealing out
+// several decks needs
+const MAX_STEPS: usize = 100_000;
+// every record from one move shares its span, so a move can be picked out of the log
+static NEXT_SPAN: AtomicU64 = AtomicU64::new(1);
+
+// a record from the move being made, tagged with its span, which forks never write
+macro_rules! log_move {
+    ($runtime:expr, $level:ident, $message:literal $(, $key:ident $(:$capture:tt)? = $value:expr)*) => {
+        if $runtime.logging {
+            log::$level!(target: "cardlang::runtime", $($key $(:$capture)? = $value,)* span = $runtime.log_span; $message);
+        }
+    };
+}
 
 #[derive(Clone, Debug)]
 pub struct Runtime {
     callbacks: Callbacks,
     status: GameState,
-    deck: Vec<Card>,
+    // the deck, players and stacks are shared with forks until one side changes them, through
+    // `Arc::make_mut`, so a fork costs little more than counting references
+    deck: Arc<Vec<Card>>,
+    decks: u32,
+    deck_spec: DeckSpec,
+    values: RankValues,
+    face_down: Vec<String>,
     winners: Vec<f64>,
     current_player: usize,
-    players: Vec<Player>,
-    card_stacks: HashMap<String, Vec<Card>>,
-    call_stack: Vec<HashMap<String, ArgumentValue>>
+    players: Arc<Vec<Player>>,
+    card_stacks: Arc<HashMap<String, Vec<Card>>>,
+    deal: Option<u32>,
+    hand_size: Option<u32>,
+    bank: bool,
+    simultaneous: bool,
+    match_to: Option<f64>,
+    first_player: usize,
+    hand: u32,
+    // moves made and times round the table this hand, as `turn` and `round` in the rules
+    turn: u32,
+    round: u32,
+    // a phase's own callbacks are defined as `scoring:player_move`, and used in place of the game's
+    phase: String,
+    // who has moved so far this round, when turns are simultaneous
+    moved: Vec<usize>,
+    passing: Option<Pass>,
+    pot: f64,
+    call_stack: Vec<StackFrame>,
+    rng: StdRng,
+    debugger: Debugger,
+    tracing: bool,
+    trace: Vec<String>,
+    coverage: Option<Coverage>,
+    host: Option<HostHandle>,
+    builtins: Builtins,
+    // forks are trials, so only the game being played writes log records
+    logging: bool,
+    // the log span of the move being made
+    log_span: Option<u64>,
+    // everything that went wrong during the last move, or setup
+    violations: Vec<Violation>,
+    // what's left of the move's budget, and why it was given up on, once that runs out or its
+    // calls go too deep
+    steps: usize,
+    abandoned: Option<RuntimeError>,
+    metrics: Metrics,
+    // counted apart from the other metrics, as forks are taken from a shared reference
+    clones: Cell<u64>
 }
 
 impl Runtime {
@@ -74,24 +219,145 @@ impl Runtime {
 
         Runtime {
             status: GameState::Pending,
-            deck:  standard_deck(),
+            deck: Arc::new(initial_values.deck.cards(initial_values.decks.max(1))),
+            decks: initial_values.decks.max(1),
+            deck_spec: initial_values.deck.clone(),
+            values: initial_values.values.clone(),
+            face_down: initial_values.face_down.clone(),
             winners: vec!(),
             current_player: initial_values.current_player,
             call_stack: vec!(),
-            card_stacks,
-            players: Self::generate_players(initial_values.players),
-            callbacks
+            card_stacks: Arc::new(card_stacks),
+            deal: initial_values.deal,
+            hand_size: initial_values.hand_size,
+            bank: initial_values.chips.is_some(),
+            simultaneous: initial_values.simultaneous,
+            match_to: initial_values.match_to,
+            first_player: initial_values.current_player,
+            hand: 1,
+            turn: 1,
+            round: 1,
+            phase: FIRST_PHASE.to_string(),
+            moved: vec!(),
+            passing: None,
+            pot: 0.0,
+            players: Arc::new(Self::generate_players(&initial_values)),
+            callbacks,
+            rng: StdRng::from_entropy(),
+            debugger: Debugger::default(),
+            tracing: false,
+            trace: vec!(),
+            coverage: None,
+            host: None,
+            builtins: Builtins::default(),
+            logging: true,
+            log_span: None,
+            violations: vec!(),
+            steps: MAX_STEPS,
+            abandoned: None,
+            metrics: Metrics::default(),
+            clones: Cell::new(0)
+        }
+    }
+
+    // a copy for exploring moves, sharing the cards until either side moves them, and without the
+    // debugger's recordings or any trace
+    pub fn fork(&self) -> Runtime {
+        self.record_clone();
+        Runtime {
+            callbacks: self.callbacks.clone(),
+            status: self.status.clone(),
+            deck: self.deck.clone(),
+            decks: self.decks,
+            deck_spec: self.deck_spec.clone(),
+            values: self.values.clone(),
+            face_down: self.face_down.clone(),
+            winners: self.winners.clone(),
+            current_player: self.current_player,
+            players: self.players.clone(),
+            card_stacks: self.card_stacks.clone(),
+            deal: self.deal,
+            hand_size: self.hand_size,
+            bank: self.bank,
+            simultaneous: self.simultaneous,
+            match_to: self.match_to,
+            first_player: self.first_player,
+            hand: self.hand,
+            turn: self.turn,
+            round: self.round,
+            phase: self.phase.clone(),
+            moved: self.moved.clone(),
+            passing: self.passing.clone(),
+            pot: self.pot,
+            call_stack: vec!(),
+            rng: self.rng.clone(),
+            debugger: Debugger::default(),
+            tracing: false,
+            trace: vec!(),
+            coverage: None,
+            host: None,
+            builtins: self.builtins.clone(),
+            logging: false,
+            log_span: None,
+            violations: vec!(),
+            steps: MAX_STEPS,
+            abandoned: None,
+            metrics: Metrics::default(),
+            clones: Cell::new(0)
         }
     }
 
+    pub fn record_clone(&self) {
+        self.clones.set(self.clones.get() + 1);
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        Metrics { clones: self.clones.get(), ..self.metrics.clone() }
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn set_host(&mut self, host: Option<HostHandle>) {
+        self.host = host;
+    }
+
+    pub fn set_coverage(&mut self, coverage: Option<Coverage>) {
+        self.coverage = coverage;
+    }
+
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.tracing = tracing;
+    }
+
+    pub fn take_trace(&mut self) -> Vec<String> {
+        ::std::mem::take(&mut self.trace)
+    }
+
+    fn trace(&mut self, line: String) {
+        if self.tracing {
+            let depth = self.call_stack.len().saturating_sub(1);
+            self.trace.push(format!("{}{}", "  ".repeat(depth), line));
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // the game's own defines come first, so a builtin added later can't change what a game means
     fn handle_function_call(&mut self, f: &FunctionCall) -> Option<PrimitiveValue>{
-        match f.name.as_str() {
+        if let Some(definition) = self.callbacks.definitions.get(&f.name).cloned() {
+            return self.call_definition(&definition, f);
+        }
+        match builtin_name(&f.name) {
             "end" => {
                 end(&mut self.status);
                 None
             },
             "shuffle" => {
-                shuffle(&mut self.deck);
+                self.shuffle_stack(f);
                 None
             },
             "winner" => {
@@ -103,37 +369,293 @@ impl Runtime {
                 winner(&mut self.winners, player_id);
                 None
             },
+            "shuffle_into" => {
+                if let (Some(Expression::Symbol(from)), Some(Expression::Symbol(to))) = (f.arguments.first(), f.arguments.get(1)) {
+                    self.shuffle_into(from, to);
+                }
+                None
+            },
+            "move_all_matching" => {
+                if let [Expression::Symbol(from), Expression::Symbol(to), Expression::Symbol(predicate)] = f.arguments.as_slice() {
+                    if let Some(predicate) = self.callbacks.definitions.get(predicate).cloned() {
+                        self.move_all_matching(from, to, &predicate);
+                    }
+                }
+                None
+            },
+            "exchange" => {
+                let players = (self.player_index(f.arguments.first()), self.player_index(f.arguments.get(1)));
+                let count = f.arguments.get(2).map(|n| self.resolve_expression(n));
+                match (players, count) {
+                    ((Some(a), Some(b)), None) => self.exchange(a + 1, b + 1, None),
+                    ((Some(a), Some(b)), Some(PrimitiveValue::Number(n))) => self.exchange(a + 1, b + 1, Some(n as usize)),
+                    _ => ()
+                }
+                None
+            },
+            "burn" => {
+                let n = match f.arguments.first().map(|a| self.resolve_expression(a)) {
+                    Some(PrimitiveValue::Number(n)) => n as usize,
+                    _ => 1
+                };
+                let pile = Arc::make_mut(&mut self.card_stacks).entry(BURN_PILE.to_string()).or_default();
+                burn(Arc::make_mut(&mut self.deck), pile, n);
+                None
+            },
             "count" => {
-                let stack_to_count = self.resolve_expression(&f.arguments[0]);
-                let c = count(stack_to_count);
+                let c = match f.arguments.first().map(|a| self.resolve_expression(a)) {
+                    // a name that isn't a value is a stack, `count(middle)`
+                    Some(PrimitiveValue::String(name)) => self.get_stack(&name).map(|s| s.cards().len()).unwrap_or(0),
+                    Some(stack_to_count) => count(stack_to_count),
+                    None => 0
+                };
                 Some(PrimitiveValue::Number(c as f64))
             },
+            "summary" => {
+                let cards = self.stack_argument(f.arguments.first());
+                let lines: Vec<String> = summary(&cards).iter().map(|(label, value)| format!("{}: {}", label, value)).collect();
+                Some(PrimitiveValue::String(lines.join("\n")))
+            },
+            "chance" => {
+                let stack = self.stack_argument(f.arguments.first());
+                let predicate = match f.arguments.get(1) {
+                    Some(Expression::Symbol(name)) => self.callbacks.definitions.get(name).cloned(),
+                    _ => None
+                };
+                let chance = match predicate {
+                    Some(predicate) if !stack.is_empty() => {
+                        let total = stack.len();
+                        let face = match f.arguments.first() {
+                            Some(Expression::Symbol(name)) => self.facing(name),
+                            _ => "up"
+                        };
+                        self.filter(stack, &predicate, face).len() as f64 / total as f64
+                    },
+                    _ => 0.0
+                };
+                Some(PrimitiveValue::Number(chance))
+            },
+            "count_where" => {
+                let stack = match f.arguments.first().map(|a| self.resolve_expression(a)) {
+                    Some(PrimitiveValue::Stack(cards)) => cards,
+                    _ => vec!()
+                };
+                let predicate = match f.arguments.get(1) {
+                    Some(Expression::Symbol(name)) => self.callbacks.definitions.get(name).cloned(),
+                    _ => None
+                };
+                let c = match predicate {
+                    Some(predicate) => {
+                        let face = match f.arguments.first() {
+                            Some(Expression::Symbol(name)) => self.facing(name),
+                            _ => "up"
+                        };
+                        self.filter(stack, &predicate, face).len()
+                    },
+                    None => 0
+                };
+                Some(PrimitiveValue::Number(c as f64))
+            },
+            "pass" => {
+                let n = match f.arguments.first().map(|a| self.resolve_expression(a)) {
+                    Some(PrimitiveValue::Number(n)) => n as usize,
+                    _ => 1
+                };
+                match f.arguments.get(1) {
+                    Some(Expression::Symbol(d)) if d == "left" => self.open_pass(n, Direction::Left),
+                    Some(Expression::Symbol(d)) if d == "right" => self.open_pass(n, Direction::Right),
+                    _ => ()
+                }
+                None
+            },
             "next_player" => {
-                self.current_player = if self.current_player < self.players.len() {
-                    self.current_player + 1
-                } else {
-                    1
+                self.advance_player();
+                None
+            },
+            "add_score" => {
+                let points = f.arguments.get(1).map(|a| self.resolve_expression(a));
+                if let (Some(index), Some(PrimitiveValue::Number(n))) = (self.player_index(f.arguments.first()), points) {
+                    let player = &mut Arc::make_mut(&mut self.players)[index];
+                    player.set_score(player.score() + n);
+                }
+                None
+            },
+            "phase" => match f.arguments.first().map(|a| self.resolve_expression(a)) {
+                Some(PrimitiveValue::String(phase)) => {
+                    self.enter_phase(&phase);
+                    None
+                },
+                _ => Some(PrimitiveValue::String(self.phase.to_string()))
+            },
+            "value" => Some(PrimitiveValue::Number(self.card_value(f.arguments.first()))),
+            "get_score" => self.player_index(f.arguments.first()).map(|index| PrimitiveValue::Number(self.players[index].score())),
+            "pay" => {
+                let (from, to) = (self.player_index(f.arguments.first()), self.player_index(f.arguments.get(1)));
+                let amount = f.arguments.get(2).map(|n| self.resolve_expression(n));
+                if let (Some(from), Some(to), Some(PrimitiveValue::Number(n))) = (from, to, amount) {
+                    self.pay(from, to, n);
+                }
+                None
+            },
+            "bet" | "raise" | "call" | "fold" | "award_pot" => {
+                let player = self.player_index(f.arguments.first())?;
+                let amount = match f.arguments.get(1).map(|n| self.resolve_expression(n)) {
+                    Some(PrimitiveValue::Number(n)) => n,
+                    _ => 0.0
                 };
+                match builtin_name(&f.name) {
+                    "bet" => self.bet(player, amount),
+                    "raise" => {
+                        let to_call = self.highest_bet() - self.players[player].bet();
+                        self.bet(player, to_call + amount);
+                    },
+                    "call" => {
+                        let to_call = self.highest_bet() - self.players[player].bet();
+                        self.bet(player, to_call);
+                    },
+                    "fold" => Arc::make_mut(&mut self.players)[player].set_folded(true),
+                    _ => self.award_pot(player)
+                }
                 None
             },
-            _ => None
+            "bets_settled" => {
+                let highest = self.highest_bet();
+                let settled = self.players.iter().filter(|p| !p.folded()).all(|p| p.bet() == highest);
+                Some(PrimitiveValue::Bool(settled))
+            },
+            "end_betting" => {
+                Arc::make_mut(&mut self.players).iter_mut().for_each(|p| p.set_bet(0.0));
+                None
+            },
+            name => self.call_native(name, f)
         }        
     }
 
+    // players and cards are passed as they are, anything else as its value. Every call costs a
+    // step, so a define calling itself twice over runs out like a loop would, and one calling
+    // itself too deep gives the move up the same way
+    fn call_definition(&mut self, definition: &Definition, f: &FunctionCall) -> Option<PrimitiveValue> {
+        if !self.take_step() {
+            return None;
+        }
+        if self.call_stack.len() >= MAX_CALL_DEPTH {
+            self.trace(format!("{} stopped {} calls deep", f.name, MAX_CALL_DEPTH));
+            log_move!(self, warn, "call too deep", name:% = f.name);
+            self.abandoned = Some(RuntimeError::TooDeep(f.name.to_string(), MAX_CALL_DEPTH));
+            return None;
+        }
+        let mut frame = StackFrame::new(&definition.name);
+        for (name, argument) in definition.arguments.iter().zip(f.arguments.iter()) {
+            let passed = match argument {
+                Expression::Symbol(s) => self.find_in_call_stack(s),
+                _ => None
+            };
+            let value = passed.unwrap_or_else(|| ArgumentValue::Value(self.resolve_expression(argument)));
+            frame.values.insert(name.to_string(), value);
+        }
+        self.call_stack.push(frame);
+        let result = self.handle_statements(&definition.body);
+        self.call_stack.pop();
+        Some(result)
+    }
+
+    // a player passed as they are, `add_score(player, 2)`, or by seat, `add_score(player:id, 2)`
+    fn player_index(&mut self, argument: Option<&Expression>) -> Option<usize> {
+        let passed = match argument {
+            Some(Expression::Symbol(s)) => match self.find_in_call_stack(s) {
+                Some(ArgumentValue::Obj(o)) => o.get("id").cloned(),
+                _ => None
+            },
+            _ => None
+        };
+        match passed.or_else(|| argument.map(|a| self.resolve_expression(a))) {
+            Some(PrimitiveValue::Number(n)) if n >= 1.0 && n as usize <= self.players.len() => Some(n as usize - 1),
+            _ => None
+        }
+    }
+
+    // what a card is worth to the game, or a stack of them added up
+    fn card_value(&mut self, argument: Option<&Expression>) -> f64 {
+        if let Some(Expression::Symbol(s)) = argument {
+            if let Some(ArgumentValue::Obj(o)) = self.find_in_call_stack(s) {
+                return match o.get("value") {
+                    Some(PrimitiveValue::Number(n)) => *n,
+                    _ => 0.0
+                };
+            }
+        }
+        self.stack_argument(argument).iter().map(|card| self.values.value(card.rank())).sum()
+    }
+
+    // a stack given by name, like a transfer, so the deck and shared stacks work as well as player:hand
+    fn stack_argument(&mut self, argument: Option<&Expression>) -> Vec<Card> {
+        if let Some(Expression::Symbol(name)) = argument {
+            if let Some(stack) = self.get_stack(name) {
+                return stack.cards();
+            }
+        }
+        match argument.map(|a| self.resolve_expression(a)) {
+            Some(PrimitiveValue::Stack(cards)) => cards,
+            _ => vec!()
+        }
+    }
+
     pub fn get_status(&self) -> String {
         format!("{}", self.status)
     }
 
+    pub fn is_active(&self) -> bool {
+        self.status == GameState::Active
+    }
+
     pub fn get_current_player(&self) -> usize {
         self.current_player
     }
 
     pub fn get_deck(&self) -> Vec<Card> {
-        self.deck.clone()
+        self.deck.to_vec()
+    }
+
+    pub fn deck(&self) -> &[Card] {
+        &self.deck
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn status(&self) -> GameState {
+        self.status.clone()
+    }
+
+    pub fn winners(&self) -> &[f64] {
+        &self.winners
+    }
+
+    // for a player who runs out of time without a legal move
+    pub fn pass_turn(&mut self) {
+        self.advance_player();
+    }
+
+    // play passing back round to a seat at or before the last one starts another round
+    fn advance_player(&mut self) {
+        let next = self.next_in_hand(self.current_player);
+        if next <= self.current_player {
+            self.round += 1;
+        }
+        self.current_player = next;
+    }
+
+    pub fn pot(&self) -> f64 {
+        self.pot
+    }
+
+    pub fn stack(&self, key: &str) -> Option<&[Card]> {
+        self.card_stacks.get(key).map(|s| s.as_slice())
     }
 
     pub fn get_players(&self) -> Vec<Player> {
-        self.players.clone()
+        self.players.to_vec()
     }
 
     pub fn get_player(&self, n: usize) -> Player {
@@ -144,66 +666,461 @@ impl Runtime {
         self.winners.clone()
     }
 
-    pub fn player_move(&mut self, n: usize) {
-        if self.status != GameState::Active {
-            return;
+    pub fn player_move(&mut self, n: usize) -> bool {
+        self.try_player_move(n).is_ok()
+    }
+
+    pub fn try_player_move(&mut self, n: usize) -> Result<(), RuntimeError> {
+        self.violations.clear();
+        self.log_span = if self.logging && log::log_enabled!(target: "cardlang::runtime", log::Level::Info) {
+            Some(NEXT_SPAN.fetch_add(1, Ordering::Relaxed))
+        } else {
+            None
+        };
+        log_move!(self, debug, "move started", player = n);
+        let started = Instant::now();
+        let result = self.make_move(n);
+        let elapsed = started.elapsed();
+        self.metrics.record_move(elapsed);
+        match &result {
+            // already recorded, along with any other checks that failed
+            Err(RuntimeError::CheckFailed(..)) | Ok(()) => (),
+            Err(e) => self.violations.push(Violation::Rejected(e.clone()))
+        }
+        let outcome = match &result {
+            Ok(()) => "accepted".to_string(),
+            Err(e) => e.to_string()
+        };
+        log_move!(self, info, "move", player = n, hand = self.hand, result:% = outcome, elapsed:? = elapsed);
+        self.log_span = None;
+        result
+    }
+
+    fn make_move(&mut self, n: usize) -> Result<(), RuntimeError> {
+        if !self.is_active() {
+            return Err(RuntimeError::NotActive);
+        }
+
+        if n == 0 || n > self.players.len() {
+            return Err(RuntimeError::UnknownPlayer(n));
         }
 
-        let p_move = self.callbacks.player_move.clone().unwrap();
+        if self.players[n - 1].eliminated() {
+            return Err(RuntimeError::Eliminated(n));
+        }
+
+        // a player choosing what to pass chooses once, whoever's turn it is
+        let choosing = self.passing.is_some();
+        if self.moved.contains(&n) || self.passing.as_ref().is_some_and(|p| p.chosen.contains(&n)) {
+            return Err(RuntimeError::AlreadyMoved(n));
+        }
 
-        let mut call_stack_frame = HashMap::new();
+        let p_move = match self.phase_callback("player_move").or_else(|| self.callbacks.player_move.clone()) {
+            Some(p) => p,
+            None => return Err(RuntimeError::NoPlayerMove)
+        };
+
+        let mut call_stack_frame = StackFrame::new(&p_move.name);
         match p_move.arguments.get(0) {
             Some(arg) => {
                 let player = self.players[n - 1].clone();
-                call_stack_frame.insert(arg.clone(), Self::build_player_object(player));
+                let is_current = n == self.current_player;
+                call_stack_frame.values.insert(arg.clone(), Self::build_player_object(player, is_current));
             },
             None => ()
         }
+        let before = (self.hand_size.is_some() || choosing).then(|| self.fork());
+        self.debugger.reset();
+        self.refill_steps();
+        self.call_stack.push(call_stack_frame);
+        let result = self.run_block(&p_move.body);
+        self.call_stack.pop();
+        let state = self.snapshot();
+        self.debugger.finish(state);
+
+        if let Some(e) = self.abandoned.take() {
+            return Err(e);
+        }
+
+        if let BlockResult::CheckFailed(span) = result {
+            return Err(RuntimeError::CheckFailed(p_move.name.clone(), span.start));
+        }
+
+        if let Some(limit) = self.hand_size {
+            self.draw_up_to(n - 1, limit);
+            let cards = self.players[n - 1].hand().len();
+            if cards > limit as usize {
+                if let Some(before) = before {
+                    self.restore(before);
+                }
+                return Err(RuntimeError::HandSizeExceeded(n, cards, limit));
+            }
+        }
+
+        // a hand too small to pass the full count passes all it has
+        if let Some(count) = self.passing.as_ref().filter(|_| choosing).map(|p| p.count) {
+            let player = &self.players[n - 1];
+            let chosen = player.stack(PASSING).map_or(0, |cards| cards.len());
+            if chosen > count || (chosen < count && !player.hand().is_empty()) {
+                if let Some(before) = before {
+                    self.restore(before);
+                }
+                return Err(RuntimeError::WrongPass(n, chosen, count));
+            }
+            if let Some(pass) = self.passing.as_mut() {
+                pass.chosen.push(n);
+            }
+            self.hand_on_passes();
+        } else if self.simultaneous {
+            self.moved.push(n);
+            self.resolve_round();
+        }
+        self.turn += 1;
+        self.finish_hand();
+        Ok(())
+    }
+
+    // a hand that ends short of the match's target is followed by another from a fresh deck,
+    // and once someone reaches it the highest scores win the match
+    fn finish_hand(&mut self) {
+        let target = match self.match_to {
+            Some(target) if self.status == GameState::GameOver => target,
+            _ => return
+        };
+
+        let top = self.players.iter().map(|p| p.score()).fold(f64::NEG_INFINITY, f64::max);
+        if top >= target {
+            self.winners = self.players.iter().filter(|p| p.score() == top).map(|p| p.get_id() as f64).collect();
+            return;
+        }
+
+        self.hand += 1;
+        self.turn = 1;
+        self.round = 1;
+        self.phase = FIRST_PHASE.to_string();
+        self.passing = None;
+        self.deck = Arc::new(self.deck_spec.cards(self.decks));
+        let deck = Arc::make_mut(&mut self.deck);
+        shuffle(deck, &mut self.rng);
+        for stack in Arc::make_mut(&mut self.card_stacks).values_mut() {
+            stack.clear();
+        }
+        for player in Arc::make_mut(&mut self.players).iter_mut() {
+            player.set_hand(vec!());
+            let stacks: Vec<String> = player.stacks().keys().cloned().collect();
+            for stack in stacks.iter() {
+                player.set_stack(stack, vec!());
+            }
+            player.set_bet(0.0);
+            player.set_folded(false);
+        }
+        self.winners.clear();
+        self.moved.clear();
+        self.pot = 0.0;
+        self.current_player = self.first_player;
+        self.setup();
+    }
+
+    pub fn hand_number(&self) -> u32 {
+        self.hand
+    }
+
+    pub fn turn_number(&self) -> u32 {
+        self.turn
+    }
+
+    pub fn round_number(&self) -> u32 {
+        self.round
+    }
+
+    pub fn phase(&self) -> &str {
+        &self.phase
+    }
+
+    // the phase's own version of a callback, as `define scoring:player_move(player)`
+    fn phase_callback(&self, name: &str) -> Option<Arc<Definition>> {
+        self.callbacks.definitions.get(&format!("{}:{}", self.phase, name)).cloned()
+    }
+
+    // `define scoring:setup()` runs as play enters the scoring phase
+    fn enter_phase(&mut self, phase: &str) {
+        self.phase = phase.to_string();
+        self.trace(format!("phase {}", phase));
+        if let Some(setup) = self.phase_callback("setup") {
+            self.call_stack.push(StackFrame::new(&setup.name));
+            self.handle_statements(&setup.body);
+            self.call_stack.pop();
+        }
+    }
+
+    // once everyone still in has moved, `define resolve()` compares what they played
+    fn resolve_round(&mut self) {
+        let waiting = self.players.iter().any(|p| !p.eliminated() && !self.moved.contains(&(p.get_id() as usize)));
+        if waiting {
+            return;
+        }
+
+        self.moved.clear();
+        self.round += 1;
+        if let Some(resolve) = self.phase_callback("resolve").or_else(|| self.callbacks.definitions.get("resolve").cloned()) {
+            self.call_stack.push(StackFrame::new(&resolve.name));
+            self.handle_statements(&resolve.body);
+            self.call_stack.pop();
+        }
+    }
+
+    // tops a hand up from the deck, leaving it as it is once the deck runs out
+    fn draw_up_to(&mut self, player: usize, limit: u32) {
+        while self.players[player].hand().len() < limit as usize {
+            let card = match Arc::make_mut(&mut self.deck).pop() {
+                Some(card) => card,
+                None => break
+            };
+            let mut hand = self.players[player].get_hand();
+            hand.push(card);
+            Arc::make_mut(&mut self.players)[player].set_hand(hand);
+        }
+    }
+
+    // puts the table back as it was, keeping the debugger and trace of the rejected move
+    fn restore(&mut self, before: Runtime) {
+        self.status = before.status;
+        self.deck = before.deck;
+        self.winners = before.winners;
+        self.current_player = before.current_player;
+        self.round = before.round;
+        self.phase = before.phase;
+        self.passing = before.passing;
+        self.players = before.players;
+        self.card_stacks = before.card_stacks;
+        self.pot = before.pot;
+        self.rng = before.rng;
+    }
+
+    pub fn random_index(&mut self, n: usize) -> usize {
+        self.rng.gen_range(0..n)
+    }
+
+    // picks the move `define bot_move(player)` scores highest, at random between ties,
+    // or at random from them all when the game has no bot_move
+    pub fn choose_move(&mut self, legal_moves: &[usize]) -> Option<usize> {
+        let bot_move = self.callbacks.definitions.get("bot_move").cloned();
+        let best: Vec<usize> = match bot_move {
+            Some(bot_move) => {
+                let scores: Vec<f64> = legal_moves.iter().map(|&n| self.fork().bot_score(&bot_move, n)).collect();
+                let top = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                legal_moves.iter().zip(scores.iter()).filter(|(_, s)| **s == top).map(|(m, _)| *m).collect()
+            },
+            None => legal_moves.to_vec()
+        };
+
+        if best.is_empty() {
+            return None;
+        }
+        // from a fork, so choosing leaves the game's shuffles as they would have been and replays match
+        let choice = self.fork().random_index(best.len());
+        Some(best[choice])
+    }
+
+    // true counts as 1 and anything that isn't a number as 0
+    fn bot_score(&mut self, bot_move: &Definition, n: usize) -> f64 {
+        let mut call_stack_frame = StackFrame::new(&bot_move.name);
+        if let Some(arg) = bot_move.arguments.first() {
+            let player = self.players[n - 1].clone();
+            call_stack_frame.values.insert(arg.clone(), Self::build_player_object(player, n == self.current_player));
+        }
         self.call_stack.push(call_stack_frame);
-        self.handle_statements(&p_move.body.clone());
+        let score = self.handle_statements(&bot_move.body);
         self.call_stack.pop();
+        match score {
+            PrimitiveValue::Number(s) => s,
+            PrimitiveValue::Bool(true) => 1.0,
+            _ => 0.0
+        }
     }
 
     pub fn setup(&mut self) {
+        self.violations.clear();
         self.status = GameState::Active;
         let setup = self.callbacks.setup.clone();
         match setup {
-            Some(setup) => { self.handle_statements(&setup.body.clone()); },
+            Some(setup) => {
+                self.debugger.reset();
+                self.refill_steps();
+                self.call_stack.push(StackFrame::new(&setup.name));
+                self.handle_statements(&setup.body);
+                self.call_stack.pop();
+                if let Some(e) = self.abandoned.take() {
+                    self.violations.push(Violation::Rejected(e));
+                }
+            },
             _ => ()
         }
+
+        // after setup, so a shuffle there happens first
+        if let Some(n) = self.deal {
+            let deal = Transfer{
+                from: "deck".to_string(),
+                to: "players".to_string(),
+                modifier: None,
+                count: None,
+                span: SourceSpan::default()
+            };
+            for _ in 0..n {
+                self.handle_transfer(&deal);
+            }
+        }
+
+        // a card at a time around the table, like a deal
+        if let Some(limit) = self.hand_size {
+            for round in 1..=limit {
+                for player in 0..self.players.len() {
+                    self.draw_up_to(player, round);
+                }
+            }
+        }
+    }
+
+    pub fn debugger(&mut self) -> &mut Debugger {
+        &mut self.debugger
     }
 
     fn handle_statements(&mut self, statements: &Vec<Statement>) -> PrimitiveValue {
-        let default_return = PrimitiveValue::Bool(false);
-        for statement in statements.iter() {
+        match self.run_block(statements) {
+            BlockResult::Returned(value) => value,
+            _ => PrimitiveValue::Bool(false)
+        }
+    }
+
+    fn refill_steps(&mut self) {
+        self.steps = MAX_STEPS;
+        self.abandoned = None;
+    }
+
+    // false once the budget is spent, when whatever is running should stop where it is
+    fn take_step(&mut self) -> bool {
+        if self.abandoned.is_some() {
+            return false;
+        }
+        match self.steps.checked_sub(1) {
+            Some(steps) => self.steps = steps,
+            None => {
+                let name = self.call_stack.last().map(|frame| frame.name.to_string()).unwrap_or_default();
+                self.trace(format!("{} stopped after {} steps", name, MAX_STEPS));
+                log_move!(self, warn, "out of steps", name:% = name);
+                self.abandoned = Some(RuntimeError::OutOfSteps(name, MAX_STEPS));
+            }
+        }
+        self.abandoned.is_none()
+    }
+
+    fn run_block(&mut self, statements: &[Statement]) -> BlockResult {
+        for (i, statement) in statements.iter().enumerate() {
+            if !self.take_step() {
+                return BlockResult::Abandoned;
+            }
+            self.metrics.statements += 1;
+            let state = if self.debugger.watching(&self.call_stack) { Some(self.snapshot()) } else { None };
+            self.debugger.record(statement, &self.call_stack, state);
+            if let Some(coverage) = &self.coverage {
+                coverage.record(statement);
+            }
+            // blocks trace their own statements as they run them
+            let before = match statement {
+                Statement::Transfer(_) | Statement::FunctionCall(_) if self.tracing => Some(self.snapshot()),
+                _ => None
+            };
             match statement {
                 Statement::Transfer(t) => self.handle_transfer(t),
                 Statement::FunctionCall(f) => {
+                    if self.is_builtin(&f.name) || self.callbacks.definitions.contains_key(&f.name) {
+                        self.trace(describe_statement(statement));
+                    } else {
+                        let hint = crate::suggest::did_you_mean(&f.name, &BUILT_IN_FUNCTIONS);
+                        self.trace(format!("{} - unknown function, ignored{}", describe_statement(statement), hint));
+                    }
+                    log_move!(self, trace, "function call", name:% = f.name, known = self.is_builtin(&f.name));
                     let _ = self.handle_function_call(f);
                 },
                 Statement::IfStatement(i) => self.handle_if_statement(i),
+                Statement::WhileStatement(w) => self.handle_while_statement(w),
+                Statement::ForeachStatement(f) => self.handle_foreach_statement(f),
+                Statement::MatchStatement(m) => self.handle_match_statement(m),
                 Statement::CheckStatement(c) => {
-                    if !self.resolve_to_bool(&c.expression) {
-                        return default_return;
+                    let passed = self.resolve_to_bool(&c.expression);
+                    self.trace(format!("{} -> {}", describe_statement(statement), passed));
+                    if !passed {
+                        self.record_failed_check(c);
+                        self.record_remaining_checks(&statements[i + 1..]);
+                        return BlockResult::CheckFailed(c.span);
                     }
                 },
                 Statement::ReturnStatement(r) => {
-                    return self.resolve_expression(&r.expression);
+                    let value = self.resolve_expression(&r.expression);
+                    self.trace(format!("{} -> {}", describe_statement(statement), value));
+                    return BlockResult::Returned(value);
                 }
                 _ => ()
             }
+            if let Some(before) = before {
+                for change in diff(&before, &self.snapshot()) {
+                    self.trace(format!("  {}", change));
+                }
+            }
+        }
+
+        BlockResult::Completed
+    }
+
+    fn record_failed_check(&mut self, check: &CheckStatement) {
+        let name = self.call_stack.last().map(|frame| frame.name.to_string()).unwrap_or_default();
+        self.violations.push(Violation::CheckFailed(name, check.span.start));
+    }
+
+    // the move is already refused, but the checks after the one that failed can still say why else it would be
+    fn record_remaining_checks(&mut self, statements: &[Statement]) {
+        for statement in statements.iter() {
+            if let Statement::CheckStatement(c) = statement {
+                if !self.resolve_to_bool(&c.expression) {
+                    self.record_failed_check(c);
+                }
+            }
         }
+    }
 
-        default_return
+    // `"{player:id} wins"` reads what's between the braces as an expression, and leaves anything
+    // that isn't one as written
+    fn interpolate(&mut self, text: &str) -> String {
+        let mut interpolated = String::new();
+        let mut rest = text;
+        while let Some((open, close)) = rest.find('{').and_then(|open| Some((open, open + rest[open..].find('}')?))) {
+            interpolated.push_str(&rest[..open]);
+            let inner = crate::lex::lexer(&rest[open + 1..close]).ok()
+                .and_then(|tokens| crate::parse::parse_expression(&tokens).ok());
+            match inner {
+                Some(expression) => interpolated.push_str(&self.resolve_expression(&expression).to_string()),
+                None => interpolated.push_str(&rest[open..=close])
+            }
+            rest = &rest[close + 1..];
+        }
+        interpolated.push_str(rest);
+        interpolated
     }
 
     fn resolve_expression(&mut self, expression: &Expression) -> PrimitiveValue {
+        self.metrics.expressions += 1;
         match expression {
             // todo - could push globals into top of call stack
             Expression::Symbol(s) => {
                 if s == "current_player" {
                     return PrimitiveValue::Number(self.current_player as f64);
                 }
+                if s == "pot" {
+                    return PrimitiveValue::Number(self.pot);
+                }
+                if let (true, Some(n)) = (s == "hand_size", self.hand_size) {
+                    return PrimitiveValue::Number(n as f64);
+                }
                 let components: Vec<&str> = s.split(&[':'][..]).collect();
                 match self.find_in_call_stack(components[0]) {
                     Some(ArgumentValue::Obj(o)) if components.len() > 1 => {
@@ -212,61 +1129,392 @@ impl Runtime {
                             None => PrimitiveValue::Bool(false)
                         }
                     },
+                    Some(ArgumentValue::Value(v)) if components.len() == 1 => v,
+                    // after the call stack, so an argument called round is still read as itself
+                    _ if s == "turn" => PrimitiveValue::Number(self.turn as f64),
+                    _ if s == "round" => PrimitiveValue::Number(self.round as f64),
                     _ => PrimitiveValue::String(s.to_string())
                 }
             },
             Expression::FunctionCall(f) => self.handle_function_call(&f).unwrap_or(PrimitiveValue::Bool(false)),
+            Expression::String(s) => PrimitiveValue::String(self.interpolate(s)),
             Expression::Number(n) => PrimitiveValue::Number(*n),
-            Expression::Bool(_) | Expression::Comparison(_) => PrimitiveValue::Bool(self.resolve_to_bool(expression)),
-            _ => PrimitiveValue::Bool(false)
+            Expression::Arithmetic(a) => {
+                match (self.resolve_expression(&a.left), self.resolve_expression(&a.right)) {
+                    // nothing can be divided by zero, which is false like any other value that isn't a number
+                    (PrimitiveValue::Number(_), PrimitiveValue::Number(right))
+                        if right == 0.0 && matches!(a.operator, Operator::Divide | Operator::Modulo) => PrimitiveValue::Bool(false),
+                    (PrimitiveValue::Number(left), PrimitiveValue::Number(right)) => PrimitiveValue::Number(match a.operator {
+                        Operator::Add => left + right,
+                        Operator::Subtract => left - right,
+                        Operator::Multiply => left * right,
+                        Operator::Divide => left / right,
+                        Operator::Modulo => left.rem_euclid(right)
+                    }),
+                    _ => PrimitiveValue::Bool(false)
+                }
+            },
+            Expression::Bool(_) | Expression::Comparison(_) | Expression::Between(_) | Expression::And(_) | Expression::Or(_) => {
+                PrimitiveValue::Bool(self.resolve_to_bool(expression))
+            }
         }
     }
 
-    fn generate_players(n: u32) -> Vec<Player>{
+    // with `teams 2`, players 1 and 3 are on team 1 and players 2 and 4 on team 2
+    fn generate_players(initial_values: &InitialValues) -> Vec<Player>{
         let mut players = vec!();
-        for i in 0..n {
-            players.push(
-                Player::new(i + 1)
-            );
+        for i in 0..initial_values.players {
+            let mut player = Player::new(i + 1);
+            if let Some(teams) = initial_values.teams.filter(|t| *t > 0) {
+                player.set_team(i % teams + 1);
+            }
+            for stack in initial_values.player_stacks.iter() {
+                player.set_stack(stack, vec!());
+            }
+            if let Some(chips) = initial_values.chips {
+                player.set_chips(chips);
+            }
+            players.push(player);
         }
         players
     }
 
-    fn build_player_object(player: Player) -> ArgumentValue {
+    fn build_player_object(player: Player, is_current: bool) -> ArgumentValue {
         let id = player.get_id();
         let mut player_object = HashMap::new();
         let internal_ref = format!("players:{}", id as usize - 1);
         player_object.insert(INTERNAL_REF.to_string(), PrimitiveValue::String(internal_ref));
         player_object.insert("id".to_string(), PrimitiveValue::Number(id as f64));
         player_object.insert("hand".to_string(), PrimitiveValue::Stack(player.get_hand()));
+        player_object.insert("score".to_string(), PrimitiveValue::Number(player.score()));
+        player_object.insert("team".to_string(), PrimitiveValue::Number(player.team() as f64));
+        player_object.insert("is_current".to_string(), PrimitiveValue::Bool(is_current));
+        player_object.insert("chips".to_string(), PrimitiveValue::Number(player.chips()));
+        player_object.insert("bet".to_string(), PrimitiveValue::Number(player.bet()));
+        player_object.insert("folded".to_string(), PrimitiveValue::Bool(player.folded()));
+        player_object.insert("eliminated".to_string(), PrimitiveValue::Bool(player.eliminated()));
+        for (name, cards) in player.stacks().iter() {
+            player_object.insert(name.to_string(), PrimitiveValue::Stack(cards.clone()));
+        }
         ArgumentValue::Obj(player_object)
     }
 
-    fn build_card_object(card: Card) -> ArgumentValue {
+    // `value` is what the game's `values { .. }` make the card worth, and `face` is "down" for
+    // a card in a stack declared `facedown`
+    fn build_card_object(&self, card: Card, face: &str) -> ArgumentValue {
         let mut card_object = HashMap::new();
         card_object.insert("rank".to_string(), PrimitiveValue::String(card.get_rank_str()));
         card_object.insert("suit".to_string(), PrimitiveValue::String(card.get_suit_str()));
+        card_object.insert("color".to_string(), PrimitiveValue::String(card.get_color_str()));
+        card_object.insert("value".to_string(), PrimitiveValue::Number(self.values.value(card.rank())));
+        card_object.insert("id".to_string(), PrimitiveValue::Number(card.id() as f64));
+        card_object.insert("face".to_string(), PrimitiveValue::String(face.to_string()));
         ArgumentValue::Obj(card_object)
     }
 
+    // `p:tricks` is face down when `player:tricks` is, whichever player it belongs to
+    fn facing(&self, stack: &str) -> &'static str {
+        let declared = match stack.split_once(':') {
+            Some((_, own)) => format!("player:{}", own),
+            None => stack.to_string()
+        };
+        match self.face_down.contains(&declared) {
+            true => "down",
+            false => "up"
+        }
+    }
+
     fn handle_transfer(&mut self, t: &Transfer) {
         let from = self.get_stack(&t.from);
         let to = self.get_stack(&t.to);
+        let from_count = from.as_ref().map(|f| f.count()).unwrap_or(0);
+
+        // a count that isn't a number of cards fails like a missing stack
+        let count = match &t.count {
+            None => Some(1),
+            Some(_) if t.to_end() => Some(from_count),
+            Some(count) => match self.resolve_expression(count) {
+                PrimitiveValue::Number(n) if n >= 0.0 => Some(n as usize),
+                _ => None
+            }
+        };
 
-        let transfer_result = transfer(from, to, t.count.as_ref());
+        let transfer_result = count.and_then(|count| transfer(from, to, count, t.modifier));
 
         let (new_from, new_to) = match transfer_result {
             Some((a, b)) => (a, b),
-            _ => return
+            _ => {
+                self.trace(format!("{} > {} (failed)", t.from, t.to));
+                log_move!(self, debug, "transfer", from:% = t.from, to:% = t.to, outcome = "failed");
+                self.violations.push(Violation::InvalidTransfer(t.from.to_string(), t.to.to_string(), t.span.start));
+                return;
+            }
         };
 
+        if let Some(host) = &self.host {
+            let mut cards = self.get_stack(&t.from).map(|f| f.cards()).unwrap_or_default();
+            for card in new_from.cards().iter() {
+                if let Some(i) = cards.iter().position(|c| c == card) {
+                    cards.remove(i);
+                }
+            }
+            if host.with(|h| h.before_transfer(&t.from, &t.to, &cards)) == Some(TransferDecision::Deny) {
+                self.trace(format!("{} > {} (denied by the host)", t.from, t.to));
+                log_move!(self, debug, "transfer", from:% = t.from, to:% = t.to, outcome = "denied");
+                self.violations.push(Violation::TransferDenied(t.from.to_string(), t.to.to_string(), t.span.start));
+                return;
+            }
+        }
+
+        let moved = from_count - new_from.count();
+        self.trace(format!("{} > {} ({} cards moved)", t.from, t.to, moved));
+        log_move!(self, debug, "transfer", from:% = t.from, to:% = t.to, moved = moved);
+
         self.set_stack(&t.from, new_from);
         self.set_stack(&t.to, new_to);
     }
 
+    // `shuffle(deck, riffle 7)`, the deck when no stack is named
+    fn shuffle_stack(&mut self, f: &FunctionCall) {
+        let times = match f.arguments.get(2).map(|e| self.resolve_expression(e)) {
+            Some(PrimitiveValue::Number(n)) if n >= 0.0 => Some(n as u32),
+            _ => None
+        };
+        let method = match f.arguments.get(1) {
+            None => Some(Shuffle::Uniform),
+            Some(Expression::Symbol(name)) => Shuffle::by_name(name, times),
+            Some(_) => None
+        };
+        let stack = match f.arguments.first() {
+            Some(Expression::Symbol(name)) => name.as_str(),
+            _ => "deck"
+        };
+        if let (Some(method), Some(TransferTarget::Stack(mut cards))) = (method, self.get_stack(stack)) {
+            // every pass is a step, so `riffle 1000000000` runs out of them like a loop would
+            let (pass, times) = method.passes();
+            for _ in 0..times {
+                if !self.take_step() {
+                    return;
+                }
+                shuffle_with(&mut cards, pass, &mut self.rng);
+            }
+            self.set_stack(stack, TransferTarget::Stack(cards));
+        }
+    }
+
+    // a stack shuffled into itself is just shuffled
+    fn shuffle_into(&mut self, from: &str, to: &str) {
+        let (mut source, mut target) = match (self.get_stack(from), self.get_stack(to)) {
+            (Some(TransferTarget::Stack(source)), Some(TransferTarget::Stack(target))) => (source, target),
+            _ => return
+        };
+
+        if from != to {
+            target.append(&mut source);
+            self.set_stack(from, TransferTarget::Stack(source));
+        }
+        shuffle(&mut target, &mut self.rng);
+        self.set_stack(to, TransferTarget::Stack(target));
+    }
+
+    // matching cards keep their order as they move
+    fn move_all_matching(&mut self, from: &str, to: &str, predicate: &Definition) {
+        let (mut source, mut target) = match (self.get_stack(from), self.get_stack(to)) {
+            (Some(TransferTarget::Stack(source)), Some(TransferTarget::Stack(target))) if from != to => (source, target),
+            _ => return
+        };
+
+        let face = self.facing(from);
+        let matching = self.filter(source.clone(), predicate, face);
+        source.retain(|card| !matching.contains(card));
+        target.extend(matching);
+        self.set_stack(from, TransferTarget::Stack(source));
+        self.set_stack(to, TransferTarget::Stack(target));
+    }
+
+    // play goes left, so the player on the left is the next one
+    fn neighbour(&self, player: usize, direction: Direction) -> usize {
+        let players = self.players.len();
+        match direction {
+            Direction::Left if player < players => player + 1,
+            Direction::Left => 1,
+            Direction::Right if player > 1 => player - 1,
+            Direction::Right => players
+        }
+    }
+
+    // players who have folded sit out until the pot is awarded, eliminated players for good
+    fn next_in_hand(&self, player: usize) -> usize {
+        let mut next = self.neighbour(player, Direction::Left);
+        for _ in 1..self.players.len() {
+            let p = &self.players[next - 1];
+            if !p.folded() && !p.eliminated() {
+                break;
+            }
+            next = self.neighbour(next, Direction::Left);
+        }
+        next
+    }
+
+    fn highest_bet(&self) -> f64 {
+        self.players.iter().map(|p| p.bet()).fold(0.0, f64::max)
+    }
+
+    fn bet(&mut self, player: usize, amount: f64) {
+        let p = &mut Arc::make_mut(&mut self.players)[player];
+        p.set_chips(p.chips() - amount);
+        p.set_bet(p.bet() + amount);
+        self.pot += amount;
+    }
+
+    // the hand is over, so every player is back in for the next one
+    fn award_pot(&mut self, player: usize) {
+        let winner = &mut Arc::make_mut(&mut self.players)[player];
+        winner.set_chips(winner.chips() + self.pot);
+        self.pot = 0.0;
+        for p in Arc::make_mut(&mut self.players).iter_mut() {
+            p.set_bet(0.0);
+            p.set_folded(false);
+        }
+        self.eliminate_bankrupt_players();
+    }
+
+    fn pay(&mut self, from: usize, to: usize, amount: f64) {
+        let players = Arc::make_mut(&mut self.players);
+        let payer = &mut players[from];
+        payer.set_chips(payer.chips() - amount);
+        let payee = &mut players[to];
+        payee.set_chips(payee.chips() + amount);
+        self.eliminate_bankrupt_players();
+    }
+
+    // only checked once chips have changed hands for good - a player who bets everything is still
+    // in until the pot is awarded - and the last player left with chips wins
+    fn eliminate_bankrupt_players(&mut self) {
+        if !self.bank {
+            return;
+        }
+
+        for p in Arc::make_mut(&mut self.players).iter_mut().filter(|p| p.chips() <= 0.0) {
+            p.set_eliminated(true);
+        }
+
+        let remaining: Vec<u32> = self.players.iter().filter(|p| !p.eliminated()).map(|p| p.get_id()).collect();
+        if let [last] = remaining.as_slice() {
+            if self.status == GameState::Active {
+                winner(&mut self.winners, *last as f64);
+                end(&mut self.status);
+            }
+        }
+    }
+
+    // every player chooses their cards on a move of their own, in the passing phase, and none are
+    // handed on until they all have
+    fn open_pass(&mut self, count: usize, direction: Direction) {
+        for player in Arc::make_mut(&mut self.players).iter_mut() {
+            if player.stack(PASSING).is_none() {
+                player.set_stack(PASSING, vec!());
+            }
+        }
+        self.passing = Some(Pass { count, direction, chosen: vec!(), phase: self.phase.clone() });
+        self.enter_phase(PASSING);
+    }
+
+    fn hand_on_passes(&mut self) {
+        let pass = match self.passing.take() {
+            Some(pass) if self.players.iter().all(|p| p.eliminated() || pass.chosen.contains(&(p.get_id() as usize))) => pass,
+            waiting => {
+                self.passing = waiting;
+                return;
+            }
+        };
+
+        let passed: Vec<Vec<Card>> = Arc::make_mut(&mut self.players).iter_mut().map(|p| {
+            let cards = p.stack(PASSING).map(|cards| cards.to_vec()).unwrap_or_default();
+            p.set_stack(PASSING, vec!());
+            cards
+        }).collect();
+
+        for (i, cards) in passed.into_iter().enumerate() {
+            let to = self.neighbour(i + 1, pass.direction) - 1;
+            let mut hand = self.players[to].get_hand();
+            hand.extend(cards);
+            Arc::make_mut(&mut self.players)[to].set_hand(hand);
+        }
+        self.trace(format!("phase {}", pass.phase));
+        self.phase = pass.phase;
+    }
+
+    // the top n cards of each hand go to the other, or the whole hands without a count
+    fn exchange(&mut self, a: usize, b: usize, n: Option<usize>) {
+        let known = |p: usize| p > 0 && p <= self.players.len();
+        if a == b || !known(a) || !known(b) {
+            return;
+        }
+
+        let mut hand_a = self.players[a - 1].get_hand();
+        let mut hand_b = self.players[b - 1].get_hand();
+        let (given, taken) = match n {
+            Some(n) => (
+                hand_a.split_off(hand_a.len().saturating_sub(n)),
+                hand_b.split_off(hand_b.len().saturating_sub(n))
+            ),
+            None => (::std::mem::take(&mut hand_a), ::std::mem::take(&mut hand_b))
+        };
+        hand_a.extend(taken);
+        hand_b.extend(given);
+        Arc::make_mut(&mut self.players)[a - 1].set_hand(hand_a);
+        Arc::make_mut(&mut self.players)[b - 1].set_hand(hand_b);
+    }
+
     fn handle_if_statement(&mut self, i: &IfStatement) {
-        if self.resolve_to_bool(&i.expression) {
-            self.handle_statements(&i.body.clone());
+        let condition = self.resolve_to_bool(&i.expression);
+        self.trace(format!("if(..) -> {}", condition));
+        if condition {
+            self.handle_statements(&i.body);
+        }
+    }
+
+    // the body runs like an if's, again and again, with each pass costing a step even when the
+    // body is empty
+    fn handle_while_statement(&mut self, w: &WhileStatement) {
+        let mut iterations = 0;
+        while self.resolve_to_bool(&w.expression) {
+            if !self.take_step() {
+                return;
+            }
+            iterations += 1;
+            self.handle_statements(&w.body);
+        }
+        self.trace(format!("while(..) -> {} passes", iterations));
+    }
+
+    // the cards are read before the body runs, so it can move them without any being skipped.
+    // Each card gets a frame of its own, named for the define it's in so failed checks still say where
+    fn handle_foreach_statement(&mut self, f: &ForeachStatement) {
+        let cards = self.get_stack(&f.stack).map(|s| s.cards()).unwrap_or_default();
+        self.trace(format!("foreach {} in {} -> {} cards", f.variable, f.stack, cards.len()));
+        let name = self.call_stack.last().map(|frame| frame.name.to_string()).unwrap_or_default();
+        let face = self.facing(&f.stack);
+        for card in cards.into_iter() {
+            if self.abandoned.is_some() {
+                return;
+            }
+            let mut frame = StackFrame::new(&name);
+            frame.values.insert(f.variable.to_string(), self.build_card_object(card, face));
+            self.call_stack.push(frame);
+            self.handle_statements(&f.body);
+            self.call_stack.pop();
+        }
+    }
+
+    // arms run like the body of an if
+    fn handle_match_statement(&mut self, m: &MatchStatement) {
+        let value = self.resolve_expression(&m.expression);
+        self.trace(format!("match(..) -> {}", value));
+        let arm = m.arms.iter().find(|arm| self.resolve_expression(&arm.pattern) == value);
+        if let Some(body) = arm.map(|a| &a.body).or(m.otherwise.as_ref()) {
+            self.handle_statements(body);
         }
     }
 
@@ -275,39 +1523,48 @@ impl Runtime {
             Expression::Bool(b) => *b,
             Expression::Comparison(c) => self.resolve_expression(&c.left) == self.resolve_expression(&c.right),
             Expression::And(c) => self.resolve_to_bool(&c.left) && self.resolve_to_bool(&c.right),
-            _ => false
+            Expression::Or(o) => self.resolve_to_bool(&o.left) || self.resolve_to_bool(&o.right),
+            Expression::Between(b) => {
+                let bounds = (self.resolve_expression(&b.value), self.resolve_expression(&b.low), self.resolve_expression(&b.high));
+                match bounds {
+                    (PrimitiveValue::Number(n), PrimitiveValue::Number(low), PrimitiveValue::Number(high)) => low <= n && n <= high,
+                    _ => false
+                }
+            },
+            Expression::FunctionCall(_) | Expression::Symbol(_) => self.resolve_expression(expression) == PrimitiveValue::Bool(true),
+            Expression::Number(_) | Expression::String(_) | Expression::Arithmetic(_) => false
         }
     }
 
     fn get_stack(&self, stack_key: &str) -> Option<TransferTarget> {    
         let instructions: Vec<&str> = stack_key.split(&[' ', ':'][..]).collect();
         match instructions[0] {
-            "deck" => Some(TransferTarget::Stack(self.deck.clone())),
+            "deck" => Some(TransferTarget::Stack(self.deck.to_vec())),
             "players" => Some(TransferTarget::StackList(self.players.iter().map(|p| p.get_hand()).collect())),
-            key => self.find_dynamic_stack(key)
+            key => self.find_dynamic_stack(key, instructions.get(1).copied())
         }
     }
 
     fn set_stack(&mut self, stack_key: &str, stack: TransferTarget) {
         let instructions: Vec<&str> = stack_key.split(&[' ', ':'][..]).collect();
         match instructions[0] {
-            "deck" => self.deck = stack.get_stack(0),
-            "players" => self.players.iter_mut().enumerate().for_each(|(n, p)| {
+            "deck" => self.deck = Arc::new(stack.get_stack(0)),
+            "players" => Arc::make_mut(&mut self.players).iter_mut().enumerate().for_each(|(n, p)| {
                 let new_hand = stack.get_stack(n);
                 p.set_hand(new_hand)
             }),
-            key => self.set_dynamic_stack(key, stack)
+            key => self.set_dynamic_stack(key, instructions.get(1).copied(), stack)
         }
     }
 
-    fn find_dynamic_stack(&self, key: &str) -> Option<TransferTarget> {
+    fn find_dynamic_stack(&self, key: &str, property: Option<&str>) -> Option<TransferTarget> {
         let custom_stack = self.find_custom_stack(key);
 
         if custom_stack.is_some() {
             return custom_stack;
         }
 
-        let call_stack = self.find_transfer_target_in_call_stack(key);
+        let call_stack = self.find_transfer_target_in_call_stack(key, property);
 
         if call_stack.is_some() {
             return call_stack;
@@ -316,15 +1573,15 @@ impl Runtime {
         return None;
     }
 
-    fn set_dynamic_stack(&mut self, key: &str, stack: TransferTarget) {
+    fn set_dynamic_stack(&mut self, key: &str, property: Option<&str>, stack: TransferTarget) {
         let custom_stack = self.find_custom_stack(key);
 
         if custom_stack.is_some() {
-            self.card_stacks.insert(key.to_string(), stack.get_stack(0));
+            Arc::make_mut(&mut self.card_stacks).insert(key.to_string(), stack.get_stack(0));
             return;
         }
 
-        self.set_transfer_target_in_call_stack(key, stack);
+        self.set_transfer_target_in_call_stack(key, property, stack);
     }
 
     fn find_custom_stack(&self, key: &str) -> Option<TransferTarget> {
@@ -335,7 +1592,8 @@ impl Runtime {
         }
     }
 
-    fn find_transfer_target_in_call_stack(&self, key: &str) -> Option<TransferTarget> {
+    // player:hand, or one of the stacks every player has
+    fn find_transfer_target_in_call_stack(&self, key: &str, property: Option<&str>) -> Option<TransferTarget> {
         let obj = self.find_in_call_stack(key);
         match obj {
             Some(ArgumentValue::Obj(p)) => {
@@ -344,8 +1602,11 @@ impl Runtime {
                         let parts: Vec<&str> = s.split(":").collect();
                         let i = parts[1].parse::<usize>().unwrap();
 
-                        let stack = self.players[i].get_hand();
-                        Some(TransferTarget::Stack(stack.to_vec()))
+                        let player = &self.players[i];
+                        match property {
+                            None | Some("hand") => Some(TransferTarget::Stack(player.get_hand())),
+                            Some(name) => player.stack(name).map(|s| TransferTarget::Stack(s.to_vec()))
+                        }
                     },
                     _ => None
                 }
@@ -354,7 +1615,7 @@ impl Runtime {
         }
     }
 
-    fn set_transfer_target_in_call_stack(&mut self, key: &str, stack: TransferTarget) {
+    fn set_transfer_target_in_call_stack(&mut self, key: &str, property: Option<&str>, stack: TransferTarget) {
         let obj = self.find_in_call_stack(key);
         match obj {
             Some(ArgumentValue::Obj(p)) => {
@@ -363,7 +1624,10 @@ impl Runtime {
                         let parts: Vec<&str> = s.split(":").collect();
                         let i = parts[1].parse::<usize>().unwrap();
 
-                        self.players[i].set_hand(stack.get_stack(0));
+                        match property {
+                            None | Some("hand") => Arc::make_mut(&mut self.players)[i].set_hand(stack.get_stack(0)),
+                            Some(name) => Arc::make_mut(&mut self.players)[i].set_stack(name, stack.get_stack(0))
+                        }
                     },
                     _ => ()
                 }
@@ -381,7 +1645,7 @@ impl Runtime {
 
     fn find_in_call_stack(&self, key: &str) -> Option<ArgumentValue> {
         for frame in self.call_stack.iter().rev(){
-            let result = frame.get(key);
+            let result = frame.values.get(key);
             match result {
                 Some(r)  => return Some(r.clone()),
                 _ => ()
@@ -391,18 +1655,18 @@ impl Runtime {
         None
     }
 
-    pub fn filter(&mut self, stack: Vec<Card>, function: Definition) -> Vec<Card> {
+    pub fn filter(&mut self, stack: Vec<Card>, function: &Definition, face: &str) -> Vec<Card> {
         let card_arg = match function.arguments.get(0) {
             Some(arg) => arg,
             None => "card"
         }.to_string();
 
         return stack.iter().filter(|&card|{
-            let mut call_stack_frame = HashMap::new();
-            let card_obj = Self::build_card_object(*card);
-            call_stack_frame.insert(card_arg.clone(), card_obj);
+            let mut call_stack_frame = StackFrame::new(&function.name);
+            let card_obj = self.build_card_object(*card, face);
+            call_stack_frame.values.insert(card_arg.clone(), card_obj);
             self.call_stack.push(call_stack_frame);
-            let keep_card = self.handle_statements(&function.body.clone());
+            let keep_card = self.handle_statements(&function.body);
             self.call_stack.pop();
             match keep_card {
                 PrimitiveValue::Bool(b) => b,
@@ -426,28 +1690,42 @@ mod test{
     fn filter_executes_a_function_against_a_stack_and_keeps_cards_when_true() {
         let cards = standard_deck();
         let return_statement = Statement::ReturnStatement(ReturnStatement{
-            expression: Expression::Bool(true)
+            expression: Expression::Bool(true),
+            span: SourceSpan::default()
         });
         let func = Definition{
             name: "_".to_string(),
             arguments: vec!("card".to_string()),
-            body: vec!(return_statement)
+            body: vec!(return_statement),
+            span: SourceSpan::default()
         };
 
         let initial_values = InitialValues{
             players: 1,
             card_stacks: vec!(),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
             current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
         };
 
         let callbacks = Callbacks{
             player_move: None,
-            setup: None
+            setup: None,
+            definitions: HashMap::new()
         };
 
         let mut runtime = Runtime::new(initial_values, callbacks);
 
-        let filtered_cards = runtime.filter(cards, func);
+        let filtered_cards = runtime.filter(cards, &func, "up");
 
         assert_eq!(filtered_cards.len(), 52);
     }
@@ -456,28 +1734,42 @@ mod test{
     fn filter_executes_a_function_against_a_stack_and_keeps_cards_when_false() {
         let cards = standard_deck();
         let return_statement = Statement::ReturnStatement(ReturnStatement{
-            expression: Expression::Bool(false)
+            expression: Expression::Bool(false),
+            span: SourceSpan::default()
         });
         let func = Definition{
             name: "_".to_string(),
             arguments: vec!("card".to_string()),
-            body: vec!(return_statement)
+            body: vec!(return_statement),
+            span: SourceSpan::default()
         };
 
         let initial_values = InitialValues{
             players: 1,
             card_stacks: vec!(),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
             current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
         };
 
         let callbacks = Callbacks{
             player_move: None,
-            setup: None
+            setup: None,
+            definitions: HashMap::new()
         };
 
         let mut runtime = Runtime::new(initial_values, callbacks);
 
-        let filtered_cards = runtime.filter(cards, func);
+        let filtered_cards = runtime.filter(cards, &func, "up");
 
         assert_eq!(filtered_cards.len(), 0);
     }
@@ -488,31 +1780,149 @@ mod test{
         let expression = Expression::Comparison(Box::new(Comparison{
             left: Expression::Symbol("card:rank".to_string()),
             right: Expression::Symbol("Ace".to_string()),
-            negative: false
+            negative: false,
+            span: SourceSpan::default()
         }));
 
-        let return_statement = Statement::ReturnStatement(ReturnStatement{ expression });
+        let return_statement = Statement::ReturnStatement(ReturnStatement{ expression, span: SourceSpan::default() });
         let func = Definition{
             name: "_".to_string(),
             arguments: vec!("card".to_string()),
-            body: vec!(return_statement)
+            body: vec!(return_statement),
+            span: SourceSpan::default()
         };
 
         let initial_values = InitialValues{
             players: 1,
             card_stacks: vec!(),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
             current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
         };
 
         let callbacks = Callbacks{
             player_move: None,
-            setup: None
+            setup: None,
+            definitions: HashMap::new()
         };
 
         let mut runtime = Runtime::new(initial_values, callbacks);
 
-        let filtered_cards = runtime.filter(cards, func);
+        let filtered_cards = runtime.filter(cards, &func, "up");
 
         assert_eq!(filtered_cards.len(), 4);
     }
-}
\ No newline at end of file
+
+    fn count_kept(predicate: &str) -> usize {
+        let source = format!("define keep(card){{\nreturn({})\n}}", predicate);
+        let tokens = crate::lex::lexer(&source).unwrap();
+        let func = match crate::parse::parse(&tokens).unwrap().remove(0) {
+            Statement::Definition(d) => d,
+            _ => panic!("expected a definition")
+        };
+        let initial_values = InitialValues{
+            players: 1,
+            card_stacks: vec!(),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
+            current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
+        };
+        let callbacks = Callbacks{
+            player_move: None,
+            setup: None,
+            definitions: HashMap::new()
+        };
+
+        Runtime::new(initial_values, callbacks).filter(standard_deck(), &func, "up").len()
+    }
+
+    #[test]
+    fn cards_carry_their_color_value_and_id() {
+        assert_eq!(count_kept("card:color is Red"), 26);
+        assert_eq!(count_kept("card:value between 2 and 10"), 36);
+        assert_eq!(count_kept("card:value is 13"), 4);
+        assert_eq!(count_kept("card:id is 51"), 1);
+    }
+
+    #[test]
+    fn summary_returns_the_counts_as_text() {
+        let initial_values = InitialValues{
+            players: 1,
+            card_stacks: vec!(),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
+            current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
+        };
+        let callbacks = Callbacks{ player_move: None, setup: None, definitions: HashMap::new() };
+        let mut runtime = Runtime::new(initial_values, callbacks);
+        Arc::make_mut(&mut runtime.deck).truncate(14);
+        let call = FunctionCall{ name: "summary".to_string(), arguments: vec!(Expression::Symbol("deck".to_string())), span: SourceSpan::default() };
+
+        assert_eq!(
+            runtime.handle_function_call(&call),
+            Some(PrimitiveValue::String("cards: 14\nsuits: spades 13, hearts 1\nranks: ace 2, two 1, three 1, four 1, five 1, six 1, seven 1, eight 1, nine 1, ten 1, jack 1, queen 1, king 1".to_string()))
+        );
+    }
+
+    #[test]
+    fn forks_share_the_cards_until_one_side_changes_them() {
+        let initial_values = InitialValues{
+            players: 2,
+            card_stacks: vec!("middle".to_string()),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
+            current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
+        };
+        let callbacks = Callbacks{ player_move: None, setup: None, definitions: HashMap::new() };
+        let runtime = Runtime::new(initial_values, callbacks);
+        let mut fork = runtime.fork();
+
+        assert!(Arc::ptr_eq(&runtime.deck, &fork.deck));
+        assert!(Arc::ptr_eq(&runtime.players, &fork.players));
+        assert!(Arc::ptr_eq(&runtime.card_stacks, &fork.card_stacks));
+
+        fork.handle_transfer(&Transfer{ from: "deck".to_string(), to: "middle".to_string(), modifier: None, count: None, span: SourceSpan::default() });
+
+        assert!(!Arc::ptr_eq(&runtime.deck, &fork.deck));
+        assert!(!Arc::ptr_eq(&runtime.card_stacks, &fork.card_stacks));
+        assert!(Arc::ptr_eq(&runtime.players, &fork.players));
+        assert_eq!((runtime.deck.len(), runtime.card_stacks["middle"].len()), (52, 0));
+        assert_eq!((fork.deck.len(), fork.card_stacks["middle"].len()), (51, 1));
+    }
+}
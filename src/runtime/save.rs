@@ -0,0 +1,345 @@
+use super::*;
+use crate::json::Json;
+
+// bumped whenever the saved shape changes, with a migration from the version before
+pub const SAVE_VERSION: u32 = 5;
+
+// each migration takes a save from the version at its index + 1 to the next one up
+const MIGRATIONS: [fn(Json) -> Json; 4] = [
+    add_player_economy,
+    add_turn_counters,
+    add_phase,
+    add_passing
+];
+
+impl Runtime {
+    pub fn to_save(&self) -> Json {
+        let mut stacks: Vec<(&String, &Vec<Card>)> = self.card_stacks.iter().collect();
+        stacks.sort_by_key(|(name, _)| name.to_string());
+        let players = self.players.iter().map(|p| {
+            let mut player_stacks: Vec<(&String, &Vec<Card>)> = p.stacks().iter().collect();
+            player_stacks.sort_by_key(|(name, _)| name.to_string());
+            Json::object(vec!(
+                ("hand", cards_json(p.hand())),
+                ("stacks", Json::Object(player_stacks.into_iter().map(|(k, v)| (k.to_string(), cards_json(v))).collect())),
+                ("score", Json::Number(p.score())),
+                ("chips", Json::Number(p.chips())),
+                ("bet", Json::Number(p.bet())),
+                ("folded", Json::Bool(p.folded())),
+                ("eliminated", Json::Bool(p.eliminated()))
+            ))
+        }).collect();
+
+        Json::object(vec!(
+            ("version", Json::Number(SAVE_VERSION as f64)),
+            ("status", Json::String(self.status.to_string())),
+            ("current_player", Json::Number(self.current_player as f64)),
+            ("winners", Json::Array(self.winners.iter().map(|w| Json::Number(*w)).collect())),
+            ("deck", cards_json(&self.deck)),
+            ("stacks", Json::Object(stacks.into_iter().map(|(k, v)| (k.to_string(), cards_json(v))).collect())),
+            ("players", Json::Array(players)),
+            ("pot", Json::Number(self.pot)),
+            ("hand", Json::Number(self.hand as f64)),
+            ("turn", Json::Number(self.turn as f64)),
+            ("round", Json::Number(self.round as f64)),
+            ("phase", Json::string(&self.phase)),
+            ("moved", Json::Array(self.moved.iter().map(|m| Json::Number(*m as f64)).collect())),
+            ("passing", self.passing.as_ref().map(pass_json).unwrap_or(Json::Null))
+        ))
+    }
+
+    // None when the save is from a newer release, malformed, or for a different number of players
+    pub fn load_save(&mut self, save: &Json) -> Option<()> {
+        let save = migrate(save.clone())?;
+
+        let players = save.get("players")?.as_array()?;
+        if players.len() != self.players.len() {
+            return None;
+        }
+        for (player, saved) in Arc::make_mut(&mut self.players).iter_mut().zip(players.iter()) {
+            player.set_hand(cards_from_json(saved.get("hand")?)?);
+            if let Json::Object(stacks) = saved.get("stacks")? {
+                for (name, cards) in stacks.iter() {
+                    player.set_stack(name, cards_from_json(cards)?);
+                }
+            }
+            player.set_score(saved.get("score")?.as_f64()?);
+            player.set_chips(saved.get("chips")?.as_f64()?);
+            player.set_bet(saved.get("bet")?.as_f64()?);
+            player.set_folded(saved.get("folded")?.as_bool()?);
+            player.set_eliminated(saved.get("eliminated")?.as_bool()?);
+        }
+
+        if let Json::Object(stacks) = save.get("stacks")? {
+            for (name, cards) in stacks.iter() {
+                Arc::make_mut(&mut self.card_stacks).insert(name.to_string(), cards_from_json(cards)?);
+            }
+        }
+
+        self.status = match save.get("status")?.as_str()? {
+            "pending" => GameState::Pending,
+            "active" => GameState::Active,
+            "game over" => GameState::GameOver,
+            _ => return None
+        };
+        self.current_player = save.get("current_player")?.as_f64()? as usize;
+        self.winners = save.get("winners")?.as_array()?.iter().map(|w| w.as_f64()).collect::<Option<Vec<f64>>>()?;
+        self.deck = Arc::new(cards_from_json(save.get("deck")?)?);
+        self.pot = save.get("pot")?.as_f64()?;
+        self.hand = save.get("hand")?.as_f64()? as u32;
+        self.turn = save.get("turn")?.as_f64()? as u32;
+        self.round = save.get("round")?.as_f64()? as u32;
+        self.phase = save.get("phase")?.as_str()?.to_string();
+        self.moved = save.get("moved")?.as_array()?.iter().map(|m| m.as_f64().map(|m| m as usize)).collect::<Option<Vec<usize>>>()?;
+        self.passing = match save.get("passing")? {
+            Json::Null => None,
+            pass => Some(pass_from_json(pass)?)
+        };
+        Some(())
+    }
+}
+
+fn migrate(mut save: Json) -> Option<Json> {
+    let version = save.get("version")?.as_f64()? as u32;
+    if !(1..=SAVE_VERSION).contains(&version) {
+        return None;
+    }
+    for migration in MIGRATIONS[version as usize - 1..].iter() {
+        save = migration(save);
+    }
+    Some(save)
+}
+
+// the first saves only had the cards, from before chips, betting and matches
+fn add_player_economy(save: Json) -> Json {
+    let mut fields = match save {
+        Json::Object(fields) => fields,
+        other => return other
+    };
+
+    for (key, value) in fields.iter_mut() {
+        match (key.as_str(), value) {
+            ("version", value) => *value = Json::Number(2.0),
+            ("players", Json::Array(players)) => {
+                for player in players.iter_mut() {
+                    if let Json::Object(player_fields) = player {
+                        player_fields.push(("stacks".to_string(), Json::Object(vec!())));
+                        player_fields.push(("score".to_string(), Json::Number(0.0)));
+                        player_fields.push(("chips".to_string(), Json::Number(0.0)));
+                        player_fields.push(("bet".to_string(), Json::Number(0.0)));
+                        player_fields.push(("folded".to_string(), Json::Bool(false)));
+                        player_fields.push(("eliminated".to_string(), Json::Bool(false)));
+                    }
+                }
+            },
+            _ => ()
+        }
+    }
+    fields.push(("pot".to_string(), Json::Number(0.0)));
+    fields.push(("hand".to_string(), Json::Number(1.0)));
+    fields.push(("moved".to_string(), Json::Array(vec!())));
+    Json::Object(fields)
+}
+
+// saves from before turns and rounds were counted pick the count up from the start
+fn add_turn_counters(save: Json) -> Json {
+    let mut fields = match save {
+        Json::Object(fields) => fields,
+        other => return other
+    };
+
+    for (key, value) in fields.iter_mut() {
+        if key == "version" {
+            *value = Json::Number(3.0);
+        }
+    }
+    fields.push(("turn".to_string(), Json::Number(1.0)));
+    fields.push(("round".to_string(), Json::Number(1.0)));
+    Json::Object(fields)
+}
+
+// and from before phases, when every game was played in the one
+fn add_phase(save: Json) -> Json {
+    let mut fields = match save {
+        Json::Object(fields) => fields,
+        other => return other
+    };
+
+    for (key, value) in fields.iter_mut() {
+        if key == "version" {
+            *value = Json::Number(4.0);
+        }
+    }
+    fields.push(("phase".to_string(), Json::string(FIRST_PHASE)));
+    Json::Object(fields)
+}
+
+// and from before players chose what to pass, when nobody could be partway through choosing
+fn add_passing(save: Json) -> Json {
+    let mut fields = match save {
+        Json::Object(fields) => fields,
+        other => return other
+    };
+
+    for (key, value) in fields.iter_mut() {
+        if key == "version" {
+            *value = Json::Number(5.0);
+        }
+    }
+    fields.push(("passing".to_string(), Json::Null));
+    Json::Object(fields)
+}
+
+fn pass_json(pass: &Pass) -> Json {
+    Json::object(vec!(
+        ("count", Json::Number(pass.count as f64)),
+        ("direction", Json::string(match pass.direction {
+            Direction::Left => "left",
+            Direction::Right => "right"
+        })),
+        ("chosen", Json::Array(pass.chosen.iter().map(|c| Json::Number(*c as f64)).collect())),
+        ("phase", Json::string(&pass.phase))
+    ))
+}
+
+fn pass_from_json(pass: &Json) -> Option<Pass> {
+    Some(Pass {
+        count: pass.get("count")?.as_f64()? as usize,
+        direction: match pass.get("direction")?.as_str()? {
+            "left" => Direction::Left,
+            "right" => Direction::Right,
+            _ => return None
+        },
+        chosen: pass.get("chosen")?.as_array()?.iter().map(|c| c.as_f64().map(|c| c as usize)).collect::<Option<Vec<usize>>>()?,
+        phase: pass.get("phase")?.as_str()?.to_string()
+    })
+}
+
+// cards from the first deck are saved by name alone, as before there could be more than one,
+// the rest as `king diamonds (deck 2)`
+fn cards_json(cards: &[Card]) -> Json {
+    Json::Array(cards.iter().map(|c| match c.deck_number() {
+        0 => Json::String(c.to_string()),
+        n => Json::String(format!("{} (deck {})", c, n + 1))
+    }).collect())
+}
+
+fn cards_from_json(cards: &Json) -> Option<Vec<Card>> {
+    let deck = crate::cards::decks(1);
+    cards.as_array()?.iter().map(|card| {
+        let saved = card.as_str()?;
+        let (name, deck_number) = match saved.strip_suffix(')').and_then(|s| s.split_once(" (deck ")) {
+            Some((name, n)) => (name, n.parse::<u32>().ok().filter(|n| *n > 0)? - 1),
+            None => (saved, 0)
+        };
+        deck.iter().find(|c| c.to_string() == name).map(|c| c.in_deck(deck_number))
+    }).collect()
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn two_player_runtime() -> Runtime {
+        let initial_values = InitialValues{
+            players: 2,
+            card_stacks: vec!("middle".to_string()),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
+            current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
+        };
+        let callbacks = Callbacks{ player_move: None, setup: None, definitions: HashMap::new() };
+        Runtime::new(initial_values, callbacks)
+    }
+
+    #[test]
+    fn saves_load_back_into_the_same_state() {
+        let mut runtime = two_player_runtime();
+        let card = Arc::make_mut(&mut runtime.deck).pop().unwrap();
+        Arc::make_mut(&mut runtime.players)[1].set_hand(vec!(card));
+        Arc::make_mut(&mut runtime.players)[0].set_score(12.0);
+        Arc::make_mut(&mut runtime.card_stacks).insert("middle".to_string(), vec!(Arc::make_mut(&mut runtime.deck).remove(0)));
+        runtime.status = GameState::Active;
+        runtime.current_player = 2;
+        runtime.turn = 4;
+        runtime.round = 2;
+        runtime.phase = "scoring".to_string();
+
+        let mut loaded = two_player_runtime();
+        loaded.load_save(&runtime.to_save()).unwrap();
+
+        assert_eq!(loaded.to_save(), runtime.to_save());
+        assert_eq!(loaded.players[1].hand(), &[card]);
+        assert_eq!(loaded.players[0].score(), 12.0);
+        assert_eq!(loaded.deck.len(), 50);
+    }
+
+    #[test]
+    fn cards_from_later_decks_keep_their_ids() {
+        let mut runtime = two_player_runtime();
+        let second_deck = crate::cards::decks(2).split_off(52);
+        Arc::make_mut(&mut runtime.players)[0].set_hand(second_deck[..2].to_vec());
+        Arc::make_mut(&mut runtime.players)[1].set_hand(vec!(runtime.deck[0]));
+
+        let save = runtime.to_save();
+        let mut loaded = two_player_runtime();
+        loaded.load_save(&save).unwrap();
+
+        assert!(save.to_string().contains(r#""hand":["ace spades (deck 2)","two spades (deck 2)"]"#));
+        assert_eq!(loaded.players[0].hand(), &second_deck[..2]);
+        assert_eq!(loaded.players[1].hand()[0].id(), 0);
+    }
+
+    #[test]
+    fn first_version_saves_are_migrated() {
+        let save = Json::parse(r#"{"version": 1, "status": "active", "current_player": 2, "winners": [],
+            "deck": ["two hearts"], "stacks": {"middle": []},
+            "players": [{"hand": ["ace spades"]}, {"hand": []}]}"#).unwrap();
+        let mut runtime = two_player_runtime();
+
+        assert_eq!(runtime.load_save(&save), Some(()));
+        assert_eq!(runtime.get_current_player(), 2);
+        assert_eq!(runtime.players[0].get_hand()[0].to_string(), "ace spades".to_string());
+        assert_eq!(runtime.players[0].chips(), 0.0);
+        assert_eq!(runtime.pot, 0.0);
+        assert_eq!((runtime.turn, runtime.round), (1, 1));
+        assert_eq!(runtime.phase(), "play");
+        assert!(runtime.passing.is_none());
+    }
+
+    #[test]
+    fn a_pass_partway_through_choosing_is_saved() {
+        let mut runtime = two_player_runtime();
+        runtime.open_pass(3, Direction::Right);
+        if let Some(pass) = runtime.passing.as_mut() {
+            pass.chosen.push(2);
+        }
+
+        let mut loaded = two_player_runtime();
+        loaded.load_save(&runtime.to_save()).unwrap();
+
+        assert_eq!(loaded.phase(), "passing");
+        assert_eq!(loaded.players[0].stack("passing"), Some(&[][..]));
+        let pass = loaded.passing.unwrap();
+        assert_eq!((pass.count, pass.direction, pass.chosen, pass.phase), (3, Direction::Right, vec!(2), "play".to_string()));
+    }
+
+    #[test]
+    fn saves_from_newer_releases_are_rejected() {
+        let mut save = two_player_runtime().to_save();
+        if let Json::Object(fields) = &mut save {
+            fields[0].1 = Json::Number((SAVE_VERSION + 1) as f64);
+        }
+
+        assert_eq!(two_player_runtime().load_save(&save), None);
+    }
+}
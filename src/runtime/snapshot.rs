@@ -0,0 +1,205 @@
+use super::*;
+
+// beyond this, changes to a pile are counted rather than listed card by card
+const LISTED_CARDS: usize = 3;
+
+// the table at one moment, to compare against another
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    status: String,
+    current_player: usize,
+    winners: Vec<f64>,
+    pot: f64,
+    // every pile by name, the deck and stacks first and then each player's hand and stacks
+    piles: Vec<(String, Vec<Card>)>,
+    // numbers and flags, like a player's score, by name
+    fields: Vec<(String, String)>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub zone: String,
+    pub detail: String
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.zone, self.detail)
+    }
+}
+
+impl Runtime {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut piles = vec!(("deck".to_string(), self.deck.to_vec()));
+        let mut stacks: Vec<(String, Vec<Card>)> = self.card_stacks.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        stacks.sort_by(|a, b| a.0.cmp(&b.0));
+        piles.extend(stacks);
+
+        let mut fields = vec!();
+        for player in self.players.iter() {
+            let id = player.get_id();
+            piles.push((format!("player {} hand", id), player.get_hand()));
+            let mut player_stacks: Vec<(&String, &Vec<Card>)> = player.stacks().iter().collect();
+            player_stacks.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, cards) in player_stacks {
+                piles.push((format!("player {} {}", id, name), cards.clone()));
+            }
+
+            fields.push((format!("player {} score", id), player.score().to_string()));
+            fields.push((format!("player {} chips", id), player.chips().to_string()));
+            fields.push((format!("player {} bet", id), player.bet().to_string()));
+            fields.push((format!("player {} folded", id), player.folded().to_string()));
+            fields.push((format!("player {} eliminated", id), player.eliminated().to_string()));
+        }
+
+        Snapshot {
+            status: self.status.to_string(),
+            current_player: self.current_player,
+            winners: self.winners.clone(),
+            pot: self.pot,
+            piles,
+            fields
+        }
+    }
+}
+
+// what changed from `before` to `after`, in the order the table is shown
+pub fn diff(before: &Snapshot, after: &Snapshot) -> Vec<Change> {
+    let mut changes = vec!();
+    let mut change = |zone: &str, detail: String| changes.push(Change{ zone: zone.to_string(), detail });
+
+    if before.status != after.status {
+        change("status", format!("{} -> {}", before.status, after.status));
+    }
+    if before.current_player != after.current_player {
+        change("current_player", format!("{} -> {}", before.current_player, after.current_player));
+    }
+    if before.winners != after.winners {
+        change("winners", format!("{} -> {}", describe_winners(&before.winners), describe_winners(&after.winners)));
+    }
+    if before.pot != after.pot {
+        change("pot", format!("{} -> {}", before.pot, after.pot));
+    }
+
+    for (name, cards) in after.piles.iter() {
+        let previous = before.piles.iter().find(|(n, _)| n == name).map(|(_, c)| c.as_slice()).unwrap_or(&[]);
+        if let Some(detail) = describe_pile_change(previous, cards) {
+            change(name, detail);
+        }
+    }
+
+    for (name, value) in after.fields.iter() {
+        match before.fields.iter().find(|(n, _)| n == name) {
+            Some((_, previous)) if previous == value => (),
+            Some((_, previous)) => change(name, format!("{} -> {}", previous, value)),
+            None => change(name, value.to_string())
+        }
+    }
+    changes
+}
+
+fn describe_winners(winners: &[f64]) -> String {
+    if winners.is_empty() {
+        return "none".to_string();
+    }
+    winners.iter().map(|w| w.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+fn describe_pile_change(before: &[Card], after: &[Card]) -> Option<String> {
+    if before == after {
+        return None;
+    }
+
+    let mut remaining = after.to_vec();
+    let mut lost = vec!();
+    for card in before.iter() {
+        match remaining.iter().position(|c| c == card) {
+            Some(i) => { remaining.remove(i); },
+            None => lost.push(*card)
+        }
+    }
+    let gained = remaining;
+
+    if lost.is_empty() && gained.is_empty() {
+        return Some("reordered".to_string());
+    }
+
+    let describe = |sign: &str, cards: &[Card]| {
+        if cards.len() > LISTED_CARDS {
+            vec!(format!("{}{} cards", sign, cards.len()))
+        } else {
+            cards.iter().map(|c| format!("{}{}", sign, c)).collect()
+        }
+    };
+    let mut parts = describe("+", &gained);
+    parts.extend(describe("-", &lost));
+    Some(parts.join(", "))
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn two_player_runtime() -> Runtime {
+        let initial_values = InitialValues{
+            players: 2,
+            card_stacks: vec!("middle".to_string()),
+            player_stacks: vec!(),
+            teams: None,
+            deal: None,
+            hand_size: None,
+            chips: None,
+            simultaneous: false,
+            match_to: None,
+            current_player: 1,
+            decks: 1,
+            deck: DeckSpec::standard(),
+            values: RankValues::default(),
+            face_down: vec!()
+        };
+        let callbacks = Callbacks{ player_move: None, setup: None, definitions: HashMap::new() };
+        Runtime::new(initial_values, callbacks)
+    }
+
+    #[test]
+    fn unchanged_snapshots_have_no_differences() {
+        let runtime = two_player_runtime();
+
+        assert_eq!(diff(&runtime.snapshot(), &runtime.snapshot()), vec!());
+    }
+
+    #[test]
+    fn it_reports_moved_cards_and_changed_fields() {
+        let mut runtime = two_player_runtime();
+        let before = runtime.snapshot();
+        let card = Arc::make_mut(&mut runtime.deck).pop().unwrap();
+        Arc::make_mut(&mut runtime.players)[0].set_hand(vec!(card));
+        Arc::make_mut(&mut runtime.players)[1].set_score(5.0);
+        runtime.current_player = 2;
+
+        let changes: Vec<String> = diff(&before, &runtime.snapshot()).iter().map(|c| c.to_string()).collect();
+
+        assert_eq!(changes, vec!(
+            "current_player: 1 -> 2".to_string(),
+            "deck: -king diamonds".to_string(),
+            "player 1 hand: +king diamonds".to_string(),
+            "player 2 score: 0 -> 5".to_string()
+        ));
+    }
+
+    #[test]
+    fn large_moves_and_shuffles_are_summarised() {
+        let mut runtime = two_player_runtime();
+        let before = runtime.snapshot();
+        Arc::make_mut(&mut runtime.deck).reverse();
+        let shuffled = runtime.snapshot();
+        let middle: Vec<Card> = Arc::make_mut(&mut runtime.deck).drain(..10).collect();
+        Arc::make_mut(&mut runtime.card_stacks).insert("middle".to_string(), middle);
+
+        assert_eq!(diff(&before, &shuffled), vec!(Change{ zone: "deck".to_string(), detail: "reordered".to_string() }));
+        assert_eq!(diff(&shuffled, &runtime.snapshot()).iter().map(|c| c.to_string()).collect::<Vec<String>>(), vec!(
+            "deck: -10 cards".to_string(),
+            "middle: +10 cards".to_string()
+        ));
+    }
+}
@@ -1,10 +1,99 @@
-use crate::cards::Card;
-use rand::seq::SliceRandom;
+use crate::cards::{standard_deck, Card};
+use rand::{seq::SliceRandom, rngs::StdRng, Rng};
 use super::{PrimitiveValue, GameState};
 
-pub fn shuffle(stack: &mut Vec<Card>) {
-    let mut rng = rand::thread_rng();
-    stack.shuffle(&mut rng);
+// created for any game that burns cards - burn is reserved, so it can't be declared
+pub const BURN_PILE: &str = "burn";
+
+// the most cards an overhand shuffle moves in one packet
+const OVERHAND_PACKET: usize = 8;
+
+pub fn shuffle(stack: &mut [Card], rng: &mut StdRng) {
+    stack.shuffle(rng);
+}
+
+// how `shuffle(deck, riffle 7)` mixes the cards, a uniform fisher-yates shuffle unless another
+// is named. The others are how people shuffle, with the same weaknesses when done too few times
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shuffle {
+    Uniform,
+    Riffle(u32),
+    Overhand(u32),
+    // a faro, cut exactly in half and interleaved, so eight of them put 52 cards back in order
+    Perfect(u32)
+}
+
+impl Shuffle {
+    pub fn by_name(name: &str, times: Option<u32>) -> Option<Shuffle> {
+        match name {
+            "uniform" => Some(Shuffle::Uniform),
+            "riffle" => Some(Shuffle::Riffle(times.unwrap_or(7))),
+            "overhand" => Some(Shuffle::Overhand(times.unwrap_or(7))),
+            "perfect" => Some(Shuffle::Perfect(times.unwrap_or(1))),
+            _ => None
+        }
+    }
+
+    // a single pass of the shuffle, and how many of them it makes
+    pub fn passes(self) -> (Shuffle, u32) {
+        match self {
+            Shuffle::Uniform => (Shuffle::Uniform, 1),
+            Shuffle::Riffle(times) => (Shuffle::Riffle(1), times),
+            Shuffle::Overhand(times) => (Shuffle::Overhand(1), times),
+            Shuffle::Perfect(times) => (Shuffle::Perfect(1), times)
+        }
+    }
+}
+
+pub fn shuffle_with(stack: &mut Vec<Card>, method: Shuffle, rng: &mut StdRng) {
+    match method {
+        Shuffle::Uniform => shuffle(stack, rng),
+        Shuffle::Riffle(times) => (0..times).for_each(|_| riffle(stack, rng)),
+        Shuffle::Overhand(times) => (0..times).for_each(|_| overhand(stack, rng)),
+        Shuffle::Perfect(times) => (0..times).for_each(|_| perfect(stack))
+    }
+}
+
+// the gilbert-shannon-reeds model: a binomial cut, then cards drop from each half
+// in proportion to how many that half has left
+fn riffle(stack: &mut Vec<Card>, rng: &mut StdRng) {
+    let cut = (0..stack.len()).filter(|_| rng.gen_bool(0.5)).count();
+    let right = stack.split_off(cut);
+    let left = std::mem::take(stack);
+    let (mut l, mut r) = (0, 0);
+    while l < left.len() || r < right.len() {
+        let left_remaining = left.len() - l;
+        let remaining = left_remaining + right.len() - r;
+        if rng.gen_range(0..remaining) < left_remaining {
+            stack.push(left[l]);
+            l += 1;
+        } else {
+            stack.push(right[r]);
+            r += 1;
+        }
+    }
+}
+
+// small packets come off the top, each landing on the last, so runs of cards stay together
+fn overhand(stack: &mut Vec<Card>, rng: &mut StdRng) {
+    let mut rest = std::mem::take(stack);
+    while !rest.is_empty() {
+        let size = rng.gen_range(1..=rest.len().min(OVERHAND_PACKET));
+        let packet = rest.split_off(rest.len() - size);
+        stack.extend(packet);
+    }
+}
+
+// an out shuffle, the top and bottom cards stay where they are
+fn perfect(stack: &mut Vec<Card>) {
+    let second = stack.split_off(stack.len().div_ceil(2));
+    let first = std::mem::take(stack);
+    for (i, card) in first.into_iter().enumerate() {
+        stack.push(card);
+        if let Some(card) = second.get(i) {
+            stack.push(*card);
+        }
+    }
 }
 
 pub fn end(status: &mut GameState) {
@@ -15,9 +104,84 @@ pub fn winner(winners: &mut Vec<f64>, player: f64) {
     winners.push(player);
 }
 
+// from the top of the deck, stopping early if it runs out
+pub fn burn(deck: &mut Vec<Card>, pile: &mut Vec<Card>, n: usize) {
+    for _ in 0..n {
+        match deck.pop() {
+            Some(card) => pile.push(card),
+            None => break
+        }
+    }
+}
+
+// how many of each suit and each rank, leaving out the ones with none
+pub fn summary(cards: &[Card]) -> Vec<(String, String)> {
+    let deck = standard_deck();
+    let mut suits = [0; 4];
+    let mut ranks = [0; 13];
+    for card in cards.iter() {
+        suits[card.suit() as usize] += 1;
+        ranks[card.rank() as usize] += 1;
+    }
+
+    let describe = |counts: &[usize], name: &dyn Fn(usize) -> String| counts.iter().enumerate()
+        .filter(|(_, n)| **n > 0)
+        .map(|(i, n)| format!("{} {}", name(i), n))
+        .collect::<Vec<String>>()
+        .join(", ");
+    vec!(
+        ("cards".to_string(), cards.len().to_string()),
+        ("suits".to_string(), describe(&suits, &|i| deck[i * 13].get_suit_str().to_lowercase())),
+        ("ranks".to_string(), describe(&ranks, &|i| deck[i].get_rank_str().to_lowercase()))
+    )
+}
+
 pub fn count(stack: PrimitiveValue) -> usize {
     match stack {
         PrimitiveValue::Stack(v) => v.len(),
         _ => 0
     }
 }
+
+#[cfg(test)]
+mod test{
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn eight_perfect_shuffles_restore_the_deck() {
+        let mut deck = standard_deck();
+        shuffle_with(&mut deck, Shuffle::Perfect(1), &mut StdRng::seed_from_u64(1));
+        assert_eq!((deck[0], deck[1], deck[51]), (standard_deck()[0], standard_deck()[26], standard_deck()[51]));
+
+        shuffle_with(&mut deck, Shuffle::Perfect(7), &mut StdRng::seed_from_u64(1));
+        assert_eq!(deck, standard_deck());
+    }
+
+    #[test]
+    fn every_shuffle_keeps_the_same_cards() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for method in [Shuffle::Uniform, Shuffle::Riffle(7), Shuffle::Overhand(7), Shuffle::Perfect(3)].iter() {
+            let mut deck = standard_deck();
+            shuffle_with(&mut deck, *method, &mut rng);
+            deck.sort_by_key(|c| c.id());
+
+            assert_eq!(deck, standard_deck(), "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn one_riffle_leaves_two_rising_sequences() {
+        let mut deck = standard_deck();
+        shuffle_with(&mut deck, Shuffle::Riffle(1), &mut StdRng::seed_from_u64(3));
+        let mut positions = vec!(0; deck.len());
+        for (i, card) in deck.iter().enumerate() {
+            positions[card.id() as usize] = i;
+        }
+        let breaks = positions.windows(2).filter(|p| p[1] < p[0]).count();
+
+        assert!(breaks <= 1);
+        assert_eq!(Shuffle::by_name("riffle", None), Some(Shuffle::Riffle(7)));
+        assert_eq!(Shuffle::by_name("shuffle", None), None);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::cards::Card;
-use crate::ast::*;
+use crate::ast::TransferModifier;
 
 pub enum TransferTarget {
     Stack(Stack),
@@ -14,6 +14,13 @@ impl TransferTarget {
         }
     }
 
+    pub fn cards(&self) -> Stack {
+        match self {
+            TransferTarget::Stack(s) => s.clone(),
+            TransferTarget::StackList(s) => s.concat()
+        }
+    }
+
     pub fn get_stack(&self, n: usize) -> Stack {
         match self {
             TransferTarget::Stack(s) => s.clone(),
@@ -27,12 +34,12 @@ pub type Stack = Vec<Card>;
 pub fn transfer(
     mut from: Option<TransferTarget>,
     mut to: Option<TransferTarget>,
-    t_count: Option<&TransferCount>
+    mut count: usize,
+    modifier: Option<TransferModifier>
 ) -> Option<(TransferTarget, TransferTarget)> {
-    let mut count = match t_count {
-        None => 1,
-        Some(TransferCount::End) => from.as_ref().unwrap().count()
-    };
+    let from_bottom = modifier.is_some_and(|m| m.from_bottom());
+    let to_bottom = modifier.is_some_and(|m| m.to_bottom());
+
 
     // multiply by number of target stacks
     count *= match &to {
@@ -46,6 +53,7 @@ pub fn transfer(
     while count > 0 {
 
         let card_result = match from {
+            Some(TransferTarget::Stack(ref mut s)) if from_bottom && !s.is_empty() => Some(s.remove(0)),
             Some(TransferTarget::Stack(ref mut s)) => s.pop(),
             _ => None
         };
@@ -63,13 +71,18 @@ pub fn transfer(
 
         match to {
             Some(TransferTarget::StackList(ref mut s)) => {
-                s[transfer_index].push(card);
+                if to_bottom {
+                    s[transfer_index].insert(0, card);
+                } else {
+                    s[transfer_index].push(card);
+                }
                 if transfer_index >= s.len() - 1 {
                     transfer_index = 0;
                 } else {
                     transfer_index += 1
                 }
             },
+            Some(TransferTarget::Stack(ref mut s)) if to_bottom => s.insert(0, card),
             Some(TransferTarget::Stack(ref mut s)) => s.push(card),
             _ => ()
         }
@@ -95,10 +108,22 @@ mod test{
         let from = Some(TransferTarget::Stack(standard_deck()));
         let to = Some(TransferTarget::Stack(vec!()));
 
-        let result = transfer(from, to, None);
+        let result = transfer(from, to, 1, None);
 
         let (_new_from, new_to) = result.unwrap();
 
         assert_eq!(new_to.count(), 1);
     }
+
+    #[test]
+    fn it_can_take_from_and_put_under_the_bottom() {
+        let deck = standard_deck();
+        let from = Some(TransferTarget::Stack(deck.clone()));
+        let to = Some(TransferTarget::Stack(vec!(deck[10].clone())));
+
+        let (new_from, new_to) = transfer(from, to, 2, Some(TransferModifier::BottomToBottom)).unwrap();
+
+        assert_eq!(new_from.cards()[0], deck[2]);
+        assert_eq!(new_to.cards(), vec!(deck[1].clone(), deck[0].clone(), deck[10].clone()));
+    }
 }
\ No newline at end of file
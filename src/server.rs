@@ -0,0 +1,251 @@
+use std::io::{stdin, BufRead};
+use crate::interpreter::Game;
+use crate::json::Json;
+use crate::profile::ProfileStore;
+
+const PARSE_ERROR: f64 = -32700.0;
+const INVALID_REQUEST: f64 = -32600.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const INVALID_PARAMS: f64 = -32602.0;
+const GAME_ERROR: f64 = -32000.0;
+
+type RpcResult = Result<Json, (f64, String)>;
+
+#[derive(Default)]
+pub struct Server {
+    game: Option<Game>,
+    profiles: Option<ProfileStore>
+}
+
+impl Server {
+    // returns None for notifications (requests without an id)
+    pub fn handle(&mut self, line: &str) -> Option<Json> {
+        let request = match Json::parse(line) {
+            Ok(r) => r,
+            Err(e) => {
+                let message = format!("invalid json at {}", e.position);
+                return Some(Self::response(Json::Null, Err((PARSE_ERROR, message))));
+            }
+        };
+
+        let id = request.get("id").cloned();
+        let params = request.get("params").cloned().unwrap_or(Json::Object(vec!()));
+        let method = request.get("method").and_then(|m| m.as_str());
+        let result = match method {
+            Some(method) => self.dispatch(method, &params),
+            None => Err((INVALID_REQUEST, "missing method".to_string()))
+        };
+        match &result {
+            Ok(_) => log::debug!(target: "cardlang::server", method; "request"),
+            Err((code, message)) => log::warn!(target: "cardlang::server", method, code, error = message.as_str(); "request failed")
+        }
+
+        id.map(|id| Self::response(id, result))
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Json) -> RpcResult {
+        match method {
+            "build" => self.build(params),
+            "start" => {
+                let game = self.game()?;
+                game.start();
+                Ok(game.show_json("game"))
+            },
+            "move" => {
+                let player = match params.get("player").and_then(|p| p.as_f64()) {
+                    Some(p) if p >= 1.0 => p as usize,
+                    _ => return Err((INVALID_PARAMS, "expected a player number".to_string()))
+                };
+                let game = self.game()?;
+                let accepted = game.player_move(player);
+                let result = game.move_json(player, accepted);
+                if let (Some(store), Some(game)) = (self.profiles.as_mut(), self.game.as_ref()) {
+                    store.update(game).map_err(|e| (GAME_ERROR, e.to_string()))?;
+                }
+                Ok(result)
+            },
+            "profiles" => {
+                if let Some(path) = params.get("path").and_then(|p| p.as_str()) {
+                    self.profiles = Some(ProfileStore::open(path).map_err(|e| (GAME_ERROR, e.to_string()))?);
+                }
+                match self.profiles.as_ref() {
+                    Some(store) => Ok(store.profiles().to_json()),
+                    None => Err((GAME_ERROR, "no profile store open".to_string()))
+                }
+            },
+            "name" => {
+                let (player, name) = match (params.get("player").and_then(|p| p.as_f64()), params.get("name").and_then(|n| n.as_str())) {
+                    (Some(p), Some(name)) if p >= 1.0 => (p as usize, name.to_string()),
+                    _ => return Err((INVALID_PARAMS, "expected a player number and a name".to_string()))
+                };
+                match self.profiles.as_mut() {
+                    Some(store) => {
+                        store.name_seat(player, &name);
+                        Ok(Json::Null)
+                    },
+                    None => Err((GAME_ERROR, "no profile store open".to_string()))
+                }
+            },
+            "show" => {
+                let key = match params.get("key").and_then(|k| k.as_str()) {
+                    Some(k) => k.to_string(),
+                    None => return Err((INVALID_PARAMS, "expected a key".to_string()))
+                };
+                Ok(self.game()?.show_json(&key))
+            },
+            "legal_moves" => {
+                let moves = self.game()?.legal_moves().into_iter().map(|m| Json::Number(m as f64));
+                Ok(Json::Array(moves.collect()))
+            },
+            "semantic_tokens" => {
+                let source = match params.get("source").and_then(|s| s.as_str()) {
+                    Some(s) => s.to_string(),
+                    None => return Err((INVALID_PARAMS, "expected a source".to_string()))
+                };
+                let tokens = crate::highlight::semantic_tokens(&source).map_err(|e| (GAME_ERROR, e.to_string()))?;
+                Ok(Json::Array(tokens.iter().map(|t| t.to_json()).collect()))
+            },
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method)))
+        }
+    }
+
+    fn build(&mut self, params: &Json) -> RpcResult {
+        let source = match (params.get("source"), params.get("path")) {
+            (Some(Json::String(source)), _) => source.to_string(),
+            (_, Some(Json::String(path))) => crate::read_source(path).map_err(|e| (GAME_ERROR, e.to_string()))?,
+            _ => return Err((INVALID_PARAMS, "expected a source or path".to_string()))
+        };
+
+        match crate::load_game(&source) {
+            Ok(game) => {
+                let name = game.show_json("name");
+                self.game = Some(game);
+                Ok(name)
+            },
+            Err(e) => Err((GAME_ERROR, e.to_string()))
+        }
+    }
+
+    fn game(&mut self) -> Result<&mut Game, (f64, String)> {
+        match self.game {
+            Some(ref mut game) => Ok(game),
+            None => Err((GAME_ERROR, "no game built".to_string()))
+        }
+    }
+
+    fn response(id: Json, result: RpcResult) -> Json {
+        let outcome = match result {
+            Ok(value) => ("result", value),
+            Err((code, message)) => ("error", Json::object(vec!(
+                ("code", Json::Number(code)),
+                ("message", Json::String(message))
+            )))
+        };
+
+        Json::object(vec!(("jsonrpc", Json::string("2.0")), ("id", id), outcome))
+    }
+}
+
+pub fn serve_stdio() {
+    let mut server = Server::default();
+    for line in stdin().lock().lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = server.handle(&line) {
+            println!("{}", response);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    const SOURCE: &str = r#"{"jsonrpc":"2.0","id":1,"method":"build","params":{"source":"name rpc\nplayers 2\ndefine player_move(player){\ncheck(player:id is current_player)\nnext_player()\n}"}}"#;
+
+    #[test]
+    fn it_builds_and_plays_a_game() {
+        let mut server = Server::default();
+
+        let build = server.handle(SOURCE).unwrap();
+        assert_eq!(build.to_string(), r#"{"jsonrpc":"2.0","id":1,"result":{"name":"rpc"}}"#);
+
+        server.handle(r#"{"jsonrpc":"2.0","id":2,"method":"start"}"#);
+
+        let legal_moves = server.handle(r#"{"jsonrpc":"2.0","id":3,"method":"legal_moves"}"#).unwrap();
+        assert_eq!(legal_moves.to_string(), r#"{"jsonrpc":"2.0","id":3,"result":[1]}"#);
+
+        let player_move = server.handle(r#"{"jsonrpc":"2.0","id":4,"method":"move","params":{"player":1}}"#).unwrap();
+        assert_eq!(
+            player_move.to_string(),
+            r#"{"jsonrpc":"2.0","id":4,"result":{"move":1,"accepted":true,"status":"active","current_player":2}}"#
+        );
+
+        let show = server.handle(r#"{"jsonrpc":"2.0","id":5,"method":"show","params":{"key":"current_player"}}"#).unwrap();
+        assert_eq!(show.to_string(), r#"{"jsonrpc":"2.0","id":5,"result":{"current_player":2}}"#);
+    }
+
+    #[test]
+    fn it_reports_errors() {
+        let mut server = Server::default();
+
+        let no_game = server.handle(r#"{"jsonrpc":"2.0","id":1,"method":"start"}"#).unwrap();
+        assert_eq!(no_game.to_string(), r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"no game built"}}"#);
+
+        let unknown = server.handle(r#"{"jsonrpc":"2.0","id":2,"method":"fly"}"#).unwrap();
+        assert_eq!(
+            unknown.to_string(),
+            r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32601,"message":"unknown method 'fly'"}}"#
+        );
+
+        let invalid = server.handle("{").unwrap();
+        assert_eq!(invalid.get("error").and_then(|e| e.get("code")), Some(&Json::Number(-32700.0)));
+
+        let flood = server.handle(&"[".repeat(1_000_000)).unwrap();
+        assert_eq!(flood.get("error").and_then(|e| e.get("code")), Some(&Json::Number(-32700.0)));
+    }
+
+    #[test]
+    fn it_returns_semantic_tokens() {
+        let mut server = Server::default();
+
+        let tokens = server.handle(r#"{"jsonrpc":"2.0","id":1,"method":"semantic_tokens","params":{"source":"name rpc"}}"#).unwrap();
+        assert_eq!(
+            tokens.to_string(),
+            r#"{"jsonrpc":"2.0","id":1,"result":[{"kind":"keyword","line":1,"column":1,"length":4},{"kind":"symbol","line":1,"column":6,"length":3}]}"#
+        );
+    }
+
+    #[test]
+    fn named_players_are_recorded_when_the_game_ends() {
+        let path = std::env::temp_dir().join(format!("cardlang-server-profiles-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut server = Server::default();
+        server.handle(r#"{"jsonrpc":"2.0","id":1,"method":"build","params":{"source":"players 2\ndefine player_move(player){\nwinner(player:id)\nend()\n}"}}"#);
+        server.handle(&format!(r#"{{"jsonrpc":"2.0","id":2,"method":"profiles","params":{{"path":"{}"}}}}"#, path));
+        server.handle(r#"{"jsonrpc":"2.0","id":3,"method":"name","params":{"player":2,"name":"bo"}}"#);
+        server.handle(r#"{"jsonrpc":"2.0","id":4,"method":"start"}"#);
+        server.handle(r#"{"jsonrpc":"2.0","id":5,"method":"move","params":{"player":2}}"#);
+
+        let profiles = server.handle(r#"{"jsonrpc":"2.0","id":6,"method":"profiles"}"#).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            profiles.to_string(),
+            r#"{"jsonrpc":"2.0","id":6,"result":{"bo":{"games":1,"wins":1,"rating":1500}}}"#
+        );
+    }
+
+    #[test]
+    fn notifications_get_no_response() {
+        let mut server = Server::default();
+
+        assert_eq!(server.handle(r#"{"jsonrpc":"2.0","method":"start"}"#), None);
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration
+};
+use crate::interpreter::Game;
+use crate::runtime::GameState;
+
+const TARGET: &str = "cardlang::session";
+
+// the one game every connection shares, and the seats they've taken at it
+#[derive(Debug)]
+pub struct Table {
+    game: Game,
+    // in the order they were taken, so whoever sat down first is the host
+    taken: Vec<usize>
+}
+
+impl Table {
+    pub fn new(game: Game) -> Table {
+        Table { game, taken: vec!() }
+    }
+
+    fn host(&self) -> Option<usize> {
+        self.taken.first().copied()
+    }
+
+    fn in_play(&self) -> bool {
+        self.game.state().status == GameState::Active
+    }
+
+    fn leave(&mut self, seat: usize) {
+        self.taken.retain(|s| *s != seat);
+    }
+}
+
+// a player's view onto a shared game, only able to see and move for its own seat
+#[derive(Debug, Default)]
+pub struct Session {
+    seat: Option<usize>
+}
+
+impl Session {
+    pub fn handle(&mut self, table: &mut Table, line: &str) -> String {
+        let command: Vec<&str> = line.trim().split(' ').collect();
+        match command[0] {
+            "seat" => {
+                match command.get(1).and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) if self.seat == Some(n) => format!("seated as player {}", n),
+                    Some(n) if table.taken.contains(&n) => format!("seat {} is taken", n),
+                    Some(n) if n > 0 && n <= table.game.get_players().len() => {
+                        self.leave(table);
+                        table.taken.push(n);
+                        self.seat = Some(n);
+                        format!("seated as player {}", n)
+                    },
+                    _ => "expected a player number!".to_string()
+                }
+            },
+            "show" => {
+                if self.seat.is_none() {
+                    return "take a seat first".to_string();
+                }
+                table.game.show_for_seat(&command[1..].join(" "), self.seat)
+            },
+            "move" => {
+                match self.seat {
+                    Some(seat) => {
+                        if table.game.player_move(seat) {
+                            "move made".to_string()
+                        } else {
+                            "move rejected".to_string()
+                        }
+                    },
+                    None => "take a seat first".to_string()
+                }
+            },
+            // nobody, the host included, can restart everyone else's game from under them
+            "start" if table.in_play() => "the game is already under way".to_string(),
+            "start" if self.seat.is_some() && self.seat == table.host() => {
+                table.game.start();
+                "game started".to_string()
+            },
+            "start" => "only the host can start the game".to_string(),
+            _ => "unrecognised command".to_string()
+        }
+    }
+
+    // frees the seat for whoever connects next
+    pub fn leave(&mut self, table: &mut Table) {
+        if let Some(seat) = self.seat.take() {
+            table.leave(seat);
+        }
+    }
+}
+
+pub fn serve_tcp(address: &str, path: &str) {
+    let table = match crate::read_source(path).and_then(|source| crate::load_game(&source)) {
+        Ok(g) => Arc::new(Mutex::new(Table::new(g))),
+        Err(e) => {
+            log::error!(target: TARGET, path, error:% = e; "unable to load the game");
+            return;
+        }
+    };
+
+    let listener = match TcpListener::bind(address) {
+        Ok(l) => l,
+        Err(e) => {
+            log::error!(target: TARGET, address, error:% = e; "unable to listen");
+            return;
+        }
+    };
+
+    // so a player who walks away can't stall everyone else
+    let timed = Arc::clone(&table);
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        match timed.lock() {
+            Ok(mut table) => {
+                if let Some(message) = table.game.check_turn_timer() {
+                    log::info!(target: TARGET, detail:% = message; "turn timed out");
+                }
+            },
+            Err(_) => break
+        }
+    });
+
+    log::info!(target: TARGET, path, address; "serving");
+    for stream in listener.incoming().flatten() {
+        let table = Arc::clone(&table);
+        thread::spawn(move || handle_connection(stream, table));
+    }
+}
+
+fn handle_connection(stream: TcpStream, table: Arc<Mutex<Table>>) {
+    let mut writer = match stream.try_clone() {
+        Ok(w) => w,
+        Err(_) => return
+    };
+    let mut session = Session::default();
+    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+    log::info!(target: TARGET, peer = peer.as_str(); "connected");
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break
+        };
+
+        let response = match table.lock() {
+            Ok(mut t) => session.handle(&mut t, &line),
+            Err(_) => break
+        };
+
+        log::debug!(target: TARGET, peer = peer.as_str(), command = line.as_str(), response = response.as_str(); "command");
+        if writeln!(writer, "{}", response).is_err() {
+            break;
+        }
+    }
+    log::info!(target: TARGET, peer = peer.as_str(), seat = session.seat; "disconnected");
+    if let Ok(mut t) = table.lock() {
+        session.leave(&mut t);
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn two_player_table() -> Table {
+        Table::new(crate::load_game("players 2\ndefine setup(){\ndeck > players\n}\ndefine player_move(player){\ncheck(player:id is current_player)\nnext_player()\n}").unwrap())
+    }
+
+    #[test]
+    fn sessions_only_see_their_own_hand() {
+        let mut table = two_player_table();
+        let mut first = Session::default();
+        let mut second = Session::default();
+
+        first.handle(&mut table, "seat 1");
+        second.handle(&mut table, "seat 2");
+        first.handle(&mut table, "start");
+
+        assert_eq!(first.handle(&mut table, "show player 1 hand"), "king diamonds".to_string());
+        assert_eq!(first.handle(&mut table, "show player 2 hand"), "hidden (1 cards)".to_string());
+        assert_eq!(second.handle(&mut table, "show player 2 hand"), "queen diamonds".to_string());
+    }
+
+    #[test]
+    fn sessions_can_only_move_for_their_seat() {
+        let mut table = two_player_table();
+        let mut second = Session::default();
+
+        assert_eq!(second.handle(&mut table, "move"), "take a seat first".to_string());
+
+        second.handle(&mut table, "seat 2");
+        second.handle(&mut table, "start");
+
+        assert_eq!(second.handle(&mut table, "move"), "move rejected".to_string());
+    }
+
+    #[test]
+    fn seats_must_exist() {
+        let mut table = two_player_table();
+        let mut session = Session::default();
+
+        assert_eq!(session.handle(&mut table, "seat 3"), "expected a player number!".to_string());
+    }
+
+    #[test]
+    fn sessions_can_ask_for_players_that_arent_there() {
+        let mut table = two_player_table();
+        let mut session = Session::default();
+
+        session.handle(&mut table, "seat 1");
+        session.handle(&mut table, "start");
+
+        assert_eq!(session.handle(&mut table, "show player 9 hand"), "player 9 not found".to_string());
+        assert_eq!(session.handle(&mut table, "show player 0 hand"), "player 0 not found".to_string());
+        assert_eq!(session.handle(&mut table, "show player 9 discard"), "player 9 not found".to_string());
+        assert_eq!(session.handle(&mut table, "show player 1 hand"), "king diamonds".to_string());
+    }
+
+    #[test]
+    fn seats_can_only_be_taken_once() {
+        let mut table = two_player_table();
+        let mut first = Session::default();
+        let mut second = Session::default();
+
+        assert_eq!(first.handle(&mut table, "seat 1"), "seated as player 1".to_string());
+        assert_eq!(second.handle(&mut table, "seat 1"), "seat 1 is taken".to_string());
+        assert_eq!(second.handle(&mut table, "show player 1 hand"), "take a seat first".to_string());
+
+        first.leave(&mut table);
+
+        assert_eq!(second.handle(&mut table, "seat 1"), "seated as player 1".to_string());
+    }
+
+    #[test]
+    fn moving_seats_frees_the_old_one() {
+        let mut table = two_player_table();
+        let mut first = Session::default();
+        let mut second = Session::default();
+
+        first.handle(&mut table, "seat 1");
+        first.handle(&mut table, "seat 2");
+
+        assert_eq!(second.handle(&mut table, "seat 1"), "seated as player 1".to_string());
+        assert_eq!(second.handle(&mut table, "seat 2"), "seat 2 is taken".to_string());
+    }
+
+    #[test]
+    fn only_the_host_starts() {
+        let mut table = two_player_table();
+        let mut host = Session::default();
+        let mut guest = Session::default();
+        let mut watcher = Session::default();
+
+        assert_eq!(guest.handle(&mut table, "start"), "only the host can start the game".to_string());
+
+        host.handle(&mut table, "seat 2");
+        guest.handle(&mut table, "seat 1");
+
+        assert_eq!(guest.handle(&mut table, "start"), "only the host can start the game".to_string());
+        assert_eq!(watcher.handle(&mut table, "start"), "only the host can start the game".to_string());
+        assert_eq!(host.handle(&mut table, "start"), "game started".to_string());
+    }
+
+    #[test]
+    fn a_game_in_play_cant_be_restarted() {
+        let mut table = Table::new(crate::load_game("players 2\ndefine player_move(player){\nend()\n}").unwrap());
+        let mut host = Session::default();
+        let mut guest = Session::default();
+
+        host.handle(&mut table, "seat 1");
+        guest.handle(&mut table, "seat 2");
+        host.handle(&mut table, "start");
+
+        assert_eq!(host.handle(&mut table, "start"), "the game is already under way".to_string());
+
+        host.leave(&mut table);
+
+        assert_eq!(guest.handle(&mut table, "start"), "the game is already under way".to_string());
+
+        guest.handle(&mut table, "move");
+
+        assert_eq!(guest.handle(&mut table, "start"), "game started".to_string());
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt;
+use crate::interpreter::Game;
+use crate::runtime::CoverageReport;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Simulation {
+    pub games: usize,
+    // wins for each player, player 1 first
+    pub wins: Vec<usize>,
+    pub no_winner: usize,
+    pub coverage: CoverageReport
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "games: {}", self.games)?;
+        for (player, wins) in self.wins.iter().enumerate() {
+            write!(f, "\nplayer {} won: {}", player + 1, wins)?;
+        }
+        write!(f, "\nno winner: {}\n{}", self.no_winner, self.coverage)
+    }
+}
+
+// plays games out at random, one seed after another, to see which rules ever come into play
+pub fn simulate(game: &mut Game, games: usize, seed: u64) -> Simulation {
+    let players = game.state().player_count;
+    let mut wins = vec!(0; players);
+    let mut no_winner = 0;
+
+    game.track_coverage();
+    for n in 0..games {
+        game.set_seed(seed.wrapping_add(n as u64));
+        game.start();
+        game.auto(None);
+
+        let winners = game.state().winners;
+        if winners.is_empty() {
+            no_winner += 1;
+        }
+        for winner in winners.iter().filter(|w| **w >= 1 && **w <= players) {
+            wins[winner - 1] += 1;
+        }
+    }
+
+    Simulation { games, wins, no_winner, coverage: game.coverage_report().unwrap_or_default() }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn it_finds_rules_that_never_trigger() {
+        let source = "players 2\ndefine setup(){\ndeck > players\n}\ndefine player_move(player){\ncheck(player:id is current_player)\nif(player:id is 3){\nwinner(player:id)\n}\nif(count(player:hand) is 5){\nend()\n}\nnext_player()\n}";
+        let mut game = crate::load_game(source).unwrap();
+
+        let simulation = simulate(&mut game, 1, 1);
+
+        assert_eq!(simulation.to_string(), "games: 1
+player 1 won: 0
+player 2 won: 0
+no winner: 1
+coverage: 5/7 statements
+never ran in player_move: winner(..)
+never ran in player_move: end(..)".to_string());
+    }
+
+    #[test]
+    fn it_counts_wins_per_player() {
+        let mut game = crate::load_game(crate::examples::find("war").unwrap()).unwrap();
+
+        let simulation = simulate(&mut game, 3, 1);
+
+        assert_eq!(simulation.wins.iter().sum::<usize>() + simulation.no_winner, 3);
+        assert!(simulation.coverage.uncovered.is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+// levenshtein distance, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec!(i + 1);
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// the closest known name, as long as it's close enough to plausibly be a typo
+pub fn closest<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    known.iter()
+        .filter(|k| **k != name)
+        .map(|k| (edit_distance(name, k), *k))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, k)| k)
+}
+
+// appended to an error message, e.g. "unknown function 'shufle', did you mean 'shuffle'?"
+pub fn did_you_mean(name: &str, known: &[&str]) -> String {
+    match closest(name, known) {
+        Some(k) => format!(", did you mean '{}'?", k),
+        None => String::new()
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn it_measures_edits() {
+        assert_eq!(edit_distance("shuffle", "shuffle"), 0);
+        assert_eq!(edit_distance("shufle", "shuffle"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn it_suggests_the_closest_name() {
+        let known = ["end", "shuffle", "winner", "count", "next_player"];
+
+        assert_eq!(closest("shufle", &known), Some("shuffle"));
+        assert_eq!(closest("nextplayer", &known), Some("next_player"));
+        assert_eq!(did_you_mean("fly", &known), String::new());
+        assert_eq!(did_you_mean("wibber", &known), ", did you mean 'winner'?".to_string());
+    }
+}
@@ -1,8 +1,11 @@
 #[derive(Debug, PartialEq, Clone)]
-pub enum Token {
+pub enum Token<'a> {
     Name,
-    Symbol(String),
-    Number(f64),
+    Symbol(&'a str),
+    // the text between the quotes
+    String(&'a str),
+    // the value, and how many characters it was written with, as `3.50` and `3.5` are the same number
+    Number(f64, u32),
     Stack,
     Deck,
     Players,
@@ -17,17 +20,52 @@ pub enum Token {
     Check,
     Is,
     If,
+    While,
+    Foreach,
     Newline,
     True,
     False,
     Ampersand,
+    Pipe,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
     Return,
     Test,
-    Not
+    Not,
+    Match,
+    Between,
+    Teams,
+    HandSize
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct SourceToken {
-    pub token: Token,
-    pub line_number: u32
-}
\ No newline at end of file
+pub struct SourceToken<'a> {
+    pub token: Token<'a>,
+    pub line_number: u32,
+    pub column: u32
+}
+
+impl Token<'_> {
+    // how many characters the token takes up in canonical source
+    pub fn width(&self) -> u32 {
+        match self {
+            Token::Symbol(s) => s.chars().count() as u32,
+            Token::String(s) => s.chars().count() as u32 + 2,
+            Token::Number(_, length) => *length,
+            Token::Name | Token::Deck | Token::True => 4,
+            Token::Stack | Token::Check | Token::False | Token::Test | Token::Match | Token::Teams | Token::While => 5,
+            Token::Players | Token::Between | Token::Foreach => 7,
+            Token::CurrentPlayer => 14,
+            Token::Define | Token::Return => 6,
+            Token::Is | Token::If => 2,
+            Token::Not => 3,
+            Token::HandSize => 9,
+            Token::OpenParens | Token::CloseParens | Token::Comma | Token::OpenBracket
+                | Token::CloseBracket | Token::Transfer | Token::Newline | Token::Ampersand | Token::Pipe
+                | Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent => 1
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+use crate::bot::{self, Bot, Seats};
+use crate::interpreter::Game;
+use crate::rating::Ratings;
+
+// builds a fresh bot for each game from that game's seed
+pub type BotFactory = Box<dyn Fn(u64) -> Box<dyn Bot>>;
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Record {
+    pub name: String,
+    pub played: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    // only filled in by run_rated
+    pub rating: Option<f64>
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Results {
+    pub games: usize,
+    pub records: Vec<Record>
+}
+
+impl fmt::Display for Results {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "games: {}", self.games)?;
+        for r in self.records.iter() {
+            write!(f, "\n{}: {} wins, {} draws, {} losses", r.name, r.wins, r.draws, r.losses)?;
+            if let Some(rating) = r.rating {
+                write!(f, ", rating {:.0}", rating)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Tournament {
+    game: Game,
+    entrants: Vec<(String, BotFactory)>,
+    games: usize,
+    seed: u64
+}
+
+impl Tournament {
+    pub fn new(game: Game, games: usize, seed: u64) -> Tournament {
+        Tournament { game, entrants: vec!(), games, seed }
+    }
+
+    // a name already taken gets a number after it, so two copies of a bot can play each other
+    pub fn add(&mut self, name: &str, factory: BotFactory) {
+        let taken = self.entrants.iter().filter(|(n, _)| n == name || n.starts_with(&format!("{} (", name))).count();
+        let name = if taken == 0 { name.to_string() } else { format!("{} ({})", name, taken + 1) };
+        self.entrants.push((name, factory));
+    }
+
+    // one of the built in bots, by the names `bot::create` understands
+    pub fn add_builtin(&mut self, kind: &str) -> bool {
+        if bot::create(kind, Some(0)).is_none() {
+            return false;
+        }
+
+        let factory_kind = kind.to_string();
+        self.add(kind, Box::new(move |seed| {
+            bot::create(&factory_kind, Some(seed)).expect("checked bot kind")
+        }));
+        true
+    }
+
+    // seats rotate through the entrants each game, and every game gets its own seed
+    pub fn run(&self) -> Results {
+        self.play(None)
+    }
+
+    // as run, updating each bot's rating after every game
+    pub fn run_rated(&self, ratings: &mut Ratings) -> Results {
+        let mut results = self.play(Some(ratings));
+        for record in results.records.iter_mut() {
+            record.rating = Some(ratings.get(&record.name));
+        }
+        results
+    }
+
+    fn play(&self, mut ratings: Option<&mut Ratings>) -> Results {
+        let mut records: Vec<Record> = self.entrants.iter()
+            .map(|(name, _)| Record { name: name.to_string(), ..Record::default() })
+            .collect();
+
+        if self.entrants.is_empty() {
+            return Results { games: 0, records };
+        }
+
+        let players = self.game.state().player_count;
+        for round in 0..self.games {
+            let seed = self.seed.wrapping_add(round as u64);
+            let seating: Vec<usize> = (0..players).map(|seat| (seat + round) % self.entrants.len()).collect();
+            let mut seats: Seats = seating.iter().enumerate()
+                .map(|(seat, entrant)| (seat + 1, (self.entrants[*entrant].1)(seed.wrapping_add(seat as u64))))
+                .collect();
+
+            let mut game = self.game.fork();
+            game.set_seed(seed);
+            game.start();
+            bot::play_bots(&mut game, &mut seats);
+
+            let winners = game.state().winners;
+            let finished = game.get_status() == "game over" && !winners.is_empty();
+            let mut scores = vec!();
+            for (entrant, record) in records.iter_mut().enumerate() {
+                let entrant_seats: Vec<usize> = seating.iter().enumerate()
+                    .filter(|(_, e)| **e == entrant)
+                    .map(|(seat, _)| seat + 1)
+                    .collect();
+                if entrant_seats.is_empty() {
+                    continue;
+                }
+
+                record.played += 1;
+                let score = if !finished {
+                    record.draws += 1;
+                    0.5
+                } else if entrant_seats.iter().any(|s| winners.contains(s)) {
+                    record.wins += 1;
+                    1.0
+                } else {
+                    record.losses += 1;
+                    0.0
+                };
+                scores.push((record.name.to_string(), score));
+            }
+
+            if let Some(ratings) = ratings.as_mut() {
+                ratings.record_game(&scores);
+            }
+        }
+
+        Results { games: self.games, records }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+    use crate::bot::{GameView, Move};
+
+    // the first player to move wins
+    const FIRST_MOVE_WINS: &str = "players 2\ndefine player_move(player){\ncheck(player:id is current_player)\nwinner(player:id)\nend()\n}";
+
+    struct FirstMove;
+
+    impl Bot for FirstMove {
+        fn name(&self) -> &str {
+            "first"
+        }
+
+        fn choose_move(&mut self, view: &GameView) -> Move {
+            view.legal_moves[0]
+        }
+    }
+
+    #[test]
+    fn seats_alternate_between_games() {
+        let mut tournament = Tournament::new(crate::load_game(FIRST_MOVE_WINS).unwrap(), 4, 1);
+        tournament.add("first", Box::new(|_| Box::new(FirstMove)));
+        tournament.add_builtin("random");
+
+        let results = tournament.run();
+
+        assert_eq!(results.to_string(), "games: 4\nfirst: 2 wins, 0 draws, 2 losses\nrandom: 2 wins, 0 draws, 2 losses".to_string());
+    }
+
+    #[test]
+    fn games_without_a_winner_are_draws() {
+        let source = "players 2\ndefine player_move(player){\ncheck(player:id is current_player)\nnext_player()\n}";
+        let mut tournament = Tournament::new(crate::load_game(source).unwrap(), 2, 1);
+        tournament.add_builtin("random");
+        tournament.add_builtin("random");
+
+        let results = tournament.run();
+
+        assert_eq!(results.records[1], Record {
+            name: "random (2)".to_string(),
+            played: 2,
+            wins: 0,
+            draws: 2,
+            losses: 0,
+            rating: None
+        });
+    }
+
+    #[test]
+    fn rated_runs_carry_ratings_forward() {
+        let mut tournament = Tournament::new(crate::load_game(FIRST_MOVE_WINS).unwrap(), 1, 1);
+        tournament.add("first", Box::new(|_| Box::new(FirstMove)));
+        tournament.add("second", Box::new(|_| Box::new(FirstMove)));
+        let mut ratings = Ratings::default();
+
+        let results = tournament.run_rated(&mut ratings);
+
+        assert_eq!(results.records[0].rating, Some(1516.0));
+        assert_eq!(results.records[1].rating, Some(1484.0));
+        assert!(results.to_string().ends_with("second: 0 wins, 0 draws, 1 losses, rating 1484"));
+
+        tournament.run_rated(&mut ratings);
+        assert_eq!(ratings.entries()[0].games, 2);
+    }
+
+    #[test]
+    fn unknown_bots_are_refused() {
+        let mut tournament = Tournament::new(crate::load_game(FIRST_MOVE_WINS).unwrap(), 1, 1);
+
+        assert!(!tournament.add_builtin("genius"));
+    }
+}
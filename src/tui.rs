@@ -0,0 +1,140 @@
+use crate::interpreter::Game;
+
+const WIDTH: usize = 78;
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+pub fn render_screen(game: Option<&Game>, seat: Option<usize>, output: &str) -> String {
+    let table = match game {
+        Some(g) => render_table(g, seat),
+        None => "no game built".to_string()
+    };
+
+    if output.is_empty() {
+        format!("{}{}\n", CLEAR_SCREEN, table)
+    } else {
+        format!("{}{}\n{}\n", CLEAR_SCREEN, table, output)
+    }
+}
+
+// shown as `show` would show it to the seat, so the frame gives away nothing the seat couldn't ask for
+pub fn render_table(game: &Game, seat: Option<usize>) -> String {
+    let title = format!(
+        " {} - {} ",
+        game.get_name().unwrap_or_else(|| "cardlang".to_string()),
+        game.get_status()
+    );
+
+    let mut lines = vec!(border('┌', &title));
+    lines.push(row(&format!("deck ({})", game.get_deck().len())));
+
+    for (name, cards) in game.get_stacks().iter() {
+        lines.push(row(&format!("{} ({}): {}", name, cards.len(), game.show_for_seat(name, seat))));
+    }
+
+    lines.push(border('├', ""));
+
+    let current_player = game.get_current_player();
+    for player in game.get_players().iter() {
+        let hand = player.get_hand();
+        let is_current = player.get_id() as usize == current_player;
+        let marker = if is_current { '▶' } else { ' ' };
+        let shown = game.show_for_seat(&format!("player {}", player.get_id()), seat);
+        let text = format!("{} player {} ({}): {}", marker, player.get_id(), hand.len(), shown);
+        if is_current {
+            lines.push(format!("│ {}{}{}", HIGHLIGHT, fit(&text), RESET));
+        } else {
+            lines.push(row(&text));
+        }
+    }
+
+    lines.push(border('└', ""));
+    lines.join("\n")
+}
+
+fn fit(text: &str) -> String {
+    let length = text.chars().count();
+    if length > WIDTH {
+        let truncated: String = text.chars().take(WIDTH - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        format!("{}{}", text, " ".repeat(WIDTH - length))
+    }
+}
+
+fn row(text: &str) -> String {
+    format!("│ {}", fit(text))
+}
+
+fn border(corner: char, title: &str) -> String {
+    let length = title.chars().count();
+    format!("{}─{}{}", corner, title, "─".repeat((WIDTH + 1).saturating_sub(length)))
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+    use crate::ast::*;
+
+    fn two_player_game() -> Game {
+        let ast = vec!(
+            Statement::Declaration(Declaration{
+                key: GlobalKey::Name,
+                value: Expression::Symbol("turns".to_string()),
+                span: SourceSpan::default()
+            }),
+            Statement::Declaration(Declaration{
+                key: GlobalKey::Players,
+                value: Expression::Number(2.0),
+                span: SourceSpan::default()
+            }),
+            Statement::Declaration(Declaration{
+                key: GlobalKey::Stack,
+                value: Expression::Symbol("middle".to_string()),
+                span: SourceSpan::default()
+            })
+        );
+        Game::new(ast)
+    }
+
+    #[test]
+    fn it_renders_every_zone_of_the_table() {
+        let table = render_table(&two_player_game(), None);
+        let lines: Vec<&str> = table.split('\n').collect();
+
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].starts_with("┌─ turns - pending ─"));
+        assert!(lines[1].starts_with("│ deck (52)"));
+        assert!(lines[2].starts_with("│ middle (0): "));
+        assert!(lines[4].contains(HIGHLIGHT));
+        assert!(lines[4].contains("▶ player 1 (0): "));
+        assert!(lines[5].starts_with("│   player 2 (0): "));
+    }
+
+    #[test]
+    fn a_seat_only_sees_what_it_could_ask_to_see() {
+        let source = "players 2\nstack pile facedown\ndefine setup(){\ndeck > players\ndeck > pile\n}";
+        let mut game = crate::load_game(source).unwrap();
+        game.start();
+        let table = render_table(&game, Some(2));
+
+        assert!(table.contains("pile (1): ?? (1 cards)"));
+        assert!(table.contains("player 1 (1): hidden (1 cards)"));
+        assert!(table.contains("player 2 (1): queen diamonds"));
+    }
+
+    #[test]
+    fn long_rows_are_truncated() {
+        let text = "x".repeat(WIDTH * 2);
+
+        assert_eq!(fit(&text).chars().count(), WIDTH);
+    }
+
+    #[test]
+    fn the_screen_is_cleared_before_rendering() {
+        let screen = render_screen(None, None, "unrecognised command");
+
+        assert_eq!(screen, format!("{}no game built\nunrecognised command\n", CLEAR_SCREEN));
+    }
+}
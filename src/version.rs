@@ -0,0 +1,84 @@
+use crate::ast::*;
+
+// the newest language this release reads. A game says which it was written for with `cardlang 2`,
+// and files from before the pragma existed are version 1
+pub const LANGUAGE_VERSION: u32 = 2;
+
+// what each version changed, taken back out of older games so they keep their original meaning
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    // `deck > middle 3` moves three cards, where version 1 moved one and ignored anything but `end`
+    TransferCounts
+}
+
+impl Feature {
+    pub fn since(&self) -> u32 {
+        match self {
+            Feature::TransferCounts => 2
+        }
+    }
+
+    pub fn enabled(&self, version: u32) -> bool {
+        version >= self.since()
+    }
+}
+
+pub fn language_version(ast: &[Statement]) -> u32 {
+    ast.iter().find_map(|statement| match statement {
+        Statement::Declaration(Declaration{ key: GlobalKey::Language, value: Expression::Number(n), .. }) => Some(*n as u32),
+        _ => None
+    }).unwrap_or(1)
+}
+
+// the game as the version it declares reads it
+pub fn lower(ast: Vec<Statement>) -> Vec<Statement> {
+    let version = language_version(&ast);
+    if Feature::TransferCounts.enabled(version) {
+        return ast;
+    }
+    lower_statements(ast)
+}
+
+fn lower_statements(statements: Vec<Statement>) -> Vec<Statement> {
+    statements.into_iter().map(lower_statement).collect()
+}
+
+fn lower_statement(statement: Statement) -> Statement {
+    match statement {
+        Statement::Transfer(t) if t.count.is_some() && !t.to_end() => Statement::Transfer(Transfer{ count: None, ..t }),
+        Statement::Definition(d) => Statement::Definition(Definition{ body: lower_statements(d.body), ..d }),
+        Statement::IfStatement(i) => Statement::IfStatement(IfStatement{ body: lower_statements(i.body), ..i }),
+        Statement::WhileStatement(w) => Statement::WhileStatement(WhileStatement{ body: lower_statements(w.body), ..w }),
+        Statement::ForeachStatement(f) => Statement::ForeachStatement(ForeachStatement{ body: lower_statements(f.body), ..f }),
+        Statement::MatchStatement(m) => Statement::MatchStatement(MatchStatement{
+            arms: m.arms.into_iter().map(|a| MatchArm{ body: lower_statements(a.body), ..a }).collect(),
+            otherwise: m.otherwise.map(lower_statements),
+            ..m
+        }),
+        statement => statement
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn transfer_count(source: &str) -> Option<Expression> {
+        match &lower(crate::parse_source(source).unwrap()).last() {
+            Some(Statement::Definition(d)) => match &d.body[0] {
+                Statement::Transfer(t) => t.count.clone(),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    #[test]
+    fn older_games_keep_their_transfers() {
+        let source = "define setup(){\ndeck > players 3\n}";
+
+        assert_eq!(transfer_count(source), None);
+        assert_eq!(transfer_count(&format!("cardlang 2\n{}", source)), Some(Expression::Number(3.0)));
+        assert_eq!(language_version(&crate::parse_source("players 2").unwrap()), 1);
+    }
+}